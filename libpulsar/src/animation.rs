@@ -0,0 +1,174 @@
+use crate::math::Transform;
+
+/// What a clip does when sampled outside `0..=duration`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WrapMode {
+    /// Holds the first or last pose.
+    #[default]
+    Clamp,
+    /// Repeats from the start.
+    Loop,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Keyframe {
+    /// Seconds from the start of the clip.
+    pub time: f32,
+    pub transform: Transform,
+}
+
+/// Keyframes of one joint, sorted by time.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct JointTrack {
+    pub keyframes: Vec<Keyframe>,
+}
+
+impl JointTrack {
+    pub fn new(mut keyframes: Vec<Keyframe>) -> Self {
+        keyframes.sort_by(|a, b| a.time.total_cmp(&b.time));
+        JointTrack { keyframes }
+    }
+
+    /// Pose at `time`, interpolating between the surrounding keyframes. An
+    /// empty track gives the identity.
+    pub fn sample(&self, time: f32) -> Transform {
+        let keyframes = &self.keyframes;
+        let (Some(first), Some(last)) = (keyframes.first(), keyframes.last()) else {
+            return Transform::identity();
+        };
+        if time <= first.time {
+            return first.transform;
+        }
+        if time >= last.time {
+            return last.transform;
+        }
+        // First keyframe strictly after `time`; the bounds checks above keep it in 1..len
+        let next = keyframes.partition_point(|keyframe| keyframe.time <= time);
+        let (a, b) = (&keyframes[next - 1], &keyframes[next]);
+        let span = b.time - a.time;
+        let t = if span > 0.0 { (time - a.time) / span } else { 0.0 };
+        a.transform.lerp(&b.transform, t)
+    }
+}
+
+/// Keyframed joint poses, sampled each frame to drive a `Skeleton`'s palette.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AnimationClip {
+    /// One track per joint, in skeleton order.
+    pub tracks: Vec<JointTrack>,
+    pub wrap: WrapMode,
+    duration: f32,
+}
+
+impl AnimationClip {
+    pub fn new(tracks: Vec<JointTrack>, wrap: WrapMode) -> Self {
+        let duration = tracks
+            .iter()
+            .filter_map(|track| track.keyframes.last())
+            .map(|keyframe| keyframe.time)
+            .fold(0.0, f32::max);
+        AnimationClip { tracks, wrap, duration }
+    }
+
+    /// Time of the last keyframe across all tracks.
+    pub fn duration(&self) -> f32 {
+        self.duration
+    }
+
+    /// Maps `time` into the clip according to `wrap`.
+    pub fn local_time(&self, time: f32) -> f32 {
+        if self.duration <= 0.0 {
+            return 0.0;
+        }
+        match self.wrap {
+            WrapMode::Clamp => time.clamp(0.0, self.duration),
+            WrapMode::Loop => time.rem_euclid(self.duration),
+        }
+    }
+
+    /// Local pose of every joint at `time` seconds.
+    pub fn sample(&self, time: f32) -> Vec<Transform> {
+        let time = self.local_time(time);
+        self.tracks.iter().map(|track| track.sample(time)).collect()
+    }
+}
+
+/// Crossfades two poses: `weight` 0 gives `a`, 1 gives `b`. Joints only one
+/// pose has are taken from it unchanged.
+pub fn blend(a: &[Transform], b: &[Transform], weight: f32) -> Vec<Transform> {
+    let weight = weight.clamp(0.0, 1.0);
+    (0..a.len().max(b.len()))
+        .map(|i| match (a.get(i), b.get(i)) {
+            (Some(a), Some(b)) => a.lerp(b, weight),
+            (Some(only), None) | (None, Some(only)) => *only,
+            (None, None) => unreachable!(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::Vector3;
+    use std::f32::consts::FRAC_PI_2;
+
+    fn key(time: f32, x: f32, yaw: f32) -> Keyframe {
+        Keyframe {
+            time,
+            transform: Transform::new(Vector3::new(x, 0.0, 0.0), Vector3::new(0.0, yaw, 0.0), Vector3::one()),
+        }
+    }
+
+    fn clip(wrap: WrapMode) -> AnimationClip {
+        AnimationClip::new(vec![JointTrack::new(vec![key(0.0, 0.0, 0.0), key(2.0, 4.0, FRAC_PI_2)])], wrap)
+    }
+
+    #[test]
+    fn midpoint_interpolates_position_and_slerps_rotation() {
+        let pose = clip(WrapMode::Clamp).sample(1.0);
+        assert_eq!(pose.len(), 1);
+        assert!((pose[0].position - Vector3::new(2.0, 0.0, 0.0)).length() < 1e-5);
+        assert!((pose[0].rotation.y - FRAC_PI_2 / 2.0).abs() < 1e-5, "{:?}", pose[0].rotation);
+        assert!(pose[0].rotation.x.abs() < 1e-5 && pose[0].rotation.z.abs() < 1e-5);
+    }
+
+    #[test]
+    fn keyframes_are_sorted_by_time() {
+        let track = JointTrack::new(vec![key(2.0, 4.0, 0.0), key(0.0, 0.0, 0.0)]);
+        assert_eq!(track.keyframes[0].time, 0.0);
+        assert!((track.sample(0.5).position.x - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn clamp_holds_the_end_poses() {
+        let clip = clip(WrapMode::Clamp);
+        assert_eq!(clip.duration(), 2.0);
+        assert_eq!(clip.sample(-1.0)[0].position.x, 0.0);
+        assert_eq!(clip.sample(5.0)[0].position.x, 4.0);
+    }
+
+    #[test]
+    fn loop_repeats_from_the_start() {
+        let clip = clip(WrapMode::Loop);
+        assert!((clip.local_time(2.5) - 0.5).abs() < 1e-6);
+        assert!((clip.local_time(-0.5) - 1.5).abs() < 1e-6);
+        assert!((clip.sample(3.0)[0].position.x - 2.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn empty_tracks_give_the_identity() {
+        assert_eq!(JointTrack::default().sample(1.0), Transform::identity());
+        assert_eq!(AnimationClip::new(Vec::new(), WrapMode::Loop).local_time(3.0), 0.0);
+    }
+
+    #[test]
+    fn blend_crossfades_and_keeps_unmatched_joints() {
+        let a = [key(0.0, 0.0, 0.0).transform];
+        let b = [key(0.0, 2.0, 0.0).transform, key(0.0, 7.0, 0.0).transform];
+        let pose = blend(&a, &b, 0.25);
+        assert_eq!(pose.len(), 2);
+        assert!((pose[0].position.x - 0.5).abs() < 1e-6);
+        assert_eq!(pose[1], b[1]);
+        assert_eq!(blend(&a, &b, 2.0)[0].position, b[0].position);
+    }
+}
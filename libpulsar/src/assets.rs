@@ -0,0 +1,522 @@
+use std::collections::HashMap;
+use std::num::NonZeroUsize;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use log::{debug, warn};
+use thiserror::Error;
+
+use crate::color::linear_to_srgb_rgb;
+use crate::engine::render::config::Labels;
+use crate::mesh::{flip_winding, Mesh, MeshData, MeshIssue, Vertex, WindingOrder};
+
+#[derive(Debug, Error)]
+pub enum AssetError {
+    #[error("Failed to read asset: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Failed to parse glTF: {0}")]
+    Gltf(#[from] gltf::Error),
+    #[error("Asset contains no triangle mesh")]
+    NoMesh,
+    #[error("Mesh has {0} vertices, more than a u16 index buffer can address")]
+    TooManyVertices(usize),
+    #[error("Index {0} does not fit in a u16 index buffer")]
+    IndexOutOfRange(u32),
+    #[error("Mesh is invalid: {}", .0.iter().map(ToString::to_string).collect::<Vec<_>>().join("; "))]
+    InvalidMesh(Vec<MeshIssue>),
+}
+
+/// Refers to an asset queued with `AssetLoader::load_async`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AssetHandle(pub(crate) u64);
+
+/// CPU-side data produced by the worker threads.
+#[derive(Debug)]
+pub enum AssetData {
+    Mesh { vertices: Vec<Vertex>, indices: Vec<u16> },
+    Bytes(Vec<u8>),
+}
+
+/// A loaded asset, with any GPU resources already created.
+pub enum Asset {
+    Mesh(Mesh),
+    Bytes(Vec<u8>),
+}
+
+#[derive(Debug)]
+pub enum AssetState {
+    Loading,
+    Ready,
+    Failed(AssetError),
+}
+
+/// Most worker threads a loader starts by default; parsing is mostly disk
+/// bound, so more rarely helps.
+pub const MAX_DEFAULT_WORKERS: usize = 4;
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// Parses files on a fixed pool of background threads and uploads the
+/// results on the thread that calls `poll`, so large loads don't stall the
+/// window. Loads beyond the pool size wait in a queue.
+pub struct AssetLoader {
+    next_id: u64,
+    /// Dropping it stops the workers once they finish their current job.
+    jobs: Sender<Job>,
+    sender: Sender<(AssetHandle, Result<AssetData, AssetError>)>,
+    receiver: Receiver<(AssetHandle, Result<AssetData, AssetError>)>,
+    states: HashMap<AssetHandle, AssetState>,
+    assets: HashMap<AssetHandle, Asset>,
+    winding: Option<WindingOrder>,
+}
+
+impl AssetLoader {
+    /// A loader with one worker per core, up to `MAX_DEFAULT_WORKERS`.
+    pub fn new() -> Self {
+        let cores = thread::available_parallelism().map_or(1, NonZeroUsize::get);
+        Self::with_workers(cores.min(MAX_DEFAULT_WORKERS))
+    }
+
+    /// A loader parsing at most `workers` (at least one) assets at a time.
+    pub fn with_workers(workers: usize) -> Self {
+        let (jobs, queue) = mpsc::channel::<Job>();
+        let queue = Arc::new(Mutex::new(queue));
+        for index in 0..workers.max(1) {
+            let queue = Arc::clone(&queue);
+            thread::Builder::new()
+                .name(format!("asset-worker-{index}"))
+                .spawn(move || work(&queue))
+                .expect("failed to spawn asset worker thread");
+        }
+
+        let (sender, receiver) = mpsc::channel();
+        Self {
+            next_id: 0,
+            jobs,
+            sender,
+            receiver,
+            states: HashMap::new(),
+            assets: HashMap::new(),
+            winding: None,
+        }
+    }
+
+    /// Declares the winding of meshes loaded from now on. `None` (the default)
+    /// detects it from the vertex normals. Clockwise meshes are flipped to
+    /// counter-clockwise so they work with back-face culling.
+    pub fn set_winding(&mut self, winding: Option<WindingOrder>) {
+        self.winding = winding;
+    }
+
+    /// Queues `path` for parsing on a worker thread. `.gltf`/`.glb` files become
+    /// meshes, anything else is loaded as raw bytes.
+    pub fn load_async(&mut self, path: impl Into<PathBuf>) -> AssetHandle {
+        let path = path.into();
+        let winding = self.winding;
+        self.spawn(move || parse_file(&path, winding))
+    }
+
+    /// Queues an arbitrary parser for a worker thread, e.g. for procedural data.
+    pub fn spawn<F>(&mut self, parse: F) -> AssetHandle
+    where
+        F: FnOnce() -> Result<AssetData, AssetError> + Send + 'static,
+    {
+        let handle = AssetHandle(self.next_id);
+        self.next_id += 1;
+        self.states.insert(handle, AssetState::Loading);
+
+        let sender = self.sender.clone();
+        let job: Job = Box::new(move || {
+            // The loader may have been dropped by now; nothing to report to
+            let _ = sender.send((handle, parse()));
+        });
+        // Workers only exit once the loader drops `jobs`, so this can't fail
+        let _ = self.jobs.send(job);
+        handle
+    }
+
+    /// Uploads every asset whose parsing has finished. Returns how many
+    /// assets completed (successfully or not) during this call.
+    pub fn poll(&mut self, device: &wgpu::Device, labels: &Labels, config: &wgpu::SurfaceConfiguration) -> usize {
+        self.complete(|data| upload(device, labels, config, data))
+    }
+
+    /// Moves finished parses into their final state, turning successful ones
+    /// into assets with `upload`.
+    fn complete(&mut self, mut upload: impl FnMut(AssetData) -> Asset) -> usize {
+        let mut completed = 0;
+        while let Ok((handle, result)) = self.receiver.try_recv() {
+            // Unloaded while it was still parsing
+            if !self.states.contains_key(&handle) {
+                continue;
+            }
+            completed += 1;
+            let state = match result {
+                Ok(data) => {
+                    self.assets.insert(handle, upload(data));
+                    debug!("Asset {:?} loaded", handle);
+                    AssetState::Ready
+                }
+                Err(err) => {
+                    warn!("Asset {:?} failed to load: {}", handle, err);
+                    AssetState::Failed(err)
+                }
+            };
+            self.states.insert(handle, state);
+        }
+        completed
+    }
+
+    /// Forgets `handle`, dropping the asset and its GPU buffers. An asset
+    /// still loading is discarded when its worker finishes.
+    pub fn unload(&mut self, handle: AssetHandle) -> Option<Asset> {
+        self.states.remove(&handle);
+        self.assets.remove(&handle)
+    }
+
+    pub fn state(&self, handle: AssetHandle) -> Option<&AssetState> {
+        self.states.get(&handle)
+    }
+
+    pub fn is_ready(&self, handle: AssetHandle) -> bool {
+        matches!(self.states.get(&handle), Some(AssetState::Ready))
+    }
+
+    pub fn get(&self, handle: AssetHandle) -> Option<&Asset> {
+        self.assets.get(&handle)
+    }
+
+    /// The loaded mesh for `handle`, or `placeholder` while it is still loading
+    /// (or if it failed).
+    pub fn mesh_or<'a>(&'a self, handle: AssetHandle, placeholder: &'a Mesh) -> &'a Mesh {
+        match self.assets.get(&handle) {
+            Some(Asset::Mesh(mesh)) => mesh,
+            _ => placeholder,
+        }
+    }
+}
+
+impl Default for AssetLoader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Runs queued jobs until the loader goes away. A panicking parser leaves its
+/// asset loading forever but doesn't take the worker down with it.
+fn work(queue: &Mutex<Receiver<Job>>) {
+    loop {
+        let job = match queue.lock() {
+            Ok(queue) => queue.recv(),
+            Err(_) => return,
+        };
+        let Ok(job) = job else {
+            return;
+        };
+        if panic::catch_unwind(AssertUnwindSafe(job)).is_err() {
+            warn!("Asset parser panicked");
+        }
+    }
+}
+
+fn upload(device: &wgpu::Device, labels: &Labels, config: &wgpu::SurfaceConfiguration, data: AssetData) -> Asset {
+    match data {
+        AssetData::Mesh { vertices, indices } if indices.is_empty() => {
+            Asset::Mesh(Mesh::from_vertices(device, labels, config, &vertices))
+        }
+        AssetData::Mesh { vertices, indices } => {
+            Asset::Mesh(Mesh::from_indexed(device, labels, config, &vertices, &indices))
+        }
+        AssetData::Bytes(bytes) => Asset::Bytes(bytes),
+    }
+}
+
+fn parse_file(path: &Path, winding: Option<WindingOrder>) -> Result<AssetData, AssetError> {
+    let extension = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.to_ascii_lowercase());
+    match extension.as_deref() {
+        Some("gltf") | Some("glb") => parse_gltf(path)
+            .and_then(|data| validate(path, data))
+            .map(|data| to_ccw(data, winding)),
+        _ => Ok(AssetData::Bytes(std::fs::read(path)?)),
+    }
+}
+
+/// Rejects meshes the GPU can't draw safely and logs the lesser issues.
+fn validate(path: &Path, data: AssetData) -> Result<AssetData, AssetError> {
+    let AssetData::Mesh { vertices, indices } = data else {
+        return Ok(data);
+    };
+    let mesh = MeshData::new(vertices, indices);
+    if let Err(issues) = mesh.validate() {
+        let (fatal, minor): (Vec<_>, Vec<_>) = issues.into_iter().partition(MeshIssue::is_fatal);
+        if let Some(first) = minor.first() {
+            warn!("{}: {} mesh issue(s), first: {}", path.display(), minor.len(), first);
+        }
+        if !fatal.is_empty() {
+            return Err(AssetError::InvalidMesh(fatal));
+        }
+    }
+    Ok(AssetData::Mesh {
+        vertices: mesh.vertices,
+        indices: mesh.indices,
+    })
+}
+
+/// Flips clockwise meshes so every imported mesh is counter-clockwise.
+fn to_ccw(data: AssetData, winding: Option<WindingOrder>) -> AssetData {
+    match data {
+        AssetData::Mesh {
+            mut vertices,
+            mut indices,
+        } => {
+            let winding = winding.or_else(|| WindingOrder::detect(&vertices, &indices));
+            if winding == Some(WindingOrder::Cw) {
+                debug!("Flipping clockwise mesh to counter-clockwise");
+                flip_winding(&mut vertices, &mut indices);
+            }
+            AssetData::Mesh { vertices, indices }
+        }
+        other => other,
+    }
+}
+
+/// Reads the first primitive of the first mesh. glTF vertex colors are
+/// linear, so they are re-encoded as sRGB to match `Vertex::color`. Vertices
+/// without colors are white; missing normals are left zeroed, as are missing
+/// texture coordinates and skinning joints and weights.
+fn parse_gltf(path: &Path) -> Result<AssetData, AssetError> {
+    let (document, buffers, _images) = gltf::import(path)?;
+    let primitive = document
+        .meshes()
+        .flat_map(|mesh| mesh.primitives())
+        .find(|primitive| primitive.mode() == gltf::mesh::Mode::Triangles)
+        .ok_or(AssetError::NoMesh)?;
+    let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+
+    let positions: Vec<[f32; 3]> = reader.read_positions().ok_or(AssetError::NoMesh)?.collect();
+    let colors: Vec<[f32; 3]> = reader
+        .read_colors(0)
+        .map(|colors| colors.into_rgb_f32().map(linear_to_srgb_rgb).collect())
+        .unwrap_or_else(|| vec![[1.0, 1.0, 1.0]; positions.len()]);
+
+    let normals: Vec<[f32; 3]> = reader
+        .read_normals()
+        .map(|normals| normals.collect())
+        .unwrap_or_else(|| vec![[0.0, 0.0, 0.0]; positions.len()]);
+
+    let uvs: Vec<[f32; 2]> = reader
+        .read_tex_coords(0)
+        .map(|uvs| uvs.into_f32().collect())
+        .unwrap_or_else(|| vec![[0.0, 0.0]; positions.len()]);
+
+    let joints: Vec<[u16; 4]> = reader
+        .read_joints(0)
+        .map(|joints| joints.into_u16().collect())
+        .unwrap_or_else(|| vec![[0; 4]; positions.len()]);
+    let weights: Vec<[f32; 4]> = reader
+        .read_weights(0)
+        .map(|weights| weights.into_f32().collect())
+        .unwrap_or_else(|| vec![[0.0; 4]; positions.len()]);
+
+    let indices = match reader.read_indices() {
+        // Only an index buffer limits how many vertices can be addressed
+        Some(_) if positions.len() > u16::MAX as usize + 1 => {
+            return Err(AssetError::TooManyVertices(positions.len()));
+        }
+        Some(indices) => narrow_indices(indices.into_u32())?,
+        None => Vec::new(),
+    };
+
+    let vertices = positions
+        .into_iter()
+        .zip(colors)
+        .zip(normals)
+        .zip(joints.into_iter().zip(weights))
+        .zip(uvs)
+        .map(|((((position, color), normal), (joint_indices, weights)), uv)| Vertex {
+            joint_indices,
+            weights,
+            uv,
+            ..Vertex::new(position, color, normal)
+        })
+        .collect();
+
+    Ok(AssetData::Mesh { vertices, indices })
+}
+
+/// Converts glTF indices to the u16 indices `Mesh` draws with, rejecting any
+/// that would wrap.
+fn narrow_indices(indices: impl Iterator<Item = u32>) -> Result<Vec<u16>, AssetError> {
+    indices
+        .map(|index| u16::try_from(index).map_err(|_| AssetError::IndexOutOfRange(index)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::*;
+    use crate::test_log;
+
+    /// Completes parses until `handle` leaves the loading state.
+    fn wait_for(loader: &mut AssetLoader, handle: AssetHandle) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while matches!(loader.state(handle), Some(AssetState::Loading)) {
+            assert!(Instant::now() < deadline, "asset {handle:?} never finished loading");
+            loader.complete(|data| match data {
+                AssetData::Bytes(bytes) => Asset::Bytes(bytes),
+                AssetData::Mesh { .. } => panic!("unexpected mesh"),
+            });
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    fn parsed_assets_become_ready_once_completed() {
+        let mut loader = AssetLoader::new();
+        let handle = loader.spawn(|| Ok(AssetData::Bytes(vec![1, 2, 3])));
+        wait_for(&mut loader, handle);
+        assert!(loader.is_ready(handle));
+        assert!(matches!(loader.get(handle), Some(Asset::Bytes(bytes)) if bytes == &[1, 2, 3]));
+    }
+
+    #[test]
+    fn parse_errors_are_reported_as_failed() {
+        let mut loader = AssetLoader::new();
+        let handle = loader.spawn(|| Err(AssetError::NoMesh));
+        wait_for(&mut loader, handle);
+        assert!(matches!(
+            loader.state(handle),
+            Some(AssetState::Failed(AssetError::NoMesh))
+        ));
+        assert!(loader.get(handle).is_none());
+    }
+
+    #[test]
+    fn assets_unloaded_while_loading_are_discarded() {
+        let mut loader = AssetLoader::new();
+        let (release, gate) = mpsc::channel::<()>();
+        let handle = loader.spawn(move || {
+            gate.recv().ok();
+            Ok(AssetData::Bytes(Vec::new()))
+        });
+        assert!(matches!(loader.state(handle), Some(AssetState::Loading)));
+        loader.unload(handle);
+        release.send(()).unwrap();
+
+        // Wait for the worker, then hand its result back to the loader
+        let finished = loader.receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+        loader.sender.send(finished).unwrap();
+        assert_eq!(loader.complete(|_| panic!("discarded asset was uploaded")), 0);
+        assert!(loader.state(handle).is_none());
+    }
+
+    #[test]
+    fn loads_beyond_the_worker_count_wait_for_a_free_worker() {
+        let mut loader = AssetLoader::with_workers(1);
+        let (release, gate) = mpsc::channel::<()>();
+        let first = loader.spawn(move || {
+            gate.recv().ok();
+            Ok(AssetData::Bytes(vec![1]))
+        });
+        let second = loader.spawn(|| Ok(AssetData::Bytes(vec![2])));
+
+        // The only worker is busy with the first load
+        thread::sleep(Duration::from_millis(20));
+        assert!(loader.receiver.try_recv().is_err());
+        assert!(matches!(loader.state(second), Some(AssetState::Loading)));
+
+        release.send(()).unwrap();
+        wait_for(&mut loader, first);
+        wait_for(&mut loader, second);
+        assert!(loader.is_ready(first) && loader.is_ready(second));
+    }
+
+    #[test]
+    fn a_panicking_parser_does_not_stop_its_worker() {
+        let mut loader = AssetLoader::with_workers(1);
+        let panicked = loader.spawn(|| panic!("parser bug"));
+        let handle = loader.spawn(|| Ok(AssetData::Bytes(vec![1])));
+        wait_for(&mut loader, handle);
+        assert!(loader.is_ready(handle));
+        assert!(matches!(loader.state(panicked), Some(AssetState::Loading)));
+    }
+
+    #[test]
+    fn handles_are_unique() {
+        let mut loader = AssetLoader::new();
+        let first = loader.spawn(|| Ok(AssetData::Bytes(Vec::new())));
+        let second = loader.spawn(|| Ok(AssetData::Bytes(Vec::new())));
+        assert_ne!(first, second);
+    }
+
+    fn imported_indices(data: AssetData) -> Vec<u16> {
+        match data {
+            AssetData::Mesh { indices, .. } => indices,
+            AssetData::Bytes(_) => panic!("expected a mesh"),
+        }
+    }
+
+    fn clockwise_mesh() -> AssetData {
+        let vertices = [[0.0, 0.0, 0.0], [0.0, 1.0, 0.0], [1.0, 0.0, 0.0]]
+            .map(|position| Vertex::new(position, [1.0; 3], [0.0, 0.0, 1.0]))
+            .to_vec();
+        AssetData::Mesh {
+            vertices,
+            indices: vec![0, 1, 2],
+        }
+    }
+
+    #[test]
+    fn clockwise_imports_are_flipped_to_counter_clockwise() {
+        assert_eq!(imported_indices(to_ccw(clockwise_mesh(), Some(WindingOrder::Cw))), vec![0, 2, 1]);
+        // Detected from the normals when no winding is given
+        assert_eq!(imported_indices(to_ccw(clockwise_mesh(), None)), vec![0, 2, 1]);
+    }
+
+    #[test]
+    fn winding_override_skips_detection() {
+        assert_eq!(imported_indices(to_ccw(clockwise_mesh(), Some(WindingOrder::Ccw))), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn imports_with_fatal_mesh_issues_are_rejected() {
+        let mut data = clockwise_mesh();
+        if let AssetData::Mesh { indices, .. } = &mut data {
+            indices[2] = 9;
+        }
+        let error = validate(Path::new("broken.glb"), data).unwrap_err();
+        assert!(matches!(&error, AssetError::InvalidMesh(issues) if issues.len() == 1), "{error:?}");
+        assert!(error.to_string().contains("vertex 9"), "{error}");
+    }
+
+    #[test]
+    fn imports_with_minor_mesh_issues_load_with_a_warning() {
+        let mut data = clockwise_mesh();
+        if let AssetData::Mesh { vertices, .. } = &mut data {
+            vertices[0].normal = [0.0, 0.0, 3.0];
+        }
+        let (result, warnings) = test_log::warnings(|| validate(Path::new("scaled.glb"), data));
+        assert_eq!(imported_indices(result.unwrap()), vec![0, 1, 2]);
+        assert_eq!(warnings.len(), 1, "{warnings:?}");
+        assert!(warnings[0].starts_with("scaled.glb: 1 mesh issue(s)"), "{warnings:?}");
+    }
+
+    #[test]
+    fn indices_that_fit_in_u16_are_kept() {
+        assert_eq!(narrow_indices([0, 1, u16::MAX as u32].into_iter()).unwrap(), vec![0, 1, u16::MAX]);
+    }
+
+    #[test]
+    fn indices_past_u16_are_rejected_instead_of_wrapping() {
+        let error = narrow_indices([0, 1, 65_536].into_iter()).unwrap_err();
+        assert!(matches!(error, AssetError::IndexOutOfRange(65_536)), "{error:?}");
+    }
+}
@@ -0,0 +1,178 @@
+use glam::Vec3;
+
+use crate::camera::{Camera, Projection};
+use crate::debug_lines::{DebugLines, LineSegment};
+use crate::engine::render::config::Labels;
+use crate::gizmo::GizmoAxis;
+use crate::mesh::Mesh;
+use crate::resources::Tracked;
+
+/// Side of the overlay's square viewport, in pixels.
+pub const AXES_OVERLAY_SIZE: u32 = 96;
+/// Gap between the overlay and the bottom-left corner of the window, in pixels.
+pub const AXES_OVERLAY_MARGIN: u32 = 16;
+const LINE_WIDTH: f32 = 2.0;
+
+/// Unit X, Y and Z lines from the origin, colored like the gizmo axes.
+pub fn axis_segments() -> [LineSegment; 3] {
+    GizmoAxis::ALL.map(|axis| LineSegment {
+        start: Vec3::ZERO,
+        end: axis.direction().into(),
+        color: axis.color(),
+    })
+}
+
+/// Orthographic camera looking at the origin along the same direction, and
+/// with the same up vector, as `main`, so the axes turn with the main view.
+pub fn overlay_camera(main: &Camera) -> Camera {
+    let mut camera = Camera::from_transform(&main.transform, 1.0);
+    camera.set_position(-main.forward() * 3.0);
+    camera.znear = 0.1;
+    camera.zfar = 10.0;
+    // Unit axes plus some room around them
+    camera.projection = Projection::Orthographic { height: 2.6 };
+    camera
+}
+
+/// Pixel rectangle `(x, y, width, height)` of the overlay in a
+/// `width`x`height` target, shrunk to fit very small windows.
+pub fn overlay_viewport(width: u32, height: u32) -> (u32, u32, u32, u32) {
+    let size = AXES_OVERLAY_SIZE.min(width).min(height);
+    let margin = AXES_OVERLAY_MARGIN.min(width - size).min(height - size);
+    (margin, height - size - margin, size, size)
+}
+
+/// Small world-axes indicator drawn in a corner after the main scene.
+pub struct AxesOverlay {
+    lines: DebugLines,
+    depth_texture: (Tracked<wgpu::Texture>, wgpu::TextureView),
+    width: u32,
+    height: u32,
+    labels: Labels,
+}
+
+impl AxesOverlay {
+    pub fn new(device: &wgpu::Device, labels: &Labels, config: &wgpu::SurfaceConfiguration) -> Self {
+        let mut lines = DebugLines::new(device, labels, config, 1);
+        lines.set_line_width(LINE_WIDTH);
+        for segment in axis_segments() {
+            lines.line(segment.start, segment.end, segment.color);
+        }
+        Self {
+            lines,
+            depth_texture: Mesh::create_depth_texture(device, labels, config),
+            width: config.width,
+            height: config.height,
+            labels: labels.clone(),
+        }
+    }
+
+    pub fn segments(&self) -> &[LineSegment] {
+        self.lines.segments()
+    }
+
+    pub fn resize(&mut self, device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) {
+        self.depth_texture = Mesh::create_depth_texture(device, &self.labels, config);
+        self.width = config.width;
+        self.height = config.height;
+    }
+
+    /// Orients the axes to match `main` for the next frame.
+    pub fn prepare(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, main: &Camera) {
+        let (_, _, _, size) = overlay_viewport(self.width, self.height);
+        self.lines.prepare(device, queue, &overlay_camera(main), size as f32);
+    }
+
+    /// Draws the axes over whatever `target` already holds.
+    pub fn draw(&self, encoder: &mut wgpu::CommandEncoder, target: &wgpu::TextureView) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some(&self.labels.label("Axes Overlay Pass")),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &self.depth_texture.1,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(1.0),
+                    store: wgpu::StoreOp::Discard,
+                }),
+                stencil_ops: None,
+            }),
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        let (x, y, width, height) = overlay_viewport(self.width, self.height);
+        render_pass.set_viewport(x as f32, y as f32, width as f32, height as f32, 0.0, 1.0);
+        self.lines.draw(&mut render_pass);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render_target::RenderTarget;
+    use crate::test_gpu;
+
+    #[test]
+    fn axes_are_red_green_blue_unit_lines() {
+        let [x, y, z] = axis_segments();
+        assert_eq!((x.end, x.color), (Vec3::X, [1.0, 0.0, 0.0]));
+        assert_eq!((y.end, y.color), (Vec3::Y, [0.0, 1.0, 0.0]));
+        assert_eq!((z.end, z.color), (Vec3::Z, [0.0, 0.0, 1.0]));
+        assert!([x, y, z].iter().all(|segment| segment.start == Vec3::ZERO));
+    }
+
+    #[test]
+    fn viewport_sits_in_the_bottom_left_and_shrinks_to_fit() {
+        assert_eq!(overlay_viewport(800, 600), (16, 600 - 96 - 16, 96, 96));
+        assert_eq!(overlay_viewport(50, 40), (0, 0, 40, 40));
+    }
+
+    #[test]
+    fn overlay_camera_looks_along_the_main_view() {
+        let mut main = Camera::new(Vec3::new(10.0, 5.0, 0.0), 1.0);
+        main.look_at(Vec3::new(10.0, 5.0, 4.0), Vec3::Y);
+        let camera = overlay_camera(&main);
+        assert!(camera.position().abs_diff_eq(Vec3::new(0.0, 0.0, -3.0), 1e-6));
+        assert!(camera.forward().abs_diff_eq(Vec3::Z, 1e-6));
+        assert_eq!(camera.up(), main.up());
+    }
+
+    #[test]
+    fn overlay_draws_three_colored_axes_inside_its_viewport() {
+        let Some((device, queue)) = test_gpu::device() else {
+            return;
+        };
+        let labels = Labels::default();
+        let size = 128;
+        let config = test_gpu::surface_config(size, size);
+        let target = RenderTarget::new(&device, &labels, &config, size, size);
+        let mut overlay = AxesOverlay::new(&device, &labels, &config);
+        // Seen from a diagonal, no axis points straight at the camera
+        let main = Camera::new(Vec3::new(3.0, 3.0, 3.0), 1.0);
+        overlay.prepare(&device, &queue, &main);
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        overlay.draw(&mut encoder, target.color_view());
+        queue.submit(std::iter::once(encoder.finish()));
+
+        let pixels = test_gpu::read_pixels(&device, &queue, &target.color_texture.0);
+        let (left, top, width, height) = overlay_viewport(size, size);
+        let mut counts = [0; 3];
+        for (index, pixel) in pixels.chunks_exact(4).enumerate() {
+            let (x, y) = (index as u32 % size, index as u32 / size);
+            for (channel, count) in counts.iter_mut().enumerate() {
+                let others = (0..3).filter(|&other| other != channel);
+                if pixel[channel] > 128 && others.clone().all(|other| pixel[other] < 64) {
+                    *count += 1;
+                    assert!((left..left + width).contains(&x) && (top..top + height).contains(&y), "({x}, {y})");
+                }
+            }
+        }
+        assert!(counts.iter().all(|&count| count > 0), "{counts:?}");
+    }
+}
@@ -1,4 +1,6 @@
-use crate::math::Vector3;
+use std::collections::BTreeSet;
+
+use crate::math::{Quaternion, Transform, Vector3};
 
 use dynasty_rs::prelude::*;
 use super::Object;
@@ -9,6 +11,13 @@ pub struct Actor {
     pub position: Vector3,
     pub rotation: Vector3,
     pub scale: Vector3,
+    /// Disabled actors, and everything under them in a `Scene`, are not updated.
+    pub enabled: bool,
+    /// Hidden actors, and everything under them in a `Scene`, are not rendered.
+    pub visible: bool,
+    /// Whether `Scene::pick` can return this actor.
+    pub pickable: bool,
+    tags: BTreeSet<String>,
 }
 
 impl Actor {
@@ -17,9 +26,56 @@ impl Actor {
             position: Vector3::new(0.0, 0.0, 0.0),
             rotation: Vector3::new(0.0, 0.0, 0.0),
             scale: Vector3::new(1.0, 1.0, 1.0),
+            enabled: true,
+            visible: true,
+            pickable: true,
+            tags: BTreeSet::new(),
         }
     }
 
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn set_visible(&mut self, visible: bool) {
+        self.visible = visible;
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    pub fn set_pickable(&mut self, pickable: bool) {
+        self.pickable = pickable;
+    }
+
+    pub fn is_pickable(&self) -> bool {
+        self.pickable
+    }
+
+    /// Adds a gameplay tag, returning false if the actor already had it.
+    pub fn add_tag(&mut self, tag: impl Into<String>) -> bool {
+        self.tags.insert(tag.into())
+    }
+
+    /// Removes a tag, returning false if the actor didn't have it.
+    pub fn remove_tag(&mut self, tag: &str) -> bool {
+        self.tags.remove(tag)
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.contains(tag)
+    }
+
+    /// Tags in alphabetical order.
+    pub fn tags(&self) -> impl Iterator<Item = &str> {
+        self.tags.iter().map(String::as_str)
+    }
+
     pub fn set_position(&mut self, x: f32, y: f32, z: f32) {
         self.position = Vector3::new(x, y, z);
     }
@@ -66,14 +122,20 @@ impl Actor {
         self.scale = transform.scale;
     }
 
+    /// Turns the actor so its forward (+Z) axis points at `target`, keeping its
+    /// up axis as close to world up as possible. Does nothing if `target` is
+    /// the actor's own position.
     pub fn look_at(&mut self, target: Vector3) {
         let direction = target - self.position;
-        let rotation = Vector3::new(
-            direction.y.atan2(direction.x),
-            direction.z.atan2(direction.y),
-            direction.x.atan2(direction.z),
-        );
-        self.rotation = rotation;
+        if direction.try_normalize().is_none() {
+            return;
+        }
+        self.rotation = Transform::looking_at(self.position, target, Vector3::up()).rotation;
+    }
+
+    /// Direction the actor is facing.
+    pub fn forward(&self) -> Vector3 {
+        self.get_transform().forward()
     }
 
     pub fn move_towards(&mut self, target: Vector3, speed: f32) {
@@ -82,25 +144,25 @@ impl Actor {
         if distance <= speed {
             self.position = target;
         } else {
-            let velocity = direction.normalize() * speed;
+            let velocity = direction.normalize_or_zero() * speed;
             self.position += velocity;
         }
     }
 
-    pub fn rotate_towards(&mut self, target: Vector3, speed: f32) {
-        let direction = target - self.position;
-        let rotation = Vector3::new(
-            direction.y.atan2(direction.x),
-            direction.z.atan2(direction.y),
-            direction.x.atan2(direction.z),
-        );
-        let delta = rotation - self.rotation;
-        let distance = delta.length();
-        if distance <= speed {
-            self.rotation = rotation;
+    /// Turns the actor towards facing `target`, as `look_at` would, by at
+    /// most `max_angle` radians. Does nothing if `target` is the actor's own
+    /// position.
+    pub fn rotate_towards(&mut self, target: Vector3, max_angle: f32) {
+        if (target - self.position).try_normalize().is_none() {
+            return;
+        }
+        let current = Quaternion::from_euler_xyz(self.rotation);
+        let goal = Transform::looking_at(self.position, target, Vector3::up()).rotation_quaternion();
+        let angle = current.angle_between(&goal);
+        if angle <= max_angle {
+            self.rotation = goal.to_euler_xyz();
         } else {
-            let velocity = delta.normalize() * speed;
-            self.rotation += velocity;
+            self.rotation = current.slerp(&goal, (max_angle / angle).min(1.0)).to_euler_xyz();
         }
     }
 
@@ -110,7 +172,7 @@ impl Actor {
         if distance <= speed {
             self.scale = target;
         } else {
-            let velocity = direction.normalize() * speed;
+            let velocity = direction.normalize_or_zero() * speed;
             self.scale += velocity;
         }
     }
@@ -120,4 +182,59 @@ impl Actor {
         self.look_at(target.position);
         self.as_parent().set_rotation(target.rotation);
     }
-} 
\ No newline at end of file
+} 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn look_at_own_position_keeps_rotation() {
+        let mut actor = Actor::new();
+        actor.set_rotation(0.1, 0.2, 0.3);
+        actor.look_at(actor.get_position());
+        assert_eq!(actor.get_rotation(), Vector3::new(0.1, 0.2, 0.3));
+    }
+
+    #[test]
+    fn moving_and_scaling_towards_an_infinite_offset_stays_finite() {
+        // Longer than the speed, so the direction is normalized, and a plain
+        // `normalize` would divide infinity by infinity
+        let infinite = Vector3::new(f32::INFINITY, 0.0, 0.0);
+        assert!(!infinite.normalize().is_finite());
+        let mut actor = Actor::new();
+        actor.move_towards(actor.get_position() + infinite, 1.0);
+        actor.scale_towards(actor.get_scale() + infinite, 1.0);
+        assert!(actor.get_position().is_finite());
+        assert!(actor.get_scale().is_finite());
+    }
+
+    #[test]
+    fn forward_points_at_the_target_after_look_at() {
+        let targets = [
+            Vector3::new(0.0, 0.0, 10.0),
+            Vector3::new(0.0, 0.0, -10.0),
+            Vector3::new(5.0, 2.0, -3.0),
+            Vector3::new(-4.0, -1.0, 7.0),
+        ];
+        for target in targets {
+            let mut actor = Actor::new();
+            actor.set_position(1.0, 2.0, 3.0);
+            actor.look_at(target);
+            let expected = (target - actor.get_position()).normalize();
+            assert!(actor.forward().approx_eq(&expected, 1e-5), "{:?} vs {:?}", actor.forward(), expected);
+        }
+    }
+
+    #[test]
+    fn tags_are_unique_and_listed_alphabetically() {
+        let mut actor = Actor::new();
+        assert!(actor.add_tag("enemy"));
+        assert!(!actor.add_tag("enemy"));
+        assert!(actor.add_tag("boss"));
+        assert_eq!(actor.tags().collect::<Vec<_>>(), ["boss", "enemy"]);
+        assert!(actor.remove_tag("boss"));
+        assert!(!actor.remove_tag("boss"));
+        assert!(actor.has_tag("enemy") && !actor.has_tag("boss"));
+    }
+}
@@ -1,4 +1,6 @@
 mod object;
 pub use object::Object;
 mod actor;
-pub use actor::Actor;
\ No newline at end of file
+pub use actor::Actor;
+mod scene;
+pub use scene::{ActorId, ActorSnapshot, Scene, WorldSnapshot};
@@ -0,0 +1,992 @@
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use glam::Vec3;
+use serde::{Deserialize, Serialize};
+
+use crate::lod::Lod;
+use crate::math::{BoundingSphere, Matrix4, Ray, RayHit, Rng, Transform, Vector3};
+use crate::mesh::{Mesh, MeshData};
+use crate::picking::PickingPass;
+
+use super::Actor;
+
+/// Identifies an actor spawned into a `Scene`. Ids are never reused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct ActorId(u64);
+
+struct SceneNode {
+    actor: Actor,
+    parent: Option<ActorId>,
+    children: Vec<ActorId>,
+    /// Cached world matrix; `None` when it has to be recomputed.
+    world: Cell<Option<Matrix4>>,
+}
+
+/// State of one actor in a `WorldSnapshot`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ActorSnapshot {
+    pub id: ActorId,
+    pub parent: Option<ActorId>,
+    pub children: Vec<ActorId>,
+    pub transform: Transform,
+    pub enabled: bool,
+    pub visible: bool,
+    #[serde(default = "default_pickable")]
+    pub pickable: bool,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+fn default_pickable() -> bool {
+    true
+}
+
+/// Serializable copy of a `Scene`, with actors in a stable (id) order.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WorldSnapshot {
+    pub next_id: u64,
+    pub actors: Vec<ActorSnapshot>,
+    /// State of the scene's `Rng`, so a restored scene replays the same
+    /// random numbers.
+    #[serde(default)]
+    pub rng: Rng,
+}
+
+impl WorldSnapshot {
+    pub fn actor(&self, id: ActorId) -> Option<&ActorSnapshot> {
+        self.actors
+            .binary_search_by_key(&id, |state| state.id)
+            .ok()
+            .map(|index| &self.actors[index])
+    }
+}
+
+/// Reverse index from tag to the actors carrying it.
+#[derive(Default)]
+struct TagIndex {
+    actors: HashMap<String, BTreeSet<ActorId>>,
+    /// Actors handed out by `Scene::actor_mut`, whose tags may have changed.
+    /// They are left out of `actors` until the next query re-adds them.
+    stale: BTreeSet<ActorId>,
+}
+
+impl TagIndex {
+    fn insert(&mut self, id: ActorId, actor: &Actor) {
+        for tag in actor.tags() {
+            self.actors.entry(tag.to_string()).or_default().insert(id);
+        }
+    }
+
+    fn remove(&mut self, id: ActorId, actor: &Actor) {
+        if self.stale.remove(&id) {
+            return;
+        }
+        self.remove_tags(id, actor.tags());
+    }
+
+    fn remove_tags<'a>(&mut self, id: ActorId, tags: impl Iterator<Item = &'a str>) {
+        for tag in tags {
+            if let Some(ids) = self.actors.get_mut(tag) {
+                ids.remove(&id);
+                if ids.is_empty() {
+                    self.actors.remove(tag);
+                }
+            }
+        }
+    }
+
+    /// Moves `id` from its `old` tags to the ones `actor` carries now. Stale
+    /// actors are left for the next query to re-add.
+    fn retag(&mut self, id: ActorId, old: &[String], actor: &Actor) {
+        if self.stale.contains(&id) {
+            return;
+        }
+        self.remove_tags(id, old.iter().map(String::as_str));
+        self.insert(id, actor);
+    }
+}
+
+/// Owns the actors of a level and their parent/child relationships.
+///
+/// World matrices are computed lazily and cached. Changing an actor's local
+/// transform, or re-parenting it, only invalidates that actor and its
+/// descendants.
+#[derive(Default)]
+pub struct Scene {
+    nodes: BTreeMap<ActorId, SceneNode>,
+    next_id: u64,
+    tags: RefCell<TagIndex>,
+    rng: Rng,
+}
+
+impl Scene {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A scene whose `rng` starts from `seed`.
+    pub fn with_seed(seed: u64) -> Self {
+        Self {
+            rng: Rng::new(seed),
+            ..Self::default()
+        }
+    }
+
+    /// Random numbers for gameplay. Drawing from this rather than a separate
+    /// generator keeps snapshots and replays deterministic.
+    pub fn rng(&self) -> &Rng {
+        &self.rng
+    }
+
+    pub fn rng_mut(&mut self) -> &mut Rng {
+        &mut self.rng
+    }
+
+    pub fn spawn(&mut self, actor: Actor) -> ActorId {
+        let id = ActorId(self.next_id);
+        self.next_id += 1;
+        self.tags.get_mut().insert(id, &actor);
+        self.nodes.insert(
+            id,
+            SceneNode {
+                actor,
+                parent: None,
+                children: Vec::new(),
+                world: Cell::new(None),
+            },
+        );
+        id
+    }
+
+    /// Removes the actor and its whole subtree, returning the actor itself.
+    pub fn despawn(&mut self, id: ActorId) -> Option<Actor> {
+        let node = self.nodes.remove(&id)?;
+        self.tags.get_mut().remove(id, &node.actor);
+        if let Some(parent) = node.parent.and_then(|parent| self.nodes.get_mut(&parent)) {
+            parent.children.retain(|child| *child != id);
+        }
+        for child in node.children {
+            self.despawn(child);
+        }
+        Some(node.actor)
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Actor ids in spawn order.
+    pub fn ids(&self) -> impl Iterator<Item = ActorId> + '_ {
+        self.nodes.keys().copied()
+    }
+
+    pub fn actor(&self, id: ActorId) -> Option<&Actor> {
+        self.nodes.get(&id).map(|node| &node.actor)
+    }
+
+    /// Mutable access to an actor. Its cached world matrix, and those of its
+    /// descendants, are invalidated since the transform may change.
+    pub fn actor_mut(&mut self, id: ActorId) -> Option<&mut Actor> {
+        self.invalidate(id);
+        let node = self.nodes.get_mut(&id)?;
+        // Its tags may change, so re-index it on the next tag query
+        let tags = self.tags.get_mut();
+        if !tags.stale.contains(&id) {
+            tags.remove(id, &node.actor);
+            tags.stale.insert(id);
+        }
+        Some(&mut node.actor)
+    }
+
+    /// Actors carrying `tag`, in spawn order.
+    pub fn find_by_tag(&self, tag: &str) -> Vec<ActorId> {
+        let mut tags = self.tags.borrow_mut();
+        for id in std::mem::take(&mut tags.stale) {
+            if let Some(node) = self.nodes.get(&id) {
+                tags.insert(id, &node.actor);
+            }
+        }
+        tags.actors.get(tag).map_or_else(Vec::new, |ids| ids.iter().copied().collect())
+    }
+
+    pub fn has_tag(&self, id: ActorId, tag: &str) -> bool {
+        self.actor(id).is_some_and(|actor| actor.has_tag(tag))
+    }
+
+    pub fn set_local_transform(&mut self, id: ActorId, transform: Transform) {
+        if let Some(actor) = self.actor_mut(id) {
+            actor.set_transform(transform);
+        }
+    }
+
+    pub fn parent(&self, id: ActorId) -> Option<ActorId> {
+        self.nodes.get(&id)?.parent
+    }
+
+    pub fn children(&self, id: ActorId) -> &[ActorId] {
+        self.nodes.get(&id).map_or(&[], |node| node.children.as_slice())
+    }
+
+    /// Attaches `child` under `parent`, or detaches it with `None`. Refuses
+    /// (returning false) to create a cycle or to use an unknown id.
+    pub fn set_parent(&mut self, child: ActorId, parent: Option<ActorId>) -> bool {
+        if !self.nodes.contains_key(&child) {
+            return false;
+        }
+        if let Some(parent) = parent {
+            if !self.nodes.contains_key(&parent) || self.is_ancestor_or_self(child, parent) {
+                return false;
+            }
+        }
+
+        let old_parent = self.nodes[&child].parent;
+        if let Some(old) = old_parent.and_then(|old| self.nodes.get_mut(&old)) {
+            old.children.retain(|c| *c != child);
+        }
+        if let Some(new) = parent.and_then(|parent| self.nodes.get_mut(&parent)) {
+            new.children.push(child);
+        }
+        if let Some(node) = self.nodes.get_mut(&child) {
+            node.parent = parent;
+        }
+        self.invalidate(child);
+        true
+    }
+
+    fn is_ancestor_or_self(&self, ancestor: ActorId, mut id: ActorId) -> bool {
+        loop {
+            if id == ancestor {
+                return true;
+            }
+            match self.parent(id) {
+                Some(parent) => id = parent,
+                None => return false,
+            }
+        }
+    }
+
+    /// Whether `id` and all of its ancestors are enabled.
+    pub fn is_active(&self, id: ActorId) -> bool {
+        self.all_ancestors_or_self(id, |actor| actor.enabled)
+    }
+
+    /// Whether `id` should be drawn: it and all of its ancestors are enabled
+    /// and visible.
+    pub fn is_rendered(&self, id: ActorId) -> bool {
+        self.all_ancestors_or_self(id, |actor| actor.enabled && actor.visible)
+    }
+
+    fn all_ancestors_or_self(&self, mut id: ActorId, check: impl Fn(&Actor) -> bool) -> bool {
+        loop {
+            let Some(node) = self.nodes.get(&id) else {
+                return false;
+            };
+            if !check(&node.actor) {
+                return false;
+            }
+            match node.parent {
+                Some(parent) => id = parent,
+                None => return true,
+            }
+        }
+    }
+
+    /// Runs `update` on every active actor, skipping disabled subtrees. Only
+    /// actors whose transform or tags actually changed are invalidated or
+    /// re-indexed.
+    pub fn update(&mut self, dt: f32, mut update: impl FnMut(ActorId, &mut Actor, f32)) {
+        let active: Vec<ActorId> = self.ids().filter(|id| self.is_active(*id)).collect();
+        for id in active {
+            let Some(node) = self.nodes.get_mut(&id) else {
+                continue;
+            };
+            let transform = node.actor.get_transform();
+            let tags: Vec<String> = node.actor.tags().map(str::to_string).collect();
+            update(id, &mut node.actor, dt);
+
+            if !node.actor.tags().eq(tags.iter().map(String::as_str)) {
+                self.tags.get_mut().retag(id, &tags, &node.actor);
+            }
+            if node.actor.get_transform() != transform {
+                self.invalidate(id);
+            }
+        }
+    }
+
+    /// Actors the render loop should draw, in spawn order.
+    pub fn rendered(&self) -> impl Iterator<Item = ActorId> + '_ {
+        self.ids().filter(|id| self.is_rendered(*id))
+    }
+
+    /// Meshes to draw for the rendered actors with an entry in `lods`, in
+    /// spawn order. Each actor's `Lod` first switches to the level for its
+    /// distance to `camera_position`.
+    pub fn lod_meshes<'a, M>(
+        &self,
+        camera_position: Vec3,
+        lods: &'a mut BTreeMap<ActorId, Lod<M>>,
+    ) -> Vec<(ActorId, &'a M)> {
+        lods.iter_mut()
+            .filter(|(id, _)| self.is_rendered(**id))
+            .filter_map(|(&id, lod)| {
+                let position = self.world_matrix(id)?.transform_point(Vector3::zero());
+                Some((id, lod.update(camera_position, position.into())))
+            })
+            .collect()
+    }
+
+    /// Closest actor `ray` hits, among rendered actors that are pickable.
+    /// `shape` gives an actor's local-space mesh and bounds, or `None` for
+    /// actors without geometry; the bounds reject most actors before any
+    /// triangle is tested.
+    pub fn pick<'a>(
+        &self,
+        ray: &Ray,
+        shape: impl Fn(ActorId) -> Option<(&'a MeshData, BoundingSphere)>,
+    ) -> Option<(ActorId, RayHit)> {
+        let mut closest: Option<(ActorId, RayHit)> = None;
+        for id in self.rendered() {
+            if !self.nodes[&id].actor.pickable {
+                continue;
+            }
+            let (Some((mesh, bounds)), Some(world)) = (shape(id), self.world_matrix(id)) else {
+                continue;
+            };
+            let bounds = bounds.transform(&world);
+            let Some(entry) = ray.intersect_sphere(bounds.center, bounds.radius) else {
+                continue;
+            };
+            if closest.is_some_and(|(_, hit)| hit.distance < entry) {
+                continue;
+            }
+            for triangle in mesh.triangle_positions() {
+                let [a, b, c] = triangle.map(|corner| Vector3::from(corner).transform(&world));
+                let Some(distance) = ray.intersect_triangle(a, b, c) else {
+                    continue;
+                };
+                if closest.is_some_and(|(_, hit)| hit.distance <= distance) {
+                    continue;
+                }
+                let normal = (b - a).cross(&(c - a)).normalize_or_zero();
+                let normal = if normal.dot(&ray.direction) > 0.0 { -normal } else { normal };
+                closest = Some((
+                    id,
+                    RayHit {
+                        distance,
+                        point: ray.at(distance),
+                        normal,
+                    },
+                ));
+            }
+        }
+        closest
+    }
+
+    /// Actor under the cursor, found by drawing every rendered, pickable
+    /// actor's id into `picking` and reading back the pixel at
+    /// (`mouse_x`, `mouse_y`) in physical pixels. Exact for any shape,
+    /// unlike `pick`, but blocks on the GPU. `mesh` gives an actor's mesh, or
+    /// `None` for actors without geometry.
+    pub fn pick_gpu<'a>(
+        &self,
+        picking: &mut PickingPass,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        camera_bind_group: &wgpu::BindGroup,
+        (mouse_x, mouse_y): (f64, f64),
+        mesh: impl Fn(ActorId) -> Option<&'a Mesh>,
+    ) -> Option<ActorId> {
+        if mouse_x < 0.0 || mouse_y < 0.0 {
+            return None;
+        }
+        let objects: Vec<_> = self
+            .rendered()
+            .filter(|id| self.nodes[id].actor.pickable)
+            .filter_map(|id| Some((id, self.world_matrix(id)?, mesh(id)?)))
+            .collect();
+        picking.render(device, queue, camera_bind_group, &objects);
+        picking.actor_at(device, queue, mouse_x as u32, mouse_y as u32)
+    }
+
+    /// Captures the state of every actor, in id order, so it can be restored
+    /// later for replays or lockstep resyncs.
+    pub fn snapshot(&self) -> WorldSnapshot {
+        WorldSnapshot {
+            next_id: self.next_id,
+            actors: self
+                .nodes
+                .iter()
+                .map(|(id, node)| ActorSnapshot {
+                    id: *id,
+                    parent: node.parent,
+                    children: node.children.clone(),
+                    transform: node.actor.get_transform(),
+                    enabled: node.actor.enabled,
+                    visible: node.actor.visible,
+                    pickable: node.actor.pickable,
+                    tags: node.actor.tags().map(str::to_string).collect(),
+                })
+                .collect(),
+            rng: self.rng.clone(),
+        }
+    }
+
+    /// Replaces the whole scene with the state captured by `snapshot`.
+    /// Actors spawned since are removed and ids continue where the snapshot
+    /// left off.
+    pub fn restore(&mut self, snapshot: &WorldSnapshot) {
+        self.next_id = snapshot.next_id;
+        self.rng = snapshot.rng.clone();
+        self.nodes = snapshot
+            .actors
+            .iter()
+            .map(|state| {
+                let mut actor = Actor::new();
+                actor.set_transform(state.transform);
+                actor.enabled = state.enabled;
+                actor.visible = state.visible;
+                actor.pickable = state.pickable;
+                for tag in &state.tags {
+                    actor.add_tag(tag.clone());
+                }
+                let node = SceneNode {
+                    actor,
+                    parent: state.parent,
+                    children: state.children.clone(),
+                    world: Cell::new(None),
+                };
+                (state.id, node)
+            })
+            .collect();
+        let mut tags = TagIndex::default();
+        for (id, node) in &self.nodes {
+            tags.insert(*id, &node.actor);
+        }
+        self.tags = RefCell::new(tags);
+    }
+
+    /// Whether the cached world matrix of `id` has to be recomputed.
+    pub fn is_dirty(&self, id: ActorId) -> bool {
+        self.nodes.get(&id).is_some_and(|node| node.world.get().is_none())
+    }
+
+    /// Marks `id` and all of its descendants as needing a new world matrix.
+    pub fn invalidate(&self, id: ActorId) {
+        let Some(node) = self.nodes.get(&id) else {
+            return;
+        };
+        // A child can only be cached if its parent is, so an already dirty
+        // node has an already dirty subtree.
+        if node.world.take().is_none() {
+            return;
+        }
+        for child in &node.children {
+            self.invalidate(*child);
+        }
+    }
+
+    /// Local-to-world matrix of `id`, recomputed only if it was invalidated.
+    pub fn world_matrix(&self, id: ActorId) -> Option<Matrix4> {
+        self.resolve_world(id, &|_, node| node.actor.get_transform().matrix())
+    }
+
+    /// World matrix of `id` between its state in `previous` (t = 0) and now
+    /// (t = 1), for rendering between fixed simulation steps: snapshot before
+    /// stepping, then draw with the leftover fraction of a step. Actors that
+    /// are not in `previous` use their current transform.
+    pub fn interpolated_world_matrix(&self, id: ActorId, previous: &WorldSnapshot, t: f32) -> Option<Matrix4> {
+        let node = self.nodes.get(&id)?;
+        let current = node.actor.get_transform();
+        let local = previous
+            .actor(id)
+            .map_or(current, |state| state.transform.lerp(&current, t))
+            .matrix();
+        Some(match node.parent.and_then(|parent| self.interpolated_world_matrix(parent, previous, t)) {
+            Some(parent_world) => local * parent_world,
+            None => local,
+        })
+    }
+
+    fn resolve_world(&self, id: ActorId, local: &impl Fn(ActorId, &SceneNode) -> Matrix4) -> Option<Matrix4> {
+        let node = self.nodes.get(&id)?;
+        if let Some(world) = node.world.get() {
+            return Some(world);
+        }
+        let local_matrix = local(id, node);
+        let world = match node.parent.and_then(|parent| self.resolve_world(parent, local)) {
+            Some(parent_world) => local_matrix * parent_world,
+            None => local_matrix,
+        };
+        node.world.set(Some(world));
+        Some(world)
+    }
+
+    /// Recomputes the world matrix of every invalidated actor, so later
+    /// `world_matrix` calls are plain cache reads.
+    pub fn refresh_world_matrices(&self) {
+        for id in self.ids() {
+            self.world_matrix(id);
+        }
+    }
+
+    /// Sets the local transform of many actors at once, then recomputes the
+    /// affected world matrices in a single pass. Unknown ids are ignored; if
+    /// an id appears twice the last transform wins.
+    pub fn update_transforms(&mut self, updates: &[(ActorId, Transform)]) {
+        for (id, transform) in updates {
+            if let Some(node) = self.nodes.get_mut(id) {
+                node.actor.set_transform(*transform);
+            }
+            self.invalidate(*id);
+        }
+        self.refresh_world_matrices();
+    }
+
+    /// Like `update_transforms`, but applies the transforms and builds the
+    /// local matrices of dirty actors on the rayon thread pool. Only the
+    /// parent-to-child composition stays sequential.
+    #[cfg(feature = "rayon")]
+    pub fn par_update_transforms(&mut self, updates: &[(ActorId, Transform)]) {
+        use rayon::prelude::*;
+
+        let updates: HashMap<ActorId, Transform> = updates.iter().copied().collect();
+        for id in updates.keys() {
+            self.invalidate(*id);
+        }
+
+        let locals: HashMap<ActorId, Matrix4> = self
+            .nodes
+            .par_iter_mut()
+            .filter_map(|(id, node)| {
+                if let Some(transform) = updates.get(id) {
+                    node.actor.set_transform(*transform);
+                }
+                node.world.get_mut().is_none().then(|| (*id, node.actor.get_transform().matrix()))
+            })
+            .collect();
+
+        for id in locals.keys() {
+            self.resolve_world(*id, &|id, node| {
+                locals.get(&id).copied().unwrap_or_else(|| node.actor.get_transform().matrix())
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::Rng;
+
+    fn actor_at(x: f32, y: f32, z: f32) -> Actor {
+        let mut actor = Actor::new();
+        actor.set_position(x, y, z);
+        actor
+    }
+
+    #[test]
+    fn lod_meshes_pick_each_actor_level_by_camera_distance() {
+        use crate::lod::LodLevel;
+
+        let lod = || {
+            Lod::new(vec![
+                LodLevel { mesh: "high", max_distance: 10.0 },
+                LodLevel { mesh: "low", max_distance: f32::INFINITY },
+            ])
+            .unwrap()
+        };
+        let mut scene = Scene::new();
+        let near = scene.spawn(actor_at(0.0, 0.0, 5.0));
+        let far = scene.spawn(actor_at(0.0, 0.0, 50.0));
+        let hidden = scene.spawn(actor_at(0.0, 0.0, 1.0));
+        scene.actor_mut(hidden).unwrap().set_visible(false);
+        let mut lods = BTreeMap::from([(near, lod()), (far, lod()), (hidden, lod())]);
+
+        assert_eq!(scene.lod_meshes(Vec3::ZERO, &mut lods), vec![(near, &"high"), (far, &"low")]);
+        // Moving the camera next to the far actor swaps the levels
+        assert_eq!(scene.lod_meshes(Vec3::new(0.0, 0.0, 50.0), &mut lods), vec![(near, &"low"), (far, &"high")]);
+    }
+
+    fn world_position(scene: &Scene, id: ActorId) -> Vector3 {
+        scene.world_matrix(id).unwrap().transform_point(Vector3::zero())
+    }
+
+    #[test]
+    fn child_world_matrix_includes_its_parent() {
+        let mut scene = Scene::new();
+        let parent = scene.spawn(actor_at(0.0, 2.0, 0.0));
+        let child = scene.spawn(actor_at(1.0, 0.0, 0.0));
+        assert!(scene.set_parent(child, Some(parent)));
+        assert!(world_position(&scene, child).approx_eq(&Vector3::new(1.0, 2.0, 0.0), 1e-6));
+    }
+
+    #[test]
+    fn moving_a_parent_dirties_its_children_but_not_unrelated_actors() {
+        let mut scene = Scene::new();
+        let parent = scene.spawn(actor_at(0.0, 0.0, 0.0));
+        let child = scene.spawn(actor_at(1.0, 0.0, 0.0));
+        let unrelated = scene.spawn(actor_at(5.0, 0.0, 0.0));
+        scene.set_parent(child, Some(parent));
+        scene.refresh_world_matrices();
+        assert!(!scene.is_dirty(parent) && !scene.is_dirty(child) && !scene.is_dirty(unrelated));
+
+        scene.set_local_transform(parent, actor_at(0.0, 3.0, 0.0).get_transform());
+        assert!(scene.is_dirty(parent));
+        assert!(scene.is_dirty(child));
+        assert!(!scene.is_dirty(unrelated));
+
+        assert!(world_position(&scene, child).approx_eq(&Vector3::new(1.0, 3.0, 0.0), 1e-6));
+        assert!(!scene.is_dirty(parent));
+    }
+
+    #[test]
+    fn moving_a_child_leaves_its_parent_cached() {
+        let mut scene = Scene::new();
+        let parent = scene.spawn(actor_at(0.0, 0.0, 0.0));
+        let child = scene.spawn(actor_at(1.0, 0.0, 0.0));
+        scene.set_parent(child, Some(parent));
+        scene.refresh_world_matrices();
+
+        scene.set_local_transform(child, actor_at(2.0, 0.0, 0.0).get_transform());
+        assert!(scene.is_dirty(child));
+        assert!(!scene.is_dirty(parent));
+    }
+
+    #[test]
+    fn reparenting_refuses_cycles() {
+        let mut scene = Scene::new();
+        let a = scene.spawn(Actor::new());
+        let b = scene.spawn(Actor::new());
+        assert!(scene.set_parent(b, Some(a)));
+        assert!(!scene.set_parent(a, Some(b)));
+        assert!(!scene.set_parent(a, Some(a)));
+        assert_eq!(scene.parent(a), None);
+        assert_eq!(scene.children(a), &[b]);
+    }
+
+    #[test]
+    fn despawning_a_parent_removes_its_subtree() {
+        let mut scene = Scene::new();
+        let parent = scene.spawn(Actor::new());
+        let child = scene.spawn(Actor::new());
+        let other = scene.spawn(Actor::new());
+        scene.set_parent(child, Some(parent));
+        assert!(scene.despawn(parent).is_some());
+        assert_eq!(scene.ids().collect::<Vec<_>>(), vec![other]);
+        assert_eq!(scene.world_matrix(child), None);
+    }
+
+    #[test]
+    fn disabled_subtrees_are_not_updated() {
+        let mut scene = Scene::new();
+        let parent = scene.spawn(Actor::new());
+        let child = scene.spawn(Actor::new());
+        let other = scene.spawn(Actor::new());
+        scene.set_parent(child, Some(parent));
+        scene.actor_mut(parent).unwrap().set_enabled(false);
+
+        let mut updated = Vec::new();
+        scene.update(0.016, |id, _, _| updated.push(id));
+        assert_eq!(updated, vec![other]);
+        assert!(!scene.is_active(child));
+
+        scene.actor_mut(parent).unwrap().set_enabled(true);
+        updated.clear();
+        scene.update(0.016, |id, _, _| updated.push(id));
+        assert_eq!(updated, vec![parent, child, other]);
+    }
+
+    #[test]
+    fn invisible_or_disabled_subtrees_are_not_rendered() {
+        let mut scene = Scene::new();
+        let hidden = scene.spawn(Actor::new());
+        let hidden_child = scene.spawn(Actor::new());
+        let disabled = scene.spawn(Actor::new());
+        let shown = scene.spawn(Actor::new());
+        scene.set_parent(hidden_child, Some(hidden));
+        scene.actor_mut(hidden).unwrap().set_visible(false);
+        scene.actor_mut(disabled).unwrap().set_enabled(false);
+
+        assert_eq!(scene.rendered().collect::<Vec<_>>(), vec![shown]);
+        // Hidden actors are still updated
+        assert!(scene.is_active(hidden_child));
+    }
+
+    /// One simulation step: every active actor moves by an offset drawn from
+    /// the scene's own `Rng`.
+    fn step(scene: &mut Scene) {
+        let count = scene.len();
+        let rng = scene.rng_mut();
+        let mut offsets: Vec<[f32; 3]> =
+            (0..count).map(|_| [rng.next_f32(), rng.next_f32(), rng.next_f32()]).collect();
+        scene.update(1.0 / 60.0, |_, actor, _| {
+            let [x, y, z] = offsets.pop().unwrap();
+            actor.translate(x, y, z);
+        });
+    }
+
+    fn simulated_scene() -> Scene {
+        let mut scene = Scene::with_seed(7);
+        let parent = scene.spawn(actor_at(1.0, 2.0, 3.0));
+        let mut child = actor_at(0.0, 1.0, 0.0);
+        child.add_tag("enemy");
+        let child = scene.spawn(child);
+        scene.set_parent(child, Some(parent));
+        let mut hidden = Actor::new();
+        hidden.set_visible(false);
+        scene.spawn(hidden);
+        scene
+    }
+
+    #[test]
+    fn restoring_a_snapshot_undoes_a_step() {
+        let mut scene = simulated_scene();
+        let before = scene.snapshot();
+        assert_eq!(before.rng, Rng::new(7));
+        let world_before: Vec<_> = scene.ids().map(|id| scene.world_matrix(id)).collect();
+
+        step(&mut scene);
+        scene.spawn(Actor::new());
+        assert_ne!(scene.snapshot(), before);
+        assert_ne!(scene.rng(), &before.rng);
+
+        scene.restore(&before);
+        assert_eq!(scene.snapshot(), before);
+        assert_eq!(scene.rng(), &Rng::new(7));
+        assert_eq!(scene.ids().map(|id| scene.world_matrix(id)).collect::<Vec<_>>(), world_before);
+        assert_eq!(scene.find_by_tag("enemy").len(), 1);
+
+        // Replaying from the snapshot is deterministic
+        let mut replay = simulated_scene();
+        step(&mut scene);
+        step(&mut replay);
+        assert_eq!(scene.snapshot(), replay.snapshot());
+    }
+
+    #[test]
+    fn ids_continue_after_the_snapshot() {
+        let mut scene = simulated_scene();
+        let snapshot = scene.snapshot();
+        let discarded = scene.spawn(Actor::new());
+        scene.restore(&snapshot);
+        assert_eq!(scene.spawn(Actor::new()), discarded);
+    }
+
+    #[test]
+    fn snapshot_survives_a_json_round_trip() {
+        let mut scene = simulated_scene();
+        step(&mut scene);
+        let snapshot = scene.snapshot();
+        let json = serde_json::to_string(&snapshot).unwrap();
+        assert_eq!(serde_json::from_str::<WorldSnapshot>(&json).unwrap(), snapshot);
+    }
+
+    #[test]
+    fn interpolation_blends_from_the_previous_snapshot() {
+        let mut scene = Scene::new();
+        let id = scene.spawn(actor_at(0.0, 0.0, 0.0));
+        let previous = scene.snapshot();
+        scene.set_local_transform(id, actor_at(2.0, 0.0, 0.0).get_transform());
+        let halfway = scene.interpolated_world_matrix(id, &previous, 0.5).unwrap();
+        assert!(halfway.transform_point(Vector3::zero()).approx_eq(&Vector3::new(1.0, 0.0, 0.0), 1e-6));
+    }
+
+    /// 100 roots with nine children each, and a random new transform for
+    /// every one of the 1000 actors.
+    fn bulk_scene() -> (Scene, Vec<(ActorId, Transform)>) {
+        let mut scene = Scene::new();
+        let mut ids = Vec::new();
+        for _ in 0..100 {
+            let root = scene.spawn(Actor::new());
+            ids.push(root);
+            for _ in 0..9 {
+                let child = scene.spawn(Actor::new());
+                scene.set_parent(child, Some(root));
+                ids.push(child);
+            }
+        }
+        scene.refresh_world_matrices();
+
+        let mut rng = Rng::new(99);
+        let mut random = |scale: f32| {
+            Vector3::new(rng.range(-scale, scale), rng.range(-scale, scale), rng.range(-scale, scale))
+        };
+        let updates = ids
+            .into_iter()
+            .map(|id| (id, Transform::new(random(10.0), random(3.0), Vector3::one())))
+            .collect();
+        (scene, updates)
+    }
+
+    #[test]
+    fn bulk_update_matches_individual_updates() {
+        let (mut bulk, updates) = bulk_scene();
+        let (mut individual, _) = bulk_scene();
+        assert_eq!(updates.len(), 1000);
+
+        bulk.update_transforms(&updates);
+        for (id, transform) in &updates {
+            individual.set_local_transform(*id, *transform);
+        }
+        for (id, _) in &updates {
+            assert!(!bulk.is_dirty(*id));
+            assert_eq!(bulk.world_matrix(*id), individual.world_matrix(*id));
+        }
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn parallel_bulk_update_matches_individual_updates() {
+        let (mut parallel, updates) = bulk_scene();
+        let (mut individual, _) = bulk_scene();
+        parallel.par_update_transforms(&updates);
+        for (id, transform) in &updates {
+            individual.set_local_transform(*id, *transform);
+        }
+        for (id, _) in &updates {
+            assert_eq!(parallel.world_matrix(*id), individual.world_matrix(*id));
+        }
+    }
+
+    #[test]
+    fn bulk_update_ignores_unknown_ids_and_keeps_the_last_duplicate() {
+        let mut scene = Scene::new();
+        let id = scene.spawn(Actor::new());
+        let gone = scene.spawn(Actor::new());
+        scene.despawn(gone);
+        scene.update_transforms(&[
+            (id, actor_at(1.0, 0.0, 0.0).get_transform()),
+            (gone, actor_at(5.0, 0.0, 0.0).get_transform()),
+            (id, actor_at(2.0, 0.0, 0.0).get_transform()),
+        ]);
+        assert_eq!(scene.len(), 1);
+        assert!(world_position(&scene, id).approx_eq(&Vector3::new(2.0, 0.0, 0.0), 1e-6));
+    }
+
+    #[test]
+    fn pick_returns_the_nearer_of_two_overlapping_actors() {
+        let cube = MeshData::cube();
+        let bounds = BoundingSphere::new(Vector3::zero(), 0.9);
+        let mut scene = Scene::new();
+        let far = scene.spawn(actor_at(0.2, 0.0, 5.0));
+        let near = scene.spawn(actor_at(0.0, 0.1, 3.0));
+        let ray = Ray::new(Vector3::zero(), Vector3::new(0.0, 0.0, 1.0));
+
+        let (id, hit) = scene.pick(&ray, |_| Some((&cube, bounds))).unwrap();
+        assert_eq!(id, near);
+        assert!((hit.distance - 2.5).abs() < 1e-5);
+        assert!(hit.normal.approx_eq(&Vector3::new(0.0, 0.0, -1.0), 1e-5));
+
+        scene.actor_mut(near).unwrap().set_pickable(false);
+        let (id, hit) = scene.pick(&ray, |_| Some((&cube, bounds))).unwrap();
+        assert_eq!(id, far);
+        assert!((hit.distance - 4.5).abs() < 1e-5);
+
+        scene.actor_mut(far).unwrap().set_visible(false);
+        assert!(scene.pick(&ray, |_| Some((&cube, bounds))).is_none());
+    }
+
+    #[test]
+    fn pick_skips_actors_without_geometry_and_misses() {
+        let cube = MeshData::cube();
+        let bounds = BoundingSphere::new(Vector3::zero(), 0.9);
+        let mut scene = Scene::new();
+        let empty = scene.spawn(actor_at(0.0, 0.0, 2.0));
+        let solid = scene.spawn(actor_at(0.0, 0.0, 4.0));
+        let shape = |id| (id != empty).then_some((&cube, bounds));
+
+        let forward = Ray::new(Vector3::zero(), Vector3::new(0.0, 0.0, 1.0));
+        assert_eq!(scene.pick(&forward, shape).map(|(id, _)| id), Some(solid));
+        let sideways = Ray::new(Vector3::zero(), Vector3::new(1.0, 0.0, 0.0));
+        assert!(scene.pick(&sideways, shape).is_none());
+    }
+
+    fn tagged(tag: &str) -> Actor {
+        let mut actor = Actor::new();
+        actor.add_tag(tag);
+        actor
+    }
+
+    #[test]
+    fn find_by_tag_returns_each_tag_set_and_follows_despawns() {
+        let mut scene = Scene::new();
+        let enemies: Vec<ActorId> = (0..3).map(|_| scene.spawn(tagged("enemy"))).collect();
+        let player = scene.spawn(tagged("player"));
+        scene.spawn(Actor::new());
+
+        assert_eq!(scene.find_by_tag("enemy"), enemies);
+        assert_eq!(scene.find_by_tag("player"), vec![player]);
+        assert!(scene.find_by_tag("pickup").is_empty());
+        assert!(scene.has_tag(player, "player"));
+        assert!(!scene.has_tag(player, "enemy"));
+
+        scene.despawn(enemies[1]);
+        assert_eq!(scene.find_by_tag("enemy"), vec![enemies[0], enemies[2]]);
+    }
+
+    #[test]
+    fn retagging_through_actor_mut_updates_the_index() {
+        let mut scene = Scene::new();
+        let id = scene.spawn(tagged("enemy"));
+        let actor = scene.actor_mut(id).unwrap();
+        actor.remove_tag("enemy");
+        actor.add_tag("ally");
+        assert!(scene.find_by_tag("enemy").is_empty());
+        assert_eq!(scene.find_by_tag("ally"), vec![id]);
+    }
+
+    #[test]
+    fn despawning_a_parent_untags_its_children() {
+        let mut scene = Scene::new();
+        let parent = scene.spawn(Actor::new());
+        let child = scene.spawn(tagged("enemy"));
+        scene.set_parent(child, Some(parent));
+        scene.despawn(parent);
+        assert!(scene.find_by_tag("enemy").is_empty());
+    }
+
+    #[test]
+    fn updates_that_change_nothing_keep_caches_and_the_tag_index() {
+        let mut scene = Scene::new();
+        let parent = scene.spawn(actor_at(1.0, 0.0, 0.0));
+        let mut child = Actor::new();
+        child.add_tag("enemy");
+        let child = scene.spawn(child);
+        scene.set_parent(child, Some(parent));
+        scene.world_matrix(child);
+        assert_eq!(scene.find_by_tag("enemy"), vec![child]);
+
+        scene.update(0.016, |_, _, _| {});
+        assert!(scene.nodes[&parent].world.get().is_some());
+        assert!(scene.nodes[&child].world.get().is_some());
+        assert!(scene.tags.borrow().stale.is_empty());
+    }
+
+    #[test]
+    fn updates_that_move_or_retag_an_actor_are_picked_up() {
+        let mut scene = Scene::new();
+        let parent = scene.spawn(Actor::new());
+        let mut child = Actor::new();
+        child.add_tag("enemy");
+        let child = scene.spawn(child);
+        scene.set_parent(child, Some(parent));
+        scene.world_matrix(child);
+
+        scene.update(0.016, |id, actor, _| {
+            if id == parent {
+                actor.translate(0.0, 2.0, 0.0);
+            } else {
+                actor.remove_tag("enemy");
+                actor.add_tag("ally");
+            }
+        });
+        assert_eq!(world_position(&scene, child), Vector3::new(0.0, 2.0, 0.0));
+        assert!(scene.find_by_tag("enemy").is_empty());
+        assert_eq!(scene.find_by_tag("ally"), vec![child]);
+    }
+}
@@ -0,0 +1,272 @@
+use bytemuck::{Pod, Zeroable};
+use glam::Vec3;
+use wgpu::util::DeviceExt;
+
+use crate::camera::Camera;
+use crate::engine::render::config::Labels;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BillboardMode {
+    /// Fully faces the camera; for particles and sprites.
+    #[default]
+    Spherical,
+    /// Only turns about the world Y axis; for trees and grass that must stay upright.
+    Cylindrical,
+}
+
+/// Orientation of a billboard quad at `position`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BillboardBasis {
+    pub right: Vec3,
+    pub up: Vec3,
+    /// Points from the quad towards the camera.
+    pub normal: Vec3,
+}
+
+/// Computes the same basis `billboard.wgsl` uses, e.g. for picking or sorting.
+pub fn billboard_basis(mode: BillboardMode, camera: &Camera, position: Vec3) -> BillboardBasis {
+    let mut to_camera = camera.position() - position;
+    let mut up = camera.up();
+    if mode == BillboardMode::Cylindrical {
+        to_camera.y = 0.0;
+        up = Vec3::Y;
+    }
+    let normal = to_camera.try_normalize().unwrap_or(Vec3::Z);
+    // Fall back to another reference when looking straight along `up`
+    let right = up
+        .cross(normal)
+        .try_normalize()
+        .unwrap_or_else(|| normal.any_orthonormal_vector());
+    BillboardBasis {
+        right,
+        up: normal.cross(right),
+        normal,
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, Pod, Zeroable)]
+pub struct BillboardInstance {
+    pub position: [f32; 3],
+    pub size: [f32; 2],
+    pub color: [f32; 4],
+}
+
+impl BillboardInstance {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 3] =
+        wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x2, 2 => Float32x4];
+
+    pub fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<BillboardInstance>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+struct BillboardUniform {
+    view_proj: [[f32; 4]; 4],
+    camera_position: [f32; 3],
+    cylindrical: u32,
+    camera_up: [f32; 3],
+    _padding: f32,
+}
+
+/// Draws many camera-facing quads with one instanced draw call.
+pub struct BillboardRenderer {
+    pipeline: wgpu::RenderPipeline,
+    uniform_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    instance_buffer: Option<wgpu::Buffer>,
+    num_instances: u32,
+    pub mode: BillboardMode,
+    labels: Labels,
+}
+
+impl BillboardRenderer {
+    pub fn new(device: &wgpu::Device, labels: &Labels, config: &wgpu::SurfaceConfiguration, sample_count: u32) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(&labels.label("Billboard Shader")),
+            source: wgpu::ShaderSource::Wgsl(include_str!("billboard.wgsl").into()),
+        });
+
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&labels.label("Billboard Uniform Buffer")),
+            contents: bytemuck::cast_slice(&[BillboardUniform::zeroed()]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+            label: Some(&labels.label("billboard_bind_group_layout")),
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform_buffer.as_entire_binding(),
+            }],
+            label: Some(&labels.label("billboard_bind_group")),
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some(&labels.label("Billboard Pipeline Layout")),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(&labels.label("Billboard Pipeline")),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[BillboardInstance::desc()],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: config.format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: None,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: wgpu::TextureFormat::Depth32Float,
+                // Blended quads test against the scene but don't occlude each other
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: sample_count,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+            cache: None,
+        });
+
+        Self {
+            pipeline,
+            uniform_buffer,
+            bind_group,
+            instance_buffer: None,
+            num_instances: 0,
+            mode: BillboardMode::default(),
+            labels: labels.clone(),
+        }
+    }
+
+    /// Replaces the billboards to draw, growing the instance buffer if needed.
+    pub fn set_instances(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, instances: &[BillboardInstance]) {
+        self.num_instances = instances.len() as u32;
+        if instances.is_empty() {
+            return;
+        }
+        let size = std::mem::size_of_val(instances) as wgpu::BufferAddress;
+        if self.instance_buffer.as_ref().map_or(true, |buffer| buffer.size() < size) {
+            self.instance_buffer = Some(device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(&self.labels.label("Billboard Instance Buffer")),
+                size: size.next_power_of_two(),
+                usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }));
+        }
+        if let Some(buffer) = &self.instance_buffer {
+            queue.write_buffer(buffer, 0, bytemuck::cast_slice(instances));
+        }
+    }
+
+    pub fn prepare(&self, queue: &wgpu::Queue, camera: &Camera) {
+        let uniform = BillboardUniform {
+            view_proj: camera.build_view_projection_matrix().to_cols_array_2d(),
+            camera_position: camera.position().to_array(),
+            cylindrical: (self.mode == BillboardMode::Cylindrical) as u32,
+            camera_up: camera.up().to_array(),
+            _padding: 0.0,
+        };
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[uniform]));
+    }
+
+    pub fn draw(&self, render_pass: &mut wgpu::RenderPass) {
+        let Some(instance_buffer) = &self.instance_buffer else {
+            return;
+        };
+        if self.num_instances == 0 {
+            return;
+        }
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.set_vertex_buffer(0, instance_buffer.slice(..));
+        render_pass.draw(0..6, 0..self.num_instances);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_orthonormal(basis: &BillboardBasis) {
+        for axis in [basis.right, basis.up, basis.normal] {
+            assert!((axis.length() - 1.0).abs() < 1e-5, "{basis:?}");
+        }
+        assert!(basis.right.dot(basis.up).abs() < 1e-5, "{basis:?}");
+        assert!(basis.right.dot(basis.normal).abs() < 1e-5, "{basis:?}");
+        assert!(basis.up.dot(basis.normal).abs() < 1e-5, "{basis:?}");
+    }
+
+    #[test]
+    fn spherical_basis_is_orthonormal_and_faces_the_camera() {
+        let camera = Camera::new(Vec3::new(3.0, 4.0, 5.0), 1.0);
+        let position = Vec3::new(-1.0, 0.5, 2.0);
+        let basis = billboard_basis(BillboardMode::Spherical, &camera, position);
+        assert_orthonormal(&basis);
+        let to_camera = (camera.position() - position).normalize();
+        assert!(basis.normal.abs_diff_eq(to_camera, 1e-5));
+    }
+
+    #[test]
+    fn cylindrical_basis_stays_upright() {
+        let camera = Camera::new(Vec3::new(3.0, 4.0, 5.0), 1.0);
+        let basis = billboard_basis(BillboardMode::Cylindrical, &camera, Vec3::ZERO);
+        assert_orthonormal(&basis);
+        assert!(basis.up.abs_diff_eq(Vec3::Y, 1e-5));
+        assert_eq!(basis.normal.y, 0.0);
+        assert!(basis.normal.abs_diff_eq(Vec3::new(3.0, 0.0, 5.0).normalize(), 1e-5));
+    }
+
+    #[test]
+    fn camera_straight_above_still_gives_an_orthonormal_basis() {
+        let camera = Camera::new(Vec3::new(0.0, 5.0, 0.0), 1.0);
+        let basis = billboard_basis(BillboardMode::Spherical, &camera, Vec3::ZERO);
+        assert_orthonormal(&basis);
+        assert!(basis.normal.abs_diff_eq(Vec3::Y, 1e-5));
+    }
+}
@@ -0,0 +1,64 @@
+// Camera-facing quads expanded from per-instance data in the vertex shader.
+
+struct BillboardUniform {
+    view_proj: mat4x4<f32>,
+    camera_position: vec3<f32>,
+    // 0 = spherical (faces the camera fully), 1 = cylindrical (rotates about Y only)
+    cylindrical: u32,
+    camera_up: vec3<f32>,
+    padding: f32,
+};
+
+@group(0) @binding(0)
+var<uniform> u: BillboardUniform;
+
+struct InstanceInput {
+    @location(0) position: vec3<f32>,
+    @location(1) size: vec2<f32>,
+    @location(2) color: vec4<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+    @location(1) uv: vec2<f32>,
+};
+
+// Two counter-clockwise triangles making up a unit quad centred on the origin.
+const corners: array<vec2<f32>, 6> = array<vec2<f32>, 6>(
+    vec2<f32>(-0.5, -0.5),
+    vec2<f32>( 0.5, -0.5),
+    vec2<f32>( 0.5,  0.5),
+    vec2<f32>( 0.5,  0.5),
+    vec2<f32>(-0.5,  0.5),
+    vec2<f32>(-0.5, -0.5)
+);
+
+@vertex
+fn vs_main(@builtin(vertex_index) vid: u32, instance: InstanceInput) -> VertexOutput {
+    var to_camera = u.camera_position - instance.position;
+    var up = u.camera_up;
+    if (u.cylindrical != 0u) {
+        to_camera.y = 0.0;
+        up = vec3<f32>(0.0, 1.0, 0.0);
+    }
+    let normal = normalize(to_camera);
+    let right = normalize(cross(up, normal));
+    let billboard_up = cross(normal, right);
+
+    let corner = corners[vid];
+    let world_position = instance.position
+        + right * corner.x * instance.size.x
+        + billboard_up * corner.y * instance.size.y;
+
+    var out: VertexOutput;
+    out.clip_position = u.view_proj * vec4<f32>(world_position, 1.0);
+    out.color = instance.color;
+    out.uv = vec2<f32>(corner.x + 0.5, 0.5 - corner.y);
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return in.color;
+}
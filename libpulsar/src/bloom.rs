@@ -0,0 +1,439 @@
+use bytemuck::{Pod, Zeroable};
+use wgpu::util::DeviceExt;
+
+use crate::engine::render::config::Labels;
+use crate::resources::{ResourceCategory, Tracked};
+
+/// Format of the HDR scene target and the bloom mip chain; keeps values
+/// above 1.0 so bright surfaces can glow.
+pub const HDR_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+/// Longest mip chain the bloom blurs through. Each level doubles the reach.
+pub const MAX_BLOOM_MIPS: u32 = 6;
+
+/// Levels in the bloom chain for a `width`x`height` scene. The first level is
+/// half the scene's size and each one halves again, stopping before a side
+/// would drop below one pixel.
+pub fn bloom_mip_count(width: u32, height: u32) -> u32 {
+    let smallest = (width.min(height) / 2).max(1);
+    (u32::BITS - smallest.leading_zeros()).min(MAX_BLOOM_MIPS)
+}
+
+/// Size of bloom mip `level` for a `width`x`height` scene.
+pub fn bloom_mip_size(width: u32, height: u32, level: u32) -> (u32, u32) {
+    ((width >> (level + 1)).max(1), (height >> (level + 1)).max(1))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BloomSettings {
+    /// Brightness a pixel needs before it starts to glow; 1.0 keeps anything
+    /// an LDR surface could show out of the bloom.
+    pub threshold: f32,
+    /// Strength of the glow added back onto the scene.
+    pub intensity: f32,
+    /// Spread of each upsampling tap in texels; larger values give a wider,
+    /// softer glow.
+    pub radius: f32,
+}
+
+impl Default for BloomSettings {
+    fn default() -> Self {
+        Self {
+            threshold: 1.0,
+            intensity: 0.3,
+            radius: 1.0,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+struct BloomUniform {
+    threshold: f32,
+    intensity: f32,
+    radius: f32,
+    _padding: f32,
+}
+
+impl From<BloomSettings> for BloomUniform {
+    fn from(settings: BloomSettings) -> Self {
+        Self {
+            threshold: settings.threshold.max(0.0),
+            intensity: settings.intensity.max(0.0),
+            radius: settings.radius.max(0.0),
+            _padding: 0.0,
+        }
+    }
+}
+
+/// Bloom over an HDR scene texture: extracts pixels above the threshold,
+/// blurs them through a mip chain and adds the result back while writing
+/// the scene to the output target.
+pub struct BloomPass {
+    settings: BloomSettings,
+    uniform_buffer: wgpu::Buffer,
+    prefilter: wgpu::RenderPipeline,
+    downsample: wgpu::RenderPipeline,
+    upsample: wgpu::RenderPipeline,
+    composite: wgpu::RenderPipeline,
+    mips: (Tracked<wgpu::Texture>, Vec<wgpu::TextureView>),
+    prefilter_bind_group: wgpu::BindGroup,
+    /// Reads level `i`, for drawing into level `i + 1`.
+    down_bind_groups: Vec<wgpu::BindGroup>,
+    /// Reads level `i + 1`, for drawing into level `i`.
+    up_bind_groups: Vec<wgpu::BindGroup>,
+    composite_bind_group: wgpu::BindGroup,
+    labels: Labels,
+}
+
+impl BloomPass {
+    /// `scene` is a `width`x`height` `HDR_FORMAT` view; the composite writes
+    /// to targets of `output_format`. Recreate the pass when the scene
+    /// texture changes.
+    pub fn new(
+        device: &wgpu::Device,
+        labels: &Labels,
+        output_format: wgpu::TextureFormat,
+        scene: &wgpu::TextureView,
+        width: u32,
+        height: u32,
+        settings: BloomSettings,
+    ) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(&labels.label("Bloom Shader")),
+            source: wgpu::ShaderSource::Wgsl(include_str!("bloom.wgsl").into()),
+        });
+
+        let texture_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                texture_entry(1),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                texture_entry(3),
+            ],
+            label: Some(&labels.label("bloom_bind_group_layout")),
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some(&labels.label("Bloom Pipeline Layout")),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let create_pipeline = |label, entry_point, format, blend| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(&labels.label(label)),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: Some("vs_main"),
+                    buffers: &[],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: Some(entry_point),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format,
+                        blend: Some(blend),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+                cache: None,
+            })
+        };
+        let additive = wgpu::BlendState {
+            color: wgpu::BlendComponent {
+                src_factor: wgpu::BlendFactor::One,
+                dst_factor: wgpu::BlendFactor::One,
+                operation: wgpu::BlendOperation::Add,
+            },
+            alpha: wgpu::BlendComponent::REPLACE,
+        };
+        let prefilter = create_pipeline("Bloom Prefilter Pipeline", "fs_prefilter", HDR_FORMAT, wgpu::BlendState::REPLACE);
+        let downsample = create_pipeline("Bloom Downsample Pipeline", "fs_downsample", HDR_FORMAT, wgpu::BlendState::REPLACE);
+        let upsample = create_pipeline("Bloom Upsample Pipeline", "fs_upsample", HDR_FORMAT, additive);
+        let composite = create_pipeline("Bloom Composite Pipeline", "fs_composite", output_format, wgpu::BlendState::REPLACE);
+
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&labels.label("Bloom Uniform Buffer")),
+            contents: bytemuck::bytes_of(&BloomUniform::from(settings)),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some(&labels.label("Bloom Sampler")),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let mips = Self::create_mips(device, labels, width, height);
+        let bind_group = |source: &wgpu::TextureView| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout: &bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: uniform_buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::TextureView(source),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: wgpu::BindingResource::Sampler(&sampler),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 3,
+                        resource: wgpu::BindingResource::TextureView(scene),
+                    },
+                ],
+                label: Some(&labels.label("bloom_bind_group")),
+            })
+        };
+        let levels = &mips.1;
+        let prefilter_bind_group = bind_group(scene);
+        let down_bind_groups = levels[..levels.len() - 1].iter().map(bind_group).collect();
+        let up_bind_groups = levels[1..].iter().map(bind_group).collect();
+        let composite_bind_group = bind_group(&levels[0]);
+
+        Self {
+            settings,
+            uniform_buffer,
+            prefilter,
+            downsample,
+            upsample,
+            composite,
+            mips,
+            prefilter_bind_group,
+            down_bind_groups,
+            up_bind_groups,
+            composite_bind_group,
+            labels: labels.clone(),
+        }
+    }
+
+    fn create_mips(
+        device: &wgpu::Device,
+        labels: &Labels,
+        width: u32,
+        height: u32,
+    ) -> (Tracked<wgpu::Texture>, Vec<wgpu::TextureView>) {
+        let mip_level_count = bloom_mip_count(width, height);
+        let (mip_width, mip_height) = bloom_mip_size(width, height, 0);
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(&labels.label("Bloom Mip Chain")),
+            size: wgpu::Extent3d {
+                width: mip_width,
+                height: mip_height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: HDR_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let views = (0..mip_level_count)
+            .map(|level| {
+                texture.create_view(&wgpu::TextureViewDescriptor {
+                    label: Some(&labels.label("Bloom Mip View")),
+                    base_mip_level: level,
+                    mip_level_count: Some(1),
+                    ..Default::default()
+                })
+            })
+            .collect();
+        (Tracked::texture(texture, ResourceCategory::RenderTarget), views)
+    }
+
+    pub fn settings(&self) -> BloomSettings {
+        self.settings
+    }
+
+    pub fn set_settings(&mut self, queue: &wgpu::Queue, settings: BloomSettings) {
+        self.settings = settings;
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::bytes_of(&BloomUniform::from(settings)));
+    }
+
+    pub fn mip_count(&self) -> u32 {
+        self.mips.1.len() as u32
+    }
+
+    /// Records the whole bloom chain, writing the scene plus glow to `target`.
+    pub fn run(&self, encoder: &mut wgpu::CommandEncoder, target: &wgpu::TextureView) {
+        let levels = &self.mips.1;
+        let label = |name| self.labels.label(name);
+        Self::draw(encoder, &label("Bloom Prefilter Pass"), &self.prefilter, &self.prefilter_bind_group, &levels[0], true);
+        for (bind_group, level) in self.down_bind_groups.iter().zip(&levels[1..]) {
+            Self::draw(encoder, &label("Bloom Downsample Pass"), &self.downsample, bind_group, level, true);
+        }
+        // Smallest first, so each level carries the glow of all those below
+        for (bind_group, level) in self.up_bind_groups.iter().zip(levels).rev() {
+            Self::draw(encoder, &label("Bloom Upsample Pass"), &self.upsample, bind_group, level, false);
+        }
+        Self::draw(encoder, &label("Bloom Composite Pass"), &self.composite, &self.composite_bind_group, target, true);
+    }
+
+    fn draw(
+        encoder: &mut wgpu::CommandEncoder,
+        label: &str,
+        pipeline: &wgpu::RenderPipeline,
+        bind_group: &wgpu::BindGroup,
+        target: &wgpu::TextureView,
+        clear: bool,
+    ) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some(label),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: if clear {
+                        wgpu::LoadOp::Clear(wgpu::Color::BLACK)
+                    } else {
+                        wgpu::LoadOp::Load
+                    },
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_bind_group(0, bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render_target::RenderTarget;
+    use crate::test_gpu;
+
+    const SIZE: u32 = 32;
+    /// Half-float bit patterns.
+    const HALF_ONE: u16 = 0x3C00;
+
+    #[test]
+    fn mip_chain_halves_until_a_side_reaches_one_pixel() {
+        assert_eq!(bloom_mip_count(32, 32), 5);
+        assert_eq!(bloom_mip_count(1920, 1080), MAX_BLOOM_MIPS);
+        assert_eq!(bloom_mip_count(1, 1), 1);
+        assert_eq!(bloom_mip_size(32, 16, 0), (16, 8));
+        assert_eq!(bloom_mip_size(32, 16, 4), (1, 1));
+    }
+
+    /// Composites a black HDR scene with one pixel of brightness `value`
+    /// (as half-float bits) in the middle.
+    fn bloom_single_pixel(value: u16) -> Option<Vec<u8>> {
+        let (device, queue) = test_gpu::device()?;
+        let labels = Labels::default();
+        let size = wgpu::Extent3d {
+            width: SIZE,
+            height: SIZE,
+            depth_or_array_layers: 1,
+        };
+        let scene = device.create_texture(&wgpu::TextureDescriptor {
+            label: None,
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: HDR_FORMAT,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        let mut texels = vec![0u16; (SIZE * SIZE * 4) as usize];
+        let center = ((SIZE / 2 * SIZE + SIZE / 2) * 4) as usize;
+        texels[center..center + 4].copy_from_slice(&[value, value, value, HALF_ONE]);
+        queue.write_texture(
+            scene.as_image_copy(),
+            bytemuck::cast_slice(&texels),
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(SIZE * 8),
+                rows_per_image: Some(SIZE),
+            },
+            size,
+        );
+
+        let config = test_gpu::surface_config(SIZE, SIZE);
+        let target = RenderTarget::new(&device, &labels, &config, SIZE, SIZE);
+        let settings = BloomSettings {
+            intensity: 1.0,
+            ..Default::default()
+        };
+        let scene_view = scene.create_view(&wgpu::TextureViewDescriptor::default());
+        let bloom = BloomPass::new(&device, &labels, config.format, &scene_view, SIZE, SIZE, settings);
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        bloom.run(&mut encoder, target.color_view());
+        queue.submit(std::iter::once(encoder.finish()));
+        Some(test_gpu::read_pixels(&device, &queue, &target.color_texture.0))
+    }
+
+    fn red_at(pixels: &[u8], x: u32, y: u32) -> u8 {
+        pixels[((y * SIZE + x) * 4) as usize]
+    }
+
+    #[test]
+    fn bright_pixel_spreads_glow_into_its_neighbors() {
+        // 8.0, far above the threshold
+        let Some(pixels) = bloom_single_pixel(0x4800) else {
+            return;
+        };
+        let middle = SIZE / 2;
+        assert_eq!(red_at(&pixels, middle, middle), 255);
+        for (x, y) in [(middle + 2, middle), (middle - 2, middle), (middle, middle + 3)] {
+            assert!(red_at(&pixels, x, y) > 0, "no glow at ({x}, {y})");
+        }
+        // The glow fades with distance
+        assert!(red_at(&pixels, 0, 0) < red_at(&pixels, middle + 2, middle));
+    }
+
+    #[test]
+    fn pixels_below_the_threshold_do_not_glow() {
+        // 0.5
+        let Some(pixels) = bloom_single_pixel(0x3800) else {
+            return;
+        };
+        let middle = SIZE / 2;
+        assert!(red_at(&pixels, middle, middle).abs_diff(128) <= 1);
+        assert_eq!(red_at(&pixels, middle + 2, middle), 0);
+    }
+}
@@ -0,0 +1,79 @@
+// Bloom: a bright pass into the top of a mip chain, Gaussian downsampling to
+// the bottom, additive upsampling back to the top, then a composite over the
+// scene.
+
+struct BloomUniform {
+    threshold: f32,
+    intensity: f32,
+    radius: f32,
+    _padding: f32,
+};
+
+@group(0) @binding(0)
+var<uniform> bloom: BloomUniform;
+@group(0) @binding(1)
+var source: texture_2d<f32>;
+@group(0) @binding(2)
+var source_sampler: sampler;
+// The HDR scene; read by the bright pass (as `source`) and the composite.
+@group(0) @binding(3)
+var scene: texture_2d<f32>;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) vid: u32) -> VertexOutput {
+    // Vertices (0,0), (2,0), (0,2) cover the whole screen with one triangle.
+    let uv = vec2<f32>(f32((vid << 1u) & 2u), f32(vid & 2u));
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+    out.uv = vec2<f32>(uv.x, 1.0 - uv.y);
+    return out;
+}
+
+// 3x3 binomial (Gaussian) blur of `source` around `uv`, taps `spread` apart.
+fn gaussian(uv: vec2<f32>, spread: vec2<f32>) -> vec3<f32> {
+    var sum = vec3<f32>(0.0);
+    for (var y = -1; y <= 1; y = y + 1) {
+        for (var x = -1; x <= 1; x = x + 1) {
+            let weight = f32((2 - abs(x)) * (2 - abs(y)));
+            let offset = vec2<f32>(f32(x), f32(y)) * spread;
+            sum += textureSampleLevel(source, source_sampler, uv + offset, 0.0).rgb * weight;
+        }
+    }
+    return sum / 16.0;
+}
+
+fn texel_size() -> vec2<f32> {
+    return 1.0 / vec2<f32>(textureDimensions(source));
+}
+
+@fragment
+fn fs_prefilter(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = gaussian(in.uv, texel_size());
+    // Keep only the part of each pixel brighter than the threshold
+    let brightness = max(color.r, max(color.g, color.b));
+    let contribution = max(brightness - bloom.threshold, 0.0) / max(brightness, 0.0001);
+    return vec4<f32>(color * contribution, 1.0);
+}
+
+@fragment
+fn fs_downsample(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(gaussian(in.uv, texel_size()), 1.0);
+}
+
+// Blended additively onto the next larger mip.
+@fragment
+fn fs_upsample(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(gaussian(in.uv, texel_size() * bloom.radius), 1.0);
+}
+
+@fragment
+fn fs_composite(in: VertexOutput) -> @location(0) vec4<f32> {
+    let base = textureSampleLevel(scene, source_sampler, in.uv, 0.0);
+    let glow = textureSampleLevel(source, source_sampler, in.uv, 0.0).rgb;
+    return vec4<f32>(base.rgb + glow * bloom.intensity, base.a);
+}
@@ -1,50 +1,394 @@
-use glam::{Vec3, Mat4};
+use glam::{EulerRot, Vec3, Mat4};
 use bytemuck::{Pod, Zeroable};
 
+use crate::math::{perlin_1d, Handedness, Transform, Vector3, HANDEDNESS};
+
 #[repr(C)]
 #[derive(Debug, Copy, Clone, Pod, Zeroable)]
 pub struct CameraUniform {
     view_proj: [[f32; 4]; 4],
+    /// World-space eye position; `w` is padding.
+    position: [f32; 4],
 }
 
 impl CameraUniform {
     pub fn new() -> Self {
         Self {
             view_proj: Mat4::IDENTITY.to_cols_array_2d(),
+            position: [0.0, 0.0, 0.0, 1.0],
         }
     }
 
+    /// Column-major view-projection matrix, as uploaded.
+    pub fn view_proj(&self) -> [[f32; 4]; 4] {
+        self.view_proj
+    }
+
+    pub fn position(&self) -> Vec3 {
+        Vec3::new(self.position[0], self.position[1], self.position[2])
+    }
+
+    /// View-space distance from the eye to `point`, for ordering draws.
+    pub fn view_distance(&self, point: Vec3) -> f32 {
+        self.position().distance(point)
+    }
+
     pub fn update_view_proj(&mut self, camera: &Camera) {
         self.view_proj = camera.build_view_projection_matrix().to_cols_array_2d();
+        self.position = camera.position().extend(1.0).to_array();
+    }
+}
+
+/// How the camera maps view space onto the screen.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Projection {
+    #[default]
+    Perspective,
+    /// Parallel projection showing `height` world units from the bottom to the
+    /// top of the screen; the width follows from the aspect ratio.
+    Orthographic { height: f32 },
+}
+
+/// Trauma-driven camera shake for impacts and explosions.
+///
+/// Trauma (0..=1) is added by events and decays linearly. The offsets scale
+/// with trauma squared, so small hits barely register while big ones shake
+/// hard, and follow smooth noise rather than jumping every frame.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CameraShake {
+    trauma: f32,
+    /// Seconds full trauma takes to decay to zero.
+    pub duration: f32,
+    /// Positional offset in world units at full trauma.
+    pub max_offset: f32,
+    /// Yaw, pitch and roll offset in radians at full trauma.
+    pub max_angle: f32,
+    /// How fast the noise is sampled; higher values shake faster.
+    pub frequency: f32,
+    time: f32,
+}
+
+impl CameraShake {
+    pub fn trauma(&self) -> f32 {
+        self.trauma
+    }
+
+    /// Adds trauma; stacked shakes clamp at 1.
+    pub fn add(&mut self, trauma: f32) {
+        self.trauma = (self.trauma + trauma).clamp(0.0, 1.0);
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        self.time += dt;
+        self.trauma = (self.trauma - dt / self.duration.max(f32::EPSILON)).max(0.0);
+    }
+
+    /// Current positional offset and (yaw, pitch, roll) offset.
+    pub fn offsets(&self) -> (Vec3, Vec3) {
+        let shake = self.trauma * self.trauma;
+        let t = self.time * self.frequency;
+        let noise = |seed| perlin_1d(t, seed) * shake;
+        (
+            Vec3::new(noise(0), noise(1), noise(2)) * self.max_offset,
+            Vec3::new(noise(3), noise(4), noise(5)) * self.max_angle,
+        )
+    }
+}
+
+impl Default for CameraShake {
+    fn default() -> Self {
+        Self {
+            trauma: 0.0,
+            duration: 1.0,
+            max_offset: 0.3,
+            max_angle: 0.05,
+            frequency: 15.0,
+            time: 0.0,
+        }
     }
 }
 
 pub struct Camera {
-    pub position: Vec3,
-    pub target: Vec3,
-    pub up: Vec3,
+    /// Placement of the camera, which looks along the transform's forward
+    /// (+Z) axis with its +Y axis up. Scale is ignored.
+    pub transform: Transform,
     pub aspect: f32,
+    /// Vertical field of view in degrees, for perspective projection.
     pub fovy: f32,
+    /// Distance to the near clipping plane.
+    ///
+    /// Depth precision falls off roughly with `znear / distance`, so this value
+    /// matters far more than `zfar`: moving it from 0.1 to 1.0 removes more
+    /// z-fighting than any change to `zfar`. Keep it as large as the closest
+    /// geometry allows, and only raise `zfar` as much as the scene needs.
     pub znear: f32,
+    /// Distance to the far clipping plane; geometry beyond it is clipped.
     pub zfar: f32,
+    pub projection: Projection,
+    /// Maps `znear` to depth 1 and `zfar` to 0. Must match `Renderer::set_reverse_z`.
+    pub reverse_z: bool,
+    pub shake: CameraShake,
 }
 
 impl Camera {
+    /// Camera at `position` looking at the origin.
     pub fn new(position: Vec3, aspect: f32) -> Self {
+        let transform = Transform::looking_at(position.into(), Vector3::zero(), Vector3::up());
+        Self::from_transform(&transform, aspect)
+    }
+
+    /// Camera placed and oriented like `transform`, looking along its
+    /// forward (+Z) axis.
+    pub fn from_transform(transform: &Transform, aspect: f32) -> Self {
         Self {
-            position,
-            target: Vec3::ZERO,
-            up: Vec3::Y,
+            transform: *transform,
             aspect,
             fovy: 45.0,
             znear: 0.1,
-            zfar: 100.0,
+            zfar: 1000.0,
+            projection: Projection::Perspective,
+            reverse_z: false,
+            shake: CameraShake::default(),
+        }
+    }
+
+    pub fn position(&self) -> Vec3 {
+        self.transform.position.into()
+    }
+
+    pub fn set_position(&mut self, position: Vec3) {
+        self.transform.position = position.into();
+    }
+
+    /// Unit view direction.
+    pub fn forward(&self) -> Vec3 {
+        self.transform.forward().into()
+    }
+
+    /// Unit direction that points up on screen.
+    pub fn up(&self) -> Vec3 {
+        Vector3::up().transform_normal(&self.transform.rotation_matrix()).into()
+    }
+
+    /// Turns the camera towards `target`, with its up axis leaning towards
+    /// `up`. Looking at its own position keeps no rotation.
+    pub fn look_at(&mut self, target: Vec3, up: Vec3) {
+        let looking = Transform::looking_at(self.transform.position, target.into(), up.into());
+        self.transform.rotation = looking.rotation;
+    }
+
+    /// Starts or strengthens a camera shake; `trauma` of 1 is the strongest.
+    pub fn add_shake(&mut self, trauma: f32) {
+        self.shake.add(trauma);
+    }
+
+    /// Advances time-based effects such as shake.
+    pub fn update(&mut self, dt: f32) {
+        self.shake.update(dt);
+    }
+
+    /// Inverse of the camera's world matrix. View space follows the crate's
+    /// `HANDEDNESS`, like the projection: under `Handedness::Right` Z is
+    /// mirrored so the transform's forward axis looks down -Z while its right
+    /// axis stays on the right.
+    pub fn view_matrix(&self) -> Mat4 {
+        if self.shake.trauma() <= 0.0 {
+            return Self::view_from(&self.transform);
+        }
+        let (offset, angles) = self.shake.offsets();
+        let shaken = Transform {
+            position: self.transform.position + Vector3::from(offset),
+            ..self.transform
+        };
+        Mat4::from_euler(EulerRot::YXZ, angles.x, angles.y, angles.z) * Self::view_from(&shaken)
+    }
+
+    fn view_from(transform: &Transform) -> Mat4 {
+        let world = Transform {
+            scale: Vector3::one(),
+            ..*transform
+        }
+        .matrix();
+        // The rows of a row-vector matrix are glam's columns
+        let world = Mat4::from_cols_array_2d(&world.to_rows());
+        Mat4::from_scale(Vec3::new(1.0, 1.0, HANDEDNESS.forward().z)) * world.inverse()
+    }
+
+    pub fn projection_matrix(&self) -> Mat4 {
+        // Swapping the planes flips the depth range to far = 0, near = 1
+        let (near, far) = if self.reverse_z {
+            (self.zfar, self.znear)
+        } else {
+            (self.znear, self.zfar)
+        };
+        let fovy = self.fovy.to_radians();
+        match (self.projection, HANDEDNESS) {
+            (Projection::Perspective, Handedness::Left) => Mat4::perspective_lh(fovy, self.aspect, near, far),
+            (Projection::Perspective, Handedness::Right) => Mat4::perspective_rh(fovy, self.aspect, near, far),
+            (Projection::Orthographic { height }, handedness) => {
+                let half_height = height * 0.5;
+                let half_width = half_height * self.aspect;
+                let orthographic = match handedness {
+                    Handedness::Left => Mat4::orthographic_lh,
+                    Handedness::Right => Mat4::orthographic_rh,
+                };
+                orthographic(-half_width, half_width, -half_height, half_height, near, far)
+            }
         }
     }
 
     pub fn build_view_projection_matrix(&self) -> Mat4 {
-        let view = Mat4::look_at_rh(self.position, self.target, self.up);
-        let proj = Mat4::perspective_rh(self.fovy.to_radians(), self.aspect, self.znear, self.zfar);
-        proj * view
+        self.projection_matrix() * self.view_matrix()
+    }
+
+    /// World-space height of the view at `distance` from the camera.
+    pub fn visible_height_at(&self, distance: f32) -> f32 {
+        match self.projection {
+            Projection::Perspective => 2.0 * distance * (self.fovy.to_radians() * 0.5).tan(),
+            Projection::Orthographic { height } => height,
+        }
+    }
+
+    /// World-space size covering `pixels` at `distance` from the camera.
+    pub fn world_size_of_pixels(&self, pixels: f32, distance: f32, viewport_height: f32) -> f32 {
+        pixels * self.visible_height_at(distance) / viewport_height.max(1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Depth a view-space point `distance` in front of the camera lands at.
+    fn depth_at(camera: &Camera, distance: f32) -> f32 {
+        let forward = Vec3::from(HANDEDNESS.forward());
+        let clip = camera.projection_matrix() * (forward * distance).extend(1.0);
+        clip.z / clip.w
+    }
+
+    #[test]
+    fn near_and_far_default_to_a_tenth_and_a_thousand() {
+        let camera = Camera::new(Vec3::ZERO, 1.0);
+        assert_eq!((camera.znear, camera.zfar), (0.1, 1000.0));
+    }
+
+    #[test]
+    fn changing_far_changes_the_depth_range_entries() {
+        let mut camera = Camera::new(Vec3::ZERO, 1.0);
+        let before = camera.projection_matrix();
+        camera.zfar = 50.0;
+        let after = camera.projection_matrix();
+        assert_ne!(before.z_axis.z, after.z_axis.z);
+        assert_ne!(before.w_axis.z, after.w_axis.z);
+        // Field of view and aspect are untouched
+        assert_eq!(before.x_axis, after.x_axis);
+        assert_eq!(before.y_axis, after.y_axis);
+    }
+
+    #[test]
+    fn near_and_far_planes_map_to_the_depth_range() {
+        for projection in [Projection::Perspective, Projection::Orthographic { height: 10.0 }] {
+            let mut camera = Camera::new(Vec3::ZERO, 1.5);
+            camera.projection = projection;
+            camera.znear = 0.5;
+            camera.zfar = 200.0;
+            assert!(depth_at(&camera, 0.5).abs() < 1e-5, "{projection:?}");
+            assert!((depth_at(&camera, 200.0) - 1.0).abs() < 1e-5, "{projection:?}");
+        }
+    }
+
+    #[test]
+    fn reverse_z_puts_far_objects_at_smaller_depths() {
+        for projection in [Projection::Perspective, Projection::Orthographic { height: 10.0 }] {
+            let mut camera = Camera::new(Vec3::ZERO, 1.0);
+            camera.projection = projection;
+            camera.reverse_z = true;
+            assert!(depth_at(&camera, 500.0) < depth_at(&camera, 5.0), "{projection:?}");
+            assert!((depth_at(&camera, camera.znear) - 1.0).abs() < 1e-5, "{projection:?}");
+            assert!(depth_at(&camera, camera.zfar).abs() < 1e-5, "{projection:?}");
+        }
+    }
+
+    #[test]
+    fn trauma_decays_to_zero_over_the_duration() {
+        let mut shake = CameraShake {
+            duration: 2.0,
+            ..Default::default()
+        };
+        shake.add(1.0);
+        shake.update(1.0);
+        assert!((shake.trauma() - 0.5).abs() < 1e-6);
+        shake.update(1.0);
+        assert_eq!(shake.trauma(), 0.0);
+        shake.update(1.0);
+        assert_eq!(shake.trauma(), 0.0);
+    }
+
+    #[test]
+    fn stacked_trauma_is_clamped_to_one() {
+        let mut camera = Camera::new(Vec3::ZERO, 1.0);
+        camera.add_shake(0.7);
+        camera.add_shake(0.7);
+        assert_eq!(camera.shake.trauma(), 1.0);
+    }
+
+    #[test]
+    fn zero_trauma_yields_no_offset() {
+        let mut camera = Camera::new(Vec3::new(0.0, 2.0, 5.0), 1.0);
+        let still = camera.view_matrix();
+        camera.update(0.37);
+        assert_eq!(camera.shake.offsets(), (Vec3::ZERO, Vec3::ZERO));
+        assert_eq!(camera.view_matrix(), still);
+
+        camera.add_shake(1.0);
+        camera.update(0.37);
+        assert_ne!(camera.view_matrix(), still);
+    }
+
+    #[test]
+    fn camera_at_a_transform_looks_along_its_forward_axis() {
+        let transform = Transform::new(
+            Vector3::new(1.0, 2.0, 3.0),
+            Vector3::new(0.3, -0.8, 0.0),
+            Vector3::new(2.0, 2.0, 2.0),
+        );
+        let camera = Camera::from_transform(&transform, 1.0);
+        let forward = Vec3::from(transform.forward());
+        assert_eq!(camera.position(), Vec3::new(1.0, 2.0, 3.0));
+        assert!(camera.forward().abs_diff_eq(forward, 1e-6));
+
+        // A point straight ahead lands on the view axis, in front of the camera
+        let ahead = camera.view_matrix().transform_point3(camera.position() + forward * 5.0);
+        assert!(ahead.abs_diff_eq(Vec3::from(HANDEDNESS.forward()) * 5.0, 1e-4), "{ahead}");
+    }
+
+    #[test]
+    fn view_keeps_the_roll_of_the_transform() {
+        let transform = Transform::new(Vector3::new(-2.0, 1.0, 4.0), Vector3::new(0.4, 1.1, 0.7), Vector3::one());
+        let camera = Camera::from_transform(&transform, 1.0);
+        assert_eq!(camera.transform, transform);
+        // The transform's up axis, roll included, points up on screen
+        let up = camera.view_matrix().transform_vector3(camera.up());
+        assert!(up.abs_diff_eq(Vec3::Y, 1e-5), "{up}");
+    }
+
+    #[test]
+    fn points_on_the_transform_right_land_on_the_right_of_the_screen() {
+        let transform = Transform::looking_at(Vector3::new(3.0, 4.0, 5.0), Vector3::zero(), Vector3::up());
+        let camera = Camera::from_transform(&transform, 1.0);
+        let right = Vec3::from(Vector3::right().transform_normal(&transform.rotation_matrix()));
+        let point = camera.position() + camera.forward() * 5.0 + right;
+        let ndc = camera.build_view_projection_matrix().project_point3(point);
+        assert!(ndc.x > 0.0, "{ndc}");
+        assert!(ndc.y.abs() < 1e-5, "{ndc}");
+    }
+
+    #[test]
+    fn look_at_turns_without_moving() {
+        let mut camera = Camera::new(Vec3::new(0.0, 0.0, 5.0), 1.0);
+        camera.look_at(Vec3::new(5.0, 0.0, 5.0), Vec3::Y);
+        assert_eq!(camera.position(), Vec3::new(0.0, 0.0, 5.0));
+        assert!(camera.forward().abs_diff_eq(Vec3::X, 1e-6));
+        assert!(camera.up().abs_diff_eq(Vec3::Y, 1e-6));
     }
 }
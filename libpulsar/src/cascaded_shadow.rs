@@ -0,0 +1,398 @@
+use bytemuck::{Pod, Zeroable};
+use glam::{Mat4, Vec3};
+use wgpu::util::DeviceExt;
+
+use crate::camera::{Camera, Projection};
+use crate::engine::render::config::Labels;
+use crate::mesh::{Mesh, Vertex};
+use crate::renderer::Renderer;
+use crate::resources::{ResourceCategory, Tracked};
+use crate::shadow::{cascade_splits, ShadowSettings, MAX_CASCADES};
+
+const SHADOW_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
+/// Layout of `CascadeParams` in `shadow_cascades.wgsl`.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, Pod, Zeroable)]
+pub struct CascadeUniform {
+    pub view_proj: [[[f32; 4]; 4]; MAX_CASCADES],
+    pub splits: [f32; MAX_CASCADES],
+    pub camera_position: [f32; 4],
+    pub camera_forward: [f32; 4],
+    pub count: u32,
+    pub _padding: [u32; 3],
+}
+
+/// World-space corners of the part of `camera`'s frustum between `near` and
+/// `far` along its view direction.
+pub fn frustum_slice_corners(camera: &Camera, near: f32, far: f32) -> [Vec3; 8] {
+    let forward = camera.forward();
+    let right = forward.cross(camera.up()).normalize();
+    let up = right.cross(forward);
+    let half_height = |distance: f32| match camera.projection {
+        Projection::Perspective => distance * (camera.fovy.to_radians() * 0.5).tan(),
+        Projection::Orthographic { height } => height * 0.5,
+    };
+    std::array::from_fn(|index| {
+        let distance = if index & 4 == 0 { near } else { far };
+        let half_height = half_height(distance);
+        let half_width = half_height * camera.aspect;
+        let x = if index & 1 == 0 { -half_width } else { half_width };
+        let y = if index & 2 == 0 { -half_height } else { half_height };
+        camera.position() + forward * distance + right * x + up * y
+    })
+}
+
+/// Orthographic light view-projection covering the slice `near..far` of
+/// `camera`'s frustum, for a directional light shining along `light_dir`.
+///
+/// The slice is fit with a bounding sphere and the result snapped to whole
+/// shadow map texels, so the shadows don't shimmer as the camera turns or
+/// moves. Casters up to twice the sphere's radius towards the light are kept.
+pub fn fit_cascade(camera: &Camera, light_dir: Vec3, near: f32, far: f32, resolution: u32) -> Mat4 {
+    let corners = frustum_slice_corners(camera, near, far);
+    let center = corners.iter().copied().sum::<Vec3>() / corners.len() as f32;
+    let radius = corners.iter().map(|corner| corner.distance(center)).fold(0.0, f32::max);
+    // Quantized, so the projection size doesn't change with rotation
+    let radius = (radius * 16.0).ceil() / 16.0;
+
+    let direction = light_dir.try_normalize().unwrap_or(Vec3::NEG_Y);
+    let up = if direction.y.abs() > 0.99 { Vec3::Z } else { Vec3::Y };
+    let view = Mat4::look_at_rh(center - direction * radius * 2.0, center, up);
+    let mut projection = Mat4::orthographic_rh(-radius, radius, -radius, radius, 0.0, radius * 3.0);
+
+    let half_resolution = resolution.max(1) as f32 * 0.5;
+    let origin = (projection * view).project_point3(Vec3::ZERO) * half_resolution;
+    let offset = (origin.round() - origin) / half_resolution;
+    projection.w_axis.x += offset.x;
+    projection.w_axis.y += offset.y;
+    projection * view
+}
+
+/// Layout of the group lit shaders read the cascades from: the depth array,
+/// its comparison sampler, the `CascadeParams` uniform and the `ShadowParams`
+/// filtering uniform of `shadow_pcf.wgsl`, in that order.
+pub fn shadow_bind_group_layout(device: &wgpu::Device, labels: &Labels) -> wgpu::BindGroupLayout {
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        entries: &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Depth,
+                    view_dimension: wgpu::TextureViewDimension::D2Array,
+                    multisampled: false,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Comparison),
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 2,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 3,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+        ],
+        label: Some(&labels.label("shadow_bind_group_layout")),
+    })
+}
+
+/// Directional light shadows split into cascades along the view, each with
+/// its own layer of a depth texture array. Lit shaders sample them through
+/// `shadow_cascades.wgsl`, with `bind_group` bound to a group laid out by
+/// `shadow_bind_group_layout`.
+pub struct CascadedShadowMaps {
+    pipeline: wgpu::RenderPipeline,
+    texture: Tracked<wgpu::Texture>,
+    array_view: wgpu::TextureView,
+    layer_views: Vec<wgpu::TextureView>,
+    sampler: wgpu::Sampler,
+    /// Light matrix of each cascade, for the depth pass.
+    light_buffers: Vec<(wgpu::Buffer, wgpu::BindGroup)>,
+    uniform_buffer: wgpu::Buffer,
+    uniform: CascadeUniform,
+    /// `ShadowUniform` with the PCF kernel and biases.
+    params_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    labels: Labels,
+}
+
+impl CascadedShadowMaps {
+    pub fn new(device: &wgpu::Device, labels: &Labels, settings: &ShadowSettings) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(&labels.label("Shadow Depth Shader")),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shadow_depth.wgsl").into()),
+        });
+
+        let light_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+            label: Some(&labels.label("shadow_light_bind_group_layout")),
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some(&labels.label("Shadow Depth Pipeline Layout")),
+            bind_group_layouts: &[&light_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(&labels.label("Shadow Depth Pipeline")),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[Vertex::desc()],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: None,
+            // Both faces cast, so open meshes still shadow
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: SHADOW_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::LessEqual,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+
+        let resolution = settings.cascades.resolution.max(1);
+        let layers = MAX_CASCADES as u32;
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(&labels.label("Shadow Cascade Texture")),
+            size: wgpu::Extent3d {
+                width: resolution,
+                height: resolution,
+                depth_or_array_layers: layers,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: SHADOW_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let array_view = texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some(&labels.label("Shadow Cascade Array View")),
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
+            ..Default::default()
+        });
+        let layer_views = (0..layers)
+            .map(|layer| {
+                texture.create_view(&wgpu::TextureViewDescriptor {
+                    label: Some(&labels.label("Shadow Cascade Layer View")),
+                    dimension: Some(wgpu::TextureViewDimension::D2),
+                    base_array_layer: layer,
+                    array_layer_count: Some(1),
+                    ..Default::default()
+                })
+            })
+            .collect();
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some(&labels.label("Shadow Cascade Sampler")),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            compare: Some(wgpu::CompareFunction::LessEqual),
+            ..Default::default()
+        });
+
+        let light_buffers = (0..layers)
+            .map(|_| {
+                let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some(&labels.label("Shadow Light Buffer")),
+                    contents: bytemuck::cast_slice(&Mat4::IDENTITY.to_cols_array_2d()),
+                    usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                });
+                let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    layout: &light_layout,
+                    entries: &[wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: buffer.as_entire_binding(),
+                    }],
+                    label: Some(&labels.label("shadow_light_bind_group")),
+                });
+                (buffer, bind_group)
+            })
+            .collect();
+
+        let uniform = CascadeUniform::zeroed();
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&labels.label("Shadow Cascade Uniform Buffer")),
+            contents: bytemuck::bytes_of(&uniform),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let params_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&labels.label("Shadow Params Buffer")),
+            contents: bytemuck::bytes_of(&settings.uniform()),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &shadow_bind_group_layout(device, labels),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&array_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: params_buffer.as_entire_binding(),
+                },
+            ],
+            label: Some(&labels.label("shadow_bind_group")),
+        });
+
+        Self {
+            pipeline,
+            texture: Tracked::texture(texture, ResourceCategory::RenderTarget),
+            array_view,
+            layer_views,
+            sampler,
+            light_buffers,
+            uniform_buffer,
+            uniform,
+            params_buffer,
+            bind_group,
+            labels: labels.clone(),
+        }
+    }
+
+    /// Splits and light matrices for `camera` and a directional light
+    /// shining along `light_dir`.
+    pub fn cascades(camera: &Camera, light_dir: Vec3, settings: &ShadowSettings) -> CascadeUniform {
+        let cascades = &settings.cascades;
+        let splits = cascade_splits(camera.znear, camera.zfar, cascades.count, cascades.lambda);
+        let mut uniform = CascadeUniform::zeroed();
+        let mut near = camera.znear;
+        for (index, split) in splits.iter().enumerate() {
+            uniform.view_proj[index] =
+                fit_cascade(camera, light_dir, near, *split, cascades.resolution).to_cols_array_2d();
+            uniform.splits[index] = *split;
+            near = *split;
+        }
+        uniform.camera_position = camera.position().extend(1.0).to_array();
+        uniform.camera_forward = camera.forward().extend(0.0).to_array();
+        uniform.count = splits.len() as u32;
+        uniform
+    }
+
+    /// Fits the cascades to `camera` and renders `meshes` into each one.
+    pub fn render(
+        &mut self,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        camera: &Camera,
+        light_dir: Vec3,
+        settings: &ShadowSettings,
+        meshes: &[&Mesh],
+    ) {
+        self.update(queue, camera, light_dir, settings);
+        self.draw(encoder, meshes);
+    }
+
+    /// Fits the cascades to `camera` and uploads their matrices, along with
+    /// the filtering `settings`, for the following `draw`s and lit passes.
+    pub fn update(&mut self, queue: &wgpu::Queue, camera: &Camera, light_dir: Vec3, settings: &ShadowSettings) {
+        self.uniform = Self::cascades(camera, light_dir, settings);
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::bytes_of(&self.uniform));
+        queue.write_buffer(&self.params_buffer, 0, bytemuck::bytes_of(&settings.uniform()));
+        for (index, (buffer, _)) in self.light_buffers.iter().enumerate().take(self.uniform.count as usize) {
+            queue.write_buffer(buffer, 0, bytemuck::cast_slice(&self.uniform.view_proj[index]));
+        }
+    }
+
+    /// Renders `meshes` into each cascade fitted by the last `update`. Does
+    /// nothing before the first one.
+    pub fn draw(&self, encoder: &mut wgpu::CommandEncoder, meshes: &[&Mesh]) {
+        for index in 0..self.uniform.count as usize {
+            let (_, bind_group) = &self.light_buffers[index];
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some(&self.labels.label("Shadow Cascade Pass")),
+                color_attachments: &[],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &self.layer_views[index],
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    stencil_ops: None,
+                }),
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+            render_pass.set_pipeline(&self.pipeline);
+            render_pass.set_bind_group(0, bind_group, &[]);
+            for mesh in meshes {
+                Renderer::draw_mesh(&mut render_pass, mesh);
+            }
+        }
+    }
+
+    /// The values uploaded by the last `render`.
+    pub fn uniform(&self) -> &CascadeUniform {
+        &self.uniform
+    }
+
+    pub fn uniform_buffer(&self) -> &wgpu::Buffer {
+        &self.uniform_buffer
+    }
+
+    pub fn array_view(&self) -> &wgpu::TextureView {
+        &self.array_view
+    }
+
+    pub fn sampler(&self) -> &wgpu::Sampler {
+        &self.sampler
+    }
+
+    /// Group to bind for `shadow_cascades.wgsl`; see `shadow_bind_group_layout`.
+    pub fn bind_group(&self) -> &wgpu::BindGroup {
+        &self.bind_group
+    }
+
+    pub fn resolution(&self) -> u32 {
+        self.texture.width()
+    }
+}
@@ -0,0 +1,79 @@
+//! Color space conversions.
+//!
+//! Colors the engine is handed by hand — vertex colors, debug line colors —
+//! are authored in sRGB, the way color pickers show them. Shaders convert
+//! them to linear before lighting or blending, and the sRGB surface encodes
+//! the result again on write, so `[0.5, 0.5, 0.5]` ends up as sRGB 0.5 on
+//! screen. Textures get the same treatment from their `*Srgb` formats.
+
+/// Decodes one sRGB-encoded channel to linear. Mirrors `srgb_to_linear` in
+/// the shaders.
+pub fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Encodes one linear channel as sRGB; the inverse of `srgb_to_linear`.
+pub fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+pub fn srgb_to_linear_rgb(rgb: [f32; 3]) -> [f32; 3] {
+    rgb.map(srgb_to_linear)
+}
+
+pub fn linear_to_srgb_rgb(rgb: [f32; 3]) -> [f32; 3] {
+    rgb.map(linear_to_srgb)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::CameraUniform;
+    use crate::engine::render::config::Labels;
+    use crate::mesh::{Mesh, Vertex};
+    use crate::render_target::RenderTarget;
+    use crate::renderer::Renderer;
+    use crate::test_gpu;
+
+    #[test]
+    fn conversions_round_trip_and_keep_the_endpoints() {
+        for c in [0.0, 0.002, 0.04, 0.2, 0.5, 0.8, 1.0] {
+            assert!((linear_to_srgb(srgb_to_linear(c)) - c).abs() < 1e-5, "{c}");
+        }
+        assert_eq!(srgb_to_linear(0.0), 0.0);
+        assert!((srgb_to_linear(1.0) - 1.0).abs() < 1e-6);
+        // sRGB mid-gray is about a fifth of the light of white
+        assert!((srgb_to_linear(0.5) - 0.214).abs() < 1e-3);
+    }
+
+    #[test]
+    fn mid_gray_vertex_color_reaches_an_srgb_surface_unchanged() {
+        let Some((device, queue)) = test_gpu::device() else {
+            return;
+        };
+        let labels = Labels::default();
+        let config = wgpu::SurfaceConfiguration {
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            ..test_gpu::surface_config(4, 4)
+        };
+        let renderer = pollster::block_on(Renderer::new(&device, &labels, &queue, &config)).unwrap();
+        let target = RenderTarget::new(&device, &labels, &config, 4, 4);
+        let vertices = [[-1.0, -1.0], [3.0, -1.0], [-1.0, 3.0]]
+            .map(|[x, y]| Vertex::new([x, y, 0.5], [0.5; 3], [0.0, 0.0, 1.0]));
+        let mesh = Mesh::from_vertices(&device, &labels, &config, &vertices);
+        renderer.render_to(&device, &queue, &target, &CameraUniform::new(), &[&mesh]);
+
+        let pixels = test_gpu::read_pixels(&device, &queue, &target.color_texture.0);
+        for channel in &pixels[..3] {
+            assert!(channel.abs_diff(128) <= 1, "{:?}", &pixels[..4]);
+        }
+    }
+}
@@ -0,0 +1,199 @@
+use glam::Vec3;
+
+use crate::debug_lines::{DebugLines, LineSegment};
+use crate::math::Aabb;
+
+/// Segments per circle when drawing spheres.
+pub const SPHERE_SEGMENTS: usize = 24;
+
+/// A segment and how many more seconds it stays on screen.
+#[derive(Debug, Copy, Clone)]
+struct Mark {
+    segment: LineSegment,
+    remaining: f32,
+}
+
+/// Immediate-mode debug drawing: shapes added at any point during the update
+/// are handed to a `DebugLines` by `flush` once per frame. Plain calls last a
+/// single frame; `timed` keeps marks up for a number of seconds.
+#[derive(Debug, Clone, Default)]
+pub struct DebugDraw {
+    marks: Vec<Mark>,
+}
+
+impl DebugDraw {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Draws through the returned pen for `seconds`. Marks are always shown
+    /// for at least one frame.
+    pub fn timed(&mut self, seconds: f32) -> DebugPen<'_> {
+        DebugPen {
+            draw: self,
+            duration: seconds.max(0.0),
+        }
+    }
+
+    pub fn line(&mut self, start: Vec3, end: Vec3, color: [f32; 3]) {
+        self.timed(0.0).line(start, end, color);
+    }
+
+    pub fn sphere(&mut self, center: Vec3, radius: f32, color: [f32; 3]) {
+        self.timed(0.0).sphere(center, radius, color);
+    }
+
+    pub fn aabb(&mut self, aabb: &Aabb, color: [f32; 3]) {
+        self.timed(0.0).aabb(aabb, color);
+    }
+
+    pub fn cross(&mut self, point: Vec3, size: f32, color: [f32; 3]) {
+        self.timed(0.0).cross(point, size, color);
+    }
+
+    /// Segments still to be drawn.
+    pub fn len(&self) -> usize {
+        self.marks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.marks.is_empty()
+    }
+
+    /// Adds every live mark to `lines`, then ages them by the frame's `dt`
+    /// and drops the expired ones.
+    pub fn flush(&mut self, lines: &mut DebugLines, dt: f32) {
+        for mark in &self.marks {
+            lines.line(mark.segment.start, mark.segment.end, mark.segment.color);
+        }
+        self.advance(dt);
+    }
+
+    /// The aging half of `flush`, without drawing.
+    pub fn advance(&mut self, dt: f32) {
+        self.marks.retain_mut(|mark| {
+            mark.remaining -= dt;
+            mark.remaining > 0.0
+        });
+    }
+
+    /// Removes every mark, timed or not.
+    pub fn clear(&mut self) {
+        self.marks.clear();
+    }
+}
+
+/// Adds shapes to a `DebugDraw` with a shared lifetime.
+pub struct DebugPen<'a> {
+    draw: &'a mut DebugDraw,
+    duration: f32,
+}
+
+impl DebugPen<'_> {
+    pub fn line(&mut self, start: Vec3, end: Vec3, color: [f32; 3]) -> &mut Self {
+        self.draw.marks.push(Mark {
+            segment: LineSegment { start, end, color },
+            remaining: self.duration,
+        });
+        self
+    }
+
+    /// Three circles, one around each axis.
+    pub fn sphere(&mut self, center: Vec3, radius: f32, color: [f32; 3]) -> &mut Self {
+        let point = |axis: usize, step: usize| {
+            let (sin, cos) = (step as f32 / SPHERE_SEGMENTS as f32 * std::f32::consts::TAU).sin_cos();
+            let offset = match axis {
+                0 => Vec3::new(0.0, cos, sin),
+                1 => Vec3::new(cos, 0.0, sin),
+                _ => Vec3::new(cos, sin, 0.0),
+            };
+            center + offset * radius
+        };
+        for axis in 0..3 {
+            for step in 0..SPHERE_SEGMENTS {
+                self.line(point(axis, step), point(axis, step + 1), color);
+            }
+        }
+        self
+    }
+
+    /// The twelve edges of `aabb`.
+    pub fn aabb(&mut self, aabb: &Aabb, color: [f32; 3]) -> &mut Self {
+        for (start, end) in aabb.edges() {
+            self.line(start.into(), end.into(), color);
+        }
+        self
+    }
+
+    /// Three axis-aligned lines `size` long, crossing at `point`.
+    pub fn cross(&mut self, point: Vec3, size: f32, color: [f32; 3]) -> &mut Self {
+        let half = size * 0.5;
+        for axis in [Vec3::X, Vec3::Y, Vec3::Z] {
+            self.line(point - axis * half, point + axis * half, color);
+        }
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::Vector3;
+
+    const RED: [f32; 3] = [1.0, 0.0, 0.0];
+
+    #[test]
+    fn plain_marks_last_one_frame() {
+        let mut draw = DebugDraw::new();
+        draw.line(Vec3::ZERO, Vec3::X, RED);
+        draw.cross(Vec3::ZERO, 1.0, RED);
+        assert_eq!(draw.len(), 4);
+        draw.advance(1.0 / 60.0);
+        assert!(draw.is_empty());
+    }
+
+    #[test]
+    fn timed_marks_stay_up_for_their_duration() {
+        let mut draw = DebugDraw::new();
+        draw.timed(0.5).line(Vec3::ZERO, Vec3::X, RED).cross(Vec3::Y, 2.0, RED);
+        draw.line(Vec3::ZERO, Vec3::Z, RED);
+        assert_eq!(draw.len(), 5);
+
+        draw.advance(0.2);
+        assert_eq!(draw.len(), 4);
+        draw.advance(0.2);
+        assert_eq!(draw.len(), 4);
+        draw.advance(0.2);
+        assert!(draw.is_empty());
+    }
+
+    #[test]
+    fn negative_durations_still_show_one_frame() {
+        let mut draw = DebugDraw::new();
+        draw.timed(-3.0).line(Vec3::ZERO, Vec3::X, RED);
+        assert_eq!(draw.len(), 1);
+        draw.advance(0.0);
+        assert!(draw.is_empty());
+    }
+
+    #[test]
+    fn shapes_expand_to_their_segments() {
+        let mut draw = DebugDraw::new();
+        draw.sphere(Vec3::new(1.0, 2.0, 3.0), 2.0, RED);
+        assert_eq!(draw.len(), 3 * SPHERE_SEGMENTS);
+        for mark in &draw.marks {
+            let distance = mark.segment.start.distance(Vec3::new(1.0, 2.0, 3.0));
+            assert!((distance - 2.0).abs() < 1e-5, "{distance}");
+        }
+        draw.clear();
+
+        draw.aabb(&Aabb::new(Vector3::zero(), Vector3::one()), RED);
+        assert_eq!(draw.len(), 12);
+        draw.clear();
+
+        draw.cross(Vec3::ONE, 2.0, RED);
+        let ends: Vec<_> = draw.marks.iter().map(|mark| (mark.segment.start, mark.segment.end)).collect();
+        assert_eq!(ends[0], (Vec3::new(0.0, 1.0, 1.0), Vec3::new(2.0, 1.0, 1.0)));
+        assert_eq!(ends[2], (Vec3::new(1.0, 1.0, 0.0), Vec3::new(1.0, 1.0, 2.0)));
+    }
+}
@@ -0,0 +1,352 @@
+use glam::{Mat4, Vec3};
+use wgpu::util::DeviceExt;
+
+use crate::camera::{Camera, CameraUniform};
+use crate::engine::render::config::Labels;
+use crate::math::{box_edges, Aabb};
+use crate::mesh::Vertex;
+use crate::shader;
+
+#[derive(Debug, Copy, Clone)]
+pub struct LineSegment {
+    pub start: Vec3,
+    pub end: Vec3,
+    pub color: [f32; 3],
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum LineMode {
+    /// One pixel wide `LineList` primitives.
+    Hardware,
+    /// Each line expanded into a camera-facing quad `line_width` pixels wide,
+    /// since wgpu has no wide hardware lines.
+    #[default]
+    Quads,
+}
+
+/// Collects debug line segments and draws them in a single pass.
+pub struct DebugLines {
+    segments: Vec<LineSegment>,
+    mode: LineMode,
+    line_width: f32,
+    line_pipeline: wgpu::RenderPipeline,
+    quad_pipeline: wgpu::RenderPipeline,
+    camera_buffer: wgpu::Buffer,
+    camera_bind_group: wgpu::BindGroup,
+    vertex_buffer: Option<wgpu::Buffer>,
+    num_vertices: u32,
+    labels: Labels,
+}
+
+impl DebugLines {
+    pub fn new(device: &wgpu::Device, labels: &Labels, config: &wgpu::SurfaceConfiguration, sample_count: u32) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(&labels.label("Debug Line Shader")),
+            source: wgpu::ShaderSource::Wgsl(shader::builtin("shader.wgsl").source.into()),
+        });
+
+        let camera_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&labels.label("Debug Line Camera Buffer")),
+            contents: bytemuck::cast_slice(&[CameraUniform::new()]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let camera_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+            label: Some(&labels.label("debug_line_camera_bind_group_layout")),
+        });
+
+        let camera_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &camera_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: camera_buffer.as_entire_binding(),
+            }],
+            label: Some(&labels.label("debug_line_camera_bind_group")),
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some(&labels.label("Debug Line Pipeline Layout")),
+            bind_group_layouts: &[&camera_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let line_pipeline = Self::create_pipeline(
+            device,
+            labels,
+            config,
+            &shader,
+            &pipeline_layout,
+            wgpu::PrimitiveTopology::LineList,
+            sample_count,
+        );
+        let quad_pipeline = Self::create_pipeline(
+            device,
+            labels,
+            config,
+            &shader,
+            &pipeline_layout,
+            wgpu::PrimitiveTopology::TriangleList,
+            sample_count,
+        );
+
+        Self {
+            segments: Vec::new(),
+            mode: LineMode::default(),
+            line_width: 1.0,
+            line_pipeline,
+            quad_pipeline,
+            camera_buffer,
+            camera_bind_group,
+            vertex_buffer: None,
+            num_vertices: 0,
+            labels: labels.clone(),
+        }
+    }
+
+    fn create_pipeline(
+        device: &wgpu::Device,
+        labels: &Labels,
+        config: &wgpu::SurfaceConfiguration,
+        shader: &wgpu::ShaderModule,
+        pipeline_layout: &wgpu::PipelineLayout,
+        topology: wgpu::PrimitiveTopology,
+        sample_count: u32,
+    ) -> wgpu::RenderPipeline {
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(&labels.label("Debug Line Pipeline")),
+            layout: Some(pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: shader,
+                entry_point: Some("vs_main"),
+                buffers: &[Vertex::desc()],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: shader,
+                entry_point: Some("fs_vertex_color"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: config.format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                // Quads face the camera, but their winding depends on the line direction
+                cull_mode: None,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: wgpu::TextureFormat::Depth32Float,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::LessEqual,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: sample_count,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+            cache: None,
+        })
+    }
+
+    pub fn line(&mut self, start: Vec3, end: Vec3, color: [f32; 3]) {
+        self.segments.push(LineSegment { start, end, color });
+    }
+
+    /// Adds the twelve edges of `aabb`.
+    pub fn aabb(&mut self, aabb: &Aabb, color: [f32; 3]) {
+        for (start, end) in aabb.edges() {
+            self.line(start.into(), end.into(), color);
+        }
+    }
+
+    /// Adds the twelve edges of the frustum `view_proj` sees, e.g. another
+    /// camera's, whichever depth convention it uses.
+    pub fn frustum(&mut self, view_proj: Mat4, color: [f32; 3]) {
+        let inverse = view_proj.inverse();
+        let corners: [Vec3; 8] = std::array::from_fn(|index| {
+            let ndc = Vec3::new(
+                if index & 1 == 0 { -1.0 } else { 1.0 },
+                if index & 2 == 0 { -1.0 } else { 1.0 },
+                if index & 4 == 0 { 0.0 } else { 1.0 },
+            );
+            inverse.project_point3(ndc)
+        });
+        for (a, b) in box_edges() {
+            self.line(corners[a], corners[b], color);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.segments.clear();
+    }
+
+    pub fn segments(&self) -> &[LineSegment] {
+        &self.segments
+    }
+
+    pub fn mode(&self) -> LineMode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: LineMode) {
+        self.mode = mode;
+    }
+
+    pub fn line_width(&self) -> f32 {
+        self.line_width
+    }
+
+    /// Width in pixels, only honoured in `LineMode::Quads`.
+    pub fn set_line_width(&mut self, line_width: f32) {
+        self.line_width = line_width.max(1.0);
+    }
+
+    /// Builds the vertex list for the current mode: two vertices per segment for
+    /// hardware lines, six (two triangles) per segment for quads.
+    pub fn build_vertices(&self, camera: &Camera, viewport_height: f32) -> Vec<Vertex> {
+        match self.mode {
+            LineMode::Hardware => self
+                .segments
+                .iter()
+                .flat_map(|segment| {
+                    [
+                        Vertex::new(segment.start.to_array(), segment.color, [0.0; 3]),
+                        Vertex::new(segment.end.to_array(), segment.color, [0.0; 3]),
+                    ]
+                })
+                .collect(),
+            LineMode::Quads => self
+                .segments
+                .iter()
+                .flat_map(|segment| expand_segment(segment, camera, self.line_width, viewport_height))
+                .collect(),
+        }
+    }
+
+    /// Uploads the accumulated lines for this frame.
+    pub fn prepare(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, camera: &Camera, viewport_height: f32) {
+        let mut camera_uniform = CameraUniform::new();
+        camera_uniform.update_view_proj(camera);
+        queue.write_buffer(&self.camera_buffer, 0, bytemuck::cast_slice(&[camera_uniform]));
+
+        let vertices = self.build_vertices(camera, viewport_height);
+        self.num_vertices = vertices.len() as u32;
+        if vertices.is_empty() {
+            return;
+        }
+
+        let size = std::mem::size_of_val(vertices.as_slice()) as wgpu::BufferAddress;
+        let needs_grow = self.vertex_buffer.as_ref().map_or(true, |buffer| buffer.size() < size);
+        if needs_grow {
+            self.vertex_buffer = Some(device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(&self.labels.label("Debug Line Vertex Buffer")),
+                size: size.next_power_of_two(),
+                usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }));
+        }
+        if let Some(buffer) = &self.vertex_buffer {
+            queue.write_buffer(buffer, 0, bytemuck::cast_slice(&vertices));
+        }
+    }
+
+    pub fn draw(&self, render_pass: &mut wgpu::RenderPass) {
+        let Some(vertex_buffer) = &self.vertex_buffer else {
+            return;
+        };
+        if self.num_vertices == 0 {
+            return;
+        }
+        let pipeline = match self.mode {
+            LineMode::Hardware => &self.line_pipeline,
+            LineMode::Quads => &self.quad_pipeline,
+        };
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+        render_pass.draw(0..self.num_vertices, 0..1);
+    }
+}
+
+/// Two camera-facing triangles covering `segment` at `line_width` pixels wide.
+fn expand_segment(segment: &LineSegment, camera: &Camera, line_width: f32, viewport_height: f32) -> [Vertex; 6] {
+    let midpoint = (segment.start + segment.end) * 0.5;
+    let side = (segment.end - segment.start)
+        .cross(camera.position() - midpoint)
+        .normalize_or_zero();
+
+    // Half widths are computed per endpoint so the line keeps a constant
+    // pixel width even when it recedes into the distance.
+    let start_half =
+        0.5 * camera.world_size_of_pixels(line_width, camera.position().distance(segment.start), viewport_height);
+    let end_half =
+        0.5 * camera.world_size_of_pixels(line_width, camera.position().distance(segment.end), viewport_height);
+
+    let corners = [
+        segment.start - side * start_half,
+        segment.start + side * start_half,
+        segment.end + side * end_half,
+        segment.end - side * end_half,
+    ];
+    [0, 1, 2, 2, 3, 0].map(|i| Vertex::new(corners[i].to_array(), segment.color, [0.0; 3]))
+}
+
+/// World-space width covering `line_width` pixels at `distance` from a
+/// perspective camera with vertical field of view `fovy` (radians).
+pub fn world_width_at(line_width: f32, distance: f32, fovy: f32, viewport_height: f32) -> f32 {
+    let visible_height = 2.0 * distance * (fovy * 0.5).tan();
+    line_width * visible_height / viewport_height.max(1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pixel_width_scales_with_distance() {
+        // A 90 degree field of view sees 2 units at distance 1
+        let fovy = std::f32::consts::FRAC_PI_2;
+        assert!((world_width_at(5.0, 1.0, fovy, 100.0) - 0.1).abs() < 1e-5);
+        assert!((world_width_at(5.0, 4.0, fovy, 100.0) - 0.4).abs() < 1e-5);
+    }
+
+    #[test]
+    fn segment_expands_into_two_triangles_of_constant_pixel_width() {
+        // Looking down -Z at the origin
+        let camera = Camera::new(Vec3::new(0.0, 0.0, 10.0), 1.0);
+        let segment = LineSegment {
+            start: Vec3::new(-1.0, 0.0, 0.0),
+            end: Vec3::new(1.0, 0.0, 0.0),
+            color: [1.0, 0.0, 0.0],
+        };
+        let vertices = expand_segment(&segment, &camera, 4.0, 600.0);
+        assert_eq!(vertices.len(), 6);
+
+        let [a, b] = [vertices[0], vertices[1]].map(|vertex| Vec3::from(vertex.position));
+        let expected = camera.world_size_of_pixels(4.0, camera.position().distance(segment.start), 600.0);
+        assert!((a.distance(b) - expected).abs() < 1e-5);
+        // The quad spreads across the line, not along it or towards the camera
+        assert!((a - b).normalize().dot(Vec3::Y).abs() > 0.999);
+        assert!(vertices.iter().all(|vertex| vertex.color == segment.color));
+    }
+}
@@ -0,0 +1,393 @@
+use bytemuck::{Pod, Zeroable};
+use glam::Mat4;
+use wgpu::util::DeviceExt;
+
+use crate::camera::CameraUniform;
+use crate::engine::render::config::Labels;
+use crate::material::Texture;
+use crate::math::{Matrix4, Transform};
+
+/// Smallest cosine between a decal's projection direction and a surface's
+/// normal that still receives it; steeper surfaces, and back faces, don't.
+pub const DECAL_MIN_FACING: f32 = 0.2;
+
+/// Maps world space into a decal's unit box, centered on the origin, for
+/// a decal placed by `transform`. Points with every coordinate within
+/// `-0.5..=0.5` are covered.
+pub fn world_to_decal(transform: &Transform) -> Mat4 {
+    to_glam(&transform.matrix()).inverse()
+}
+
+/// A crate `Matrix4` (row vectors) as the equivalent glam matrix (column
+/// vectors).
+fn to_glam(matrix: &Matrix4) -> Mat4 {
+    Mat4::from_cols_array_2d(&matrix.to_rows())
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+struct DecalFrameUniform {
+    inv_view_proj: [[f32; 4]; 4],
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+struct DecalUniform {
+    world_to_decal: [[f32; 4]; 4],
+    direction: [f32; 4],
+}
+
+impl DecalUniform {
+    fn new(transform: &Transform) -> Self {
+        let forward = transform.forward().normalize_or_zero();
+        Self {
+            world_to_decal: world_to_decal(transform).to_cols_array_2d(),
+            direction: [forward.x, forward.y, forward.z, DECAL_MIN_FACING],
+        }
+    }
+}
+
+/// A texture projected along its transform's forward (+Z) axis through a
+/// unit box scaled, rotated and placed by the transform. The texture's top
+/// is towards the box's +Y.
+pub struct Decal {
+    pub transform: Transform,
+    pub texture: Texture,
+    buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+}
+
+/// Draws decals over an already rendered frame, using its depth buffer to
+/// find the surfaces inside each decal's box.
+pub struct DecalRenderer {
+    pipeline: wgpu::RenderPipeline,
+    frame_layout: wgpu::BindGroupLayout,
+    decal_layout: wgpu::BindGroupLayout,
+    frame_buffer: wgpu::Buffer,
+    decals: Vec<Decal>,
+    labels: Labels,
+}
+
+impl DecalRenderer {
+    /// `format` is the color target decals are blended onto.
+    pub fn new(device: &wgpu::Device, labels: &Labels, format: wgpu::TextureFormat) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(&labels.label("Decal Shader")),
+            source: wgpu::ShaderSource::Wgsl(include_str!("decal.wgsl").into()),
+        });
+
+        let uniform_entry = wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let frame_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                uniform_entry,
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+            ],
+            label: Some(&labels.label("decal_frame_bind_group_layout")),
+        });
+        let decal_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                uniform_entry,
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+            label: Some(&labels.label("decal_bind_group_layout")),
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some(&labels.label("Decal Pipeline Layout")),
+            bind_group_layouts: &[&frame_layout, &decal_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(&labels.label("Decal Pipeline")),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::COLOR,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+
+        let frame_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&labels.label("Decal Frame Buffer")),
+            contents: bytemuck::bytes_of(&DecalFrameUniform {
+                inv_view_proj: Mat4::IDENTITY.to_cols_array_2d(),
+            }),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        Self {
+            pipeline,
+            frame_layout,
+            decal_layout,
+            frame_buffer,
+            decals: Vec::new(),
+            labels: labels.clone(),
+        }
+    }
+
+    /// Adds a decal and returns its index in `decals`.
+    pub fn add(&mut self, device: &wgpu::Device, transform: &Transform, texture: Texture) -> usize {
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&self.labels.label("Decal Buffer")),
+            contents: bytemuck::bytes_of(&DecalUniform::new(transform)),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &self.decal_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(&texture.sampler),
+                },
+            ],
+            label: Some(&self.labels.label("decal_bind_group")),
+        });
+        self.decals.push(Decal {
+            transform: *transform,
+            texture,
+            buffer,
+            bind_group,
+        });
+        self.decals.len() - 1
+    }
+
+    /// Moves decal `index`; returns false if there is no such decal.
+    pub fn set_transform(&mut self, queue: &wgpu::Queue, index: usize, transform: &Transform) -> bool {
+        let Some(decal) = self.decals.get_mut(index) else {
+            return false;
+        };
+        decal.transform = *transform;
+        queue.write_buffer(&decal.buffer, 0, bytemuck::bytes_of(&DecalUniform::new(transform)));
+        true
+    }
+
+    pub fn decals(&self) -> &[Decal] {
+        &self.decals
+    }
+
+    pub fn clear(&mut self) {
+        self.decals.clear();
+    }
+
+    /// Uploads the camera the next `draw` reconstructs positions with; must
+    /// be the one the depth buffer was rendered from.
+    pub fn prepare(&self, queue: &wgpu::Queue, camera_uniform: &CameraUniform) {
+        let inv_view_proj = Mat4::from_cols_array_2d(&camera_uniform.view_proj()).inverse();
+        queue.write_buffer(
+            &self.frame_buffer,
+            0,
+            bytemuck::bytes_of(&DecalFrameUniform {
+                inv_view_proj: inv_view_proj.to_cols_array_2d(),
+            }),
+        );
+    }
+
+    /// Blends every decal onto `color_view`. `depth_view` is the frame's
+    /// single-sample depth buffer, which needs `TEXTURE_BINDING`.
+    pub fn draw(
+        &self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        color_view: &wgpu::TextureView,
+        depth_view: &wgpu::TextureView,
+    ) {
+        if self.decals.is_empty() {
+            return;
+        }
+        let frame_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &self.frame_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: self.frame_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(depth_view),
+                },
+            ],
+            label: Some(&self.labels.label("decal_frame_bind_group")),
+        });
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some(&self.labels.label("Decal Pass")),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: color_view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &frame_bind_group, &[]);
+        for decal in &self.decals {
+            render_pass.set_bind_group(1, &decal.bind_group, &[]);
+            render_pass.draw(0..3, 0..1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec3;
+
+    use super::*;
+    use crate::camera::Camera;
+    use crate::engine::render::ctx::capture_errors;
+    use crate::math::Vector3;
+    use crate::mesh::{Mesh, Vertex};
+    use crate::render_target::RenderTarget;
+    use crate::renderer::Renderer;
+    use crate::test_gpu;
+
+    const SIZE: u32 = 32;
+
+    fn decal_at(position: Vector3, yaw: f32, scale: Vector3) -> Transform {
+        Transform::new(position, Vector3::new(0.0, yaw, 0.0), scale)
+    }
+
+    #[test]
+    fn decal_box_is_centered_on_the_transform() {
+        let transform = decal_at(Vector3::new(1.0, 2.0, 3.0), 0.0, Vector3::new(2.0, 4.0, 1.0));
+        let to_decal = world_to_decal(&transform);
+        assert!(to_decal.transform_point3(Vec3::new(1.0, 2.0, 3.0)).abs_diff_eq(Vec3::ZERO, 1e-5));
+        // Scale stretches the unit box: its corners land on the box's corners
+        let corner = to_decal.transform_point3(Vec3::new(2.0, 4.0, 3.5));
+        assert!(corner.abs_diff_eq(Vec3::splat(0.5), 1e-5), "{corner}");
+    }
+
+    #[test]
+    fn uniform_projects_along_the_forward_axis() {
+        let uniform = DecalUniform::new(&decal_at(Vector3::zero(), std::f32::consts::PI, Vector3::one()));
+        let [x, y, z, min_facing] = uniform.direction;
+        assert!(Vec3::new(x, y, z).abs_diff_eq(Vec3::NEG_Z, 1e-5));
+        assert_eq!(min_facing, DECAL_MIN_FACING);
+    }
+
+    /// Renders a blue wall through the z = 0 plane, seen from +Z, then
+    /// projects a red decal of `transform` onto it.
+    fn decal_on_wall(transform: &Transform) -> Option<Vec<u8>> {
+        let (device, queue) = test_gpu::device()?;
+        let labels = Labels::default();
+        let config = test_gpu::surface_config(SIZE, SIZE);
+        let renderer = pollster::block_on(Renderer::new(&device, &labels, &queue, &config)).unwrap();
+        let target = RenderTarget::new(&device, &labels, &config, SIZE, SIZE);
+        let mut camera_uniform = CameraUniform::new();
+        camera_uniform.update_view_proj(&Camera::new(Vec3::new(0.0, 0.0, 5.0), 1.0));
+
+        let vertices = [[-10.0, -10.0], [30.0, -10.0], [-10.0, 30.0]]
+            .map(|[x, y]| Vertex::new([x, y, 0.0], [0.0, 0.0, 1.0], [0.0, 0.0, 1.0]));
+        let wall = Mesh::from_vertices(&device, &labels, &config, &vertices);
+        renderer.render_to(&device, &queue, &target, &camera_uniform, &[&wall]);
+
+        let mut decals = DecalRenderer::new(&device, &labels, config.format);
+        let red = Texture::from_rgba8(&device, &labels, &queue, 1, 1, &[255, 0, 0, 255], &Default::default()).unwrap();
+        assert_eq!(decals.add(&device, transform, red), 0);
+        decals.prepare(&queue, &camera_uniform);
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        decals.draw(&device, &mut encoder, target.color_view(), target.depth_view());
+        queue.submit(std::iter::once(encoder.finish()));
+        Some(test_gpu::read_pixels(&device, &queue, &target.color_texture.0))
+    }
+
+    fn pixel(pixels: &[u8], x: u32, y: u32) -> &[u8] {
+        let start = ((y * SIZE + x) * 4) as usize;
+        &pixels[start..start + 4]
+    }
+
+    #[test]
+    fn decal_covers_the_wall_inside_its_box_only() {
+        // Turned to project along -Z, into the wall's front
+        let transform = decal_at(Vector3::zero(), std::f32::consts::PI, Vector3::one());
+        let Some(pixels) = decal_on_wall(&transform) else {
+            return;
+        };
+        assert_eq!(pixel(&pixels, SIZE / 2, SIZE / 2), [255, 0, 0, 255]);
+        assert_eq!(pixel(&pixels, 1, 1), [0, 0, 255, 255]);
+        assert_eq!(pixel(&pixels, SIZE - 2, SIZE / 2), [0, 0, 255, 255]);
+    }
+
+    #[test]
+    fn decal_skips_surfaces_facing_away_from_it() {
+        // Projecting along +Z reaches the wall from behind
+        let Some(pixels) = decal_on_wall(&decal_at(Vector3::zero(), 0.0, Vector3::one())) else {
+            return;
+        };
+        assert!(pixels.chunks_exact(4).all(|pixel| pixel == [0, 0, 255, 255]));
+    }
+
+    #[test]
+    fn decal_renderer_builds_without_validation_errors() {
+        let Some((device, _queue)) = test_gpu::device() else {
+            return;
+        };
+        let labels = Labels::default();
+        let config = test_gpu::surface_config(SIZE, SIZE);
+        let result = pollster::block_on(capture_errors(&device, "Decal Renderer", || {
+            DecalRenderer::new(&device, &labels, config.format)
+        }));
+        assert!(result.is_ok(), "{:?}", result.err());
+    }
+}
@@ -0,0 +1,59 @@
+// Projects decal textures onto the scene: each decal is a full-screen
+// triangle that rebuilds world positions from the depth buffer and draws
+// wherever they fall inside the decal's unit box.
+
+struct DecalFrame {
+    inv_view_proj: mat4x4<f32>,
+};
+
+@group(0) @binding(0)
+var<uniform> frame: DecalFrame;
+@group(0) @binding(1)
+// Bound as an unfilterable float texture rather than `texture_depth_2d`,
+// which GL backends cannot `textureLoad` from.
+var scene_depth: texture_2d<f32>;
+
+struct Decal {
+    world_to_decal: mat4x4<f32>,
+    // xyz: world-space projection direction; w: smallest cosine between it
+    // and a surface's normal that still receives the decal.
+    direction: vec4<f32>,
+};
+
+@group(1) @binding(0)
+var<uniform> decal: Decal;
+@group(1) @binding(1)
+var decal_texture: texture_2d<f32>;
+@group(1) @binding(2)
+var decal_sampler: sampler;
+
+@vertex
+fn vs_main(@builtin(vertex_index) vid: u32) -> @builtin(position) vec4<f32> {
+    // Vertices (0,0), (2,0), (0,2) cover the whole screen with one triangle.
+    let uv = vec2<f32>(f32((vid << 1u) & 2u), f32(vid & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+@fragment
+fn fs_main(@builtin(position) frag_coord: vec4<f32>) -> @location(0) vec4<f32> {
+    let size = vec2<f32>(textureDimensions(scene_depth));
+    let depth = textureLoad(scene_depth, vec2<i32>(frag_coord.xy), 0).r;
+    let ndc = vec2<f32>(frag_coord.x / size.x * 2.0 - 1.0, 1.0 - frag_coord.y / size.y * 2.0);
+    let clip = frame.inv_view_proj * vec4<f32>(ndc, depth, 1.0);
+    let world = clip.xyz / clip.w;
+
+    // Derivatives have to be taken before any fragment is discarded. Screen y
+    // grows downwards, so this order gives normals facing the camera.
+    let normal = normalize(cross(dpdy(world), dpdx(world)));
+
+    let local = (decal.world_to_decal * vec4<f32>(world, 1.0)).xyz;
+    if any(abs(local) > vec3<f32>(0.5)) {
+        discard;
+    }
+    // Skip surfaces turned away from the projector, e.g. the back of a wall
+    if dot(normal, -decal.direction.xyz) < decal.direction.w {
+        discard;
+    }
+    let uv = vec2<f32>(local.x + 0.5, 0.5 - local.y);
+    return textureSampleLevel(decal_texture, decal_sampler, uv, 0.0);
+}
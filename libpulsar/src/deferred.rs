@@ -0,0 +1,546 @@
+use bytemuck::{Pod, Zeroable};
+use thiserror::Error;
+
+use crate::engine::render::config::Labels;
+use crate::mesh::{Mesh, Vertex};
+use crate::renderer::Renderer;
+use crate::resources::{ResourceCategory, Tracked};
+use crate::ssao::{SsaoPass, SsaoSettings};
+
+/// Most lights the lighting pass accumulates; must match `deferred_lighting.wgsl`.
+pub const MAX_LIGHTS: usize = 64;
+
+/// Formats of the G-buffer targets: world position, normal and albedo.
+pub const GBUFFER_FORMATS: [wgpu::TextureFormat; 3] = [
+    wgpu::TextureFormat::Rgba16Float,
+    wgpu::TextureFormat::Rgba16Float,
+    wgpu::TextureFormat::Rgba8Unorm,
+];
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum DeferredError {
+    #[error("Deferred shading needs {required} color attachments, the device supports {available}")]
+    TooFewColorAttachments { required: u32, available: u32 },
+    #[error("The G-buffer needs {required} bytes per sample, the device supports {available}")]
+    ColorAttachmentBytes { required: u32, available: u32 },
+}
+
+/// Checks that the device can render to all G-buffer targets at once.
+pub fn check_support(limits: &wgpu::Limits) -> Result<(), DeferredError> {
+    let required = GBUFFER_FORMATS.len() as u32;
+    if limits.max_color_attachments < required {
+        return Err(DeferredError::TooFewColorAttachments {
+            required,
+            available: limits.max_color_attachments,
+        });
+    }
+    let bytes: u32 = GBUFFER_FORMATS
+        .iter()
+        .map(|format| format.target_pixel_byte_cost().unwrap_or(0))
+        .sum();
+    if limits.max_color_attachment_bytes_per_sample < bytes {
+        return Err(DeferredError::ColorAttachmentBytes {
+            required: bytes,
+            available: limits.max_color_attachment_bytes_per_sample,
+        });
+    }
+    Ok(())
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, Pod, Zeroable)]
+pub struct PointLight {
+    pub position: [f32; 3],
+    /// Distance at which the light's contribution reaches zero.
+    pub radius: f32,
+    pub color: [f32; 3],
+    pub intensity: f32,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+struct LightsUniform {
+    ambient: [f32; 3],
+    count: u32,
+    lights: [PointLight; MAX_LIGHTS],
+}
+
+/// Screen-sized targets written by the geometry pass.
+pub struct GBuffer {
+    pub position: (Tracked<wgpu::Texture>, wgpu::TextureView),
+    pub normal: (Tracked<wgpu::Texture>, wgpu::TextureView),
+    pub albedo: (Tracked<wgpu::Texture>, wgpu::TextureView),
+    pub depth: (Tracked<wgpu::Texture>, wgpu::TextureView),
+}
+
+impl GBuffer {
+    pub fn new(device: &wgpu::Device, labels: &Labels, config: &wgpu::SurfaceConfiguration) -> Self {
+        let target = |format, name| {
+            let texture = device.create_texture(&wgpu::TextureDescriptor {
+                label: Some(&labels.label(name)),
+                size: wgpu::Extent3d {
+                    width: config.width.max(1),
+                    height: config.height.max(1),
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            });
+            let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+            (Tracked::texture(texture, ResourceCategory::RenderTarget), view)
+        };
+        Self {
+            position: target(GBUFFER_FORMATS[0], "G-Buffer Position"),
+            normal: target(GBUFFER_FORMATS[1], "G-Buffer Normal"),
+            albedo: target(GBUFFER_FORMATS[2], "G-Buffer Albedo"),
+            depth: Mesh::create_depth_texture(device, labels, config),
+        }
+    }
+}
+
+/// Deferred shading: a geometry pass fills the G-buffer, then one full-screen
+/// pass lights every pixel with all lights, so the cost of a light no longer
+/// depends on how much geometry it touches. MSAA is not applied on this path.
+/// Optional SSAO darkens the ambient term in creases and contact points.
+pub struct DeferredRenderer {
+    geometry_pipeline: wgpu::RenderPipeline,
+    lighting_pipeline: wgpu::RenderPipeline,
+    lighting_layout: wgpu::BindGroupLayout,
+    lighting_bind_group: wgpu::BindGroup,
+    lights_buffer: wgpu::Buffer,
+    gbuffer: GBuffer,
+    ssao: SsaoPass,
+    ssao_settings: Option<SsaoSettings>,
+    reverse_z: bool,
+    labels: Labels,
+}
+
+impl DeferredRenderer {
+    pub fn new(
+        device: &wgpu::Device,
+        labels: &Labels,
+        config: &wgpu::SurfaceConfiguration,
+        reverse_z: bool,
+    ) -> Result<Self, DeferredError> {
+        check_support(&device.limits())?;
+
+        let geometry_pipeline = Self::create_geometry_pipeline(device, labels, reverse_z);
+
+        let texture_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+        let lighting_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                texture_entry(0),
+                texture_entry(1),
+                texture_entry(2),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                texture_entry(4),
+            ],
+            label: Some(&labels.label("deferred_lighting_bind_group_layout")),
+        });
+
+        let lighting_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(&labels.label("Deferred Lighting Shader")),
+            source: wgpu::ShaderSource::Wgsl(include_str!("deferred_lighting.wgsl").into()),
+        });
+        let lighting_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some(&labels.label("Deferred Lighting Pipeline Layout")),
+            bind_group_layouts: &[&lighting_layout],
+            push_constant_ranges: &[],
+        });
+        let lighting_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(&labels.label("Deferred Lighting Pipeline")),
+            layout: Some(&lighting_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &lighting_shader,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &lighting_shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: config.format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+
+        let lights_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(&labels.label("Deferred Lights Buffer")),
+            size: std::mem::size_of::<LightsUniform>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let gbuffer = GBuffer::new(device, labels, config);
+        let ssao = SsaoPass::new(device, labels, config, &gbuffer, reverse_z);
+        let lighting_bind_group = Self::create_lighting_bind_group(
+            device,
+            labels,
+            &lighting_layout,
+            &gbuffer,
+            &lights_buffer,
+            ssao.output(),
+        );
+
+        Ok(Self {
+            geometry_pipeline,
+            lighting_pipeline,
+            lighting_layout,
+            lighting_bind_group,
+            lights_buffer,
+            gbuffer,
+            ssao,
+            ssao_settings: None,
+            reverse_z,
+            labels: labels.clone(),
+        })
+    }
+
+    fn create_geometry_pipeline(device: &wgpu::Device, labels: &Labels, reverse_z: bool) -> wgpu::RenderPipeline {
+        let geometry_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(&labels.label("G-Buffer Shader")),
+            source: wgpu::ShaderSource::Wgsl(include_str!("gbuffer.wgsl").into()),
+        });
+        let geometry_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some(&labels.label("G-Buffer Pipeline Layout")),
+            bind_group_layouts: &[&Renderer::camera_bind_group_layout(device, labels)],
+            push_constant_ranges: &[],
+        });
+        let gbuffer_targets = GBUFFER_FORMATS.map(|format| {
+            Some(wgpu::ColorTargetState {
+                format,
+                blend: None,
+                write_mask: wgpu::ColorWrites::ALL,
+            })
+        });
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(&labels.label("G-Buffer Pipeline")),
+            layout: Some(&geometry_layout),
+            vertex: wgpu::VertexState {
+                module: &geometry_shader,
+                entry_point: Some("vs_main"),
+                buffers: &[Vertex::desc()],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &geometry_shader,
+                entry_point: Some("fs_main"),
+                targets: &gbuffer_targets,
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: Some(wgpu::Face::Back),
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: wgpu::TextureFormat::Depth32Float,
+                depth_write_enabled: true,
+                depth_compare: if reverse_z {
+                    wgpu::CompareFunction::Greater
+                } else {
+                    wgpu::CompareFunction::Less
+                },
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        })
+    }
+
+    fn create_lighting_bind_group(
+        device: &wgpu::Device,
+        labels: &Labels,
+        layout: &wgpu::BindGroupLayout,
+        gbuffer: &GBuffer,
+        lights_buffer: &wgpu::Buffer,
+        ambient_occlusion: &wgpu::TextureView,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&gbuffer.position.1),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&gbuffer.normal.1),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(&gbuffer.albedo.1),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: lights_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: wgpu::BindingResource::TextureView(ambient_occlusion),
+                },
+            ],
+            label: Some(&labels.label("deferred_lighting_bind_group")),
+        })
+    }
+
+    pub fn gbuffer(&self) -> &GBuffer {
+        &self.gbuffer
+    }
+
+    /// Recreates the G-buffer at the surface's new size, and the geometry
+    /// pipeline if the depth convention changed.
+    pub fn resize(&mut self, device: &wgpu::Device, config: &wgpu::SurfaceConfiguration, reverse_z: bool) {
+        if self.reverse_z != reverse_z {
+            self.reverse_z = reverse_z;
+            self.geometry_pipeline = Self::create_geometry_pipeline(device, &self.labels, reverse_z);
+        }
+        self.gbuffer = GBuffer::new(device, &self.labels, config);
+        self.ssao.resize(device, config, &self.gbuffer, reverse_z);
+        self.lighting_bind_group = Self::create_lighting_bind_group(
+            device,
+            &self.labels,
+            &self.lighting_layout,
+            &self.gbuffer,
+            &self.lights_buffer,
+            self.ssao.output(),
+        );
+    }
+
+    pub fn ssao(&self) -> Option<SsaoSettings> {
+        self.ssao_settings
+    }
+
+    /// Enables screen-space ambient occlusion on the ambient term, or
+    /// disables it with `None`.
+    pub fn set_ssao(&mut self, queue: &wgpu::Queue, settings: Option<SsaoSettings>) {
+        if let Some(settings) = &settings {
+            self.ssao.set_settings(queue, settings);
+        }
+        self.ssao_settings = settings;
+    }
+
+    /// Uploads the lights for the next frame. Lights past `MAX_LIGHTS` are ignored.
+    pub fn set_lights(&self, queue: &wgpu::Queue, ambient: [f32; 3], lights: &[PointLight]) {
+        let mut uniform = LightsUniform::zeroed();
+        uniform.ambient = ambient;
+        uniform.count = lights.len().min(MAX_LIGHTS) as u32;
+        for (slot, light) in uniform.lights.iter_mut().zip(lights) {
+            *slot = *light;
+        }
+        queue.write_buffer(&self.lights_buffer, 0, bytemuck::bytes_of(&uniform));
+    }
+
+    /// Records the geometry and lighting passes, writing the lit image to `target`.
+    pub fn render(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        camera_bind_group: &wgpu::BindGroup,
+        target: &wgpu::TextureView,
+        meshes: &[&Mesh],
+    ) {
+        {
+            let clear = |view| {
+                Some(wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })
+            };
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some(&self.labels.label("G-Buffer Pass")),
+                color_attachments: &[
+                    clear(&self.gbuffer.position.1),
+                    clear(&self.gbuffer.normal.1),
+                    clear(&self.gbuffer.albedo.1),
+                ],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &self.gbuffer.depth.1,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(if self.reverse_z { 0.0 } else { 1.0 }),
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    stencil_ops: None,
+                }),
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+            render_pass.set_pipeline(&self.geometry_pipeline);
+            render_pass.set_bind_group(0, camera_bind_group, &[]);
+            for mesh in meshes {
+                Renderer::draw_mesh(&mut render_pass, mesh);
+            }
+        }
+
+        if self.ssao_settings.is_some() {
+            self.ssao.run(encoder, camera_bind_group);
+        } else {
+            self.ssao.clear(encoder);
+        }
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some(&self.labels.label("Deferred Lighting Pass")),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color {
+                        r: 0.1,
+                        g: 0.2,
+                        b: 0.3,
+                        a: 1.0,
+                    }),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        render_pass.set_pipeline(&self.lighting_pipeline);
+        render_pass.set_bind_group(0, &self.lighting_bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::CameraUniform;
+    use crate::render_target::RenderTarget;
+    use crate::test_gpu;
+    use wgpu::util::DeviceExt;
+
+    fn camera_bind_group(device: &wgpu::Device, labels: &Labels) -> wgpu::BindGroup {
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: None,
+            contents: bytemuck::bytes_of(&CameraUniform::new()),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &Renderer::camera_bind_group_layout(device, labels),
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+            label: None,
+        })
+    }
+
+    /// Lit color of the center pixel after drawing `meshes` with an ambient-only light.
+    fn render_center(meshes: &[&Mesh], device: &wgpu::Device, queue: &wgpu::Queue) -> [u8; 4] {
+        let labels = Labels::default();
+        let config = test_gpu::surface_config(4, 4);
+        let deferred = DeferredRenderer::new(device, &labels, &config, false).unwrap();
+        deferred.set_lights(queue, [0.5, 0.5, 0.5], &[]);
+        let target = RenderTarget::new(device, &labels, &config, 4, 4);
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        deferred.render(&mut encoder, &camera_bind_group(device, &labels), target.color_view(), meshes);
+        queue.submit(std::iter::once(encoder.finish()));
+
+        let pixels = test_gpu::read_pixels(device, queue, &target.color_texture.0);
+        let center = (2 * 4 + 2) * 4;
+        pixels[center..center + 4].try_into().unwrap()
+    }
+
+    #[test]
+    fn support_check_rejects_too_few_attachments() {
+        assert_eq!(check_support(&wgpu::Limits::default()), Ok(()));
+
+        let limits = wgpu::Limits {
+            max_color_attachments: 2,
+            ..Default::default()
+        };
+        assert_eq!(
+            check_support(&limits),
+            Err(DeferredError::TooFewColorAttachments {
+                required: 3,
+                available: 2
+            })
+        );
+
+        let limits = wgpu::Limits {
+            max_color_attachment_bytes_per_sample: 16,
+            ..Default::default()
+        };
+        assert!(matches!(
+            check_support(&limits),
+            Err(DeferredError::ColorAttachmentBytes { required, available: 16 }) if required > 16
+        ));
+    }
+
+    #[test]
+    fn gbuffer_targets_use_the_expected_formats() {
+        let Some((device, _queue)) = test_gpu::device() else {
+            return;
+        };
+        let config = test_gpu::surface_config(16, 8);
+        let gbuffer = GBuffer::new(&device, &Labels::default(), &config);
+        let targets = [&gbuffer.position.0, &gbuffer.normal.0, &gbuffer.albedo.0];
+        for (texture, format) in targets.into_iter().zip(GBUFFER_FORMATS) {
+            assert_eq!(texture.format(), format);
+            assert_eq!((texture.width(), texture.height()), (16, 8));
+            assert!(texture.usage().contains(wgpu::TextureUsages::TEXTURE_BINDING));
+        }
+        assert_eq!(gbuffer.depth.0.format(), wgpu::TextureFormat::Depth32Float);
+    }
+
+    #[test]
+    fn lighting_pass_shades_the_gbuffer_contents() {
+        let Some((device, queue)) = test_gpu::device() else {
+            return;
+        };
+        let vertices = [[-1.0, -1.0], [3.0, -1.0], [-1.0, 3.0]]
+            .map(|[x, y]| Vertex::new([x, y, 0.5], [1.0, 0.0, 0.0], [0.0, 0.0, 1.0]));
+        let mesh = Mesh::from_vertices(&device, &Labels::default(), &test_gpu::surface_config(4, 4), &vertices);
+
+        // Red albedo under half ambient light
+        let [r, g, b, a] = render_center(&[&mesh], &device, &queue);
+        assert!(r.abs_diff(128) <= 2 && g == 0 && b == 0 && a == 255, "{:?}", [r, g, b, a]);
+
+        // Uncovered pixels keep the clear color
+        let [r, g, b, _] = render_center(&[], &device, &queue);
+        assert!(r.abs_diff(26) <= 1 && g.abs_diff(51) <= 1 && b.abs_diff(77) <= 1, "{:?}", [r, g, b]);
+    }
+}
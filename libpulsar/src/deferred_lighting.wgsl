@@ -0,0 +1,63 @@
+// Deferred lighting pass: a full-screen triangle reads the G-buffer and
+// accumulates every point light per pixel.
+
+const MAX_LIGHTS: u32 = 64u;
+
+struct PointLight {
+    position: vec3<f32>,
+    radius: f32,
+    color: vec3<f32>,
+    intensity: f32,
+};
+
+struct Lights {
+    ambient: vec3<f32>,
+    count: u32,
+    lights: array<PointLight, MAX_LIGHTS>,
+};
+
+@group(0) @binding(0)
+var gbuffer_position: texture_2d<f32>;
+@group(0) @binding(1)
+var gbuffer_normal: texture_2d<f32>;
+@group(0) @binding(2)
+var gbuffer_albedo: texture_2d<f32>;
+@group(0) @binding(3)
+var<uniform> lights: Lights;
+// All ones while SSAO is disabled
+@group(0) @binding(4)
+var ambient_occlusion: texture_2d<f32>;
+
+@vertex
+fn vs_main(@builtin(vertex_index) vid: u32) -> @builtin(position) vec4<f32> {
+    // Vertices (0,0), (2,0), (0,2) cover the whole screen with one triangle.
+    let uv = vec2<f32>(f32((vid << 1u) & 2u), f32(vid & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+@fragment
+fn fs_main(@builtin(position) frag_coord: vec4<f32>) -> @location(0) vec4<f32> {
+    let coord = vec2<i32>(frag_coord.xy);
+    let position = textureLoad(gbuffer_position, coord, 0);
+    if (position.w == 0.0) {
+        // Nothing was drawn here; match the forward path's clear color
+        return vec4<f32>(0.1, 0.2, 0.3, 1.0);
+    }
+    let normal = textureLoad(gbuffer_normal, coord, 0).xyz;
+    let albedo = textureLoad(gbuffer_albedo, coord, 0).rgb;
+
+    let ao = textureLoad(ambient_occlusion, coord, 0).r;
+    var lit = albedo * lights.ambient * ao;
+    for (var i = 0u; i < min(lights.count, MAX_LIGHTS); i = i + 1u) {
+        let light = lights.lights[i];
+        let to_light = light.position - position.xyz;
+        let distance = length(to_light);
+        if (distance >= light.radius || distance == 0.0) {
+            continue;
+        }
+        let falloff = 1.0 - distance / light.radius;
+        let n_dot_l = max(dot(normal, to_light / distance), 0.0);
+        lit += albedo * light.color * light.intensity * n_dot_l * falloff * falloff;
+    }
+    return vec4<f32>(lit, 1.0);
+}
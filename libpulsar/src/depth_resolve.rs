@@ -0,0 +1,262 @@
+use std::collections::HashMap;
+
+use crate::engine::render::config::Labels;
+use crate::resources::{ResourceCategory, Tracked};
+
+pub const RESOLVED_DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
+/// How the samples of a multisampled depth pixel are combined. Depth can't be
+/// averaged like color without inventing surfaces that don't exist, so one
+/// real sample is picked instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DepthResolveMode {
+    /// The first sample. Cheapest, and usually matches the resolved color.
+    #[default]
+    SampleZero,
+    /// The smallest depth: the nearest surface, or the farthest with reverse Z.
+    Min,
+    /// The largest depth: the farthest surface, or the nearest with reverse Z.
+    Max,
+}
+
+impl DepthResolveMode {
+    /// Value of the shader's `MODE` override.
+    fn shader_constant(self) -> f64 {
+        match self {
+            DepthResolveMode::SampleZero => 0.0,
+            DepthResolveMode::Min => 1.0,
+            DepthResolveMode::Max => 2.0,
+        }
+    }
+}
+
+/// Whether a resolved depth texture is needed: only a multisampled depth
+/// buffer that some effect wants to sample.
+pub fn needs_depth_resolve(sample_count: u32, depth_effects: bool) -> bool {
+    sample_count > 1 && depth_effects
+}
+
+/// Copies a multisampled depth buffer into a single-sample texture that
+/// post-process effects can read, with a full-screen pass that writes depth.
+pub struct DepthResolvePass {
+    pipeline: wgpu::RenderPipeline,
+    bind_group: wgpu::BindGroup,
+    resolved: (Tracked<wgpu::Texture>, wgpu::TextureView),
+    mode: DepthResolveMode,
+    labels: Labels,
+}
+
+impl DepthResolvePass {
+    /// `msaa_depth` must be a multisampled `Depth32Float` view created with
+    /// `TextureUsages::TEXTURE_BINDING`.
+    pub fn new(
+        device: &wgpu::Device,
+        labels: &Labels,
+        config: &wgpu::SurfaceConfiguration,
+        msaa_depth: &wgpu::TextureView,
+        mode: DepthResolveMode,
+    ) -> Self {
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: true,
+                },
+                count: None,
+            }],
+            label: Some(&labels.label("depth_resolve_bind_group_layout")),
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(msaa_depth),
+            }],
+            label: Some(&labels.label("depth_resolve_bind_group")),
+        });
+        Self {
+            pipeline: Self::create_pipeline(device, labels, &layout, mode),
+            bind_group,
+            resolved: Self::create_target(device, labels, config),
+            mode,
+            labels: labels.clone(),
+        }
+    }
+
+    fn create_pipeline(
+        device: &wgpu::Device,
+        labels: &Labels,
+        layout: &wgpu::BindGroupLayout,
+        mode: DepthResolveMode,
+    ) -> wgpu::RenderPipeline {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(&labels.label("Depth Resolve Shader")),
+            source: wgpu::ShaderSource::Wgsl(include_str!("depth_resolve.wgsl").into()),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some(&labels.label("Depth Resolve Pipeline Layout")),
+            bind_group_layouts: &[layout],
+            push_constant_ranges: &[],
+        });
+        let constants = HashMap::from([("MODE".to_string(), mode.shader_constant())]);
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(&labels.label("Depth Resolve Pipeline")),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[],
+                compilation_options: wgpu::PipelineCompilationOptions {
+                    constants: &constants,
+                    ..Default::default()
+                },
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: RESOLVED_DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::Always,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        })
+    }
+
+    fn create_target(
+        device: &wgpu::Device,
+        labels: &Labels,
+        config: &wgpu::SurfaceConfiguration,
+    ) -> (Tracked<wgpu::Texture>, wgpu::TextureView) {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(&labels.label("Resolved Depth Texture")),
+            size: wgpu::Extent3d {
+                width: config.width.max(1),
+                height: config.height.max(1),
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: RESOLVED_DEPTH_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        (Tracked::texture(texture, ResourceCategory::RenderTarget), view)
+    }
+
+    pub fn mode(&self) -> DepthResolveMode {
+        self.mode
+    }
+
+    /// The single-sample depth texture, valid once `run` has executed this frame.
+    pub fn texture(&self) -> &wgpu::Texture {
+        &self.resolved.0
+    }
+
+    pub fn output(&self) -> &wgpu::TextureView {
+        &self.resolved.1
+    }
+
+    /// Records the resolve. The multisampled depth buffer must already hold
+    /// this frame's geometry.
+    pub fn run(&self, encoder: &mut wgpu::CommandEncoder) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some(&self.labels.label("Depth Resolve Pass")),
+            color_attachments: &[],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &self.resolved.1,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::render::ctx::capture_errors;
+    use crate::renderer::{AaMode, Renderer};
+    use crate::ssao::SsaoSettings;
+    use crate::test_gpu;
+
+    #[test]
+    fn only_multisampled_depth_with_a_depth_effect_is_resolved() {
+        assert!(needs_depth_resolve(4, true));
+        assert!(!needs_depth_resolve(4, false));
+        assert!(!needs_depth_resolve(1, true));
+        assert!(!needs_depth_resolve(1, false));
+    }
+
+    #[test]
+    fn msaa_with_ssao_creates_a_single_sample_resolved_depth() {
+        let Some((adapter, device, queue)) = test_gpu::adapter() else {
+            return;
+        };
+        let labels = Labels::default();
+        let config = test_gpu::surface_config(16, 16);
+        let mut renderer = pollster::block_on(Renderer::new(&device, &labels, &queue, &config)).unwrap();
+        renderer.set_aa(&adapter, &device, &config, AaMode::Msaa(4)).unwrap();
+        renderer.set_depth_resolve(&device, &config, Some(DepthResolveMode::Min));
+        assert!(renderer.resolved_depth().is_none());
+
+        renderer.set_ssao(&device, &queue, &config, Some(SsaoSettings::default()));
+        let resolved = renderer.resolved_depth().expect("MSAA and SSAO need resolved depth");
+        assert_eq!(resolved.texture().sample_count(), 1);
+        assert_eq!(resolved.texture().format(), RESOLVED_DEPTH_FORMAT);
+        assert_eq!((resolved.texture().width(), resolved.texture().height()), (16, 16));
+        assert_eq!(resolved.mode(), DepthResolveMode::Min);
+
+        renderer.set_aa(&adapter, &device, &config, AaMode::None).unwrap();
+        assert!(renderer.resolved_depth().is_none());
+    }
+
+    #[test]
+    fn resolve_pass_builds_without_validation_errors() {
+        let Some((device, _queue)) = test_gpu::device() else {
+            return;
+        };
+        let labels = Labels::default();
+        let config = test_gpu::surface_config(16, 16);
+        let msaa_depth = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("MSAA Depth"),
+            size: wgpu::Extent3d {
+                width: 16,
+                height: 16,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 4,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Depth32Float,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let view = msaa_depth.create_view(&wgpu::TextureViewDescriptor::default());
+        let result = pollster::block_on(capture_errors(&device, "Depth Resolve", || {
+            DepthResolvePass::new(&device, &labels, &config, &view, DepthResolveMode::Max)
+        }));
+        assert!(result.is_ok(), "{:?}", result.err());
+    }
+}
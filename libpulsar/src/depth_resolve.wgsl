@@ -0,0 +1,36 @@
+// Resolves a multisampled depth buffer into a single-sample one by writing
+// each pixel's combined depth through frag_depth.
+
+// 0 = sample 0, 1 = minimum, 2 = maximum; see `DepthResolveMode`.
+override MODE: u32 = 0u;
+
+@group(0) @binding(0)
+// A multisampled float texture rather than `texture_depth_multisampled_2d`,
+// which GL backends cannot `textureLoad` from.
+var msaa_depth: texture_multisampled_2d<f32>;
+
+@vertex
+fn vs_main(@builtin(vertex_index) vid: u32) -> @builtin(position) vec4<f32> {
+    // Vertices (0,0), (2,0), (0,2) cover the whole screen with one triangle.
+    let uv = vec2<f32>(f32((vid << 1u) & 2u), f32(vid & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+@fragment
+fn fs_main(@builtin(position) frag_coord: vec4<f32>) -> @builtin(frag_depth) f32 {
+    let coord = vec2<i32>(frag_coord.xy);
+    var depth = textureLoad(msaa_depth, coord, 0).r;
+    if (MODE == 0u) {
+        return depth;
+    }
+    let samples = i32(textureNumSamples(msaa_depth));
+    for (var i = 1; i < samples; i = i + 1) {
+        let sample = textureLoad(msaa_depth, coord, i).r;
+        if (MODE == 1u) {
+            depth = min(depth, sample);
+        } else {
+            depth = max(depth, sample);
+        }
+    }
+    return depth;
+}
@@ -0,0 +1,115 @@
+use std::cmp::Ordering;
+
+/// One draw with the GPU state it needs. The ids only have to be equal for
+/// equal state, e.g. indices into the caller's pipeline and material lists.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DrawCommand {
+    pub pipeline: usize,
+    pub material: usize,
+    pub mesh: usize,
+    /// Blended draws go after the opaque ones, back to front.
+    pub transparent: bool,
+    /// Distance from the camera; only orders transparent draws.
+    pub depth: f32,
+    /// Index of the draw in the caller's list.
+    pub index: usize,
+}
+
+fn draw_order(a: &DrawCommand, b: &DrawCommand) -> Ordering {
+    match (a.transparent, b.transparent) {
+        (false, false) => (a.pipeline, a.material, a.mesh).cmp(&(b.pipeline, b.material, b.mesh)),
+        (true, true) => b.depth.total_cmp(&a.depth),
+        (transparent, _) => transparent.cmp(&!transparent),
+    }
+}
+
+/// Orders opaque draws by pipeline, then material, then mesh, so draws
+/// sharing state end up next to each other, followed by the transparent
+/// draws from farthest to nearest. The sort is stable: ties keep the
+/// caller's order.
+pub fn sort_draws(draws: &mut [DrawCommand]) {
+    draws.sort_by(draw_order);
+}
+
+/// Number of `set_pipeline` and material `set_bind_group` calls `draws`
+/// needs when redundant ones are skipped.
+pub fn state_changes(draws: &[DrawCommand]) -> (usize, usize) {
+    let changes = |key: fn(&DrawCommand) -> usize| {
+        draws
+            .iter()
+            .enumerate()
+            .filter(|(i, draw)| *i == 0 || key(&draws[i - 1]) != key(draw))
+            .count()
+    };
+    (changes(|draw| draw.pipeline), changes(|draw| draw.material))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn opaque(pipeline: usize, material: usize, mesh: usize, index: usize) -> DrawCommand {
+        DrawCommand {
+            pipeline,
+            material,
+            mesh,
+            transparent: false,
+            depth: 0.0,
+            index,
+        }
+    }
+
+    fn transparent(depth: f32, index: usize) -> DrawCommand {
+        DrawCommand {
+            transparent: true,
+            depth,
+            ..opaque(0, 0, 0, index)
+        }
+    }
+
+    fn order(draws: &[DrawCommand]) -> Vec<usize> {
+        draws.iter().map(|draw| draw.index).collect()
+    }
+
+    #[test]
+    fn draws_sharing_a_material_end_up_together() {
+        let mut draws = [
+            opaque(0, 1, 0, 0),
+            opaque(0, 2, 1, 1),
+            opaque(0, 1, 2, 2),
+            opaque(0, 2, 3, 3),
+            opaque(0, 1, 4, 4),
+        ];
+        assert_eq!(state_changes(&draws), (1, 5));
+        sort_draws(&mut draws);
+        assert_eq!(order(&draws), [0, 2, 4, 1, 3]);
+        assert_eq!(state_changes(&draws), (1, 2));
+    }
+
+    #[test]
+    fn pipelines_group_before_materials() {
+        let mut draws = [opaque(1, 0, 0, 0), opaque(0, 1, 0, 1), opaque(1, 1, 0, 2), opaque(0, 0, 0, 3)];
+        sort_draws(&mut draws);
+        assert_eq!(order(&draws), [3, 1, 0, 2]);
+        assert_eq!(state_changes(&draws), (2, 4));
+    }
+
+    #[test]
+    fn ties_keep_the_callers_order() {
+        let mut draws = [opaque(0, 0, 0, 0), opaque(0, 0, 0, 1), opaque(0, 0, 0, 2)];
+        sort_draws(&mut draws);
+        assert_eq!(order(&draws), [0, 1, 2]);
+    }
+
+    #[test]
+    fn transparent_draws_follow_opaque_ones_back_to_front() {
+        let mut draws = [transparent(2.0, 0), opaque(3, 3, 3, 1), transparent(9.0, 2), transparent(5.0, 3)];
+        sort_draws(&mut draws);
+        assert_eq!(order(&draws), [1, 2, 3, 0]);
+    }
+
+    #[test]
+    fn empty_draw_list_changes_nothing() {
+        assert_eq!(state_changes(&[]), (0, 0));
+    }
+}
@@ -0,0 +1,125 @@
+use gilrs::{EventType, Gilrs};
+use log::{debug, warn};
+
+use crate::engine::input::Input;
+
+/// Stick travel ignored around the center, as a fraction of full deflection.
+pub const DEFAULT_DEADZONE: f32 = 0.15;
+
+/// Zeroes `value` inside the deadzone and rescales the rest so output still
+/// spans the full `-1..=1` range instead of jumping at the deadzone edge.
+pub fn apply_deadzone(value: f32, deadzone: f32) -> f32 {
+    let deadzone = deadzone.clamp(0.0, 0.99);
+    let magnitude = value.abs().min(1.0);
+    if magnitude <= deadzone {
+        return 0.0;
+    }
+    value.signum() * (magnitude - deadzone) / (1.0 - deadzone)
+}
+
+/// Polls connected gamepads and feeds their buttons and axes into `Input`.
+///
+/// Buttons and axes from every connected pad are merged, which suits single
+/// player games; local multiplayer would need per-pad `Input`s.
+pub struct Gamepads {
+    /// `None` when the platform backend failed to start; polling does nothing.
+    gilrs: Option<Gilrs>,
+    pub deadzone: f32,
+}
+
+impl Default for Gamepads {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Gamepads {
+    pub fn new() -> Self {
+        let gilrs = match Gilrs::new() {
+            Ok(gilrs) => Some(gilrs),
+            Err(err) => {
+                warn!("Gamepad support unavailable: {}", err);
+                None
+            }
+        };
+        Self {
+            gilrs,
+            deadzone: DEFAULT_DEADZONE,
+        }
+    }
+
+    pub fn is_available(&self) -> bool {
+        self.gilrs.is_some()
+    }
+
+    /// Number of gamepads currently connected.
+    pub fn connected(&self) -> usize {
+        self.gilrs.as_ref().map_or(0, |gilrs| gilrs.gamepads().count())
+    }
+
+    /// Drains pending gamepad events into `input`. Call once per frame.
+    pub fn poll(&mut self, input: &mut Input) {
+        let Some(gilrs) = &mut self.gilrs else {
+            return;
+        };
+        while let Some(event) = gilrs.next_event() {
+            match event.event {
+                EventType::Connected => {
+                    debug!("Gamepad connected: {}", gilrs.gamepad(event.id).name());
+                }
+                EventType::Disconnected => {
+                    debug!("Gamepad {} disconnected", event.id);
+                    // Don't leave buttons stuck down when a pad is unplugged mid-press
+                    input.release_gamepad();
+                }
+                EventType::ButtonPressed(button, _) => input.set_gamepad_button(button, true),
+                EventType::ButtonReleased(button, _) => input.set_gamepad_button(button, false),
+                EventType::AxisChanged(axis, value, _) => {
+                    input.set_gamepad_axis(axis, apply_deadzone(value, self.deadzone));
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gilrs::Axis;
+
+    #[test]
+    fn values_inside_the_deadzone_are_zero() {
+        assert_eq!(apply_deadzone(0.1, 0.15), 0.0);
+        assert_eq!(apply_deadzone(-0.15, 0.15), 0.0);
+        assert_eq!(apply_deadzone(0.0, 0.15), 0.0);
+    }
+
+    #[test]
+    fn values_outside_are_rescaled_to_the_full_range() {
+        assert!((apply_deadzone(0.575, 0.15) - 0.5).abs() < 1e-6);
+        assert!((apply_deadzone(-0.575, 0.15) + 0.5).abs() < 1e-6);
+        assert_eq!(apply_deadzone(1.0, 0.15), 1.0);
+        assert_eq!(apply_deadzone(-1.5, 0.15), -1.0);
+        // Just past the edge stays close to zero instead of jumping
+        assert!(apply_deadzone(0.151, 0.15) < 0.01);
+    }
+
+    #[test]
+    fn zero_deadzone_passes_values_through() {
+        assert_eq!(apply_deadzone(0.3, 0.0), 0.3);
+        assert_eq!(apply_deadzone(-0.3, -1.0), -0.3);
+    }
+
+    #[test]
+    fn deadzoned_stick_drives_the_movement_actions() {
+        let mut input = Input::default();
+        input.set_gamepad_axis(Axis::LeftStickY, apply_deadzone(0.1, DEFAULT_DEADZONE));
+        assert_eq!(input.action_value("move_forward"), 0.0);
+
+        input.set_gamepad_axis(Axis::LeftStickY, apply_deadzone(-1.0, DEFAULT_DEADZONE));
+        assert_eq!(input.action_value("move_back"), 1.0);
+        assert!(input.action_down("move_back"));
+        assert!(!input.action_down("move_forward"));
+    }
+}
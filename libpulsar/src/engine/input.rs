@@ -0,0 +1,360 @@
+use std::collections::{HashMap, HashSet};
+
+use gilrs::{Axis, Button};
+use serde::{Deserialize, Serialize};
+use winit::event::{ElementState, MouseButton, WindowEvent};
+use winit::keyboard::{KeyCode, PhysicalKey};
+
+/// Axis deflection past which an axis binding counts as held.
+pub const AXIS_PRESS_THRESHOLD: f32 = 0.5;
+
+/// A physical input an action can be bound to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Binding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    GamepadButton(Button),
+    /// One direction of a stick or trigger axis.
+    GamepadAxis { axis: Axis, positive: bool },
+}
+
+/// Named actions and the inputs that trigger them, so controllers don't
+/// depend on particular keys and players can rebind them.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ActionMap {
+    bindings: HashMap<String, Vec<Binding>>,
+}
+
+impl Default for ActionMap {
+    /// WASD or the left stick to move, Space or the south face button to
+    /// jump, left Ctrl or the east face button to crouch, left Shift to
+    /// change the fly camera's speed with the scroll wheel.
+    fn default() -> Self {
+        let mut map = Self::empty();
+        let stick = |axis, positive| Binding::GamepadAxis { axis, positive };
+        map.bind("move_forward", Binding::Key(KeyCode::KeyW));
+        map.bind("move_forward", stick(Axis::LeftStickY, true));
+        map.bind("move_back", Binding::Key(KeyCode::KeyS));
+        map.bind("move_back", stick(Axis::LeftStickY, false));
+        map.bind("move_left", Binding::Key(KeyCode::KeyA));
+        map.bind("move_left", stick(Axis::LeftStickX, false));
+        map.bind("move_right", Binding::Key(KeyCode::KeyD));
+        map.bind("move_right", stick(Axis::LeftStickX, true));
+        map.bind("jump", Binding::Key(KeyCode::Space));
+        map.bind("jump", Binding::GamepadButton(Button::South));
+        map.bind("crouch", Binding::Key(KeyCode::ControlLeft));
+        map.bind("crouch", Binding::GamepadButton(Button::East));
+        map.bind("camera_speed_modifier", Binding::Key(KeyCode::ShiftLeft));
+        map
+    }
+}
+
+impl ActionMap {
+    /// A map with no actions.
+    pub fn empty() -> Self {
+        Self {
+            bindings: HashMap::new(),
+        }
+    }
+
+    /// Adds `binding` to `action`'s existing bindings.
+    pub fn bind(&mut self, action: &str, binding: Binding) {
+        let bindings = self.bindings.entry(action.to_owned()).or_default();
+        if !bindings.contains(&binding) {
+            bindings.push(binding);
+        }
+    }
+
+    /// Replaces all of `action`'s bindings.
+    pub fn rebind(&mut self, action: &str, bindings: &[Binding]) {
+        self.bindings.insert(action.to_owned(), bindings.to_vec());
+    }
+
+    pub fn unbind(&mut self, action: &str) {
+        self.bindings.remove(action);
+    }
+
+    pub fn bindings(&self, action: &str) -> &[Binding] {
+        self.bindings.get(action).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn actions(&self) -> impl Iterator<Item = &str> {
+        self.bindings.keys().map(String::as_str)
+    }
+}
+
+/// Which keys and buttons are held and where the gamepad axes are, queried
+/// through an `ActionMap`. Presses and releases are also remembered until
+/// `end_frame`, for edge-triggered actions.
+#[derive(Debug, Clone, Default)]
+pub struct Input {
+    pub actions: ActionMap,
+    held: HashSet<Binding>,
+    /// Went down since the last `end_frame`.
+    pressed: HashSet<Binding>,
+    /// Went up since the last `end_frame`.
+    released: HashSet<Binding>,
+    /// Deadzone-applied gamepad axis values.
+    axes: HashMap<Axis, f32>,
+}
+
+impl Input {
+    pub fn new(actions: ActionMap) -> Self {
+        Self {
+            actions,
+            held: HashSet::new(),
+            pressed: HashSet::new(),
+            released: HashSet::new(),
+            axes: HashMap::new(),
+        }
+    }
+
+    /// Updates held inputs from a window event. Returns whether it was an
+    /// input event.
+    pub fn handle_event(&mut self, event: &WindowEvent) -> bool {
+        match event {
+            WindowEvent::KeyboardInput { event, .. } => {
+                self.handle_key(event.physical_key, event.state, event.repeat);
+                true
+            }
+            WindowEvent::MouseInput { state, button, .. } => {
+                self.set_held(Binding::Mouse(*button), *state == ElementState::Pressed);
+                true
+            }
+            // Releases that happen while unfocused never reach us
+            WindowEvent::Focused(false) => {
+                self.clear();
+                false
+            }
+            _ => false,
+        }
+    }
+
+    /// Updates a key from a keyboard event's fields. OS key repeat re-sends
+    /// the press while the key is held; it is not a new physical press.
+    pub fn handle_key(&mut self, physical_key: PhysicalKey, state: ElementState, repeat: bool) {
+        if repeat {
+            return;
+        }
+        if let PhysicalKey::Code(code) = physical_key {
+            self.set_held(Binding::Key(code), state == ElementState::Pressed);
+        }
+    }
+
+    /// Records presses and releases only when the held state changes, so a
+    /// binding held across several events is pressed once.
+    pub fn set_held(&mut self, binding: Binding, held: bool) {
+        if held {
+            if self.held.insert(binding) {
+                self.pressed.insert(binding);
+            }
+        } else if self.held.remove(&binding) {
+            self.released.insert(binding);
+        }
+    }
+
+    /// Forgets this frame's presses and releases. The engine calls it once
+    /// per frame, after updating.
+    pub fn end_frame(&mut self) {
+        self.pressed.clear();
+        self.released.clear();
+    }
+
+    pub fn set_gamepad_button(&mut self, button: Button, held: bool) {
+        self.set_held(Binding::GamepadButton(button), held);
+    }
+
+    pub fn set_gamepad_axis(&mut self, axis: Axis, value: f32) {
+        if value == 0.0 {
+            self.axes.remove(&axis);
+        } else {
+            self.axes.insert(axis, value);
+        }
+    }
+
+    /// Releases every gamepad button and centers every axis.
+    pub fn release_gamepad(&mut self) {
+        self.held.retain(|binding| !matches!(binding, Binding::GamepadButton(_)));
+        self.axes.clear();
+    }
+
+    /// Releases everything, e.g. when focus is lost or the console opens.
+    /// No release edges are reported.
+    pub fn clear(&mut self) {
+        self.held.clear();
+        self.pressed.clear();
+        self.released.clear();
+        self.axes.clear();
+    }
+
+    pub fn axis(&self, axis: Axis) -> f32 {
+        self.axes.get(&axis).copied().unwrap_or(0.0)
+    }
+
+    /// How far `binding` is pressed, from 0 to 1. Buttons and keys are 0 or 1.
+    pub fn value(&self, binding: Binding) -> f32 {
+        match binding {
+            Binding::GamepadAxis { axis, positive } => {
+                let value = self.axis(axis);
+                if positive { value.max(0.0) } else { (-value).max(0.0) }
+            }
+            other => self.held.contains(&other) as u8 as f32,
+        }
+    }
+
+    pub fn is_held(&self, binding: Binding) -> bool {
+        match binding {
+            Binding::GamepadAxis { .. } => self.value(binding) > AXIS_PRESS_THRESHOLD,
+            other => self.held.contains(&other),
+        }
+    }
+
+    /// Whether `binding` went down this frame. Gamepad axes have no edges.
+    pub fn was_pressed(&self, binding: Binding) -> bool {
+        self.pressed.contains(&binding)
+    }
+
+    pub fn was_released(&self, binding: Binding) -> bool {
+        self.released.contains(&binding)
+    }
+
+    /// True only on the frame `key` was physically pressed, not for key repeats.
+    pub fn was_key_pressed(&self, key: KeyCode) -> bool {
+        self.was_pressed(Binding::Key(key))
+    }
+
+    pub fn was_key_released(&self, key: KeyCode) -> bool {
+        self.was_released(Binding::Key(key))
+    }
+
+    /// Whether any input bound to `action` went down this frame.
+    pub fn action_pressed(&self, action: &str) -> bool {
+        self.actions
+            .bindings(action)
+            .iter()
+            .any(|binding| self.was_pressed(*binding))
+    }
+
+    /// Whether any input bound to `action` is held. Unknown actions are never down.
+    pub fn action_down(&self, action: &str) -> bool {
+        self.actions
+            .bindings(action)
+            .iter()
+            .any(|binding| self.is_held(*binding))
+    }
+
+    /// Strongest of `action`'s bindings, from 0 to 1, so analog sticks can
+    /// drive the same actions as keys.
+    pub fn action_value(&self, action: &str) -> f32 {
+        self.actions
+            .bindings(action)
+            .iter()
+            .map(|binding| self.value(*binding))
+            .fold(0.0, f32::max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SPACE: Binding = Binding::Key(KeyCode::Space);
+    const ENTER: Binding = Binding::Key(KeyCode::Enter);
+
+    #[test]
+    fn rebinding_jump_moves_it_to_the_new_key() {
+        let mut input = Input::default();
+        input.actions.rebind("jump", &[ENTER]);
+
+        input.set_held(SPACE, true);
+        assert!(!input.action_down("jump"));
+        input.set_held(ENTER, true);
+        assert!(input.action_down("jump"));
+        assert!(input.action_pressed("jump"));
+        assert_eq!(input.actions.bindings("jump"), &[ENTER]);
+    }
+
+    #[test]
+    fn any_of_several_bindings_triggers_the_action() {
+        let mut input = Input::new(ActionMap::empty());
+        input.actions.bind("fire", Binding::Mouse(MouseButton::Left));
+        input.actions.bind("fire", ENTER);
+        input.actions.bind("fire", ENTER);
+        assert_eq!(input.actions.bindings("fire").len(), 2);
+
+        input.set_held(ENTER, true);
+        assert!(input.action_down("fire"));
+        input.set_held(ENTER, false);
+        input.set_held(Binding::Mouse(MouseButton::Left), true);
+        assert!(input.action_down("fire"));
+    }
+
+    #[test]
+    fn unbound_and_unknown_actions_are_never_down() {
+        let mut input = Input::default();
+        input.set_held(SPACE, true);
+        assert!(input.action_down("jump"));
+        input.actions.unbind("jump");
+        assert!(!input.action_down("jump"));
+        assert!(!input.action_down("no_such_action"));
+        assert_eq!(input.action_value("no_such_action"), 0.0);
+    }
+
+    #[test]
+    fn presses_last_until_the_end_of_the_frame() {
+        let mut input = Input::default();
+        input.set_held(SPACE, true);
+        input.set_held(SPACE, true);
+        assert!(input.action_pressed("jump"));
+        input.end_frame();
+        assert!(!input.action_pressed("jump"));
+        assert!(input.action_down("jump"));
+
+        input.set_held(SPACE, false);
+        assert!(input.was_released(SPACE));
+        assert!(!input.action_down("jump"));
+    }
+
+    #[test]
+    fn bindings_survive_a_json_round_trip() {
+        let mut actions = ActionMap::default();
+        actions.rebind("jump", &[ENTER, Binding::GamepadButton(Button::North)]);
+        let json = serde_json::to_string(&actions).unwrap();
+        let loaded: ActionMap = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded, actions);
+    }
+
+    #[test]
+    fn os_key_repeat_is_not_a_press() {
+        let mut input = Input::default();
+        let space = PhysicalKey::Code(KeyCode::Space);
+        input.handle_key(space, ElementState::Pressed, false);
+        assert!(input.was_key_pressed(KeyCode::Space));
+        input.end_frame();
+
+        // Holding the key makes the OS send repeated presses
+        input.handle_key(space, ElementState::Pressed, true);
+        input.handle_key(space, ElementState::Pressed, true);
+        assert!(!input.was_key_pressed(KeyCode::Space));
+        assert!(!input.action_pressed("jump"));
+        assert!(input.action_down("jump"));
+
+        input.handle_key(space, ElementState::Released, false);
+        assert!(input.was_key_released(KeyCode::Space));
+        input.end_frame();
+        input.handle_key(space, ElementState::Pressed, false);
+        assert!(input.was_key_pressed(KeyCode::Space));
+    }
+
+    #[test]
+    fn repeat_after_losing_focus_does_not_press_the_key() {
+        let mut input = Input::default();
+        let space = PhysicalKey::Code(KeyCode::Space);
+        input.handle_key(space, ElementState::Pressed, false);
+        input.end_frame();
+        assert!(!input.handle_event(&WindowEvent::Focused(false)));
+        input.handle_key(space, ElementState::Pressed, true);
+        assert!(!input.is_held(SPACE));
+        assert!(!input.was_key_pressed(KeyCode::Space));
+    }
+}
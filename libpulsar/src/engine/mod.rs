@@ -1 +1,6 @@
-pub mod render;
\ No newline at end of file
+pub mod gamepad;
+pub mod input;
+pub mod render;
+pub mod stats;
+pub mod text_input;
+pub mod time;
@@ -0,0 +1,548 @@
+use log::warn;
+use winit::dpi::{PhysicalPosition, PhysicalSize};
+use winit::monitor::MonitorHandle;
+use winit::window::{Window, WindowAttributes};
+
+/// Device limits to request, from most to least portable.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum LimitsPreset {
+    /// WebGL2-compatible limits, with texture sizes raised to what the
+    /// adapter supports. Runs everywhere.
+    #[default]
+    Downlevel,
+    /// wgpu's defaults, supported by every native Vulkan, Metal and DX12 device.
+    Default,
+    /// Everything the adapter offers, for desktop-only builds that want the
+    /// biggest textures and the most bind groups available.
+    HighPerformance,
+    Custom(wgpu::Limits),
+}
+
+/// Settings used when creating the GPU instance, device and surface.
+#[derive(Debug, Clone)]
+pub struct GpuConfig {
+    /// Prefix for every resource label, e.g. `"Pulsar/Uniform Buffer"`.
+    pub label: String,
+    /// Requests the backend validation layers and debug markers. Defaults to
+    /// on in debug builds; `WGPU_VALIDATION`/`WGPU_DEBUG` still override it.
+    pub enable_validation: bool,
+    /// How many frames the GPU may queue ahead of the one being presented,
+    /// between 1 and 3. Lower values cut input latency; higher values let the
+    /// CPU run further ahead and smooth out uneven frame times at the cost of
+    /// extra latency. Competitive games usually want 1.
+    pub frame_latency: u32,
+    /// Picks an sRGB surface format when the surface offers one (see
+    /// `select_surface_format`).
+    pub prefer_srgb: bool,
+    /// Forces a surface format, e.g. `Rgba16Float` for HDR output, instead of
+    /// the `prefer_srgb` choice. Unsupported formats fall back to it with a
+    /// warning.
+    pub surface_format: Option<wgpu::TextureFormat>,
+    /// Limits requested from the device; see `GpuConfig::required_limits`.
+    pub limits: LimitsPreset,
+}
+
+impl Default for GpuConfig {
+    fn default() -> Self {
+        Self {
+            label: String::from("Pulsar"),
+            enable_validation: cfg!(debug_assertions),
+            frame_latency: 2,
+            prefer_srgb: true,
+            surface_format: None,
+            limits: LimitsPreset::default(),
+        }
+    }
+}
+
+impl GpuConfig {
+    /// Sets `frame_latency`, clamped to the supported `1..=3` range.
+    pub fn set_frame_latency(&mut self, frame_latency: u32) {
+        self.frame_latency = frame_latency.clamp(1, 3);
+    }
+
+    /// Writes the settings owned by this config into a surface configuration.
+    pub fn apply_to_surface(&self, surface_config: &mut wgpu::SurfaceConfiguration) {
+        surface_config.desired_maximum_frame_latency = self.frame_latency.clamp(1, 3);
+    }
+
+    /// Limits to request for an adapter offering `adapter_limits`. Any limit
+    /// the preset asks for beyond the adapter's is clamped, with a warning,
+    /// rather than failing device creation.
+    pub fn required_limits(&self, adapter_limits: &wgpu::Limits) -> wgpu::Limits {
+        let requested = match &self.limits {
+            LimitsPreset::Downlevel => wgpu::Limits::downlevel_webgl2_defaults().using_resolution(adapter_limits.clone()),
+            LimitsPreset::Default => wgpu::Limits::default(),
+            LimitsPreset::HighPerformance => adapter_limits.clone(),
+            LimitsPreset::Custom(limits) => limits.clone(),
+        };
+        clamp_limits(requested, adapter_limits)
+    }
+
+    /// Surface format to use out of the surface's supported `formats`: the
+    /// `surface_format` override if the surface supports it, otherwise the
+    /// `prefer_srgb` choice.
+    pub fn surface_format(&self, formats: &[wgpu::TextureFormat]) -> Option<wgpu::TextureFormat> {
+        if let Some(requested) = self.surface_format {
+            if formats.contains(&requested) {
+                return Some(requested);
+            }
+            warn!(
+                "Surface format {:?} is not supported (available: {:?}), using the default",
+                requested, formats
+            );
+        }
+        select_surface_format(formats, self.prefer_srgb)
+    }
+
+    /// Label for a resource named `name` created under this config.
+    pub fn label(&self, name: &str) -> String {
+        label(&self.label, name)
+    }
+
+    /// The `label` prefix on its own, for renderers and passes that create
+    /// resources after construction.
+    pub fn labels(&self) -> Labels {
+        Labels::new(self.label.clone())
+    }
+
+    pub fn instance_flags(&self) -> wgpu::InstanceFlags {
+        let flags = if self.enable_validation {
+            wgpu::InstanceFlags::debugging()
+        } else {
+            wgpu::InstanceFlags::empty()
+        };
+        flags.with_env()
+    }
+
+    pub fn instance_descriptor(&self) -> wgpu::InstanceDescriptor {
+        wgpu::InstanceDescriptor {
+            flags: self.instance_flags(),
+            ..Default::default()
+        }
+    }
+
+    /// The device to request from an adapter offering `adapter_features` and
+    /// `adapter_limits`.
+    pub fn device_request(&self, adapter_features: wgpu::Features, adapter_limits: &wgpu::Limits) -> DeviceRequest {
+        DeviceRequest {
+            label: self.label("Device"),
+            // Optional: lets the renderer clamp depth when the adapter supports it
+            required_features: adapter_features & wgpu::Features::DEPTH_CLIP_CONTROL,
+            required_limits: self.required_limits(adapter_limits),
+        }
+    }
+}
+
+/// What `WgpuCtx::new` asks the adapter for when creating its device.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeviceRequest {
+    pub label: String,
+    pub required_features: wgpu::Features,
+    pub required_limits: wgpu::Limits,
+}
+
+impl DeviceRequest {
+    pub fn descriptor(&self) -> wgpu::DeviceDescriptor<'_> {
+        wgpu::DeviceDescriptor {
+            label: Some(&self.label),
+            required_features: self.required_features,
+            required_limits: self.required_limits.clone(),
+            memory_hints: wgpu::MemoryHints::Performance,
+        }
+    }
+}
+
+/// Size winit gives windows opened without one; used to center them.
+pub const DEFAULT_WINDOW_SIZE: (u32, u32) = (800, 600);
+
+/// Settings for the windows the engine opens.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EngineConfig {
+    pub title: String,
+    /// Inner size in physical pixels; `None` leaves it to the platform.
+    pub size: Option<(u32, u32)>,
+    /// Outer position of the top-left corner in physical pixels. Takes
+    /// precedence over `center_on_primary`; `None` lets the platform place it.
+    pub position: Option<(i32, i32)>,
+    /// Centers the window on the primary monitor when no `position` is set.
+    pub center_on_primary: bool,
+    pub maximized: bool,
+}
+
+impl Default for EngineConfig {
+    fn default() -> Self {
+        Self {
+            title: String::from("Zenyx"),
+            size: None,
+            position: None,
+            center_on_primary: false,
+            maximized: false,
+        }
+    }
+}
+
+impl EngineConfig {
+    /// Where the window should open, given the primary monitor's position and
+    /// size, or `None` to let the platform decide.
+    pub fn window_position(
+        &self,
+        monitor: Option<(PhysicalPosition<i32>, PhysicalSize<u32>)>,
+    ) -> Option<PhysicalPosition<i32>> {
+        if let Some((x, y)) = self.position {
+            return Some(PhysicalPosition::new(x, y));
+        }
+        let (origin, monitor_size) = monitor.filter(|_| self.center_on_primary)?;
+        let (width, height) = self.size.unwrap_or(DEFAULT_WINDOW_SIZE);
+        Some(PhysicalPosition::new(
+            origin.x + (monitor_size.width as i32 - width as i32) / 2,
+            origin.y + (monitor_size.height as i32 - height as i32) / 2,
+        ))
+    }
+
+    /// Attributes for the main window, placed relative to `primary_monitor`.
+    pub fn window_attributes(&self, primary_monitor: Option<&MonitorHandle>) -> WindowAttributes {
+        let mut attributes = Window::default_attributes()
+            .with_min_inner_size(PhysicalSize::new(100, 100))
+            .with_title(self.title.clone())
+            .with_maximized(self.maximized);
+        if let Some((width, height)) = self.size {
+            attributes = attributes.with_inner_size(PhysicalSize::new(width, height));
+        }
+        let monitor = primary_monitor.map(|monitor| (monitor.position(), monitor.size()));
+        if let Some(position) = self.window_position(monitor) {
+            attributes = attributes.with_position(position);
+        }
+        attributes
+    }
+}
+
+/// Lowers every limit in `requested` the adapter can't meet to the adapter's
+/// value (raising alignments, where smaller is better), warning for each.
+pub fn clamp_limits(requested: wgpu::Limits, adapter: &wgpu::Limits) -> wgpu::Limits {
+    requested.check_limits_with_fail_fn(adapter, false, |name, requested, allowed| {
+        warn!("Requested limit {name} = {requested} exceeds the adapter's {allowed}; clamping");
+    });
+
+    let mut limits = requested;
+    macro_rules! clamp {
+        (max: $($field:ident),* ; min: $($align:ident),*) => {
+            $(limits.$field = limits.$field.min(adapter.$field);)*
+            $(limits.$align = limits.$align.max(adapter.$align);)*
+        };
+    }
+    clamp!(
+        max: max_texture_dimension_1d, max_texture_dimension_2d, max_texture_dimension_3d,
+            max_texture_array_layers, max_bind_groups, max_bindings_per_bind_group,
+            max_dynamic_uniform_buffers_per_pipeline_layout, max_dynamic_storage_buffers_per_pipeline_layout,
+            max_sampled_textures_per_shader_stage, max_samplers_per_shader_stage,
+            max_storage_buffers_per_shader_stage, max_storage_textures_per_shader_stage,
+            max_uniform_buffers_per_shader_stage, max_uniform_buffer_binding_size,
+            max_storage_buffer_binding_size, max_vertex_buffers, max_buffer_size, max_vertex_attributes,
+            max_vertex_buffer_array_stride, max_inter_stage_shader_components, max_color_attachments,
+            max_color_attachment_bytes_per_sample, max_compute_workgroup_storage_size,
+            max_compute_invocations_per_workgroup, max_compute_workgroup_size_x,
+            max_compute_workgroup_size_y, max_compute_workgroup_size_z,
+            max_compute_workgroups_per_dimension, max_push_constant_size, max_non_sampler_bindings;
+        min: min_uniform_buffer_offset_alignment, min_storage_buffer_offset_alignment
+    );
+
+    // Anything not covered above (e.g. limits added in newer wgpu versions)
+    if !limits.check_limits(adapter) {
+        warn!("Requested limits still exceed the adapter's; using the adapter's limits");
+        return adapter.clone();
+    }
+    limits
+}
+
+/// Picks the first of `formats` (listed in the surface's order of preference)
+/// whose color space matches `prefer_srgb`, falling back to the first format.
+///
+/// With an sRGB format the GPU encodes fragment shader output on write, so
+/// shaders output linear color and blending happens in linear space. With a
+/// linear (`Unorm`) format values are stored as-is: shaders must apply the
+/// sRGB transfer function themselves, or colors will look too dark.
+pub fn select_surface_format(formats: &[wgpu::TextureFormat], prefer_srgb: bool) -> Option<wgpu::TextureFormat> {
+    formats
+        .iter()
+        .copied()
+        .find(|format| format.is_srgb() == prefer_srgb)
+        .or_else(|| formats.first().copied())
+}
+
+/// Builds a `context/name` label so resources show up grouped in graphics
+/// debuggers and validation messages.
+pub fn label(context: &str, name: &str) -> String {
+    format!("{context}/{name}")
+}
+
+/// Labels resources under a `GpuConfig::label` prefix.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Labels {
+    context: String,
+}
+
+impl Default for Labels {
+    fn default() -> Self {
+        GpuConfig::default().labels()
+    }
+}
+
+impl Labels {
+    pub fn new(context: impl Into<String>) -> Self {
+        Self { context: context.into() }
+    }
+
+    pub fn label(&self, name: &str) -> String {
+        label(&self.context, name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test_gpu, test_log};
+
+    #[test]
+    fn labels_are_prefixed_with_the_config_label() {
+        let config = GpuConfig {
+            label: String::from("Editor"),
+            ..Default::default()
+        };
+        assert_eq!(config.label("Uniform Buffer"), "Editor/Uniform Buffer");
+        assert_eq!(config.labels().label("Depth Texture"), "Editor/Depth Texture");
+    }
+
+    #[test]
+    fn default_labels_use_the_default_prefix() {
+        assert_eq!(Labels::default().label("Mesh"), "Pulsar/Mesh");
+        assert_eq!(Labels::default(), GpuConfig::default().labels());
+    }
+
+    #[test]
+    fn configured_latency_is_written_into_the_surface_configuration() {
+        let mut config = GpuConfig::default();
+        let mut surface_config = test_gpu::surface_config(8, 8);
+        config.set_frame_latency(1);
+        config.apply_to_surface(&mut surface_config);
+        assert_eq!(surface_config.desired_maximum_frame_latency, 1);
+
+        config.set_frame_latency(3);
+        config.apply_to_surface(&mut surface_config);
+        assert_eq!(surface_config.desired_maximum_frame_latency, 3);
+    }
+
+    #[test]
+    fn frame_latency_is_clamped_to_one_through_three() {
+        let mut config = GpuConfig::default();
+        config.set_frame_latency(0);
+        assert_eq!(config.frame_latency, 1);
+        config.set_frame_latency(8);
+        assert_eq!(config.frame_latency, 3);
+
+        // Set directly, the field is still clamped on the way to the surface
+        config.frame_latency = 10;
+        let mut surface_config = test_gpu::surface_config(8, 8);
+        config.apply_to_surface(&mut surface_config);
+        assert_eq!(surface_config.desired_maximum_frame_latency, 3);
+    }
+
+    #[test]
+    fn srgb_format_is_chosen_when_preferred_and_available() {
+        use wgpu::TextureFormat::{Bgra8Unorm, Bgra8UnormSrgb, Rgba16Float};
+        let formats = [Bgra8Unorm, Rgba16Float, Bgra8UnormSrgb];
+        assert_eq!(select_surface_format(&formats, true), Some(Bgra8UnormSrgb));
+        assert_eq!(select_surface_format(&formats, false), Some(Bgra8Unorm));
+        assert_eq!(GpuConfig::default().surface_format(&formats), Some(Bgra8UnormSrgb));
+    }
+
+    #[test]
+    fn surface_format_falls_back_to_the_first_offered() {
+        use wgpu::TextureFormat::{Bgra8Unorm, Rgba8UnormSrgb};
+        assert_eq!(select_surface_format(&[Bgra8Unorm], true), Some(Bgra8Unorm));
+        assert_eq!(select_surface_format(&[Rgba8UnormSrgb], false), Some(Rgba8UnormSrgb));
+        assert_eq!(select_surface_format(&[], true), None);
+    }
+
+    #[test]
+    fn limits_beyond_the_adapter_are_clamped_with_a_warning() {
+        let adapter = wgpu::Limits::downlevel_webgl2_defaults();
+        let requested = wgpu::Limits {
+            max_texture_dimension_2d: adapter.max_texture_dimension_2d * 4,
+            max_bind_groups: adapter.max_bind_groups + 4,
+            min_uniform_buffer_offset_alignment: adapter.min_uniform_buffer_offset_alignment / 2,
+            ..adapter.clone()
+        };
+        let (limits, warnings) = test_log::warnings(|| clamp_limits(requested, &adapter));
+        assert_eq!(limits.max_texture_dimension_2d, adapter.max_texture_dimension_2d);
+        assert_eq!(limits.max_bind_groups, adapter.max_bind_groups);
+        assert_eq!(
+            limits.min_uniform_buffer_offset_alignment,
+            adapter.min_uniform_buffer_offset_alignment
+        );
+        assert!(limits.check_limits(&adapter));
+        assert_eq!(warnings.len(), 3, "{warnings:?}");
+        assert!(warnings.iter().any(|warning| warning.contains("max_bind_groups")));
+    }
+
+    #[test]
+    fn limits_within_the_adapter_are_kept() {
+        let adapter = wgpu::Limits::default();
+        let requested = wgpu::Limits::downlevel_webgl2_defaults();
+        let (limits, warnings) = test_log::warnings(|| clamp_limits(requested.clone(), &adapter));
+        assert_eq!(limits, requested);
+        assert!(warnings.is_empty(), "{warnings:?}");
+    }
+
+    #[test]
+    fn presets_are_resolved_against_the_adapter() {
+        let adapter = wgpu::Limits {
+            max_texture_dimension_2d: 16384,
+            ..wgpu::Limits::default()
+        };
+        let config = |limits| GpuConfig {
+            limits,
+            ..Default::default()
+        };
+        assert_eq!(config(LimitsPreset::HighPerformance).required_limits(&adapter), adapter);
+        assert_eq!(config(LimitsPreset::Default).required_limits(&adapter), wgpu::Limits::default());
+        let downlevel = config(LimitsPreset::Downlevel).required_limits(&adapter);
+        assert_eq!(downlevel.max_texture_dimension_2d, 16384);
+        assert_eq!(downlevel.max_bind_groups, wgpu::Limits::downlevel_webgl2_defaults().max_bind_groups);
+    }
+
+    #[test]
+    fn device_request_is_labelled_and_only_asks_for_supported_features() {
+        let adapter_limits = wgpu::Limits::default();
+        let config = GpuConfig {
+            label: String::from("Editor"),
+            ..Default::default()
+        };
+        let request = config.device_request(
+            wgpu::Features::DEPTH_CLIP_CONTROL | wgpu::Features::TIMESTAMP_QUERY,
+            &adapter_limits,
+        );
+        assert_eq!(request.label, "Editor/Device");
+        assert_eq!(request.required_features, wgpu::Features::DEPTH_CLIP_CONTROL);
+        assert_eq!(request.required_limits, config.required_limits(&adapter_limits));
+
+        let descriptor = request.descriptor();
+        assert_eq!(descriptor.label, Some("Editor/Device"));
+        assert_eq!(descriptor.required_features, request.required_features);
+        assert_eq!(descriptor.required_limits, request.required_limits);
+
+        let without_depth_clip = config.device_request(wgpu::Features::TIMESTAMP_QUERY, &adapter_limits);
+        assert_eq!(without_depth_clip.required_features, wgpu::Features::empty());
+    }
+
+    #[test]
+    fn device_request_limits_follow_the_preset() {
+        let adapter_limits = wgpu::Limits::default();
+        let request = GpuConfig::default().device_request(wgpu::Features::empty(), &adapter_limits);
+        assert_eq!(request.label, "Pulsar/Device");
+        assert_eq!(
+            request.required_limits.max_bind_groups,
+            wgpu::Limits::downlevel_webgl2_defaults().max_bind_groups
+        );
+        assert!(request.required_limits.check_limits(&adapter_limits));
+
+        let high_performance = GpuConfig {
+            limits: LimitsPreset::HighPerformance,
+            ..Default::default()
+        };
+        let request = high_performance.device_request(wgpu::Features::empty(), &adapter_limits);
+        assert_eq!(request.required_limits, adapter_limits);
+    }
+
+    /// A 2560x1440 primary monitor to the right of a 1920-wide one.
+    fn monitor() -> Option<(PhysicalPosition<i32>, PhysicalSize<u32>)> {
+        Some((PhysicalPosition::new(1920, 0), PhysicalSize::new(2560, 1440)))
+    }
+
+    #[test]
+    fn explicit_position_wins_over_centering() {
+        let config = EngineConfig {
+            position: Some((-40, 25)),
+            center_on_primary: true,
+            ..Default::default()
+        };
+        assert_eq!(config.window_position(monitor()), Some(PhysicalPosition::new(-40, 25)));
+        assert_eq!(config.window_position(None), Some(PhysicalPosition::new(-40, 25)));
+    }
+
+    #[test]
+    fn centering_offsets_by_the_primary_monitor() {
+        let config = EngineConfig {
+            size: Some((1280, 720)),
+            center_on_primary: true,
+            ..Default::default()
+        };
+        assert_eq!(config.window_position(monitor()), Some(PhysicalPosition::new(1920 + 640, 360)));
+
+        // Without a size, winit's default one is centered
+        let default_size = EngineConfig {
+            center_on_primary: true,
+            ..Default::default()
+        };
+        assert_eq!(default_size.window_position(monitor()), Some(PhysicalPosition::new(1920 + 880, 420)));
+    }
+
+    #[test]
+    fn platform_places_the_window_without_a_position_or_monitor() {
+        assert_eq!(EngineConfig::default().window_position(monitor()), None);
+        let centered = EngineConfig {
+            center_on_primary: true,
+            ..Default::default()
+        };
+        assert_eq!(centered.window_position(None), None);
+    }
+
+    #[test]
+    fn window_attributes_carry_position_size_and_maximized() {
+        let config = EngineConfig {
+            size: Some((640, 480)),
+            position: Some((10, 20)),
+            maximized: true,
+            ..Default::default()
+        };
+        let attributes = config.window_attributes(None);
+        assert_eq!(attributes.position, Some(PhysicalPosition::new(10, 20).into()));
+        assert_eq!(attributes.inner_size, Some(PhysicalSize::new(640, 480).into()));
+        assert!(attributes.maximized);
+        assert_eq!(attributes.title, "Zenyx");
+
+        let defaults = EngineConfig::default().window_attributes(None);
+        assert_eq!(defaults.position, None);
+        assert!(!defaults.maximized);
+    }
+
+    #[test]
+    fn supported_surface_format_override_is_used_without_a_warning() {
+        use wgpu::TextureFormat::{Bgra8Unorm, Bgra8UnormSrgb, Rgba16Float};
+        let config = GpuConfig {
+            surface_format: Some(Rgba16Float),
+            ..Default::default()
+        };
+        let formats = [Bgra8Unorm, Rgba16Float, Bgra8UnormSrgb];
+        let (format, warnings) = test_log::warnings(|| config.surface_format(&formats));
+        assert_eq!(format, Some(Rgba16Float));
+        assert!(warnings.is_empty(), "{warnings:?}");
+    }
+
+    #[test]
+    fn unsupported_surface_format_falls_back_with_a_warning() {
+        use wgpu::TextureFormat::{Bgra8Unorm, Bgra8UnormSrgb, Rgba16Float};
+        let config = GpuConfig {
+            surface_format: Some(Rgba16Float),
+            ..Default::default()
+        };
+        let (format, warnings) = test_log::warnings(|| config.surface_format(&[Bgra8Unorm, Bgra8UnormSrgb]));
+        assert_eq!(format, Some(Bgra8UnormSrgb));
+        assert_eq!(warnings.len(), 1, "{warnings:?}");
+        assert!(warnings[0].contains("Rgba16Float"), "{warnings:?}");
+
+        // A surface offering nothing still has no format
+        let (format, _) = test_log::warnings(|| config.surface_format(&[]));
+        assert_eq!(format, None);
+    }
+}
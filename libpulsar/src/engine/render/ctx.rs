@@ -1,27 +1,91 @@
 
 use std::borrow::Cow;
+use std::path::Path;
 use std::sync::Arc;
-use std::time::Instant;
 use thiserror::Error;
 use winit::window::Window;
 use futures::executor::block_on;
+use log::debug;
+
+use super::config::GpuConfig;
+use super::ring::{ring_len, FrameRing};
+use crate::math::{Handedness, Matrix4};
+use crate::render_graph::RenderGraph;
+use crate::screenshot::{self, ScreenshotError};
+
 #[derive(Debug, Error)]
 pub enum ContextError {
     #[error("Failed to create WGPU surface: {0}")]
     SurfaceCreationFailure(#[from] wgpu::CreateSurfaceError),
+    #[error("No GPU adapter can render to the window surface")]
+    NoAdapter,
+    #[error("Failed to create rendering device: {0}")]
+    DeviceCreationFailure(#[from] wgpu::RequestDeviceError),
+    #[error("GPU validation failed while creating {label}: {message}")]
+    Validation { label: String, message: String },
+    #[error("Ran out of GPU memory while creating {label}")]
+    OutOfMemory { label: String },
+}
+
+/// Runs `create` inside validation and out-of-memory error scopes, so a bad
+/// descriptor comes back as an error naming `label` instead of reaching the
+/// device's uncaptured error handler, which panics by default.
+pub async fn capture_errors<T>(
+    device: &wgpu::Device,
+    label: &str,
+    create: impl FnOnce() -> T,
+) -> Result<T, ContextError> {
+    device.push_error_scope(wgpu::ErrorFilter::OutOfMemory);
+    device.push_error_scope(wgpu::ErrorFilter::Validation);
+    let value = create();
+    let validation = device.pop_error_scope().await;
+    let out_of_memory = device.pop_error_scope().await;
+    match validation.or(out_of_memory) {
+        None => Ok(value),
+        Some(wgpu::Error::OutOfMemory { .. }) => Err(ContextError::OutOfMemory {
+            label: label.to_string(),
+        }),
+        Some(error) => Err(ContextError::Validation {
+            label: label.to_string(),
+            message: error.to_string(),
+        }),
+    }
+}
+
+/// Vertical field of view of the procedural cube. 90° keeps the cube the
+/// size the old divide-by-z projection drew it at.
+const CUBE_FOVY: f32 = std::f32::consts::FRAC_PI_2;
+
+/// Perspective projection for the procedural cube on a `width` x `height`
+/// surface, in the crate's left-handed, row-vector convention.
+pub fn cube_projection(width: u32, height: u32) -> Matrix4 {
+    let aspect = width.max(1) as f32 / height.max(1) as f32;
+    Matrix4::perspective(CUBE_FOVY, aspect, 0.1, 100.0, Handedness::Left)
+}
+
+/// Layout of `Uniforms` in `CUBE_SHADER`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct CubeUniforms {
+    /// Rows of `cube_projection`; WGSL reads them as columns, so the shader
+    /// multiplies `projection * v` to compute `v * M`.
+    projection: [[f32; 4]; 4],
+    time: f32,
+    _padding: [f32; 3],
 }
 
 /// This WGSL shader generates a cube procedurally and rotates it around the Y axis.
-/// A uniform (u.time) is used as the rotation angle. After rotation, a simple
-/// perspective projection is applied (dividing x,y by z) to produce clip-space coordinates.
+/// A uniform (u.time) is used as the rotation angle. After rotation, the
+/// perspective projection in u.projection produces clip-space coordinates.
 const CUBE_SHADER: &str = r#"
-// Uniform block containing time and the current aspect ratio.
-// (The extra two padding floats ensure 16-byte alignment.)
+// Uniform block containing the projection matrix and time.
+// (The padding floats round the block up to 16-byte alignment.)
 struct Uniforms {
+    projection: mat4x4<f32>,
     time: f32,
-    aspect: f32,
     padding0: f32,
     padding1: f32,
+    padding2: f32,
 };
 
 @group(0) @binding(0)
@@ -61,19 +125,39 @@ const offsets: array<vec3<f32>, 8> = array<vec3<f32>, 8>(
     vec3<f32>(-0.5,  0.5,  0.5)
 );
 
-// Indices for the 12 triangles that form the six faces of the cube.
+// Indices for the 12 triangles that form the six faces of the cube. Every
+// triangle winds counter-clockwise seen from outside the cube, so back faces
+// can be culled.
 const indices: array<u32, 36> = array<u32, 36>(
-    0, 1, 2, 2, 3, 0, // Front face
-    4, 5, 6, 6, 7, 4, // Back face
-    0, 1, 5, 5, 4, 0, // Bottom face
-    2, 3, 7, 7, 6, 2, // Top face
+    0, 1, 2, 2, 3, 0, // Front face (-Z, towards the camera)
+    5, 4, 7, 7, 6, 5, // Back face
+    0, 4, 5, 5, 1, 0, // Bottom face
+    3, 2, 6, 6, 7, 3, // Top face
     0, 3, 7, 7, 4, 0, // Left face
-    1, 2, 6, 6, 5, 1  // Right face
+    1, 5, 6, 6, 2, 1  // Right face
+);
+
+// Outward normal of each face, in the same order as the index array.
+const normals: array<vec3<f32>, 6> = array<vec3<f32>, 6>(
+    vec3<f32>( 0.0,  0.0, -1.0),
+    vec3<f32>( 0.0,  0.0,  1.0),
+    vec3<f32>( 0.0, -1.0,  0.0),
+    vec3<f32>( 0.0,  1.0,  0.0),
+    vec3<f32>(-1.0,  0.0,  0.0),
+    vec3<f32>( 1.0,  0.0,  0.0)
 );
 
+// Direction towards the light, in view space (up, left and behind the camera).
+const light_dir: vec3<f32> = vec3<f32>(-0.3, 0.5, -0.8);
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) normal: vec3<f32>,
+};
+
 /// Vertex shader
 @vertex
-fn vs_main(@builtin(vertex_index) vid: u32) -> @builtin(position) vec4<f32> {
+fn vs_main(@builtin(vertex_index) vid: u32) -> VertexOutput {
     // Look up the vertex position using the index array.
     let pos = offsets[indices[vid]];
 
@@ -83,23 +167,25 @@ fn vs_main(@builtin(vertex_index) vid: u32) -> @builtin(position) vec4<f32> {
     let rotX = rotationX(u.time / 1.5 ); 
 
     // Combine the rotations: first rotate around Y, then tilt with X.
-    var transformedPos = rotX * (rotY * pos);
+    let rotation = rotX * rotY;
+    var transformedPos = rotation * pos;
 
     // Translate the cube along the Z axis so it appears in front of the camera.
     transformedPos = transformedPos + vec3<f32>(0.0, 0.0, 2.0);
 
-    // Apply a simple perspective projection:
-    // Divide x and y by z, and correct the x coordinate for the aspect ratio.
-    let projected = vec2<f32>(
-        (transformedPos.x / transformedPos.z) * (1.0 / u.aspect),
-        transformedPos.y / transformedPos.z
-    );
-    return vec4<f32>(projected, 0.0, 1.0);
+    // Project with a real field of view, so the cube keeps its proportions
+    // at any window shape.
+    var out: VertexOutput;
+    out.clip_position = u.projection * vec4<f32>(transformedPos, 1.0);
+    // Six vertices per face; the rotation is orthonormal, so it carries the
+    // normal along unchanged in length.
+    out.normal = rotation * normals[vid / 6u];
+    return out;
 }
 
 /// Fragment shader
 @fragment
-fn fs_main() -> @location(0) vec4<f32> {
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
     // The color changes over time. We use sine functions to create smooth oscillation.
     // sin(u.time) oscillates between -1.0 and 1.0.
     // Multiplying by 0.5 and adding 0.5 scales the range to 0.0 .. 1.0.
@@ -107,26 +193,36 @@ fn fs_main() -> @location(0) vec4<f32> {
     let green = 0.5 * sin(u.time + 2.094)     + 0.5; // 2.094 ≈ 2π/3 phase shift
     let blue  = 0.5 * sin(u.time + 4.188)     + 0.5; // 4.188 ≈ 4π/3 phase shift
 
+    // Lambert diffuse with a little ambient, so the faces read as a solid.
+    let diffuse = max(dot(normalize(in.normal), normalize(light_dir)), 0.0);
+    let shade = 0.25 + 0.75 * diffuse;
+
     // The resulting vec4 is our final color with full opacity.
-    return vec4<f32>(red, green, blue, 1.0);
+    return vec4<f32>(vec3<f32>(red, green, blue) * shade, 1.0);
 }
 
 "#;
 
+// Fields drop in declaration order: the surface and GPU resources go before
+// the queue and device they were created from.
 pub struct WgpuCtx<'window> {
-    device: wgpu::Device,
-    queue: wgpu::Queue,
-    surface: wgpu::Surface<'window>,
+    /// `None` for a context that only renders offscreen.
+    surface: Option<wgpu::Surface<'window>>,
     surface_config: wgpu::SurfaceConfiguration,
-    adapter: wgpu::Adapter,
     render_pipeline: wgpu::RenderPipeline,
-    uniform_buffer: wgpu::Buffer,
-    start_time: Instant,
+    /// Uniform buffer and bind group per frame in flight.
+    uniforms: FrameRing<(wgpu::Buffer, wgpu::BindGroup)>,
+    uniform_layout: wgpu::BindGroupLayout,
+    queue: wgpu::Queue,
+    device: wgpu::Device,
+    adapter: wgpu::Adapter,
+    elapsed: f32,
+    config: GpuConfig,
 }
 
 impl<'window> WgpuCtx<'window> {
-    pub async fn new(window: Arc<Window>) -> Result<WgpuCtx<'window>, ContextError> {
-        let instance = wgpu::Instance::default();
+    pub async fn new(window: Arc<Window>, config: &GpuConfig) -> Result<WgpuCtx<'window>, ContextError> {
+        let instance = wgpu::Instance::new(&config.instance_descriptor());
         let surface = instance.create_surface(Arc::clone(&window))?;
         let adapter = instance
             .request_adapter(&wgpu::RequestAdapterOptions {
@@ -135,101 +231,106 @@ impl<'window> WgpuCtx<'window> {
                 compatible_surface: Some(&surface),
             })
             .await
-            .expect("Failed to obtain render adapter");
-        let (device, queue) = adapter
-            .request_device(
-                &wgpu::DeviceDescriptor {
-                    label: None,
-                    required_features: wgpu::Features::empty(),
-                    required_limits: wgpu::Limits::downlevel_webgl2_defaults()
-                        .using_resolution(adapter.limits()),
-                    memory_hints: wgpu::MemoryHints::Performance,
-                },
-                None,
-            )
-            .await
-            .expect("Failed to create rendering device");
+            .ok_or(ContextError::NoAdapter)?;
+        let device_request = config.device_request(adapter.features(), &adapter.limits());
+        let (device, queue) = adapter.request_device(&device_request.descriptor(), None).await?;
 
         let size = window.inner_size();
         let width = size.width.max(1);
         let height = size.height.max(1);
-        let surface_config = surface.get_default_config(&adapter, width, height).unwrap();
+        let mut surface_config = surface.get_default_config(&adapter, width, height).unwrap();
+        if let Some(format) = config.surface_format(&surface.get_capabilities(&adapter).formats) {
+            surface_config.format = format;
+        }
+        config.apply_to_surface(&mut surface_config);
         surface.configure(&device, &surface_config);
+        Self::with_device(Some(surface), adapter, device, queue, surface_config, config).await
+    }
 
-        // Create a uniform buffer (16 bytes to satisfy alignment requirements)
-        let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
-            label: Some("Uniform Buffer"),
-            size: 16,
-            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-            mapped_at_creation: false,
-        });
-
+    /// Builds the cube pipeline on an already created device, presenting to
+    /// `surface` if there is one.
+    async fn with_device(
+        surface: Option<wgpu::Surface<'window>>,
+        adapter: wgpu::Adapter,
+        device: wgpu::Device,
+        queue: wgpu::Queue,
+        surface_config: wgpu::SurfaceConfiguration,
+        config: &GpuConfig,
+    ) -> Result<WgpuCtx<'window>, ContextError> {
         // Create the shader module from the inline WGSL shader.
-        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("Cube Shader"),
-            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(CUBE_SHADER)),
-        });
+        let shader = capture_errors(&device, "Cube Shader", || {
+            device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some(&config.label("Cube Shader")),
+                source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(CUBE_SHADER)),
+            })
+        })
+        .await?;
 
         // Create a bind group layout for the uniform.
         let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            label: Some("Uniform Bind Group Layout"),
+            label: Some(&config.label("Uniform Bind Group Layout")),
             entries: &[wgpu::BindGroupLayoutEntry {
                 binding: 0,
                 visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
                 ty: wgpu::BindingType::Buffer {
                     ty: wgpu::BufferBindingType::Uniform,
                     has_dynamic_offset: false,
-                    min_binding_size: wgpu::BufferSize::new(16),
+                    min_binding_size: wgpu::BufferSize::new(std::mem::size_of::<CubeUniforms>() as u64),
                 },
                 count: None,
             }],
         });
 
+        let uniforms = Self::create_uniforms(&device, &bind_group_layout, config, &surface_config);
+
         // Create the pipeline layout.
         let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: Some("Cube Pipeline Layout"),
+            label: Some(&config.label("Cube Pipeline Layout")),
             bind_group_layouts: &[&bind_group_layout],
             push_constant_ranges: &[],
         });
 
         // TODO: add proper vertex buffer
-        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Cube Render Pipeline"),
-            layout: Some(&pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &shader,
-                entry_point: Some("vs_main"),
-                buffers: &[],
-                compilation_options: wgpu::PipelineCompilationOptions::default(),
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &shader,
-                entry_point: Some("fs_main"),
-                targets: &[Some(wgpu::ColorTargetState {
-                    format: surface_config.format,
-                    blend: Some(wgpu::BlendState::REPLACE),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-                compilation_options: wgpu::PipelineCompilationOptions::default(),
-            }),
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: None,
-                polygon_mode: wgpu::PolygonMode::Fill,
-                unclipped_depth: false,
-                conservative: false,
-            },
-            depth_stencil: None,
-            multisample: wgpu::MultisampleState {
-                count: 1,
-                mask: !0,
-                alpha_to_coverage_enabled: false,
-            },
-            multiview: None,
-            cache: None,
-        });
+        let render_pipeline = capture_errors(&device, "Cube Render Pipeline", || {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(&config.label("Cube Render Pipeline")),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: Some("vs_main"),
+                    buffers: &[],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: Some("fs_main"),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: surface_config.format,
+                        blend: Some(wgpu::BlendState::REPLACE),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleList,
+                    strip_index_format: None,
+                    front_face: wgpu::FrontFace::Ccw,
+                    cull_mode: Some(wgpu::Face::Back),
+                    polygon_mode: wgpu::PolygonMode::Fill,
+                    unclipped_depth: false,
+                    conservative: false,
+                },
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState {
+                    count: 1,
+                    mask: !0,
+                    alpha_to_coverage_enabled: false,
+                },
+                multiview: None,
+                cache: None,
+            })
+        })
+        .await?;
 
         Ok(WgpuCtx {
             device,
@@ -238,49 +339,174 @@ impl<'window> WgpuCtx<'window> {
             surface_config,
             adapter,
             render_pipeline,
-            uniform_buffer,
-            start_time: Instant::now(),
+            uniforms,
+            uniform_layout: bind_group_layout,
+            elapsed: 0.0,
+            config: config.clone(),
+        })
+    }
+
+    /// One uniform buffer per frame the GPU may still be working on, so
+    /// writing this frame's data never has to wait for an earlier frame.
+    fn create_uniforms(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        config: &GpuConfig,
+        surface_config: &wgpu::SurfaceConfiguration,
+    ) -> FrameRing<(wgpu::Buffer, wgpu::BindGroup)> {
+        FrameRing::from_fn(ring_len(surface_config.desired_maximum_frame_latency), |i| {
+            let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(&config.label(&format!("Uniform Buffer {i}"))),
+                size: std::mem::size_of::<CubeUniforms>() as wgpu::BufferAddress,
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            });
+            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some(&config.label(&format!("Uniform Bind Group {i}"))),
+                layout,
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                }],
+            });
+            (buffer, bind_group)
         })
     }
 
-    pub fn new_blocking(window: Arc<Window>) -> Result<WgpuCtx<'window>, ContextError> {
-        block_on(Self::new(window))
+    pub fn new_blocking(window: Arc<Window>, config: &GpuConfig) -> Result<WgpuCtx<'window>, ContextError> {
+        block_on(Self::new(window, config))
+    }
+
+    /// Waits for all submitted GPU work to finish, then releases the surface
+    /// before the device.
+    pub fn shutdown(self) {
+        wait_idle(&self.device);
+        let WgpuCtx {
+            surface,
+            render_pipeline,
+            uniforms,
+            uniform_layout,
+            queue,
+            device,
+            adapter,
+            ..
+        } = self;
+        drop(surface);
+        drop(render_pipeline);
+        drop(uniforms);
+        drop(uniform_layout);
+        drop(queue);
+        drop(device);
+        drop(adapter);
+        debug!("GPU context shut down");
     }
 
     pub fn resize(&mut self, new_size: (u32, u32)) {
         let (width, height) = new_size;
         self.surface_config.width = width.max(1);
         self.surface_config.height = height.max(1);
-        self.surface.configure(&self.device, &self.surface_config);
+        self.config.apply_to_surface(&mut self.surface_config);
+        self.configure_surface();
+    }
+
+    fn configure_surface(&self) {
+        if let Some(surface) = &self.surface {
+            surface.configure(&self.device, &self.surface_config);
+        }
+    }
+
+    pub fn surface_config(&self) -> &wgpu::SurfaceConfiguration {
+        &self.surface_config
+    }
+
+    /// Changes the maximum frame latency (clamped to `1..=3`), reconfiguring
+    /// the surface and resizing the per-frame uniform ring to match.
+    pub fn set_frame_latency(&mut self, frame_latency: u32) {
+        self.config.set_frame_latency(frame_latency);
+        self.config.apply_to_surface(&mut self.surface_config);
+        self.configure_surface();
+        self.uniforms = Self::create_uniforms(&self.device, &self.uniform_layout, &self.config, &self.surface_config);
     }
 
-    pub fn draw(&mut self) {
-        let elapsed = self.start_time.elapsed().as_secs_f32();
-        // Compute the aspect ratio from the current surface configuration.
-        let aspect = self.surface_config.width as f32 / self.surface_config.height as f32;
-        // Pack time and aspect into four floats (with two padding zeros).
-        let uniform_data = [elapsed, aspect, 0.0, 0.0];
-        self.queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&uniform_data));
-
-        let surface_texture = self
-            .surface
-            .get_current_texture()
-            .expect("Failed to get surface texture");
+    /// Advances the animation clock by `dt` seconds.
+    pub fn update(&mut self, dt: f32) {
+        self.elapsed += dt;
+    }
+
+    /// Writes this frame's uniforms into the next buffer of the ring.
+    fn write_uniforms(&mut self) {
+        // Rebuilt every frame from the current surface size, so resizes are picked up
+        let uniform_data = CubeUniforms {
+            projection: cube_projection(self.surface_config.width, self.surface_config.height).to_rows(),
+            time: self.elapsed,
+            _padding: [0.0; 3],
+        };
+        self.uniforms.advance();
+        let (uniform_buffer, _) = self.uniforms.current();
+        self.queue.write_buffer(uniform_buffer, 0, bytemuck::bytes_of(&uniform_data));
+    }
+
+    /// Draws one frame. Fails without drawing, or touching the uniform ring,
+    /// if no surface texture could be acquired; an outdated or lost surface is
+    /// reconfigured for the next frame. Offscreen contexts always report
+    /// `SurfaceError::Lost`.
+    pub fn draw(&mut self) -> Result<(), wgpu::SurfaceError> {
+        let Some(surface) = &self.surface else {
+            return Err(wgpu::SurfaceError::Lost);
+        };
+        let surface_texture = match surface.get_current_texture() {
+            Ok(texture) => texture,
+            Err(err) => {
+                if matches!(err, wgpu::SurfaceError::Outdated | wgpu::SurfaceError::Lost) {
+                    self.configure_surface();
+                }
+                return Err(err);
+            }
+        };
+        self.write_uniforms();
         let view = surface_texture
             .texture
             .create_view(&wgpu::TextureViewDescriptor::default());
+        self.render_to(&view);
+        surface_texture.present();
+        Ok(())
+    }
 
-        let mut encoder =
-            self.device
-                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
-                    label: Some("Cube Command Encoder"),
-                });
+    /// Renders the current frame into an offscreen texture the size and
+    /// format of the surface, then writes it to `path` as a PNG.
+    pub fn save_screenshot(&mut self, path: impl AsRef<Path>) -> Result<(), ScreenshotError> {
+        self.write_uniforms();
+        let texture = self.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(&self.config.label("Screenshot Texture")),
+            size: wgpu::Extent3d {
+                width: self.surface_config.width,
+                height: self.surface_config.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: self.surface_config.format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        self.render_to(&texture.create_view(&wgpu::TextureViewDescriptor::default()));
+        let rgba = screenshot::read_texture(&self.device, &self.config.labels(), &self.queue, &texture)?;
+        screenshot::save_png(path.as_ref(), texture.width(), texture.height(), &rgba)?;
+        debug!("Saved screenshot to {}", path.as_ref().display());
+        Ok(())
+    }
 
-        {
+    /// Records and submits the cube pass into `view` with the current uniforms.
+    fn render_to(&self, view: &wgpu::TextureView) {
+        let (_, uniform_bind_group) = self.uniforms.current();
+        let pass_label = self.config.label("Cube Render Pass");
+        let mut graph = RenderGraph::new();
+        graph.add_pass(&pass_label, &[], |encoder| {
             let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Cube Render Pass"),
+                label: Some(&pass_label),
                 color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
+                    view,
                     resolve_target: None,
                     ops: wgpu::Operations {
                         load: wgpu::LoadOp::Clear(wgpu::Color {
@@ -297,21 +523,242 @@ impl<'window> WgpuCtx<'window> {
                 occlusion_query_set: None,
             });
             render_pass.set_pipeline(&self.render_pipeline);
-            // Create a bind group on the fly for the uniform.
-            let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
-                label: Some("Uniform Bind Group (per draw)"),
-                layout: &self.render_pipeline.get_bind_group_layout(0),
-                entries: &[wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: self.uniform_buffer.as_entire_binding(),
-                }],
-            });
-            render_pass.set_bind_group(0, &bind_group, &[]);
+            render_pass.set_bind_group(0, uniform_bind_group, &[]);
             // Draw 36 vertices (6 faces × 6 vertices)
             render_pass.draw(0..36, 0..1);
+        });
+        // A lone pass without dependencies always schedules
+        graph.execute(&self.device, &self.queue).expect("static pass graph is acyclic");
+    }
+}
+
+/// Blocks until every submission on `device` has finished executing.
+fn wait_idle(device: &wgpu::Device) {
+    let _ = device.poll(wgpu::Maintain::Wait);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_gpu;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    #[test]
+    fn wait_idle_finishes_submitted_work() {
+        let Some((device, queue)) = test_gpu::device() else {
+            return;
+        };
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: 4,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        queue.write_buffer(&buffer, 0, &[1, 2, 3, 4]);
+        queue.submit(None);
+
+        let mapped = Arc::new(AtomicBool::new(false));
+        let flag = mapped.clone();
+        buffer.slice(..).map_async(wgpu::MapMode::Read, move |result| {
+            flag.store(result.is_ok(), Ordering::SeqCst);
+        });
+        wait_idle(&device);
+        assert!(mapped.load(Ordering::SeqCst));
+        assert_eq!(&*buffer.slice(..).get_mapped_range(), &[1, 2, 3, 4]);
+    }
+
+    const HEADLESS_SIZE: u32 = 16;
+
+    /// A context on the test device that renders offscreen only.
+    fn headless_ctx() -> Option<WgpuCtx<'static>> {
+        let (adapter, device, queue) = test_gpu::adapter()?;
+        let surface_config = test_gpu::surface_config(HEADLESS_SIZE, HEADLESS_SIZE);
+        let ctx = pollster::block_on(WgpuCtx::with_device(
+            None,
+            adapter,
+            device,
+            queue,
+            surface_config,
+            &GpuConfig::default(),
+        ));
+        Some(ctx.expect("cube pipeline builds on the test device"))
+    }
+
+    #[test]
+    fn shutdown_after_an_offscreen_frame_does_not_panic() {
+        let Some(mut ctx) = headless_ctx() else {
+            return;
+        };
+        let texture = ctx.device.create_texture(&wgpu::TextureDescriptor {
+            label: None,
+            size: wgpu::Extent3d {
+                width: HEADLESS_SIZE,
+                height: HEADLESS_SIZE,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: test_gpu::FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        ctx.update(0.5);
+        ctx.write_uniforms();
+        ctx.render_to(&texture.create_view(&wgpu::TextureViewDescriptor::default()));
+        let pixels = test_gpu::read_pixels(&ctx.device, &ctx.queue, &texture);
+        assert_eq!(pixels.len(), (HEADLESS_SIZE * HEADLESS_SIZE * 4) as usize);
+        ctx.shutdown();
+    }
+
+    #[test]
+    fn failed_draws_do_not_advance_the_uniform_ring() {
+        let Some(mut ctx) = headless_ctx() else {
+            return;
+        };
+        let index = ctx.uniforms.index();
+        assert!(matches!(ctx.draw(), Err(wgpu::SurfaceError::Lost)));
+        assert_eq!(ctx.uniforms.index(), index);
+        ctx.shutdown();
+    }
+
+    #[test]
+    fn missing_entry_point_is_a_validation_error() {
+        let Some((device, _queue)) = test_gpu::device() else {
+            return;
+        };
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: None,
+            source: wgpu::ShaderSource::Wgsl(
+                "@vertex fn vs_main() -> @builtin(position) vec4<f32> { return vec4<f32>(); }".into(),
+            ),
+        });
+        let result = pollster::block_on(capture_errors(&device, "Broken Pipeline", || {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: None,
+                layout: None,
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: Some("does_not_exist"),
+                    buffers: &[],
+                    compilation_options: Default::default(),
+                },
+                fragment: None,
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+                cache: None,
+            })
+        }));
+        let Err(ContextError::Validation { label, message }) = result else {
+            panic!("expected a validation error");
+        };
+        assert_eq!(label, "Broken Pipeline");
+        assert!(!message.is_empty());
+    }
+
+    #[test]
+    fn successful_creation_passes_the_value_through() {
+        let Some((device, _queue)) = test_gpu::device() else {
+            return;
+        };
+        let result = pollster::block_on(capture_errors(&device, "Value", || 42));
+        assert!(matches!(result, Ok(42)));
+    }
+
+    /// Clip-space position of `point` as `CUBE_SHADER` computes it from the
+    /// uniform's rows.
+    fn project(rows: [[f32; 4]; 4], point: [f32; 3]) -> [f32; 2] {
+        let v = [point[0], point[1], point[2], 1.0];
+        let clip: [f32; 4] = std::array::from_fn(|j| (0..4).map(|i| v[i] * rows[i][j]).sum());
+        [clip[0] / clip[3], clip[1] / clip[3]]
+    }
+
+    #[test]
+    fn cube_corner_projects_with_the_field_of_view_at_a_wide_aspect() {
+        let (width, height) = (3840, 1080);
+        let aspect = width as f32 / height as f32;
+        // A corner of the unrotated cube after the shader's translation
+        let [x, y] = project(cube_projection(width, height).to_rows(), [0.5, 0.5, 2.5]);
+
+        let focal = 1.0 / (CUBE_FOVY / 2.0).tan();
+        assert!((x - 0.5 / 2.5 * focal / aspect).abs() < 1e-5, "{x}");
+        assert!((y - 0.5 / 2.5 * focal).abs() < 1e-5, "{y}");
+        // Equal world extents cover equal pixel extents
+        assert!((x * width as f32 - y * height as f32).abs() < 1e-2);
+    }
+
+    #[test]
+    fn cube_projection_survives_a_zero_sized_surface() {
+        let [x, y] = project(cube_projection(0, 0).to_rows(), [0.5, 0.5, 2.5]);
+        assert!(x.is_finite() && y.is_finite());
+    }
+
+    /// Numbers in the `CUBE_SHADER` constant array `name`, in order.
+    fn shader_numbers(name: &str) -> Vec<f32> {
+        let source = &CUBE_SHADER[CUBE_SHADER.find(&format!("const {name}:")).unwrap()..];
+        let body = &source[source.find(">(").unwrap() + 2..source.find(");").unwrap()];
+        body.lines()
+            .map(|line| line.split("//").next().unwrap().replace("vec3<f32>", ""))
+            .flat_map(|line| {
+                line.split(|c: char| !(c.is_ascii_digit() || c == '.' || c == '-'))
+                    .filter(|number| !number.is_empty())
+                    .map(|number| number.parse().unwrap())
+                    .collect::<Vec<f32>>()
+            })
+            .collect()
+    }
+
+    /// The cube's triangles as corner positions, each with its face's normal.
+    fn cube_triangles() -> Vec<([[f32; 3]; 3], [f32; 3])> {
+        let offsets = shader_numbers("offsets");
+        let indices = shader_numbers("indices");
+        let normals = shader_numbers("normals");
+        assert_eq!((offsets.len(), indices.len(), normals.len()), (24, 36, 18));
+        let corner = |index: f32| std::array::from_fn(|axis| offsets[index as usize * 3 + axis]);
+        indices
+            .chunks_exact(3)
+            .enumerate()
+            .map(|(triangle, indices)| {
+                let face = triangle / 2;
+                let normal = std::array::from_fn(|axis| normals[face * 3 + axis]);
+                ([corner(indices[0]), corner(indices[1]), corner(indices[2])], normal)
+            })
+            .collect()
+    }
+
+    fn sub(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+        std::array::from_fn(|axis| a[axis] - b[axis])
+    }
+
+    fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+        (0..3).map(|axis| a[axis] * b[axis]).sum()
+    }
+
+    #[test]
+    fn cube_triangles_lie_on_their_faces_and_wind_the_same_way() {
+        for (corners, normal) in cube_triangles() {
+            for corner in corners {
+                assert_eq!(dot(corner, normal), 0.5, "{corner:?} is off the {normal:?} face");
+            }
+            let (u, v) = (sub(corners[1], corners[0]), sub(corners[2], corners[0]));
+            let cross = [u[1] * v[2] - u[2] * v[1], u[2] * v[0] - u[0] * v[2], u[0] * v[1] - u[1] * v[0]];
+            // Clockwise about the outward normal in right-handed terms, which
+            // is counter-clockwise seen from outside in the engine's left-handed space
+            assert!(dot(cross, normal) < 0.0, "{corners:?} winds against the {normal:?} face");
         }
+    }
 
-        self.queue.submit(Some(encoder.finish()));
-        surface_texture.present();
+    #[test]
+    fn only_faces_towards_the_camera_are_counter_clockwise_on_screen() {
+        let rows = cube_projection(800, 600).to_rows();
+        for (corners, normal) in cube_triangles() {
+            // The shader's translation, before any rotation
+            let [a, b, c] = corners.map(|[x, y, z]| project(rows, [x, y, z + 2.0]));
+            let area = (b[0] - a[0]) * (c[1] - a[1]) - (c[0] - a[0]) * (b[1] - a[1]);
+            let faces_camera = dot(normal, [0.0, 0.0, 1.0]) < 0.0;
+            assert_eq!(area > 0.0, faces_camera, "{corners:?} with normal {normal:?}");
+        }
     }
-}
\ No newline at end of file
+}
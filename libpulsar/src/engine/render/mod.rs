@@ -1,73 +1,710 @@
+use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use ctx::WgpuCtx;
 use winit::application::ApplicationHandler;
-use winit::event::WindowEvent;
-use log::{debug,trace};
+use winit::event::{ElementState, StartCause, WindowEvent};
+use log::{debug,error,trace};
 use winit::event_loop::{ActiveEventLoop, EventLoop};
 use winit::event_loop::ControlFlow;
+use winit::keyboard::{Key, KeyCode, ModifiersState, NamedKey, PhysicalKey};
+use winit::dpi::PhysicalSize;
+use winit::monitor::{MonitorHandle, VideoModeHandle};
 
-use winit::window::{Window, WindowId};
+use winit::window::{Fullscreen, Window, WindowAttributes, WindowId};
+
+use crate::engine::gamepad::Gamepads;
+use crate::engine::input::Input;
+use crate::engine::stats::FrameStats;
+use crate::engine::text_input::TextInput;
+use crate::engine::time::{FrameCap, FrameClock, MAX_FRAME_DT};
+use config::{EngineConfig, GpuConfig};
+pub mod config;
 pub mod ctx;
+pub mod ring;
+
+/// Conventional screenshot key, for `App::set_screenshot_key`.
+pub const DEFAULT_SCREENSHOT_KEY: KeyCode = KeyCode::PrintScreen;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FullscreenMode {
+    #[default]
+    Windowed,
+    /// Borderless window covering the current monitor.
+    Borderless,
+    /// Exclusive fullscreen using the monitor's best video mode.
+    Exclusive,
+}
+
+impl FullscreenMode {
+    /// The mode Alt+Enter switches to: any fullscreen mode goes back to windowed,
+    /// windowed goes to borderless.
+    pub fn toggled(self) -> Self {
+        match self {
+            FullscreenMode::Windowed => FullscreenMode::Borderless,
+            FullscreenMode::Borderless | FullscreenMode::Exclusive => FullscreenMode::Windowed,
+        }
+    }
+}
+
+/// How the event loop waits between iterations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ControlFlowMode {
+    /// Loop continuously, redrawing every iteration. Lowest latency, but
+    /// keeps a CPU core busy.
+    #[default]
+    Poll,
+    /// Sleep until an event arrives and only redraw in response to one.
+    /// Gamepads are polled in the loop, so they can't wake it.
+    Wait,
+    /// Like `Wait`, but also wake up and redraw after this long idle.
+    WaitUntil(Duration),
+}
+
+impl ControlFlowMode {
+    /// The winit control flow for a loop going idle at `now`.
+    pub fn control_flow(self, now: Instant) -> ControlFlow {
+        match self {
+            ControlFlowMode::Poll => ControlFlow::Poll,
+            ControlFlowMode::Wait => ControlFlow::Wait,
+            ControlFlowMode::WaitUntil(timeout) => ControlFlow::WaitUntil(now + timeout),
+        }
+    }
+}
+
+/// A window together with the GPU context that renders into it.
+pub struct Viewport<'window> {
+    pub window: Arc<Window>,
+    pub ctx: WgpuCtx<'window>,
+}
+
+/// The per-window state `route_window_event` updates. Implemented by
+/// `Viewport`; tests use a stand-in that needs no window or GPU.
+trait WindowTarget {
+    fn resize(&mut self, size: PhysicalSize<u32>);
+    fn close(self);
+}
+
+impl WindowTarget for Viewport<'_> {
+    fn resize(&mut self, size: PhysicalSize<u32>) {
+        self.ctx.resize(size.into());
+        self.window.request_redraw();
+    }
+
+    fn close(self) {
+        self.ctx.shutdown();
+    }
+}
+
+/// Applies a resize or close request to the viewport of `window_id` only;
+/// the other viewports are left alone. Closing removes the viewport.
+fn route_window_event<T: WindowTarget>(
+    viewports: &mut HashMap<WindowId, T>,
+    window_id: WindowId,
+    event: &WindowEvent,
+) {
+    match event {
+        WindowEvent::CloseRequested => {
+            if let Some(viewport) = viewports.remove(&window_id) {
+                viewport.close();
+            }
+        }
+        WindowEvent::Resized(size) => {
+            if let Some(viewport) = viewports.get_mut(&window_id) {
+                viewport.resize(*size);
+            }
+        }
+        _ => {}
+    }
+}
+
 #[derive(Default)]
 pub struct App<'window> {
-    window: Option<Arc<Window>>,
-    ctx: Option<WgpuCtx<'window>>,
+    viewports: HashMap<WindowId, Viewport<'window>>,
+    /// The first window opened; fullscreen and minimize-pausing follow it.
+    primary: Option<WindowId>,
+    gpu_config: GpuConfig,
+    engine_config: EngineConfig,
+    fullscreen: FullscreenMode,
+    modifiers: ModifiersState,
+    clock: FrameClock,
+    frame_cap: FrameCap,
+    control_flow: ControlFlowMode,
+    /// Set by events that should be answered with a frame when not polling.
+    needs_redraw: bool,
+    /// Earliest time the next frame may start while a cap is set.
+    next_frame: Option<Instant>,
+    stats: FrameStats,
+    console: TextInput,
+    input: Input,
+    gamepads: Gamepads,
+    /// Saves a screenshot of the focused window when pressed.
+    screenshot_key: Option<KeyCode>,
+    paused: bool,
+    unfocused: bool,
+    minimized: bool,
+    pause_rendering: bool,
+}
+
+impl<'window> App<'window> {
+    pub fn with_gpu_config(gpu_config: GpuConfig) -> Self {
+        Self {
+            gpu_config,
+            ..Default::default()
+        }
+    }
+
+    /// Sets how the main window opens when the app resumes.
+    pub fn with_engine_config(mut self, engine_config: EngineConfig) -> Self {
+        self.engine_config = engine_config;
+        self
+    }
+
+    /// Opens a new window with its own `WgpuCtx`. The first window opened
+    /// becomes the primary one.
+    pub fn open_window(
+        &mut self,
+        event_loop: &ActiveEventLoop,
+        attributes: WindowAttributes,
+    ) -> WindowId {
+        let window = Arc::new(event_loop
+            .create_window(attributes)
+            .expect("create window err."));
+        let ctx = WgpuCtx::new_blocking(window.clone(), &self.gpu_config).unwrap();
+        let id = window.id();
+        self.viewports.insert(id, Viewport { window, ctx });
+        self.primary.get_or_insert(id);
+        debug!("Opened window {:?}", id);
+        id
+    }
+
+    pub fn viewport(&self, id: WindowId) -> Option<&Viewport<'window>> {
+        self.viewports.get(&id)
+    }
+
+    pub fn viewport_mut(&mut self, id: WindowId) -> Option<&mut Viewport<'window>> {
+        self.viewports.get_mut(&id)
+    }
+
+    pub fn window_count(&self) -> usize {
+        self.viewports.len()
+    }
+
+    /// See `GpuConfig::frame_latency`. Applies to open windows immediately.
+    pub fn set_frame_latency(&mut self, frame_latency: u32) {
+        self.gpu_config.set_frame_latency(frame_latency);
+        for viewport in self.viewports.values_mut() {
+            viewport.ctx.set_frame_latency(frame_latency);
+        }
+    }
+
+    pub fn stats(&self) -> &FrameStats {
+        &self.stats
+    }
+
+    /// Held keys and buttons, queried by action name.
+    pub fn input(&self) -> &Input {
+        &self.input
+    }
+
+    pub fn input_mut(&mut self) -> &mut Input {
+        &mut self.input
+    }
+
+    pub fn gamepads_mut(&mut self) -> &mut Gamepads {
+        &mut self.gamepads
+    }
+
+    /// Dev console input; game input is suppressed while it is capturing.
+    pub fn console(&self) -> &TextInput {
+        &self.console
+    }
+
+    pub fn console_mut(&mut self) -> &mut TextInput {
+        &mut self.console
+    }
+
+    /// Lets the platform IME compose text into the windows while the console is open.
+    fn allow_ime(&self, allowed: bool) {
+        for viewport in self.viewports.values() {
+            viewport.window.set_ime_allowed(allowed);
+        }
+    }
+
+    /// Whether the simulation is paused, either explicitly or because the
+    /// window lost focus or was minimized.
+    pub fn is_paused(&self) -> bool {
+        self.paused || self.unfocused || self.minimized
+    }
+
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+        self.sync_pause();
+    }
+
+    /// When set, nothing is drawn while paused. By default the last frame
+    /// keeps being redrawn.
+    pub fn set_pause_rendering(&mut self, pause_rendering: bool) {
+        self.pause_rendering = pause_rendering;
+    }
+
+    fn sync_pause(&mut self) {
+        let paused = self.is_paused();
+        if paused != self.clock.is_paused() {
+            debug!("Simulation {}", if paused { "paused" } else { "resumed" });
+        }
+        self.clock.set_paused(paused);
+    }
+
+    pub fn fullscreen(&self) -> FullscreenMode {
+        self.fullscreen
+    }
+
+    /// Applies `mode` to the primary window.
+    pub fn set_fullscreen(&mut self, mode: FullscreenMode) {
+        let primary = self.primary.and_then(|id| self.viewports.get_mut(&id));
+        if let Some(Viewport { window, .. }) = primary {
+            let fullscreen = match mode {
+                FullscreenMode::Windowed => None,
+                FullscreenMode::Borderless => Some(Fullscreen::Borderless(None)),
+                FullscreenMode::Exclusive => Some(
+                    window
+                        .current_monitor()
+                        .and_then(|monitor| best_video_mode(&monitor))
+                        .map(Fullscreen::Exclusive)
+                        // No usable video mode, borderless is the closest we can get
+                        .unwrap_or(Fullscreen::Borderless(None)),
+                ),
+            };
+            // The new size arrives with the following WindowEvent::Resized,
+            // which reconfigures the surface.
+            window.set_fullscreen(fullscreen);
+            debug!("Fullscreen mode set to {:?}", mode);
+        }
+        self.fullscreen = mode;
+    }
+
+    /// Refresh rate of the monitor the primary window is currently on. Uses
+    /// the monitor's reported rate, falling back to its video modes.
+    pub fn current_monitor_refresh_hz(&self) -> Option<f32> {
+        let monitor = self
+            .primary
+            .and_then(|id| self.viewports.get(&id))
+            .and_then(|viewport| viewport.window.current_monitor())?;
+        monitor
+            .refresh_rate_millihertz()
+            .filter(|&millihertz| millihertz > 0)
+            .map(|millihertz| millihertz as f32 / 1000.0)
+            .or_else(|| {
+                refresh_hz_from_modes(
+                    monitor.size(),
+                    monitor
+                        .video_modes()
+                        .map(|mode| (mode.size(), mode.refresh_rate_millihertz())),
+                )
+            })
+    }
+
+    pub fn screenshot_key(&self) -> Option<KeyCode> {
+        self.screenshot_key
+    }
+
+    /// Binds a key that saves the window it's pressed in to
+    /// `screenshot-<unix millis>.png` in the working directory. Off by
+    /// default; `DEFAULT_SCREENSHOT_KEY` is the usual choice.
+    pub fn set_screenshot_key(&mut self, key: Option<KeyCode>) {
+        self.screenshot_key = key;
+    }
+
+    pub fn frame_cap(&self) -> FrameCap {
+        self.frame_cap
+    }
+
+    pub fn set_frame_cap(&mut self, frame_cap: FrameCap) {
+        self.frame_cap = frame_cap;
+        self.next_frame = None;
+    }
+
+    pub fn control_flow(&self) -> ControlFlowMode {
+        self.control_flow
+    }
+
+    /// Lets power-sensitive apps idle instead of busy-looping. Takes effect
+    /// from the next loop iteration.
+    pub fn set_control_flow(&mut self, control_flow: ControlFlowMode) {
+        self.control_flow = control_flow;
+        self.needs_redraw = true;
+    }
+}
+
+/// Refresh rate in Hz of the fastest mode at the monitor's native size, from
+/// `(size, refresh_rate_millihertz)` pairs. Modes reporting 0 mHz are ignored.
+fn refresh_hz_from_modes(
+    native: PhysicalSize<u32>,
+    modes: impl IntoIterator<Item = (PhysicalSize<u32>, u32)>,
+) -> Option<f32> {
+    modes
+        .into_iter()
+        .filter(|&(size, millihertz)| size == native && millihertz > 0)
+        .map(|(_, millihertz)| millihertz)
+        .max()
+        .map(|millihertz| millihertz as f32 / 1000.0)
+}
+
+/// The primary window once `closed` is gone: unchanged unless it was the
+/// primary, in which case any of the still `open` windows takes over.
+fn primary_after_close(
+    primary: Option<WindowId>,
+    closed: WindowId,
+    mut open: impl Iterator<Item = WindowId>,
+) -> Option<WindowId> {
+    if primary == Some(closed) {
+        open.next()
+    } else {
+        primary
+    }
+}
+
+/// One loop iteration of simulation time: ticks `clock` and, unless
+/// `paused`, records the frame time in `stats` and calls `update` with it,
+/// clamped to `MAX_FRAME_DT`.
+fn step_simulation(
+    clock: &mut FrameClock,
+    paused: bool,
+    now: Instant,
+    stats: &mut FrameStats,
+    update: impl FnOnce(f32),
+) {
+    let dt = clock.tick_at(now);
+    if paused {
+        return;
+    }
+    if dt > 0.0 {
+        stats.record(dt);
+    }
+    update(dt.min(MAX_FRAME_DT));
+}
+
+/// Whether a key event is the Alt+Enter fullscreen toggle. Held-down repeats
+/// are ignored so the window doesn't flicker between modes.
+fn is_fullscreen_toggle(modifiers: ModifiersState, key: &Key, state: ElementState, repeat: bool) -> bool {
+    state == ElementState::Pressed && !repeat && modifiers.alt_key() && *key == Key::Named(NamedKey::Enter)
+}
+
+/// Picks the video mode matching the monitor's native size with the highest
+/// refresh rate and bit depth.
+fn best_video_mode(monitor: &MonitorHandle) -> Option<VideoModeHandle> {
+    let native = monitor.size();
+    monitor
+        .video_modes()
+        .filter(|mode| mode.size() == native)
+        .max_by_key(|mode| (mode.refresh_rate_millihertz(), mode.bit_depth()))
 }
 
 
 impl ApplicationHandler for App<'_> {
     fn resumed(&mut self, event_loop: &ActiveEventLoop) {
-        if self.window.is_none() {
-            let win_attr = Window::default_attributes()
-            .with_min_inner_size(winit::dpi::PhysicalSize::new(100, 100))
-            .with_title("Zenyx");
-            let window = Arc::new(event_loop
-                .create_window(win_attr)
-                .expect("create window err."));
-            self.window = Some(window.clone());
-            let wgpu_ctx = WgpuCtx::new_blocking(window.clone()).unwrap();
-            self.ctx = Some(wgpu_ctx)
+        if self.viewports.is_empty() {
+            let win_attr = self.engine_config.window_attributes(event_loop.primary_monitor().as_ref());
+            self.open_window(event_loop, win_attr);
+        }
+    }
+
+    fn new_events(&mut self, _event_loop: &ActiveEventLoop, cause: StartCause) {
+        if matches!(cause, StartCause::Init | StartCause::ResumeTimeReached { .. }) {
+            self.needs_redraw = true;
+        }
+    }
+
+    fn exiting(&mut self, _event_loop: &ActiveEventLoop) {
+        for (_, viewport) in self.viewports.drain() {
+            viewport.ctx.shutdown();
         }
     }
 
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        let display_hz = match self.frame_cap {
+            FrameCap::MatchDisplay => self.current_monitor_refresh_hz(),
+            _ => None,
+        };
+        if let Some(frame_time) = self.frame_cap.frame_time(display_hz) {
+            let now = Instant::now();
+            if let Some(next) = self.next_frame.filter(|next| *next > now) {
+                event_loop.set_control_flow(ControlFlow::WaitUntil(next));
+                return;
+            }
+            // Step from the previous deadline so the rate doesn't drift, but
+            // don't try to catch up on frames we were too slow for
+            self.next_frame = Some((self.next_frame.unwrap_or(now) + frame_time).max(now));
+        }
+        event_loop.set_control_flow(self.control_flow.control_flow(Instant::now()));
+        let redraw = self.control_flow == ControlFlowMode::Poll || std::mem::take(&mut self.needs_redraw);
+
+        self.gamepads.poll(&mut self.input);
+
+        // Advance every viewport once per loop iteration, rather than once per
+        // redraw, so opening more windows doesn't speed up the simulation.
+        let paused = self.is_paused();
+        let viewports = &mut self.viewports;
+        step_simulation(&mut self.clock, paused, Instant::now(), &mut self.stats, |dt| {
+            for viewport in viewports.values_mut() {
+                viewport.ctx.update(dt);
+            }
+        });
+        if redraw && (!paused || !self.pause_rendering) {
+            for viewport in self.viewports.values() {
+                viewport.window.request_redraw();
+            }
+        }
+        self.input.end_frame();
+    }
+
     fn window_event(
         &mut self,
         event_loop: &ActiveEventLoop,
-        _window_id: WindowId,
+        window_id: WindowId,
         event: WindowEvent,
     ) {
+        if !matches!(event, WindowEvent::RedrawRequested) {
+            self.needs_redraw = true;
+        }
         match event {
             WindowEvent::CloseRequested => {
-                event_loop.exit();
-                debug!("Window closed, exiting");
-                std::process::exit(0)
+                route_window_event(&mut self.viewports, window_id, &event);
+                debug!("Window {:?} closed", window_id);
+                self.primary = primary_after_close(self.primary, window_id, self.viewports.keys().copied());
+                if self.viewports.is_empty() {
+                    debug!("Last window closed, exiting");
+                    event_loop.exit();
+                }
             }
             WindowEvent::RedrawRequested => {
-                if let Some(ctx) = &mut self.ctx {
-                    ctx.draw();
-                }
-                if let Some(window) = &self.window {
-                    window.request_redraw();
+                if let Some(viewport) = self.viewports.get_mut(&window_id) {
+                    match viewport.ctx.draw() {
+                        Ok(()) => {}
+                        Err(wgpu::SurfaceError::Timeout | wgpu::SurfaceError::Outdated | wgpu::SurfaceError::Lost) => {
+                            self.stats.record_dropped();
+                        }
+                        Err(err) => error!("Failed to draw frame: {}", err),
+                    }
                 }
             }
+            WindowEvent::Focused(focused) => {
+                self.input.handle_event(&event);
+                self.unfocused = !focused;
+                self.sync_pause();
+            }
             WindowEvent::Resized(size) => {
-                if let (Some(wgpu_ctx),Some(window)) = (&mut self.ctx, &self.window) {
-                    wgpu_ctx.resize(size.into());
-                    window.request_redraw();
-                let size_str: String = size.height.to_string() + "x" + &size.width.to_string();
-                //self.window.as_ref().unwrap().set_title(&format!("you reszed the window to {size_str}"));
-                debug!("Window resized to {:?}", size_str);
+                if self.primary == Some(window_id) {
+                    self.minimized = size.width == 0 || size.height == 0;
+                    self.sync_pause();
+                }
+                route_window_event(&mut self.viewports, window_id, &event);
+                debug!("Window {:?} resized to {}x{}", window_id, size.width, size.height);
+            }
+            WindowEvent::ModifiersChanged(modifiers) => {
+                self.modifiers = modifiers.state();
+            }
+            WindowEvent::KeyboardInput { device_id, event, is_synthetic } => {
+                let was_capturing = self.console.is_capturing();
+                if self.console.handle_key(&event) {
+                    if self.console.is_capturing() != was_capturing {
+                        self.allow_ime(self.console.is_capturing());
+                        self.input.clear();
+                    }
+                    return;
+                }
+                if is_fullscreen_toggle(self.modifiers, &event.logical_key, event.state, event.repeat) {
+                    self.set_fullscreen(self.fullscreen.toggled());
+                }
+                if event.state == ElementState::Pressed
+                    && !event.repeat
+                    && self.screenshot_key.is_some_and(|key| event.physical_key == PhysicalKey::Code(key))
+                {
+                    if let Some(viewport) = self.viewports.get_mut(&window_id) {
+                        let millis = SystemTime::now()
+                            .duration_since(UNIX_EPOCH)
+                            .map_or(0, |elapsed| elapsed.as_millis());
+                        if let Err(err) = viewport.ctx.save_screenshot(format!("screenshot-{millis}.png")) {
+                            error!("Failed to save screenshot: {}", err);
+                        }
+                    }
+                }
+                self.input.handle_event(&WindowEvent::KeyboardInput { device_id, event, is_synthetic });
+            }
+            WindowEvent::MouseInput { .. } => {
+                if !self.console.is_capturing() {
+                    self.input.handle_event(&event);
+                }
+            }
+            WindowEvent::Ime(ime) => {
+                self.console.handle_ime(&ime);
             }
-        }
             _ => trace!("Unhandled window event"),
         }
     }
 }
 
 pub fn init_renderer() {
+    init_renderer_with(App::default());
+}
+
+/// Runs `app`, e.g. one configured with `App::set_control_flow`, with the
+/// event loop starting in the app's control-flow mode.
+pub fn init_renderer_with(mut app: App) {
     let event_loop = EventLoop::new().unwrap();
-    event_loop.set_control_flow(ControlFlow::Poll);
-    let mut app = App::default();
+    event_loop.set_control_flow(app.control_flow().control_flow(Instant::now()));
     event_loop.run_app(&mut app).unwrap();
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn toggle_switches_between_windowed_and_borderless() {
+        assert_eq!(FullscreenMode::Windowed.toggled(), FullscreenMode::Borderless);
+        assert_eq!(FullscreenMode::Borderless.toggled(), FullscreenMode::Windowed);
+        assert_eq!(FullscreenMode::Exclusive.toggled(), FullscreenMode::Windowed);
+    }
+
+    #[test]
+    fn alt_enter_press_toggles_fullscreen() {
+        let enter = Key::Named(NamedKey::Enter);
+        assert!(is_fullscreen_toggle(ModifiersState::ALT, &enter, ElementState::Pressed, false));
+        assert!(!is_fullscreen_toggle(ModifiersState::empty(), &enter, ElementState::Pressed, false));
+        assert!(!is_fullscreen_toggle(ModifiersState::ALT, &enter, ElementState::Released, false));
+        assert!(!is_fullscreen_toggle(ModifiersState::ALT, &enter, ElementState::Pressed, true));
+        let space = Key::Named(NamedKey::Space);
+        assert!(!is_fullscreen_toggle(ModifiersState::ALT, &space, ElementState::Pressed, false));
+    }
+
+    #[test]
+    fn closing_a_secondary_window_keeps_the_primary() {
+        let [primary, secondary] = [WindowId::from(1), WindowId::from(2)];
+        assert_eq!(primary_after_close(Some(primary), secondary, [primary].into_iter()), Some(primary));
+    }
+
+    #[test]
+    fn closing_the_primary_window_promotes_another() {
+        let [primary, secondary] = [WindowId::from(1), WindowId::from(2)];
+        assert_eq!(primary_after_close(Some(primary), primary, [secondary].into_iter()), Some(secondary));
+        assert_eq!(primary_after_close(Some(secondary), secondary, std::iter::empty()), None);
+    }
+
+    /// Records what routing did to one window.
+    #[derive(Debug, Clone, PartialEq)]
+    struct FakeViewport {
+        size: PhysicalSize<u32>,
+    }
+
+    impl WindowTarget for FakeViewport {
+        fn resize(&mut self, size: PhysicalSize<u32>) {
+            self.size = size;
+        }
+
+        fn close(self) {}
+    }
+
+    fn two_viewports() -> ([WindowId; 2], HashMap<WindowId, FakeViewport>) {
+        let ids = [WindowId::from(1), WindowId::from(2)];
+        let viewport = FakeViewport { size: PhysicalSize::new(800, 600) };
+        (ids, ids.into_iter().map(|id| (id, viewport.clone())).collect())
+    }
+
+    #[test]
+    fn resizing_one_window_leaves_the_other_unchanged() {
+        let ([a, b], mut viewports) = two_viewports();
+        route_window_event(&mut viewports, a, &WindowEvent::Resized(PhysicalSize::new(1024, 768)));
+        assert_eq!(viewports[&a].size, PhysicalSize::new(1024, 768));
+        assert_eq!(viewports[&b].size, PhysicalSize::new(800, 600));
+    }
+
+    #[test]
+    fn closing_one_window_leaves_the_other_open() {
+        let ([a, b], mut viewports) = two_viewports();
+        let before = viewports[&b].clone();
+        route_window_event(&mut viewports, a, &WindowEvent::CloseRequested);
+        assert!(!viewports.contains_key(&a));
+        assert_eq!(viewports.get(&b), Some(&before));
+        // Events for a window that is already gone do nothing
+        route_window_event(&mut viewports, a, &WindowEvent::Resized(PhysicalSize::new(1, 1)));
+        assert_eq!(viewports.get(&b), Some(&before));
+    }
+
+    #[test]
+    fn paused_steps_do_not_update() {
+        let start = Instant::now();
+        let (mut clock, mut stats) = (FrameClock::new(), FrameStats::default());
+        clock.set_paused(true);
+        for frame in 0..3 {
+            let now = start + Duration::from_millis(16 * frame);
+            step_simulation(&mut clock, true, now, &mut stats, |_| panic!("updated while paused"));
+        }
+
+        clock.set_paused(false);
+        let mut steps = Vec::new();
+        step_simulation(&mut clock, false, start, &mut stats, |dt| steps.push(dt));
+        step_simulation(&mut clock, false, start + Duration::from_millis(250), &mut stats, |dt| steps.push(dt));
+        assert_eq!(steps, [0.0, 0.25]);
+    }
+
+    #[test]
+    fn a_long_idle_is_clamped_to_the_maximum_step() {
+        let start = Instant::now();
+        let (mut clock, mut stats) = (FrameClock::new(), FrameStats::default());
+        step_simulation(&mut clock, false, start, &mut stats, |_| {});
+        let mut dt = None;
+        step_simulation(&mut clock, false, start + Duration::from_secs(30), &mut stats, |step| dt = Some(step));
+        assert_eq!(dt, Some(MAX_FRAME_DT));
+    }
+
+    #[test]
+    fn app_starts_without_windows() {
+        let app = App::default();
+        assert_eq!(app.window_count(), 0);
+        assert!(app.viewport(WindowId::from(1)).is_none());
+    }
+
+    #[test]
+    fn refresh_rate_is_the_fastest_native_mode() {
+        let native = PhysicalSize::new(1920, 1080);
+        let modes = [
+            (PhysicalSize::new(1920, 1080), 59_940),
+            (PhysicalSize::new(1920, 1080), 143_856),
+            (PhysicalSize::new(1280, 720), 240_000),
+            (PhysicalSize::new(1920, 1080), 0),
+        ];
+        let hz = refresh_hz_from_modes(native, modes).unwrap();
+        assert!((hz - 143.856).abs() < 1e-3, "{hz}");
+    }
+
+    #[test]
+    fn refresh_rate_is_unknown_without_native_modes() {
+        let native = PhysicalSize::new(2560, 1440);
+        assert_eq!(refresh_hz_from_modes(native, []), None);
+        assert_eq!(refresh_hz_from_modes(native, [(PhysicalSize::new(1920, 1080), 60_000)]), None);
+        assert_eq!(refresh_hz_from_modes(native, [(native, 0)]), None);
+    }
+
+    #[test]
+    fn app_polls_by_default() {
+        let app = App::default();
+        assert_eq!(app.control_flow(), ControlFlowMode::Poll);
+        assert_eq!(app.control_flow().control_flow(Instant::now()), ControlFlow::Poll);
+    }
+
+    #[test]
+    fn chosen_control_flow_reaches_the_event_loop() {
+        let mut app = App::default();
+        app.set_control_flow(ControlFlowMode::Wait);
+        assert_eq!(app.control_flow(), ControlFlowMode::Wait);
+        let now = Instant::now();
+        assert_eq!(app.control_flow().control_flow(now), ControlFlow::Wait);
+
+        let timeout = Duration::from_millis(250);
+        app.set_control_flow(ControlFlowMode::WaitUntil(timeout));
+        assert_eq!(app.control_flow().control_flow(now), ControlFlow::WaitUntil(now + timeout));
+    }
+}
@@ -0,0 +1,86 @@
+/// A fixed set of per-frame resources used round-robin, so the CPU writes
+/// into one copy while the GPU may still be reading the previous ones.
+#[derive(Debug)]
+pub struct FrameRing<T> {
+    items: Vec<T>,
+    index: usize,
+}
+
+impl<T> FrameRing<T> {
+    /// # Panics
+    /// If `items` is empty.
+    pub fn new(items: Vec<T>) -> Self {
+        assert!(!items.is_empty(), "FrameRing needs at least one item");
+        Self { items, index: 0 }
+    }
+
+    /// Builds `len` items with `make`, which receives each slot's index.
+    pub fn from_fn(len: usize, make: impl FnMut(usize) -> T) -> Self {
+        Self::new((0..len.max(1)).map(make).collect())
+    }
+
+    pub fn current(&self) -> &T {
+        &self.items[self.index]
+    }
+
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Moves to the next slot, wrapping around, and returns its index.
+    pub fn advance(&mut self) -> usize {
+        self.index = (self.index + 1) % self.items.len();
+        self.index
+    }
+
+    pub fn into_items(self) -> Vec<T> {
+        self.items
+    }
+}
+
+/// Number of per-frame copies needed so a frame never writes to a resource the
+/// GPU may still be using: one per frame in flight plus the one being recorded.
+pub fn ring_len(max_frame_latency: u32) -> usize {
+    max_frame_latency.max(1) as usize + 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn advance_wraps_after_the_last_slot() {
+        let mut ring = FrameRing::from_fn(3, |index| index * 10);
+        assert_eq!(ring.index(), 0);
+        assert_eq!(ring.advance(), 1);
+        assert_eq!(ring.advance(), 2);
+        assert_eq!(ring.advance(), 0);
+        assert_eq!(*ring.current(), 0);
+        assert_eq!(ring.advance(), 1);
+        assert_eq!(*ring.current(), 10);
+    }
+
+    #[test]
+    fn single_slot_ring_stays_on_its_slot() {
+        let mut ring = FrameRing::from_fn(0, |index| index);
+        assert_eq!(ring.len(), 1);
+        assert_eq!(ring.advance(), 0);
+        assert_eq!(ring.advance(), 0);
+    }
+
+    #[test]
+    fn ring_has_one_slot_per_frame_in_flight_plus_one() {
+        assert_eq!(ring_len(0), 2);
+        assert_eq!(ring_len(1), 2);
+        assert_eq!(ring_len(2), 3);
+        assert_eq!(ring_len(3), 4);
+    }
+}
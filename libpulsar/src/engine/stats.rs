@@ -0,0 +1,276 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use log::warn;
+
+/// Number of frames the percentile and median statistics are computed over.
+pub const DEFAULT_WINDOW: usize = 240;
+/// A frame counts as a stutter when it takes this many times the rolling median.
+pub const STUTTER_FACTOR: f32 = 2.0;
+/// Dropped frames tolerated per `DEFAULT_DROP_WINDOW` before warning.
+pub const DEFAULT_DROP_THRESHOLD: u32 = 5;
+pub const DEFAULT_DROP_WINDOW: Duration = Duration::from_secs(1);
+
+/// Frame time statistics over a sliding window of recent frames.
+#[derive(Debug, Clone)]
+pub struct FrameStats {
+    frame_times: VecDeque<f32>,
+    /// Reused by `record` to find the rolling median without allocating.
+    scratch: Vec<f32>,
+    window: usize,
+    total_frames: u64,
+    stutter_count: u64,
+    dropped_frames: u64,
+    drop_threshold: u32,
+    drop_window: Duration,
+    drop_window_start: Option<Instant>,
+    drops_in_window: u32,
+    drop_warned: bool,
+}
+
+impl Default for FrameStats {
+    fn default() -> Self {
+        Self::new(DEFAULT_WINDOW)
+    }
+}
+
+impl FrameStats {
+    pub fn new(window: usize) -> Self {
+        let window = window.max(1);
+        Self {
+            frame_times: VecDeque::with_capacity(window),
+            scratch: Vec::with_capacity(window),
+            window,
+            total_frames: 0,
+            stutter_count: 0,
+            dropped_frames: 0,
+            drop_threshold: DEFAULT_DROP_THRESHOLD,
+            drop_window: DEFAULT_DROP_WINDOW,
+            drop_window_start: None,
+            drops_in_window: 0,
+            drop_warned: false,
+        }
+    }
+
+    /// Records a frame that took `dt` seconds. Only the stutter check runs
+    /// here; the other statistics are computed when read.
+    pub fn record(&mut self, dt: f32) {
+        self.scratch.clear();
+        self.scratch.extend(self.frame_times.iter().copied());
+        if let Some(median) = median_of(&mut self.scratch) {
+            if dt > median * STUTTER_FACTOR {
+                self.stutter_count += 1;
+            }
+        }
+        if self.frame_times.len() == self.window {
+            self.frame_times.pop_front();
+        }
+        self.frame_times.push_back(dt);
+        self.total_frames += 1;
+    }
+
+    /// Records a frame skipped because no surface texture could be acquired
+    /// (timeout, outdated or lost surface).
+    pub fn record_dropped(&mut self) {
+        self.record_dropped_at(Instant::now());
+    }
+
+    /// Returns whether this drop triggered a warning. At most one warning is
+    /// logged per window, once more than the threshold of drops fall in it.
+    pub fn record_dropped_at(&mut self, now: Instant) -> bool {
+        self.dropped_frames += 1;
+        let window_expired = self
+            .drop_window_start
+            .map_or(true, |start| now.saturating_duration_since(start) >= self.drop_window);
+        if window_expired {
+            self.drop_window_start = Some(now);
+            self.drops_in_window = 0;
+            self.drop_warned = false;
+        }
+        self.drops_in_window += 1;
+        if self.drops_in_window > self.drop_threshold && !self.drop_warned {
+            self.drop_warned = true;
+            warn!(
+                "Dropped {} frames in {:.1}s ({} total); the GPU may be stalling",
+                self.drops_in_window,
+                self.drop_window.as_secs_f32(),
+                self.dropped_frames
+            );
+            return true;
+        }
+        false
+    }
+
+    /// Warns when more than `threshold` frames are dropped within `window`.
+    pub fn set_drop_warning(&mut self, threshold: u32, window: Duration) {
+        self.drop_threshold = threshold;
+        self.drop_window = window;
+    }
+
+    pub fn dropped_frames(&self) -> u64 {
+        self.dropped_frames
+    }
+
+    pub fn total_frames(&self) -> u64 {
+        self.total_frames
+    }
+
+    /// Frames that took more than `STUTTER_FACTOR` times the rolling median.
+    pub fn stutter_count(&self) -> u64 {
+        self.stutter_count
+    }
+
+    pub fn average_frame_time(&self) -> Option<f32> {
+        if self.frame_times.is_empty() {
+            return None;
+        }
+        Some(self.frame_times.iter().sum::<f32>() / self.frame_times.len() as f32)
+    }
+
+    pub fn average_fps(&self) -> Option<f32> {
+        self.average_frame_time().filter(|dt| *dt > 0.0).map(|dt| 1.0 / dt)
+    }
+
+    pub fn median_frame_time(&self) -> Option<f32> {
+        median_of(&mut self.frame_times.iter().copied().collect::<Vec<_>>())
+    }
+
+    /// Average frame time of the slowest 1% of frames in the window.
+    pub fn one_percent_low(&self) -> Option<f32> {
+        self.slowest_fraction(0.01)
+    }
+
+    /// Average frame time of the slowest 0.1% of frames in the window.
+    pub fn point_one_percent_low(&self) -> Option<f32> {
+        self.slowest_fraction(0.001)
+    }
+
+    /// Average of the slowest `fraction` of frames, always including at least
+    /// the single slowest one.
+    fn slowest_fraction(&self, fraction: f32) -> Option<f32> {
+        let sorted = self.sorted();
+        if sorted.is_empty() {
+            return None;
+        }
+        let count = ((sorted.len() as f32 * fraction).ceil() as usize).clamp(1, sorted.len());
+        let slowest = &sorted[sorted.len() - count..];
+        Some(slowest.iter().sum::<f32>() / count as f32)
+    }
+
+    fn sorted(&self) -> Vec<f32> {
+        let mut sorted: Vec<f32> = self.frame_times.iter().copied().collect();
+        sorted.sort_by(f32::total_cmp);
+        sorted
+    }
+}
+
+/// Median of `values`, which are left partially reordered. Runs in linear
+/// time rather than sorting.
+fn median_of(values: &mut [f32]) -> Option<f32> {
+    if values.is_empty() {
+        return None;
+    }
+    let (len, mid) = (values.len(), values.len() / 2);
+    let (lower, upper, _) = values.select_nth_unstable_by(mid, f32::total_cmp);
+    let upper = *upper;
+    if len % 2 == 1 {
+        return Some(upper);
+    }
+    // Everything before `mid` is no larger than it, so the largest of those is
+    // the other middle value
+    let lower = lower.iter().copied().max_by(f32::total_cmp)?;
+    Some((lower + upper) * 0.5)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn single_spike_is_one_stutter_and_sets_the_lows() {
+        let mut stats = FrameStats::new(100);
+        for _ in 0..50 {
+            stats.record(0.016);
+        }
+        stats.record(0.1);
+        for _ in 0..49 {
+            stats.record(0.016);
+        }
+        assert_eq!(stats.stutter_count(), 1);
+        assert_eq!(stats.total_frames(), 100);
+        // One frame in a hundred: the 1% low is the spike itself
+        assert_eq!(stats.one_percent_low(), Some(0.1));
+        assert_eq!(stats.point_one_percent_low(), Some(0.1));
+        assert_eq!(stats.median_frame_time(), Some(0.016));
+    }
+
+    #[test]
+    fn median_of_picks_the_middle_of_unsorted_values() {
+        assert_eq!(median_of(&mut [3.0, 1.0, 2.0]), Some(2.0));
+        assert_eq!(median_of(&mut [4.0, 1.0, 3.0, 2.0]), Some(2.5));
+        assert_eq!(median_of(&mut [5.0]), Some(5.0));
+        assert_eq!(median_of(&mut []), None);
+    }
+
+    #[test]
+    fn steady_frames_never_stutter() {
+        let mut stats = FrameStats::new(10);
+        for _ in 0..30 {
+            stats.record(0.016);
+        }
+        assert_eq!(stats.stutter_count(), 0);
+        assert_eq!(stats.one_percent_low(), Some(0.016));
+    }
+
+    #[test]
+    fn spike_leaves_the_lows_once_it_slides_out_of_the_window() {
+        let mut stats = FrameStats::new(4);
+        stats.record(0.016);
+        stats.record(0.1);
+        for _ in 0..4 {
+            stats.record(0.016);
+        }
+        assert_eq!(stats.one_percent_low(), Some(0.016));
+        assert_eq!(stats.stutter_count(), 1);
+    }
+
+    #[test]
+    fn empty_stats_have_no_statistics() {
+        let stats = FrameStats::default();
+        assert_eq!(stats.median_frame_time(), None);
+        assert_eq!(stats.one_percent_low(), None);
+        assert_eq!(stats.average_fps(), None);
+    }
+
+    #[test]
+    fn dropped_frames_are_counted_and_warn_once_per_window() {
+        let mut stats = FrameStats::default();
+        stats.set_drop_warning(3, Duration::from_secs(1));
+        let start = Instant::now();
+        let warnings = (0..10)
+            .filter(|&i| stats.record_dropped_at(start + Duration::from_millis(i * 50)))
+            .count();
+        assert_eq!(stats.dropped_frames(), 10);
+        assert_eq!(warnings, 1);
+
+        // A new window may warn again
+        let later = start + Duration::from_secs(2);
+        let warnings = (0..10)
+            .filter(|&i| stats.record_dropped_at(later + Duration::from_millis(i * 50)))
+            .count();
+        assert_eq!(stats.dropped_frames(), 20);
+        assert_eq!(warnings, 1);
+    }
+
+    #[test]
+    fn drops_at_the_threshold_do_not_warn() {
+        let mut stats = FrameStats::default();
+        stats.set_drop_warning(3, Duration::from_secs(1));
+        let start = Instant::now();
+        assert!(!(0..3).any(|i| stats.record_dropped_at(start + Duration::from_millis(i * 100))));
+        // Spread out, the same drops never fill a window
+        assert!(!(1..10).any(|i| stats.record_dropped_at(start + Duration::from_secs(i * 2))));
+        assert_eq!(stats.dropped_frames(), 12);
+        assert_eq!(stats.total_frames(), 0);
+    }
+}
@@ -0,0 +1,174 @@
+use std::collections::VecDeque;
+
+use winit::event::{ElementState, Ime, KeyEvent};
+use winit::keyboard::{Key, NamedKey};
+
+/// Key that opens and closes the console.
+pub const TOGGLE_KEY: &str = "`";
+
+/// Typed text for a dev console overlay.
+///
+/// While capturing, key presses and IME commits are appended to `buffer`
+/// instead of reaching the game. Backspace deletes the last character and
+/// Enter moves the line to the queue of submitted commands.
+#[derive(Debug, Default)]
+pub struct TextInput {
+    capturing: bool,
+    buffer: String,
+    /// Uncommitted IME composition, shown after the buffer but not part of it.
+    preedit: String,
+    submitted: VecDeque<String>,
+}
+
+impl TextInput {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_capturing(&self) -> bool {
+        self.capturing
+    }
+
+    pub fn set_capturing(&mut self, capturing: bool) {
+        self.capturing = capturing;
+        self.preedit.clear();
+    }
+
+    pub fn toggle(&mut self) {
+        self.set_capturing(!self.capturing);
+    }
+
+    /// The line being typed.
+    pub fn buffer(&self) -> &str {
+        &self.buffer
+    }
+
+    /// Text the IME is still composing, for the overlay to show underlined.
+    pub fn preedit(&self) -> &str {
+        &self.preedit
+    }
+
+    /// Next submitted command, oldest first.
+    pub fn pop_command(&mut self) -> Option<String> {
+        self.submitted.pop_front()
+    }
+
+    /// Takes all submitted commands, oldest first.
+    pub fn drain_commands(&mut self) -> impl Iterator<Item = String> + '_ {
+        self.submitted.drain(..)
+    }
+
+    /// Feeds typed text. `'\u{8}'` (backspace) deletes, `'\n'` or `'\r'`
+    /// submits the line, and other control characters are ignored.
+    pub fn push_text(&mut self, text: &str) {
+        for c in text.chars() {
+            match c {
+                '\u{8}' | '\u{7f}' => self.backspace(),
+                '\n' | '\r' => self.submit(),
+                c if c.is_control() => {}
+                c => self.buffer.push(c),
+            }
+        }
+    }
+
+    pub fn backspace(&mut self) {
+        self.buffer.pop();
+    }
+
+    /// Queues the current line as a command, unless it is blank.
+    pub fn submit(&mut self) {
+        let line = std::mem::take(&mut self.buffer);
+        if !line.trim().is_empty() {
+            self.submitted.push_back(line);
+        }
+    }
+
+    /// Handles a key event. Returns whether the event was consumed, i.e. the
+    /// game should not see it. The toggle key is always consumed.
+    pub fn handle_key(&mut self, event: &KeyEvent) -> bool {
+        if event.state != ElementState::Pressed {
+            return self.capturing;
+        }
+        if !event.repeat && matches!(&event.logical_key, Key::Character(c) if c.as_str() == TOGGLE_KEY) {
+            self.toggle();
+            return true;
+        }
+        if !self.capturing {
+            return false;
+        }
+
+        match &event.logical_key {
+            Key::Named(NamedKey::Backspace) => self.backspace(),
+            Key::Named(NamedKey::Enter) => self.submit(),
+            _ => {
+                if let Some(text) = &event.text {
+                    self.push_text(text);
+                }
+            }
+        }
+        true
+    }
+
+    /// Handles an IME event; returns whether it was consumed.
+    pub fn handle_ime(&mut self, ime: &Ime) -> bool {
+        if !self.capturing {
+            return false;
+        }
+        match ime {
+            Ime::Preedit(text, _) => self.preedit = text.clone(),
+            Ime::Commit(text) => {
+                self.preedit.clear();
+                self.push_text(text);
+            }
+            Ime::Enabled | Ime::Disabled => self.preedit.clear(),
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn typing_with_backspace_and_enter_submits_the_edited_line() {
+        let mut input = TextInput::new();
+        input.set_capturing(true);
+        input.push_text("abc\u{8}d\n");
+        assert_eq!(input.pop_command().as_deref(), Some("abd"));
+        assert_eq!(input.buffer(), "");
+        assert_eq!(input.pop_command(), None);
+    }
+
+    #[test]
+    fn blank_lines_are_not_submitted() {
+        let mut input = TextInput::new();
+        input.push_text("  \n\r");
+        assert_eq!(input.drain_commands().count(), 0);
+        input.push_text("first\nsecond\n");
+        assert_eq!(input.drain_commands().collect::<Vec<_>>(), vec!["first", "second"]);
+    }
+
+    #[test]
+    fn ime_composition_is_only_added_once_committed() {
+        let mut input = TextInput::new();
+        input.set_capturing(true);
+        assert!(input.handle_ime(&Ime::Preedit(String::from("にほ"), None)));
+        assert_eq!(input.preedit(), "にほ");
+        assert_eq!(input.buffer(), "");
+        assert!(input.handle_ime(&Ime::Commit(String::from("日本"))));
+        assert_eq!(input.preedit(), "");
+        assert_eq!(input.buffer(), "日本");
+    }
+
+    #[test]
+    fn ime_events_reach_the_game_while_not_capturing() {
+        let mut input = TextInput::new();
+        assert!(!input.handle_ime(&Ime::Commit(String::from("a"))));
+        assert_eq!(input.buffer(), "");
+        input.toggle();
+        assert!(input.is_capturing());
+        input.toggle();
+        assert!(!input.is_capturing());
+    }
+}
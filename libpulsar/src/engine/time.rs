@@ -0,0 +1,125 @@
+use std::time::{Duration, Instant};
+
+/// Refresh rate assumed by `FrameCap::MatchDisplay` when the display's is unknown.
+pub const FALLBACK_REFRESH_HZ: f32 = 60.0;
+/// Longest step, in seconds, the simulation is advanced by at once, so the
+/// first frame after the event loop idled doesn't jump ahead.
+pub const MAX_FRAME_DT: f32 = 0.25;
+
+/// Upper bound on how often frames are started.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum FrameCap {
+    #[default]
+    Unlimited,
+    /// At most this many frames per second.
+    Fps(f32),
+    /// The refresh rate of the monitor the window is on.
+    MatchDisplay,
+}
+
+impl FrameCap {
+    /// Minimum time between frames, or `None` when uncapped.
+    pub fn frame_time(self, display_hz: Option<f32>) -> Option<Duration> {
+        let fps = match self {
+            FrameCap::Unlimited => return None,
+            FrameCap::Fps(fps) => fps,
+            FrameCap::MatchDisplay => display_hz.unwrap_or(FALLBACK_REFRESH_HZ),
+        };
+        (fps > 0.0 && fps.is_finite()).then(|| Duration::from_secs_f32(1.0 / fps))
+    }
+}
+
+/// Measures the time between frames, reporting zero while paused so the
+/// simulation doesn't see one huge step when it resumes.
+#[derive(Debug, Default)]
+pub struct FrameClock {
+    last: Option<Instant>,
+    paused: bool,
+}
+
+impl FrameClock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn set_paused(&mut self, paused: bool) {
+        if self.paused && !paused {
+            // Start measuring again from the first frame after resuming
+            self.last = None;
+        }
+        self.paused = paused;
+    }
+
+    /// Seconds since the previous tick.
+    pub fn tick(&mut self) -> f32 {
+        self.tick_at(Instant::now())
+    }
+
+    pub fn tick_at(&mut self, now: Instant) -> f32 {
+        if self.paused {
+            self.last = None;
+            return 0.0;
+        }
+        let dt = self
+            .last
+            .map(|last| now.saturating_duration_since(last).as_secs_f32())
+            .unwrap_or(0.0);
+        self.last = Some(now);
+        dt
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ticks_measure_time_between_frames() {
+        let start = Instant::now();
+        let mut clock = FrameClock::new();
+        assert_eq!(clock.tick_at(start), 0.0);
+        assert_eq!(clock.tick_at(start + Duration::from_millis(250)), 0.25);
+    }
+
+    #[test]
+    fn paused_clock_reports_zero() {
+        let start = Instant::now();
+        let mut clock = FrameClock::new();
+        clock.tick_at(start);
+        clock.set_paused(true);
+        assert!(clock.is_paused());
+        assert_eq!(clock.tick_at(start + Duration::from_secs(1)), 0.0);
+    }
+
+    #[test]
+    fn resuming_skips_the_paused_time() {
+        let start = Instant::now();
+        let mut clock = FrameClock::new();
+        clock.tick_at(start);
+        clock.set_paused(true);
+        clock.tick_at(start + Duration::from_secs(5));
+        clock.set_paused(false);
+        assert_eq!(clock.tick_at(start + Duration::from_secs(10)), 0.0);
+        assert_eq!(clock.tick_at(start + Duration::from_millis(10_500)), 0.5);
+    }
+
+    #[test]
+    fn frame_cap_limits_the_frame_time() {
+        assert_eq!(FrameCap::Unlimited.frame_time(Some(144.0)), None);
+        assert_eq!(FrameCap::Fps(50.0).frame_time(Some(144.0)), Some(Duration::from_millis(20)));
+        assert_eq!(FrameCap::Fps(0.0).frame_time(None), None);
+    }
+
+    #[test]
+    fn match_display_follows_the_refresh_rate() {
+        assert_eq!(FrameCap::MatchDisplay.frame_time(Some(125.0)), Some(Duration::from_millis(8)));
+        assert_eq!(
+            FrameCap::MatchDisplay.frame_time(None),
+            Some(Duration::from_secs_f32(1.0 / FALLBACK_REFRESH_HZ))
+        );
+    }
+}
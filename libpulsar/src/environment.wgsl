@@ -0,0 +1,43 @@
+// Image-based reflections from a cubemap environment, included by
+// `shader.wgsl`. Lit shaders pass in the material's environment map, its
+// sampler, `MaterialUniform::env_mip_count` and `MaterialUniform::lod_bias`.
+
+// `view_dir` points from the surface towards the eye.
+fn reflection_dir(view_dir: vec3<f32>, normal: vec3<f32>) -> vec3<f32> {
+    return reflect(-view_dir, normal);
+}
+
+// Rougher surfaces read blurrier (smaller) mips of the environment;
+// `lod_bias` shifts the choice sharper (negative) or blurrier (positive).
+fn sample_environment(
+    env: texture_cube<f32>,
+    env_sampler: sampler,
+    dir: vec3<f32>,
+    roughness: f32,
+    mip_count: f32,
+    lod_bias: f32,
+) -> vec3<f32> {
+    let max_lod = max(mip_count - 1.0, 0.0);
+    let lod = clamp(clamp(roughness, 0.0, 1.0) * max_lod + lod_bias, 0.0, max_lod);
+    return textureSampleLevel(env, env_sampler, dir, lod).rgb;
+}
+
+// Reflected environment light, weighted by a roughness-aware Schlick Fresnel
+// term. F0 goes from 4% for dielectrics to the base color for metals.
+fn environment_specular(
+    base_color: vec3<f32>,
+    metallic: f32,
+    roughness: f32,
+    view_dir: vec3<f32>,
+    normal: vec3<f32>,
+    env: texture_cube<f32>,
+    env_sampler: sampler,
+    mip_count: f32,
+    lod_bias: f32,
+) -> vec3<f32> {
+    let f0 = mix(vec3<f32>(0.04), base_color, metallic);
+    let n_dot_v = max(dot(normal, view_dir), 0.0);
+    let fresnel = f0 + (max(vec3<f32>(1.0 - roughness), f0) - f0) * pow(1.0 - n_dot_v, 5.0);
+    let dir = reflection_dir(view_dir, normal);
+    return sample_environment(env, env_sampler, dir, roughness, mip_count, lod_bias) * fresnel;
+}
@@ -0,0 +1,192 @@
+use glam::Vec3;
+use winit::event::{MouseScrollDelta, WindowEvent};
+
+use crate::camera::Camera;
+use crate::engine::input::Input;
+use crate::math::{Handedness, HANDEDNESS};
+
+/// Action that, while held, makes the scroll wheel change the fly speed.
+pub const SPEED_MODIFIER_ACTION: &str = "camera_speed_modifier";
+/// Pixels of touchpad scrolling that count as one wheel notch.
+const PIXELS_PER_NOTCH: f32 = 40.0;
+/// Just short of straight up or down, where the look direction would flip.
+const MAX_PITCH: f32 = 89.0_f32.to_radians();
+
+/// Wheel notches in a scroll event; positive scrolls up.
+pub fn scroll_notches(delta: &MouseScrollDelta) -> f32 {
+    match delta {
+        MouseScrollDelta::LineDelta(_, y) => *y,
+        MouseScrollDelta::PixelDelta(position) => position.y as f32 / PIXELS_PER_NOTCH,
+    }
+}
+
+/// Free-flying debug camera: the move actions of the `Input`'s action map
+/// fly along the look direction, jump and crouch go straight up and down.
+pub struct FpsController {
+    /// Radians about +Y, turning right as it grows; 0 looks along the
+    /// crate's `Vector3::forward`.
+    pub yaw: f32,
+    /// Radians above the horizon.
+    pub pitch: f32,
+    /// Radians per pixel of mouse movement.
+    pub sensitivity: f32,
+    pub min_speed: f32,
+    pub max_speed: f32,
+    /// Speed multiplier per scroll notch.
+    pub speed_step: f32,
+    speed: f32,
+}
+
+impl Default for FpsController {
+    fn default() -> Self {
+        Self {
+            yaw: 0.0,
+            pitch: 0.0,
+            sensitivity: 0.002,
+            min_speed: 0.5,
+            max_speed: 100.0,
+            speed_step: 1.25,
+            speed: 5.0,
+        }
+    }
+}
+
+impl FpsController {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Units per second.
+    pub fn speed(&self) -> f32 {
+        self.speed
+    }
+
+    pub fn set_speed(&mut self, speed: f32) {
+        self.speed = speed.clamp(self.min_speed, self.max_speed);
+    }
+
+    /// Scales the speed by `speed_step` once per unit of `delta`, so
+    /// positive steps speed up and negative ones slow down. Returns the
+    /// new, clamped speed.
+    pub fn adjust_speed(&mut self, delta: f32) -> f32 {
+        self.set_speed(self.speed * self.speed_step.powf(delta));
+        self.speed
+    }
+
+    /// Adjusts the speed on scroll while `SPEED_MODIFIER_ACTION` is held.
+    /// Returns whether the event was used.
+    pub fn handle_event(&mut self, event: &WindowEvent, input: &Input) -> bool {
+        match event {
+            WindowEvent::MouseWheel { delta, .. } if input.action_down(SPEED_MODIFIER_ACTION) => {
+                self.adjust_speed(scroll_notches(delta));
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Turns by a mouse movement of `dx`, `dy` pixels.
+    pub fn look(&mut self, dx: f32, dy: f32) {
+        self.yaw += dx * self.sensitivity;
+        self.pitch = (self.pitch - dy * self.sensitivity).clamp(-MAX_PITCH, MAX_PITCH);
+    }
+
+    /// Unit look direction in world space, under the crate's `HANDEDNESS`.
+    pub fn forward(&self) -> Vec3 {
+        let (sin_yaw, cos_yaw) = self.yaw.sin_cos();
+        let (sin_pitch, cos_pitch) = self.pitch.sin_cos();
+        Vec3::new(sin_yaw * cos_pitch, sin_pitch, HANDEDNESS.forward().z * cos_yaw * cos_pitch)
+    }
+
+    /// Unit horizontal direction to the right of the look direction.
+    pub fn right(&self) -> Vec3 {
+        let forward = self.forward();
+        let right = match HANDEDNESS {
+            Handedness::Left => Vec3::Y.cross(forward),
+            Handedness::Right => forward.cross(Vec3::Y),
+        };
+        right.normalize_or_zero()
+    }
+
+    /// Moves and orients `camera` for a frame of `dt` seconds.
+    pub fn update(&self, camera: &mut Camera, input: &Input, dt: f32) {
+        let forward = self.forward();
+        let right = self.right();
+        let axis = |positive: &str, negative: &str| input.action_value(positive) - input.action_value(negative);
+        let movement = forward * axis("move_forward", "move_back")
+            + right * axis("move_right", "move_left")
+            + Vec3::Y * axis("jump", "crouch");
+        // Diagonals aren't faster, analog sticks still move slowly
+        camera.set_position(camera.position() + movement.clamp_length_max(1.0) * self.speed * dt);
+        camera.look_at(camera.position() + forward, Vec3::Y);
+    }
+
+    /// Line for the debug text overlay.
+    pub fn speed_text(&self) -> String {
+        format!("Camera speed: {:.1}", self.speed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::input::Binding;
+    use winit::event::{DeviceId, TouchPhase};
+    use winit::keyboard::KeyCode;
+
+    fn wheel(notches: f32) -> WindowEvent {
+        WindowEvent::MouseWheel {
+            device_id: DeviceId::dummy(),
+            delta: MouseScrollDelta::LineDelta(0.0, notches),
+            phase: TouchPhase::Moved,
+        }
+    }
+
+    #[test]
+    fn scrolling_up_multiplies_the_speed_and_clamps_at_the_maximum() {
+        let mut controller = FpsController::new();
+        let start = controller.speed();
+        assert!((controller.adjust_speed(1.0) - start * controller.speed_step).abs() < 1e-5);
+        assert!((controller.adjust_speed(-1.0) - start).abs() < 1e-5);
+
+        assert_eq!(controller.adjust_speed(100.0), controller.max_speed);
+        assert_eq!(controller.adjust_speed(1.0), controller.max_speed);
+        assert_eq!(controller.adjust_speed(-100.0), controller.min_speed);
+    }
+
+    #[test]
+    fn wheel_only_changes_speed_while_the_modifier_is_held() {
+        let mut controller = FpsController::new();
+        let mut input = Input::default();
+        let start = controller.speed();
+        assert!(!controller.handle_event(&wheel(2.0), &input));
+        assert_eq!(controller.speed(), start);
+
+        input.set_held(Binding::Key(KeyCode::ShiftLeft), true);
+        assert!(controller.handle_event(&wheel(2.0), &input));
+        assert!(controller.speed() > start);
+        assert_eq!(controller.speed_text(), format!("Camera speed: {:.1}", controller.speed()));
+    }
+
+    #[test]
+    fn default_look_is_the_crate_forward_with_positive_x_on_the_right() {
+        let mut controller = FpsController::new();
+        assert!(controller.forward().abs_diff_eq(crate::math::Vector3::forward().into(), 1e-6));
+        assert!(controller.right().abs_diff_eq(Vec3::X, 1e-6));
+
+        // Turning right swings the look towards +X, which the camera draws on the right
+        controller.look(100.0, 0.0);
+        assert!(controller.forward().x > 0.0);
+        let mut camera = Camera::new(Vec3::ZERO, 1.0);
+        controller.update(&mut camera, &Input::default(), 0.0);
+        let ahead = camera.position() + controller.forward() + controller.right();
+        assert!(camera.build_view_projection_matrix().project_point3(ahead).x > 0.0);
+    }
+
+    #[test]
+    fn touchpad_pixels_convert_to_notches() {
+        let delta = MouseScrollDelta::PixelDelta(winit::dpi::PhysicalPosition::new(0.0, -80.0));
+        assert_eq!(scroll_notches(&delta), -2.0);
+        assert_eq!(scroll_notches(&MouseScrollDelta::LineDelta(3.0, 1.5)), 1.5);
+    }
+}
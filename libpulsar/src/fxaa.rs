@@ -0,0 +1,203 @@
+use crate::engine::render::config::Labels;
+
+/// Full-screen FXAA pass reading a rendered scene texture and writing the
+/// antialiased result to another target, usually the swapchain.
+pub struct FxaaPass {
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    bind_group: Option<wgpu::BindGroup>,
+    labels: Labels,
+}
+
+impl FxaaPass {
+    pub fn new(device: &wgpu::Device, labels: &Labels, format: wgpu::TextureFormat) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(&labels.label("FXAA Shader")),
+            source: wgpu::ShaderSource::Wgsl(include_str!("fxaa.wgsl").into()),
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+            label: Some(&labels.label("fxaa_bind_group_layout")),
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some(&labels.label("FXAA Pipeline Layout")),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(&labels.label("FXAA Pipeline")),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some(&labels.label("FXAA Sampler")),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        Self {
+            pipeline,
+            bind_group_layout,
+            sampler,
+            bind_group: None,
+            labels: labels.clone(),
+        }
+    }
+
+    /// Points the pass at the texture holding the rendered scene. Must be
+    /// called again whenever that texture is recreated, e.g. on resize.
+    pub fn set_source(&mut self, device: &wgpu::Device, source: &wgpu::TextureView) {
+        self.bind_group = Some(device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(source),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+            ],
+            label: Some(&self.labels.label("fxaa_bind_group")),
+        }));
+    }
+
+    /// Records the pass into `encoder`. Does nothing until `set_source` was called.
+    pub fn run(&self, encoder: &mut wgpu::CommandEncoder, target: &wgpu::TextureView) {
+        let Some(bind_group) = &self.bind_group else {
+            return;
+        };
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some(&self.labels.label("FXAA Pass")),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render_target::RenderTarget;
+    use crate::test_gpu;
+
+    fn clear(device: &wgpu::Device, queue: &wgpu::Queue, view: &wgpu::TextureView, color: wgpu::Color) {
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: None,
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(color),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        queue.submit(Some(encoder.finish()));
+    }
+
+    #[test]
+    fn fxaa_fills_a_same_sized_target_and_keeps_flat_color() {
+        let Some((device, queue)) = test_gpu::device() else {
+            return;
+        };
+        let labels = Labels::default();
+        let config = test_gpu::surface_config(16, 8);
+        let scene = RenderTarget::new(&device, &labels, &config, 16, 8);
+        let output = RenderTarget::new(&device, &labels, &config, 16, 8);
+        clear(&device, &queue, scene.color_view(), wgpu::Color::BLUE);
+        clear(&device, &queue, output.color_view(), wgpu::Color::BLACK);
+
+        let mut fxaa = FxaaPass::new(&device, &labels, config.format);
+        fxaa.set_source(&device, scene.color_view());
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        fxaa.run(&mut encoder, output.color_view());
+        queue.submit(Some(encoder.finish()));
+
+        let pixels = test_gpu::read_pixels(&device, &queue, &output.color_texture.0);
+        assert_eq!(pixels.len(), 16 * 8 * 4);
+        // No edges to smooth, so every pixel keeps the scene's color
+        assert!(pixels.chunks_exact(4).all(|pixel| pixel == [0, 0, 255, 255]), "{pixels:?}");
+    }
+
+    #[test]
+    fn run_without_a_source_records_nothing() {
+        let Some((device, queue)) = test_gpu::device() else {
+            return;
+        };
+        let labels = Labels::default();
+        let config = test_gpu::surface_config(4, 4);
+        let output = RenderTarget::new(&device, &labels, &config, 4, 4);
+        clear(&device, &queue, output.color_view(), wgpu::Color::RED);
+
+        let fxaa = FxaaPass::new(&device, &labels, config.format);
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        fxaa.run(&mut encoder, output.color_view());
+        queue.submit(Some(encoder.finish()));
+
+        let pixels = test_gpu::read_pixels(&device, &queue, &output.color_texture.0);
+        assert!(pixels.chunks_exact(4).all(|pixel| pixel == [255, 0, 0, 255]));
+    }
+}
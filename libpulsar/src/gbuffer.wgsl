@@ -0,0 +1,56 @@
+// Deferred geometry pass: writes surface attributes to the G-buffer instead
+// of shading.
+
+struct CameraUniform {
+    view_proj: mat4x4<f32>,
+};
+
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) color: vec3<f32>,
+    @location(2) normal: vec3<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) world_position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) color: vec3<f32>,
+};
+
+// Vertex colors are authored in sRGB (see `color.rs`); lighting and the sRGB
+// surface both expect linear values.
+fn srgb_to_linear(c: vec3<f32>) -> vec3<f32> {
+    let low = c / 12.92;
+    let high = pow((c + 0.055) / 1.055, vec3<f32>(2.4));
+    return select(high, low, c <= vec3<f32>(0.04045));
+}
+
+@vertex
+fn vs_main(model: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    out.world_position = model.position;
+    out.normal = model.normal;
+    out.color = srgb_to_linear(model.color);
+    out.clip_position = camera.view_proj * vec4<f32>(model.position, 1.0);
+    return out;
+}
+
+struct GBufferOutput {
+    // w = 1 marks pixels covered by geometry; the clear value leaves it at 0
+    @location(0) position: vec4<f32>,
+    @location(1) normal: vec4<f32>,
+    @location(2) albedo: vec4<f32>,
+};
+
+@fragment
+fn fs_main(in: VertexOutput) -> GBufferOutput {
+    var out: GBufferOutput;
+    out.position = vec4<f32>(in.world_position, 1.0);
+    out.normal = vec4<f32>(normalize(in.normal), 0.0);
+    out.albedo = vec4<f32>(in.color, 1.0);
+    return out;
+}
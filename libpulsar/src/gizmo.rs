@@ -0,0 +1,265 @@
+use glam::Vec3;
+
+use crate::camera::Camera;
+use crate::debug_lines::DebugLines;
+use crate::math::{Ray, Vector3};
+
+const RING_SEGMENTS: usize = 48;
+const HOVER_COLOR: [f32; 3] = [1.0, 1.0, 0.0];
+/// How close, relative to the gizmo size, a ray has to pass to grab a handle.
+const PICK_TOLERANCE: f32 = 0.08;
+/// Half size of the scale handle cubes, relative to the gizmo size.
+const SCALE_HANDLE_SIZE: f32 = 0.08;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GizmoMode {
+    #[default]
+    Translate,
+    Rotate,
+    Scale,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GizmoAxis {
+    X,
+    Y,
+    Z,
+}
+
+impl GizmoAxis {
+    pub const ALL: [GizmoAxis; 3] = [GizmoAxis::X, GizmoAxis::Y, GizmoAxis::Z];
+
+    pub fn direction(self) -> Vector3 {
+        match self {
+            GizmoAxis::X => Vector3::right(),
+            GizmoAxis::Y => Vector3::up(),
+            GizmoAxis::Z => Vector3::new(0.0, 0.0, 1.0),
+        }
+    }
+
+    pub fn color(self) -> [f32; 3] {
+        match self {
+            GizmoAxis::X => [1.0, 0.0, 0.0],
+            GizmoAxis::Y => [0.0, 1.0, 0.0],
+            GizmoAxis::Z => [0.0, 0.0, 1.0],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GizmoHandle {
+    Translate(GizmoAxis),
+    Rotate(GizmoAxis),
+    Scale(GizmoAxis),
+}
+
+impl GizmoHandle {
+    pub fn axis(self) -> GizmoAxis {
+        match self {
+            GizmoHandle::Translate(axis) | GizmoHandle::Rotate(axis) | GizmoHandle::Scale(axis) => axis,
+        }
+    }
+}
+
+/// Editor manipulation handles drawn at an actor's origin. The handles keep
+/// the same size on screen regardless of the camera distance.
+#[derive(Debug, Clone)]
+pub struct Gizmo {
+    pub mode: GizmoMode,
+    pub origin: Vector3,
+    /// Length of the handles in pixels.
+    pub size: f32,
+    pub hovered: Option<GizmoHandle>,
+    pub active: Option<GizmoHandle>,
+}
+
+impl Gizmo {
+    pub fn new(origin: Vector3) -> Self {
+        Self {
+            mode: GizmoMode::default(),
+            origin,
+            size: 100.0,
+            hovered: None,
+            active: None,
+        }
+    }
+
+    /// World-space length of the handles for the current camera.
+    pub fn world_size(&self, camera: &Camera, viewport_height: f32) -> f32 {
+        let distance = camera.position().distance(self.origin.into());
+        camera.world_size_of_pixels(self.size, distance, viewport_height)
+    }
+
+    fn handles(&self) -> [GizmoHandle; 3] {
+        GizmoAxis::ALL.map(|axis| match self.mode {
+            GizmoMode::Translate => GizmoHandle::Translate(axis),
+            GizmoMode::Rotate => GizmoHandle::Rotate(axis),
+            GizmoMode::Scale => GizmoHandle::Scale(axis),
+        })
+    }
+
+    fn color(&self, handle: GizmoHandle) -> [f32; 3] {
+        if self.active == Some(handle) || self.hovered == Some(handle) {
+            HOVER_COLOR
+        } else {
+            handle.axis().color()
+        }
+    }
+
+    /// Queues the handles for the current mode into `lines`.
+    pub fn draw(&self, lines: &mut DebugLines, camera: &Camera, viewport_height: f32) {
+        let size = self.world_size(camera, viewport_height);
+        let origin: Vec3 = self.origin.into();
+        for handle in self.handles() {
+            let color = self.color(handle);
+            let axis: Vec3 = handle.axis().direction().into();
+            match handle {
+                GizmoHandle::Translate(_) => {
+                    let tip = origin + axis * size;
+                    lines.line(origin, tip, color);
+                    // Arrow head: four short lines folding back from the tip
+                    let (side_a, side_b) = axis.any_orthonormal_pair();
+                    for side in [side_a, -side_a, side_b, -side_b] {
+                        lines.line(tip, tip - axis * size * 0.15 + side * size * 0.05, color);
+                    }
+                }
+                GizmoHandle::Rotate(_) => {
+                    let (side_a, side_b) = axis.any_orthonormal_pair();
+                    let point = |i: usize| {
+                        let angle = i as f32 / RING_SEGMENTS as f32 * std::f32::consts::TAU;
+                        origin + (side_a * angle.cos() + side_b * angle.sin()) * size
+                    };
+                    for i in 0..RING_SEGMENTS {
+                        lines.line(point(i), point(i + 1), color);
+                    }
+                }
+                GizmoHandle::Scale(_) => {
+                    let tip = origin + axis * size;
+                    lines.line(origin, tip, color);
+                    let half = size * SCALE_HANDLE_SIZE;
+                    for (a, b) in box_edges(tip, half) {
+                        lines.line(a, b, color);
+                    }
+                }
+            }
+        }
+    }
+
+    /// The handle of the current mode hit by `ray`, nearest to the ray origin.
+    pub fn hit_test(&self, ray: &Ray, camera: &Camera, viewport_height: f32) -> Option<GizmoHandle> {
+        let size = self.world_size(camera, viewport_height);
+        let tolerance = size * PICK_TOLERANCE;
+
+        self.handles()
+            .into_iter()
+            .filter_map(|handle| {
+                let axis = handle.axis().direction();
+                let t = match handle {
+                    GizmoHandle::Translate(_) => {
+                        let (t, gap) = ray.closest_to_segment(self.origin, self.origin + axis * size);
+                        (gap <= tolerance).then_some(t)
+                    }
+                    GizmoHandle::Rotate(_) => {
+                        let t = ray.intersect_plane(self.origin, axis)?;
+                        let radius = ray.at(t).distance(&self.origin);
+                        ((radius - size).abs() <= tolerance).then_some(t)
+                    }
+                    GizmoHandle::Scale(_) => {
+                        let radius = size * SCALE_HANDLE_SIZE + tolerance;
+                        ray.intersect_sphere(self.origin + axis * size, radius)
+                    }
+                }?;
+                Some((t, handle))
+            })
+            .min_by(|a, b| a.0.total_cmp(&b.0))
+            .map(|(_, handle)| handle)
+    }
+
+    /// Updates `hovered` from a cursor ray and returns the new value.
+    pub fn hover(&mut self, ray: &Ray, camera: &Camera, viewport_height: f32) -> Option<GizmoHandle> {
+        self.hovered = self.hit_test(ray, camera, viewport_height);
+        self.hovered
+    }
+}
+
+fn box_edges(center: Vec3, half: f32) -> [(Vec3, Vec3); 12] {
+    let corner = |i: usize| {
+        center
+            + Vec3::new(
+                if i & 1 == 0 { -half } else { half },
+                if i & 2 == 0 { -half } else { half },
+                if i & 4 == 0 { -half } else { half },
+            )
+    };
+    [
+        (0, 1), (2, 3), (4, 5), (6, 7),
+        (0, 2), (1, 3), (4, 6), (5, 7),
+        (0, 4), (1, 5), (2, 6), (3, 7),
+    ]
+    .map(|(a, b)| (corner(a), corner(b)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VIEWPORT_HEIGHT: f32 = 600.0;
+
+    fn camera_at(distance: f32) -> Camera {
+        Camera::new(Vec3::new(0.0, 0.0, -distance), 1.0)
+    }
+
+    #[test]
+    fn world_size_grows_with_camera_distance() {
+        let gizmo = Gizmo::new(Vector3::zero());
+        let near = gizmo.world_size(&camera_at(5.0), VIEWPORT_HEIGHT);
+        let far = gizmo.world_size(&camera_at(10.0), VIEWPORT_HEIGHT);
+        assert!((far / near - 2.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn ray_onto_the_x_arrow_selects_the_x_translate_handle() {
+        let camera = camera_at(10.0);
+        let gizmo = Gizmo::new(Vector3::zero());
+        let size = gizmo.world_size(&camera, VIEWPORT_HEIGHT);
+        let ray = Ray::new(Vector3::new(size * 0.5, 10.0, 0.0), Vector3::new(0.0, -1.0, 0.0));
+        assert_eq!(
+            gizmo.hit_test(&ray, &camera, VIEWPORT_HEIGHT),
+            Some(GizmoHandle::Translate(GizmoAxis::X))
+        );
+    }
+
+    #[test]
+    fn ray_onto_a_ring_selects_its_rotate_handle() {
+        let camera = camera_at(10.0);
+        let mut gizmo = Gizmo::new(Vector3::zero());
+        gizmo.mode = GizmoMode::Rotate;
+        let size = gizmo.world_size(&camera, VIEWPORT_HEIGHT);
+        let ray = Ray::new(Vector3::new(size, 10.0, 0.0), Vector3::new(0.0, -1.0, 0.0));
+        assert_eq!(
+            gizmo.hit_test(&ray, &camera, VIEWPORT_HEIGHT),
+            Some(GizmoHandle::Rotate(GizmoAxis::Y))
+        );
+    }
+
+    #[test]
+    fn ray_past_the_handles_selects_nothing() {
+        let camera = camera_at(10.0);
+        let mut gizmo = Gizmo::new(Vector3::zero());
+        let size = gizmo.world_size(&camera, VIEWPORT_HEIGHT);
+        let ray = Ray::new(Vector3::new(size * 3.0, 10.0, size * 3.0), Vector3::new(0.0, -1.0, 0.0));
+        assert_eq!(gizmo.hover(&ray, &camera, VIEWPORT_HEIGHT), None);
+        assert_eq!(gizmo.hovered, None);
+    }
+
+    #[test]
+    fn hovered_handle_is_highlighted() {
+        let camera = camera_at(10.0);
+        let mut gizmo = Gizmo::new(Vector3::zero());
+        let size = gizmo.world_size(&camera, VIEWPORT_HEIGHT);
+        let ray = Ray::new(Vector3::new(size * 0.5, 10.0, 0.0), Vector3::new(0.0, -1.0, 0.0));
+        let hovered = gizmo.hover(&ray, &camera, VIEWPORT_HEIGHT).unwrap();
+        assert_eq!(gizmo.color(hovered), HOVER_COLOR);
+        assert_eq!(gizmo.color(GizmoHandle::Translate(GizmoAxis::Y)), GizmoAxis::Y.color());
+    }
+}
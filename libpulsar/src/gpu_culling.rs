@@ -0,0 +1,348 @@
+use bytemuck::{Pod, Zeroable};
+use glam::{Mat4, Vec3, Vec4};
+use wgpu::util::DeviceExt;
+
+use crate::engine::render::config::Labels;
+
+const WORKGROUP_SIZE: u32 = 64;
+
+/// Bounding sphere of one instance, in world space.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, Pod, Zeroable)]
+pub struct CullInstance {
+    pub center: [f32; 3],
+    pub radius: f32,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+struct CullUniform {
+    planes: [[f32; 4]; 6],
+    instance_count: u32,
+    _padding: [u32; 3],
+}
+
+/// Extracts the six frustum planes (left, right, bottom, top, near, far) from a
+/// view-projection matrix with wgpu's 0..1 depth range. Each plane's normal
+/// points into the frustum and is normalized, so `dot(n, p) + w` is a distance.
+pub fn frustum_planes(view_proj: Mat4) -> [Vec4; 6] {
+    let (r0, r1, r2, r3) = (view_proj.row(0), view_proj.row(1), view_proj.row(2), view_proj.row(3));
+    [r3 + r0, r3 - r0, r3 + r1, r3 - r1, r2, r3 - r2].map(|plane| plane / plane.truncate().length())
+}
+
+/// CPU reference for the test `gpu_culling.wgsl` runs per instance.
+pub fn sphere_in_frustum(planes: &[Vec4; 6], center: Vec3, radius: f32) -> bool {
+    planes.iter().all(|plane| plane.truncate().dot(center) + plane.w >= -radius)
+}
+
+/// Frustum culling on the GPU for scenes where culling every instance on the
+/// CPU is the bottleneck.
+///
+/// `dispatch` writes the indices of visible instances to `visible_buffer` and
+/// their count into an indexed indirect draw, which `draw_indirect` issues. The
+/// vertex shader of the instanced pipeline should bind `visible_buffer` and
+/// look up `visible[instance_index]` to find which instance it is drawing.
+pub struct GpuCuller {
+    pipeline: wgpu::ComputePipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    uniform_buffer: wgpu::Buffer,
+    instance_buffer: wgpu::Buffer,
+    visible_buffer: wgpu::Buffer,
+    indirect_buffer: wgpu::Buffer,
+    capacity: u32,
+    num_instances: u32,
+    labels: Labels,
+}
+
+impl GpuCuller {
+    pub fn new(device: &wgpu::Device, labels: &Labels, capacity: u32) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(&labels.label("GPU Culling Shader")),
+            source: wgpu::ShaderSource::Wgsl(include_str!("gpu_culling.wgsl").into()),
+        });
+
+        let storage = |binding, read_only| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                storage(1, true),
+                storage(2, false),
+                storage(3, false),
+            ],
+            label: Some(&labels.label("gpu_culling_bind_group_layout")),
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some(&labels.label("GPU Culling Pipeline Layout")),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some(&labels.label("GPU Culling Pipeline")),
+            layout: Some(&pipeline_layout),
+            module: &shader,
+            entry_point: Some("cs_main"),
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+            cache: None,
+        });
+
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&labels.label("GPU Culling Uniform Buffer")),
+            contents: bytemuck::cast_slice(&[CullUniform::zeroed()]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let indirect_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&labels.label("GPU Culling Indirect Buffer")),
+            contents: wgpu::util::DrawIndexedIndirectArgs::default().as_bytes(),
+            usage: wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::INDIRECT
+                | wgpu::BufferUsages::COPY_DST
+                | wgpu::BufferUsages::COPY_SRC,
+        });
+
+        let capacity = capacity.max(1);
+        let (instance_buffer, visible_buffer) = Self::create_instance_buffers(device, labels, capacity);
+        let bind_group = Self::create_bind_group(
+            device,
+            labels,
+            &bind_group_layout,
+            &uniform_buffer,
+            &instance_buffer,
+            &visible_buffer,
+            &indirect_buffer,
+        );
+
+        Self {
+            pipeline,
+            bind_group_layout,
+            bind_group,
+            uniform_buffer,
+            instance_buffer,
+            visible_buffer,
+            indirect_buffer,
+            capacity,
+            num_instances: 0,
+            labels: labels.clone(),
+        }
+    }
+
+    fn create_instance_buffers(device: &wgpu::Device, labels: &Labels, capacity: u32) -> (wgpu::Buffer, wgpu::Buffer) {
+        let instance_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(&labels.label("GPU Culling Instance Buffer")),
+            size: capacity as u64 * std::mem::size_of::<CullInstance>() as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let visible_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(&labels.label("GPU Culling Visible Buffer")),
+            size: capacity as u64 * std::mem::size_of::<u32>() as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        (instance_buffer, visible_buffer)
+    }
+
+    fn create_bind_group(
+        device: &wgpu::Device,
+        labels: &Labels,
+        layout: &wgpu::BindGroupLayout,
+        uniform_buffer: &wgpu::Buffer,
+        instance_buffer: &wgpu::Buffer,
+        visible_buffer: &wgpu::Buffer,
+        indirect_buffer: &wgpu::Buffer,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: instance_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: visible_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: indirect_buffer.as_entire_binding(),
+                },
+            ],
+            label: Some(&labels.label("gpu_culling_bind_group")),
+        })
+    }
+
+    /// Uploads the bounding spheres to cull, growing the buffers if needed.
+    pub fn set_instances(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, instances: &[CullInstance]) {
+        let count = instances.len() as u32;
+        if count > self.capacity {
+            self.capacity = count.next_power_of_two();
+            let (instance_buffer, visible_buffer) = Self::create_instance_buffers(device, &self.labels, self.capacity);
+            self.bind_group = Self::create_bind_group(
+                device,
+                &self.labels,
+                &self.bind_group_layout,
+                &self.uniform_buffer,
+                &instance_buffer,
+                &visible_buffer,
+                &self.indirect_buffer,
+            );
+            self.instance_buffer = instance_buffer;
+            self.visible_buffer = visible_buffer;
+        }
+        self.num_instances = count;
+        if !instances.is_empty() {
+            queue.write_buffer(&self.instance_buffer, 0, bytemuck::cast_slice(instances));
+        }
+    }
+
+    /// Records the culling pass. `index_count` is the index count of the mesh
+    /// `draw_indirect` will draw.
+    pub fn dispatch(&self, queue: &wgpu::Queue, encoder: &mut wgpu::CommandEncoder, view_proj: Mat4, index_count: u32) {
+        let uniform = CullUniform {
+            planes: frustum_planes(view_proj).map(|plane| plane.to_array()),
+            instance_count: self.num_instances,
+            _padding: [0; 3],
+        };
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[uniform]));
+
+        // The shader counts visible instances up from zero
+        let args = wgpu::util::DrawIndexedIndirectArgs {
+            index_count,
+            ..Default::default()
+        };
+        queue.write_buffer(&self.indirect_buffer, 0, args.as_bytes());
+
+        let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some(&self.labels.label("GPU Culling Pass")),
+            timestamp_writes: None,
+        });
+        compute_pass.set_pipeline(&self.pipeline);
+        compute_pass.set_bind_group(0, &self.bind_group, &[]);
+        compute_pass.dispatch_workgroups(self.num_instances.div_ceil(WORKGROUP_SIZE), 1, 1);
+    }
+
+    /// Draws the instances that survived the last `dispatch`.
+    pub fn draw_indirect(&self, render_pass: &mut wgpu::RenderPass, mesh: &crate::mesh::Mesh) {
+        let Some(index_buffer) = &mesh.index_buffer else {
+            return;
+        };
+        render_pass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+        render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+        render_pass.draw_indexed_indirect(&self.indirect_buffer, 0);
+    }
+
+    /// Compacted indices of visible instances, for binding in the draw's vertex shader.
+    pub fn visible_buffer(&self) -> &wgpu::Buffer {
+        &self.visible_buffer
+    }
+
+    /// Holds the `DrawIndexedIndirectArgs`; the visible count is at byte offset 4.
+    pub fn indirect_buffer(&self) -> &wgpu::Buffer {
+        &self.indirect_buffer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_gpu;
+
+    fn view_proj() -> Mat4 {
+        let projection = Mat4::perspective_rh(90f32.to_radians(), 1.0, 0.1, 100.0);
+        projection * Mat4::look_at_rh(Vec3::ZERO, Vec3::NEG_Z, Vec3::Y)
+    }
+
+    /// Four instances in front of a camera at the origin looking down -Z,
+    /// then six outside each of its planes.
+    fn instances() -> Vec<CullInstance> {
+        [
+            [0.0, 0.0, -5.0],
+            [1.0, 0.0, -10.0],
+            [-1.0, 1.0, -20.0],
+            [0.0, -2.0, -50.0],
+            [0.0, 0.0, 5.0],
+            [0.0, 0.0, -200.0],
+            [-100.0, 0.0, -5.0],
+            [100.0, 0.0, -5.0],
+            [0.0, 100.0, -5.0],
+            [0.0, -100.0, -5.0],
+        ]
+        .map(|center| CullInstance { center, radius: 0.5 })
+        .to_vec()
+    }
+
+    fn read_u32s(device: &wgpu::Device, queue: &wgpu::Queue, source: &wgpu::Buffer, count: usize) -> Vec<u32> {
+        let size = (count * 4) as wgpu::BufferAddress;
+        let staging = device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        encoder.copy_buffer_to_buffer(source, 0, &staging, 0, size);
+        queue.submit(Some(encoder.finish()));
+        staging.slice(..).map_async(wgpu::MapMode::Read, |result| result.unwrap());
+        let _ = device.poll(wgpu::Maintain::Wait);
+        let data = staging.slice(..).get_mapped_range();
+        bytemuck::cast_slice(&data).to_vec()
+    }
+
+    #[test]
+    fn cpu_reference_keeps_spheres_inside_or_straddling_the_frustum() {
+        let planes = frustum_planes(view_proj());
+        let visible = instances()
+            .iter()
+            .filter(|instance| sphere_in_frustum(&planes, instance.center.into(), instance.radius))
+            .count();
+        assert_eq!(visible, 4);
+        // Centered just past the near plane, but reaching back into the frustum
+        assert!(sphere_in_frustum(&planes, Vec3::new(0.0, 0.0, 0.1), 0.5));
+    }
+
+    #[test]
+    fn culling_pass_counts_the_visible_instances() {
+        let Some((device, queue)) = test_gpu::device() else {
+            return;
+        };
+        let mut culler = GpuCuller::new(&device, &Labels::default(), 4);
+        culler.set_instances(&device, &queue, &instances());
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        culler.dispatch(&queue, &mut encoder, view_proj(), 36);
+        queue.submit(Some(encoder.finish()));
+
+        // DrawIndexedIndirectArgs: index_count, instance_count, ...
+        let args = read_u32s(&device, &queue, culler.indirect_buffer(), 5);
+        assert_eq!(args[0], 36);
+        assert_eq!(args[1], 4);
+
+        let mut visible = read_u32s(&device, &queue, culler.visible_buffer(), 4);
+        visible.sort_unstable();
+        assert_eq!(visible, vec![0, 1, 2, 3]);
+    }
+}
@@ -0,0 +1,52 @@
+// Frustum culls per-instance bounding spheres and appends the survivors to a
+// compacted index list, bumping the instance count of an indexed indirect draw.
+
+struct CullUniform {
+    // left, right, bottom, top, near, far; xyz = inward normal, w = distance
+    planes: array<vec4<f32>, 6>,
+    instance_count: u32,
+    padding0: u32,
+    padding1: u32,
+    padding2: u32,
+};
+
+// Layout of `DrawIndexedIndirectArgs`
+struct DrawIndexedIndirect {
+    index_count: u32,
+    instance_count: atomic<u32>,
+    first_index: u32,
+    base_vertex: i32,
+    first_instance: u32,
+};
+
+@group(0) @binding(0)
+var<uniform> cull: CullUniform;
+
+// xyz = center, w = radius
+@group(0) @binding(1)
+var<storage, read> spheres: array<vec4<f32>>;
+
+@group(0) @binding(2)
+var<storage, read_write> visible: array<u32>;
+
+@group(0) @binding(3)
+var<storage, read_write> draw_args: DrawIndexedIndirect;
+
+@compute @workgroup_size(64)
+fn cs_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let index = id.x;
+    if (index >= cull.instance_count) {
+        return;
+    }
+
+    let sphere = spheres[index];
+    for (var i = 0u; i < 6u; i = i + 1u) {
+        let plane = cull.planes[i];
+        if (dot(plane.xyz, sphere.xyz) + plane.w < -sphere.w) {
+            return;
+        }
+    }
+
+    let slot = atomicAdd(&draw_args.instance_count, 1u);
+    visible[slot] = index;
+}
@@ -1,7 +1,52 @@
-pub mod engine;
-pub mod renderer;
-pub mod mesh;
+pub mod animation;
+pub mod assets;
+pub mod axes_overlay;
+pub mod base;
+pub mod billboard;
+pub mod bloom;
 pub mod camera;
+pub mod cascaded_shadow;
+pub mod color;
+pub mod debug_draw;
+pub mod debug_lines;
+pub mod decal;
+pub mod deferred;
+pub mod depth_resolve;
+pub mod draw_list;
+pub mod engine;
+pub mod fps_controller;
+pub mod fxaa;
+pub mod gizmo;
+pub mod gpu_culling;
+pub mod lod;
+pub mod logging;
+pub mod material;
 pub mod math;
+pub mod mesh;
+pub mod outline;
+pub mod picking;
+pub mod pixel_scale;
+pub mod render_graph;
+pub mod render_queue;
+pub mod render_scale;
+pub mod render_target;
+pub mod renderer;
+pub mod resources;
+pub mod scatter;
+pub mod screenshot;
+pub mod shader;
+pub mod shadow;
+pub mod simplify;
+pub mod skinning;
+pub mod ssao;
+pub mod streaming;
+pub mod texture_atlas;
+pub mod tonemap;
+pub mod uniform_pool;
+
+#[cfg(test)]
+mod test_gpu;
+#[cfg(test)]
+mod test_log;
 
-pub mod base;
\ No newline at end of file
+pub use logging::{init_logging, LogFormat};
\ No newline at end of file
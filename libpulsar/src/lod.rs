@@ -0,0 +1,189 @@
+use glam::Vec3;
+use thiserror::Error;
+
+use crate::mesh::Mesh;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum LodError {
+    #[error("A Lod needs at least one level")]
+    NoLevels,
+    #[error("Level {index} does not reach further than the level before it")]
+    UnorderedLevels { index: usize },
+}
+
+/// One detail level of a `Lod`.
+pub struct LodLevel<M = Mesh> {
+    pub mesh: M,
+    /// This level is used while the camera is closer than this distance. The
+    /// last level's value is ignored; it covers everything further away.
+    pub max_distance: f32,
+}
+
+/// Several versions of a mesh, from most to least detailed, switched by
+/// distance to the camera. `Scene::lod_meshes` does the switching for every
+/// rendered actor that has one.
+///
+/// To keep a mesh from flickering between two levels when the camera sits
+/// near a boundary, a switch only happens once the distance is `hysteresis`
+/// (a fraction of the boundary distance) past it.
+pub struct Lod<M = Mesh> {
+    levels: Vec<LodLevel<M>>,
+    pub hysteresis: f32,
+    current: usize,
+}
+
+impl<M> Lod<M> {
+    /// `levels` must be ordered by strictly increasing `max_distance` and not
+    /// be empty.
+    pub fn new(levels: Vec<LodLevel<M>>) -> Result<Self, LodError> {
+        if levels.is_empty() {
+            return Err(LodError::NoLevels);
+        }
+        // The last level's distance is ignored, so it isn't checked either
+        let bounded = &levels[..levels.len() - 1];
+        if let Some(before) = bounded.windows(2).position(|pair| pair[1].max_distance <= pair[0].max_distance) {
+            return Err(LodError::UnorderedLevels { index: before + 1 });
+        }
+        Ok(Self {
+            levels,
+            hysteresis: 0.1,
+            current: 0,
+        })
+    }
+
+    pub fn with_hysteresis(mut self, hysteresis: f32) -> Self {
+        self.hysteresis = hysteresis.max(0.0);
+        self
+    }
+
+    pub fn levels(&self) -> &[LodLevel<M>] {
+        &self.levels
+    }
+
+    /// Index of the level currently in use.
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
+    /// Level to use at `distance` when `current` was used last frame.
+    pub fn select(&self, distance: f32, current: usize) -> usize {
+        let last = self.levels.len() - 1;
+        let target = self
+            .levels
+            .iter()
+            .position(|level| distance < level.max_distance)
+            .unwrap_or(last);
+        if current > last {
+            return target;
+        }
+
+        // Step back one level if we're still inside the band of the boundary
+        // nearest to the target
+        if target > current {
+            let boundary = self.levels[target - 1].max_distance;
+            if distance > boundary * (1.0 + self.hysteresis) {
+                target
+            } else {
+                target - 1
+            }
+        } else if target < current {
+            let boundary = self.levels[target].max_distance;
+            if distance < boundary * (1.0 - self.hysteresis) {
+                target
+            } else {
+                target + 1
+            }
+        } else {
+            target
+        }
+    }
+
+    /// Picks the level for an object at `position` seen from `camera_position`
+    /// and returns its mesh.
+    pub fn update(&mut self, camera_position: Vec3, position: Vec3) -> &M {
+        self.current = self.select(camera_position.distance(position), self.current);
+        &self.levels[self.current].mesh
+    }
+
+    pub fn mesh(&self) -> &M {
+        &self.levels[self.current].mesh
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Levels switching at 10 and 100 units, with a 10% hysteresis band.
+    fn lod() -> Lod<&'static str> {
+        Lod::new(vec![
+            LodLevel { mesh: "high", max_distance: 10.0 },
+            LodLevel { mesh: "medium", max_distance: 100.0 },
+            LodLevel { mesh: "low", max_distance: f32::INFINITY },
+        ])
+        .unwrap()
+    }
+
+    fn at(lod: &mut Lod<&'static str>, distance: f32) -> usize {
+        lod.update(Vec3::ZERO, Vec3::new(0.0, 0.0, distance));
+        lod.current()
+    }
+
+    #[test]
+    fn distance_picks_the_matching_level() {
+        assert_eq!(at(&mut lod(), 5.0), 0);
+        assert_eq!(at(&mut lod(), 50.0), 1);
+        assert_eq!(at(&mut lod(), 500.0), 2);
+    }
+
+    #[test]
+    fn switching_away_waits_until_past_the_band() {
+        let mut lod = lod();
+        assert_eq!(at(&mut lod, 5.0), 0);
+        // Past the 10 unit boundary but inside the band up to 11
+        assert_eq!(at(&mut lod, 10.5), 0);
+        assert_eq!(at(&mut lod, 11.5), 1);
+        assert_eq!(*lod.mesh(), "medium");
+    }
+
+    #[test]
+    fn switching_back_waits_until_past_the_band() {
+        let mut lod = lod();
+        assert_eq!(at(&mut lod, 50.0), 1);
+        // Back under 10 but inside the band down to 9
+        assert_eq!(at(&mut lod, 9.5), 1);
+        assert_eq!(at(&mut lod, 8.5), 0);
+    }
+
+    #[test]
+    fn large_jumps_skip_levels() {
+        let mut lod = lod();
+        assert_eq!(at(&mut lod, 5.0), 0);
+        assert_eq!(at(&mut lod, 500.0), 2);
+        assert_eq!(at(&mut lod, 5.0), 0);
+    }
+
+    #[test]
+    fn empty_or_unordered_levels_are_rejected() {
+        assert_eq!(Lod::<()>::new(Vec::new()).err(), Some(LodError::NoLevels));
+        let levels = |distances: &[f32]| {
+            distances.iter().map(|&max_distance| LodLevel { mesh: (), max_distance }).collect::<Vec<_>>()
+        };
+        assert_eq!(
+            Lod::new(levels(&[10.0, 5.0, f32::INFINITY])).err(),
+            Some(LodError::UnorderedLevels { index: 1 })
+        );
+        assert_eq!(Lod::new(levels(&[10.0, 10.0, 0.0])).err(), Some(LodError::UnorderedLevels { index: 1 }));
+        // Only the last level may have any distance
+        assert!(Lod::new(levels(&[10.0, 20.0, 0.0])).is_ok());
+        assert!(Lod::new(levels(&[0.0])).is_ok());
+    }
+
+    #[test]
+    fn without_hysteresis_the_boundary_switches_immediately() {
+        let mut lod = lod().with_hysteresis(0.0);
+        assert_eq!(at(&mut lod, 5.0), 0);
+        assert_eq!(at(&mut lod, 10.5), 1);
+        assert_eq!(at(&mut lod, 9.5), 0);
+    }
+}
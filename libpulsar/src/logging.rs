@@ -0,0 +1,95 @@
+use std::io::Write;
+use std::str::FromStr;
+
+use log::{Record, SetLoggerError};
+
+/// Environment variable `LogFormat::from_env` reads, e.g. `PULSAR_LOG_FORMAT=json`.
+pub const LOG_FORMAT_ENV: &str = "PULSAR_LOG_FORMAT";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LogFormat {
+    /// env_logger's human-readable lines.
+    #[default]
+    Pretty,
+    /// One JSON object per line with `timestamp`, `level`, `target` and
+    /// `message` fields, for CI and telemetry tooling.
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "pretty" => Ok(LogFormat::Pretty),
+            "json" => Ok(LogFormat::Json),
+            other => Err(format!("Unknown log format \"{other}\", expected \"pretty\" or \"json\"")),
+        }
+    }
+}
+
+impl LogFormat {
+    /// Format named by `PULSAR_LOG_FORMAT`, or `Pretty` if it is unset or invalid.
+    pub fn from_env() -> Self {
+        std::env::var(LOG_FORMAT_ENV)
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or_default()
+    }
+}
+
+/// The JSON line `LogFormat::Json` writes for `record`, without the newline.
+pub fn json_line(timestamp: &str, record: &Record) -> String {
+    serde_json::json!({
+        "timestamp": timestamp,
+        "level": record.level().as_str(),
+        "target": record.target(),
+        "message": record.args().to_string(),
+    })
+    .to_string()
+}
+
+/// Installs the global logger. Filtering still follows `RUST_LOG`. Fails if
+/// a logger was already installed.
+pub fn init_logging(format: LogFormat) -> Result<(), SetLoggerError> {
+    let mut builder = env_logger::Builder::from_env(env_logger::Env::default());
+    if format == LogFormat::Json {
+        builder.format(|buf, record| {
+            let timestamp = buf.timestamp_millis().to_string();
+            writeln!(buf, "{}", json_line(&timestamp, record))
+        });
+    }
+    builder.try_init()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use log::Level;
+
+    #[test]
+    fn json_line_is_parseable_with_every_field() {
+        let line = json_line(
+            "2026-01-02T03:04:05.678Z",
+            &Record::builder()
+                .args(format_args!("Frame {} took \"{}\" ms", 42, 16.5))
+                .level(Level::Warn)
+                .target("libpulsar::engine::stats")
+                .build(),
+        );
+        assert!(!line.contains('\n'));
+        let value: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(value["timestamp"], "2026-01-02T03:04:05.678Z");
+        assert_eq!(value["level"], "WARN");
+        assert_eq!(value["target"], "libpulsar::engine::stats");
+        assert_eq!(value["message"], "Frame 42 took \"16.5\" ms");
+    }
+
+    #[test]
+    fn formats_parse_case_insensitively_and_default_to_pretty() {
+        assert_eq!("JSON".parse(), Ok(LogFormat::Json));
+        assert_eq!("pretty".parse(), Ok(LogFormat::Pretty));
+        assert!("yaml".parse::<LogFormat>().is_err());
+        assert_eq!(LogFormat::default(), LogFormat::Pretty);
+    }
+}
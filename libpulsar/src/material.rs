@@ -0,0 +1,614 @@
+use std::ops::RangeInclusive;
+use std::sync::Arc;
+
+use bytemuck::{Pod, Zeroable};
+use glam::Vec3;
+use thiserror::Error;
+
+use crate::color::{linear_to_srgb, srgb_to_linear};
+use crate::engine::render::config::Labels;
+use crate::resources::{ResourceCategory, Tracked};
+
+const BYTES_PER_PIXEL: usize = 4;
+
+/// Highest anisotropic filtering level wgpu accepts.
+pub const MAX_ANISOTROPY: u16 = 16;
+/// Mip bias beyond this picks mips so far off that the texture is either a
+/// smear or pure aliasing.
+pub const LOD_BIAS_RANGE: RangeInclusive<f32> = -4.0..=4.0;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum SamplerError {
+    #[error("LOD bias {0} is outside -4..=4")]
+    LodBiasOutOfRange(f32),
+    #[error("Anisotropy {0} is outside 1..=16")]
+    AnisotropyOutOfRange(u16),
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum TextureError {
+    #[error("Expected {expected} bytes of RGBA8 data for the texture, got {actual}")]
+    SizeMismatch { expected: usize, actual: usize },
+    #[error("Texture has zero width or height")]
+    Empty,
+}
+
+/// Filtering controls for a material's textures.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SamplerSettings {
+    /// Anisotropic filtering level, 1 to disable it.
+    pub anisotropy: u16,
+    /// Added to the mip level the hardware picks: negative is sharper,
+    /// positive blurrier. wgpu samplers have no bias, so this reaches the
+    /// shader through `MaterialUniform::lod_bias` instead.
+    pub lod_bias: f32,
+}
+
+impl Default for SamplerSettings {
+    fn default() -> Self {
+        Self {
+            anisotropy: 1,
+            lod_bias: 0.0,
+        }
+    }
+}
+
+impl SamplerSettings {
+    pub fn new(anisotropy: u16, lod_bias: f32) -> Result<Self, SamplerError> {
+        let settings = Self { anisotropy, lod_bias };
+        settings.validate()?;
+        Ok(settings)
+    }
+
+    pub fn validate(&self) -> Result<(), SamplerError> {
+        if !LOD_BIAS_RANGE.contains(&self.lod_bias) {
+            return Err(SamplerError::LodBiasOutOfRange(self.lod_bias));
+        }
+        if !(1..=MAX_ANISOTROPY).contains(&self.anisotropy) {
+            return Err(SamplerError::AnisotropyOutOfRange(self.anisotropy));
+        }
+        Ok(())
+    }
+
+    /// Trilinear sampler with these settings. Anisotropy requires linear
+    /// filtering everywhere, which this always uses.
+    pub fn descriptor<'a>(&self, label: Option<&'a str>, address_mode: wgpu::AddressMode) -> wgpu::SamplerDescriptor<'a> {
+        wgpu::SamplerDescriptor {
+            label,
+            address_mode_u: address_mode,
+            address_mode_v: address_mode,
+            address_mode_w: address_mode,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            anisotropy_clamp: self.anisotropy.clamp(1, MAX_ANISOTROPY),
+            ..Default::default()
+        }
+    }
+}
+
+/// Direction a surface reflects the eye ray into. `view` points from the
+/// surface towards the eye; matches `reflection_dir` in `environment.wgsl`.
+pub fn reflection_vector(view: Vec3, normal: Vec3) -> Vec3 {
+    let incident = -view;
+    incident - 2.0 * incident.dot(normal) * normal
+}
+
+/// A sampled RGBA8 sRGB texture with a full mip chain, so the material's
+/// LOD bias has coarser levels to pick from.
+pub struct Texture {
+    pub texture: Tracked<wgpu::Texture>,
+    pub view: wgpu::TextureView,
+    pub sampler: wgpu::Sampler,
+}
+
+impl Texture {
+    pub fn from_rgba8(
+        device: &wgpu::Device,
+        labels: &Labels,
+        queue: &wgpu::Queue,
+        width: u32,
+        height: u32,
+        rgba: &[u8],
+        settings: &SamplerSettings,
+    ) -> Result<Self, TextureError> {
+        if width == 0 || height == 0 {
+            return Err(TextureError::Empty);
+        }
+        let expected = width as usize * height as usize * BYTES_PER_PIXEL;
+        if rgba.len() != expected {
+            return Err(TextureError::SizeMismatch {
+                expected,
+                actual: rgba.len(),
+            });
+        }
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        };
+        let mip_count = size.max_mips(wgpu::TextureDimension::D2);
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(&labels.label("Texture")),
+            size,
+            mip_level_count: mip_count,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        let mut level = rgba.to_vec();
+        for mip in 0..mip_count {
+            let level_size = size.mip_level_size(mip, wgpu::TextureDimension::D2);
+            queue.write_texture(
+                wgpu::TexelCopyTextureInfo {
+                    texture: &texture,
+                    mip_level: mip,
+                    origin: wgpu::Origin3d::ZERO,
+                    aspect: wgpu::TextureAspect::All,
+                },
+                &level,
+                wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(level_size.width * BYTES_PER_PIXEL as u32),
+                    rows_per_image: Some(level_size.height),
+                },
+                level_size,
+            );
+            if mip + 1 < mip_count {
+                level = downsample(&level, level_size.width, level_size.height);
+            }
+        }
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler_label = labels.label("Texture Sampler");
+        let sampler = device.create_sampler(&settings.descriptor(Some(&sampler_label), wgpu::AddressMode::Repeat));
+        Ok(Self {
+            texture: Tracked::texture(texture, ResourceCategory::Texture),
+            view,
+            sampler,
+        })
+    }
+
+    /// 1x1 opaque white. Bound in place of a missing texture, it leaves
+    /// vertex colors and the base color unchanged.
+    pub fn white(device: &wgpu::Device, labels: &Labels, queue: &wgpu::Queue) -> Self {
+        Self::from_rgba8(device, labels, queue, 1, 1, &[255; BYTES_PER_PIXEL], &SamplerSettings::default())
+            .expect("a single white pixel is valid RGBA8")
+    }
+}
+
+/// Layout of a material at group 1 of the forward pipeline: the
+/// `MaterialUniform`, the base texture and its sampler, then the environment
+/// cubemap and its sampler.
+pub fn material_bind_group_layout(device: &wgpu::Device, labels: &Labels) -> wgpu::BindGroupLayout {
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        entries: &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 2,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 3,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    view_dimension: wgpu::TextureViewDimension::Cube,
+                    multisampled: false,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 4,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None,
+            },
+        ],
+        label: Some(&labels.label("material_bind_group_layout")),
+    })
+}
+
+/// Cubemap sampled for reflections, usually the skybox.
+///
+/// Lacking a proper prefiltered map, the mip chain is built with a box filter
+/// and rough materials simply sample blurrier mips.
+pub struct EnvironmentMap {
+    pub texture: Tracked<wgpu::Texture>,
+    pub view: wgpu::TextureView,
+    pub sampler: wgpu::Sampler,
+    mip_count: u32,
+}
+
+impl EnvironmentMap {
+    /// Creates a cubemap from six square RGBA8 sRGB faces of `size` pixels, in
+    /// +X, -X, +Y, -Y, +Z, -Z order.
+    pub fn from_faces(
+        device: &wgpu::Device,
+        labels: &Labels,
+        queue: &wgpu::Queue,
+        size: u32,
+        faces: [&[u8]; 6],
+    ) -> Self {
+        let size = size.max(1);
+        let mip_count = u32::BITS - size.leading_zeros();
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(&labels.label("Environment Map")),
+            size: wgpu::Extent3d {
+                width: size,
+                height: size,
+                depth_or_array_layers: 6,
+            },
+            mip_level_count: mip_count,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+
+        for (layer, face) in faces.into_iter().enumerate() {
+            assert_eq!(
+                face.len(),
+                size as usize * size as usize * BYTES_PER_PIXEL,
+                "environment map face {layer} is not {size}x{size} RGBA8"
+            );
+            let mut level = face.to_vec();
+            let mut level_size = size;
+            for mip in 0..mip_count {
+                queue.write_texture(
+                    wgpu::TexelCopyTextureInfo {
+                        texture: &texture,
+                        mip_level: mip,
+                        origin: wgpu::Origin3d {
+                            x: 0,
+                            y: 0,
+                            z: layer as u32,
+                        },
+                        aspect: wgpu::TextureAspect::All,
+                    },
+                    &level,
+                    wgpu::TexelCopyBufferLayout {
+                        offset: 0,
+                        bytes_per_row: Some(level_size * BYTES_PER_PIXEL as u32),
+                        rows_per_image: Some(level_size),
+                    },
+                    wgpu::Extent3d {
+                        width: level_size,
+                        height: level_size,
+                        depth_or_array_layers: 1,
+                    },
+                );
+                if level_size > 1 {
+                    level = downsample(&level, level_size, level_size);
+                    level_size /= 2;
+                }
+            }
+        }
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some(&labels.label("Environment Map View")),
+            dimension: Some(wgpu::TextureViewDimension::Cube),
+            ..Default::default()
+        });
+        let sampler = Self::create_sampler(device, labels, &SamplerSettings::default());
+
+        Self {
+            texture: Tracked::texture(texture, ResourceCategory::Texture),
+            view,
+            sampler,
+            mip_count,
+        }
+    }
+
+    /// 1x1 black cubemap. Bound for materials without an environment map,
+    /// which the shader skips since their `env_mip_count` is zero.
+    pub fn black(device: &wgpu::Device, labels: &Labels, queue: &wgpu::Queue) -> Self {
+        let face = [0, 0, 0, 255];
+        Self::from_faces(device, labels, queue, 1, [&face; 6])
+    }
+
+    fn create_sampler(device: &wgpu::Device, labels: &Labels, settings: &SamplerSettings) -> wgpu::Sampler {
+        let label = labels.label("Environment Map Sampler");
+        device.create_sampler(&settings.descriptor(Some(&label), wgpu::AddressMode::ClampToEdge))
+    }
+
+    /// Replaces the sampler, e.g. to raise anisotropy. Bind groups holding the
+    /// old sampler have to be recreated.
+    pub fn set_sampler(&mut self, device: &wgpu::Device, labels: &Labels, settings: &SamplerSettings) {
+        self.sampler = Self::create_sampler(device, labels, settings);
+    }
+
+    pub fn mip_count(&self) -> u32 {
+        self.mip_count
+    }
+
+    /// Mip level `sample_environment` reads for the given roughness.
+    pub fn mip_for_roughness(&self, roughness: f32) -> f32 {
+        roughness.clamp(0.0, 1.0) * self.mip_count.saturating_sub(1) as f32
+    }
+}
+
+/// Averages 2x2 blocks of an RGBA8 sRGB image into the next mip level. On an
+/// odd side the last block widens to 3 texels so the edge still contributes,
+/// and a side that is already 1 pixel stays 1 pixel. Color is averaged in
+/// linear space so mips don't darken; alpha is already linear.
+fn downsample(pixels: &[u8], width: u32, height: u32) -> Vec<u8> {
+    let (width, height) = (width as usize, height as usize);
+    let (half_width, half_height) = ((width / 2).max(1), (height / 2).max(1));
+    // Source texels under output texel `i` of `half` along a side of `len`
+    let block = |i: usize, half: usize, len: usize| 2 * i..if i + 1 == half { len } else { 2 * i + 2 };
+    let mut out = vec![0; half_width * half_height * BYTES_PER_PIXEL];
+    for y in 0..half_height {
+        for x in 0..half_width {
+            let mut sum = [0.0; BYTES_PER_PIXEL];
+            let mut count = 0.0;
+            for source_y in block(y, half_height, height) {
+                for source_x in block(x, half_width, width) {
+                    let texel = &pixels[(source_y * width + source_x) * BYTES_PER_PIXEL..][..BYTES_PER_PIXEL];
+                    for (channel, &value) in texel.iter().enumerate() {
+                        let value = value as f32 / 255.0;
+                        sum[channel] += if channel < 3 { srgb_to_linear(value) } else { value };
+                    }
+                    count += 1.0;
+                }
+            }
+            let texel = &mut out[(y * half_width + x) * BYTES_PER_PIXEL..][..BYTES_PER_PIXEL];
+            for (channel, value) in texel.iter_mut().enumerate() {
+                let average = sum[channel] / count;
+                let encoded = if channel < 3 { linear_to_srgb(average) } else { average };
+                *value = (encoded * 255.0).round() as u8;
+            }
+        }
+    }
+    out
+}
+
+/// Refers to a material registered with `Renderer::add_material`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MaterialId(pub(crate) usize);
+
+/// Surface parameters for lit shaders.
+#[derive(Clone)]
+pub struct Material {
+    pub base_color: [f32; 4],
+    /// 0 for dielectrics, 1 for metals.
+    pub metallic: f32,
+    /// 0 is a perfect mirror; higher values blur reflections.
+    pub roughness: f32,
+    /// Environment reflected by the surface; `None` disables reflections.
+    pub env_map: Option<Arc<EnvironmentMap>>,
+    /// Filtering for the material's textures; its LOD bias is applied in
+    /// the shader.
+    pub sampler: SamplerSettings,
+}
+
+impl Material {
+    pub fn new(base_color: [f32; 4], metallic: f32, roughness: f32) -> Self {
+        Self {
+            base_color,
+            metallic: metallic.clamp(0.0, 1.0),
+            roughness: roughness.clamp(0.0, 1.0),
+            env_map: None,
+            sampler: SamplerSettings::default(),
+        }
+    }
+
+    pub fn with_env_map(mut self, env_map: Arc<EnvironmentMap>) -> Self {
+        self.env_map = Some(env_map);
+        self
+    }
+
+    pub fn with_sampler(mut self, sampler: SamplerSettings) -> Result<Self, SamplerError> {
+        sampler.validate()?;
+        self.sampler = sampler;
+        Ok(self)
+    }
+
+    /// Binds this material's uniform (in `buffer`) with `texture` and its
+    /// environment map for the forward pipeline. Pass `Texture::white` for
+    /// untextured materials; `fallback_env` (usually `EnvironmentMap::black`)
+    /// is bound when the material has no environment map.
+    pub fn create_bind_group(
+        &self,
+        device: &wgpu::Device,
+        labels: &Labels,
+        layout: &wgpu::BindGroupLayout,
+        buffer: &wgpu::Buffer,
+        texture: &Texture,
+        fallback_env: &EnvironmentMap,
+    ) -> wgpu::BindGroup {
+        let env = self.env_map.as_deref().unwrap_or(fallback_env);
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(&texture.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::TextureView(&env.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: wgpu::BindingResource::Sampler(&env.sampler),
+                },
+            ],
+            label: Some(&labels.label("material_bind_group")),
+        })
+    }
+
+    pub fn uniform(&self) -> MaterialUniform {
+        MaterialUniform {
+            base_color: self.base_color,
+            metallic: self.metallic,
+            roughness: self.roughness,
+            env_mip_count: self.env_map.as_ref().map_or(0.0, |env| env.mip_count() as f32),
+            lod_bias: self.sampler.lod_bias,
+        }
+    }
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Material::new([1.0, 1.0, 1.0, 1.0], 0.0, 0.5)
+    }
+}
+
+/// GPU layout of a `Material`. `env_mip_count` is zero without an env map;
+/// `lod_bias` is added to the mip level of every texture sample.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+pub struct MaterialUniform {
+    pub base_color: [f32; 4],
+    pub metallic: f32,
+    pub roughness: f32,
+    pub env_mip_count: f32,
+    pub lod_bias: f32,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_gpu;
+
+    fn assert_near(actual: Vec3, expected: Vec3) {
+        assert!(actual.abs_diff_eq(expected, 1e-5), "{actual} != {expected}");
+    }
+
+    #[test]
+    fn head_on_view_reflects_back_at_the_eye() {
+        assert_near(reflection_vector(Vec3::Z, Vec3::Z), Vec3::Z);
+    }
+
+    #[test]
+    fn grazing_view_mirrors_about_the_normal() {
+        let view = Vec3::new(-1.0, 1.0, 0.0).normalize();
+        assert_near(reflection_vector(view, Vec3::Y), Vec3::new(1.0, 1.0, 0.0).normalize());
+    }
+
+    #[test]
+    fn reflection_keeps_length_and_angle_to_normal() {
+        let view = Vec3::new(0.3, 0.8, -0.5).normalize();
+        let normal = Vec3::new(0.0, 1.0, 1.0).normalize();
+        let reflected = reflection_vector(view, normal);
+        assert!((reflected.length() - 1.0).abs() < 1e-5);
+        assert!((reflected.dot(normal) - view.dot(normal)).abs() < 1e-5);
+    }
+
+    #[test]
+    fn sampler_settings_reject_out_of_range_values() {
+        assert!(SamplerSettings::new(16, -4.0).is_ok());
+        assert!(SamplerSettings::new(1, 4.0).is_ok());
+        assert_eq!(SamplerSettings::new(4, 4.5), Err(SamplerError::LodBiasOutOfRange(4.5)));
+        assert!(SamplerSettings::new(4, f32::NAN).is_err());
+        assert_eq!(SamplerSettings::new(0, 0.0), Err(SamplerError::AnisotropyOutOfRange(0)));
+        assert_eq!(SamplerSettings::new(17, 0.0), Err(SamplerError::AnisotropyOutOfRange(17)));
+    }
+
+    #[test]
+    fn sampler_descriptor_carries_the_anisotropy_with_linear_filtering() {
+        let settings = SamplerSettings::new(8, -1.0).unwrap();
+        let descriptor = settings.descriptor(Some("Test Sampler"), wgpu::AddressMode::Repeat);
+        assert_eq!(descriptor.anisotropy_clamp, 8);
+        assert_eq!(descriptor.address_mode_u, wgpu::AddressMode::Repeat);
+        assert_eq!(descriptor.mipmap_filter, wgpu::FilterMode::Linear);
+        assert_eq!(descriptor.min_filter, wgpu::FilterMode::Linear);
+        assert_eq!(descriptor.mag_filter, wgpu::FilterMode::Linear);
+    }
+
+    #[test]
+    fn configured_lod_bias_reaches_the_material_uniform() {
+        let sampler = SamplerSettings::new(1, -1.5).unwrap();
+        let material = Material::default().with_sampler(sampler).unwrap();
+        assert_eq!(material.uniform().lod_bias, -1.5);
+        assert_eq!(Material::default().uniform().lod_bias, 0.0);
+
+        let invalid = SamplerSettings { anisotropy: 1, lod_bias: 9.0 };
+        assert!(Material::default().with_sampler(invalid).is_err());
+    }
+
+    #[test]
+    fn downsampling_averages_blocks_in_linear_space() {
+        let gray = |value: u8| [value, value, value, 255];
+        // Half black, half white is linear 0.5, which is sRGB 188, not 128
+        let wide: Vec<u8> = [0, 255, 255, 0].into_iter().flat_map(gray).collect();
+        assert_eq!(downsample(&wide, 4, 1), [gray(188), gray(188)].concat());
+
+        let square: Vec<u8> = [40, 40, 80, 120].into_iter().flat_map(gray).collect();
+        assert_eq!(downsample(&square, 2, 2), gray(79));
+
+        // Alpha is linear and averages directly
+        assert_eq!(downsample(&[0, 0, 0, 0, 0, 0, 0, 255], 2, 1), [0, 0, 0, 128]);
+    }
+
+    #[test]
+    fn downsampling_odd_sides_keeps_the_edge_texels() {
+        let gray = |value: u8| [value, value, value, 255];
+        let odd: Vec<u8> = [0, 0, 255].into_iter().flat_map(gray).collect();
+        assert_eq!(downsample(&odd, 3, 1), gray(156));
+        let tall: Vec<u8> = [0, 0, 0, 0, 255].into_iter().flat_map(gray).collect();
+        assert_eq!(downsample(&tall, 1, 5), [gray(0), gray(156)].concat());
+        assert_eq!(downsample(&gray(77), 1, 1), gray(77));
+    }
+
+    #[test]
+    fn textures_get_a_full_mip_chain() {
+        let Some((device, queue)) = test_gpu::device() else {
+            return;
+        };
+        let labels = Labels::default();
+        let texture =
+            Texture::from_rgba8(&device, &labels, &queue, 8, 2, &[255; 8 * 2 * 4], &Default::default()).unwrap();
+        assert_eq!(texture.texture.mip_level_count(), 4);
+        assert_eq!(Texture::white(&device, &labels, &queue).texture.mip_level_count(), 1);
+    }
+
+    #[test]
+    fn texture_data_of_the_wrong_size_is_rejected() {
+        let Some((device, queue)) = test_gpu::device() else {
+            return;
+        };
+        let labels = Labels::default();
+        let settings = SamplerSettings::default();
+        assert_eq!(
+            Texture::from_rgba8(&device, &labels, &queue, 2, 2, &[255; 4], &settings).err(),
+            Some(TextureError::SizeMismatch { expected: 16, actual: 4 })
+        );
+        assert_eq!(
+            Texture::from_rgba8(&device, &labels, &queue, 0, 2, &[], &settings).err(),
+            Some(TextureError::Empty)
+        );
+    }
+}
@@ -0,0 +1,183 @@
+use super::{Matrix4, Vector3};
+
+/// Sphere enclosing a set of points; cheaper than a box for distance checks
+/// such as culling and LOD selection.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoundingSphere {
+    pub center: Vector3,
+    pub radius: f32,
+}
+
+impl BoundingSphere {
+    pub fn new(center: Vector3, radius: f32) -> Self {
+        BoundingSphere { center, radius }
+    }
+
+    /// Approximate minimal sphere using Ritter's algorithm, at most ~5% larger
+    /// than the optimal one. An empty slice gives a zero sphere at the origin.
+    pub fn from_points(points: &[Vector3]) -> Self {
+        let Some(first) = points.first() else {
+            return BoundingSphere::new(Vector3::zero(), 0.0);
+        };
+        let farthest_from = |from: &Vector3| {
+            *points
+                .iter()
+                .max_by(|a, b| a.distance(from).total_cmp(&b.distance(from)))
+                .unwrap_or(from)
+        };
+
+        // Start from two roughly opposite points, then grow to cover the rest
+        let a = farthest_from(first);
+        let b = farthest_from(&a);
+        let mut sphere = BoundingSphere::new((a + b) * 0.5, a.distance(&b) * 0.5);
+        for point in points {
+            let distance = point.distance(&sphere.center);
+            if distance > sphere.radius {
+                let radius = (sphere.radius + distance) * 0.5;
+                sphere.center += (*point - sphere.center) * ((radius - sphere.radius) / distance);
+                sphere.radius = radius;
+            }
+        }
+        sphere
+    }
+
+    pub fn contains(&self, point: &Vector3) -> bool {
+        self.center.distance(point) <= self.radius
+    }
+
+    pub fn intersects(&self, other: &BoundingSphere) -> bool {
+        self.center.distance(&other.center) <= self.radius + other.radius
+    }
+
+    /// Sphere enclosing this one after `matrix`. Non-uniform scale is covered
+    /// by scaling the radius with the largest axis scale.
+    pub fn transform(&self, matrix: &Matrix4) -> BoundingSphere {
+        let axis_scale = |x: f32, y: f32, z: f32| Vector3::new(x, y, z).length();
+        let max_scale = axis_scale(matrix.m11, matrix.m12, matrix.m13)
+            .max(axis_scale(matrix.m21, matrix.m22, matrix.m23))
+            .max(axis_scale(matrix.m31, matrix.m32, matrix.m33));
+        BoundingSphere::new(self.center.transform(matrix), self.radius * max_scale)
+    }
+}
+
+/// Axis-aligned bounding box.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: Vector3,
+    pub max: Vector3,
+}
+
+impl Aabb {
+    pub fn new(min: Vector3, max: Vector3) -> Self {
+        Aabb { min, max }
+    }
+
+    /// Smallest box containing `points`. An empty slice gives a zero box at the origin.
+    pub fn from_points(points: &[Vector3]) -> Self {
+        let Some(first) = points.first() else {
+            return Aabb::new(Vector3::zero(), Vector3::zero());
+        };
+        points.iter().fold(Aabb::new(*first, *first), |aabb, point| {
+            Aabb::new(
+                Vector3::from_fn(|i| aabb.min[i].min(point[i])),
+                Vector3::from_fn(|i| aabb.max[i].max(point[i])),
+            )
+        })
+    }
+
+    pub fn center(&self) -> Vector3 {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn contains(&self, point: &Vector3) -> bool {
+        (0..3).all(|i| self.min[i] <= point[i] && point[i] <= self.max[i])
+    }
+
+    /// The eight corners; bit 0, 1 and 2 of the index select max over min on
+    /// X, Y and Z respectively.
+    pub fn corners(&self) -> [Vector3; 8] {
+        std::array::from_fn(|index| {
+            Vector3::from_fn(|axis| if index & (1 << axis) == 0 { self.min[axis] } else { self.max[axis] })
+        })
+    }
+
+    /// The twelve edges as pairs of corners, for wireframe drawing.
+    pub fn edges(&self) -> [(Vector3, Vector3); 12] {
+        let corners = self.corners();
+        box_edges().map(|(a, b)| (corners[a], corners[b]))
+    }
+
+    /// Box enclosing this one after `matrix`.
+    pub fn transform(&self, matrix: &Matrix4) -> Aabb {
+        Aabb::from_points(&self.corners().map(|corner| corner.transform(matrix)))
+    }
+}
+
+/// Corner index pairs of a box's edges, using the corner order of
+/// `Aabb::corners`: each pair differs in exactly one bit.
+pub fn box_edges() -> [(usize, usize); 12] {
+    let mut edges = [(0, 0); 12];
+    let mut next = 0;
+    for corner in 0..8 {
+        for bit in [1, 2, 4] {
+            if corner & bit == 0 {
+                edges[next] = (corner, corner | bit);
+                next += 1;
+            }
+        }
+    }
+    edges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unit_cube_corners() -> Vec<Vector3> {
+        Aabb::new(Vector3::new(-0.5, -0.5, -0.5), Vector3::new(0.5, 0.5, 0.5)).corners().to_vec()
+    }
+
+    #[test]
+    fn unit_cube_sphere_reaches_its_corners() {
+        let corners = unit_cube_corners();
+        let sphere = BoundingSphere::from_points(&corners);
+        assert!((sphere.radius - 3f32.sqrt() / 2.0).abs() < 1e-5, "{sphere:?}");
+        assert!(sphere.center.approx_eq(&Vector3::zero(), 1e-5));
+        assert!(corners.iter().all(|corner| sphere.center.distance(corner) <= sphere.radius + 1e-5));
+    }
+
+    #[test]
+    fn ritter_sphere_contains_every_point() {
+        let points: Vec<Vector3> = (0..50)
+            .map(|i| {
+                let t = i as f32;
+                Vector3::new((t * 0.7).sin() * 3.0, (t * 1.3).cos() * 2.0, t * 0.1 - 2.0)
+            })
+            .collect();
+        let sphere = BoundingSphere::from_points(&points);
+        assert!(points.iter().all(|point| sphere.center.distance(point) <= sphere.radius + 1e-4));
+    }
+
+    #[test]
+    fn empty_point_set_gives_a_zero_sphere() {
+        assert_eq!(BoundingSphere::from_points(&[]), BoundingSphere::new(Vector3::zero(), 0.0));
+    }
+
+    #[test]
+    fn contains_and_intersects_include_the_boundary() {
+        let sphere = BoundingSphere::new(Vector3::zero(), 1.0);
+        assert!(sphere.contains(&Vector3::new(1.0, 0.0, 0.0)));
+        assert!(!sphere.contains(&Vector3::new(1.0, 0.1, 0.0)));
+        assert!(sphere.intersects(&BoundingSphere::new(Vector3::new(3.0, 0.0, 0.0), 2.0)));
+        assert!(!sphere.intersects(&BoundingSphere::new(Vector3::new(3.0, 0.0, 0.0), 1.5)));
+    }
+
+    #[test]
+    fn transform_scales_the_radius_by_the_largest_axis() {
+        let sphere = BoundingSphere::new(Vector3::zero(), 1.0);
+        let matrix = Matrix4::scaling(Vector3::new(1.0, 3.0, 2.0)) * Matrix4::translation(Vector3::new(5.0, 0.0, 0.0));
+        let moved = sphere.transform(&matrix);
+        assert!(moved.center.approx_eq(&Vector3::new(5.0, 0.0, 0.0), 1e-5));
+        assert!((moved.radius - 3.0).abs() < 1e-5);
+    }
+}
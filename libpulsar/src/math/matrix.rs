@@ -0,0 +1,308 @@
+use std::ops::Mul;
+
+use super::{Handedness, Vector3};
+
+/// Row-major 4x4 matrix using the row-vector convention (`v * M`), so the
+/// translation lives in `m41`, `m42`, `m43`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Matrix4 {
+    pub m11: f32, pub m12: f32, pub m13: f32, pub m14: f32,
+    pub m21: f32, pub m22: f32, pub m23: f32, pub m24: f32,
+    pub m31: f32, pub m32: f32, pub m33: f32, pub m34: f32,
+    pub m41: f32, pub m42: f32, pub m43: f32, pub m44: f32,
+}
+
+impl Matrix4 {
+    pub fn from_rows(rows: [[f32; 4]; 4]) -> Self {
+        let [r1, r2, r3, r4] = rows;
+        Matrix4 {
+            m11: r1[0], m12: r1[1], m13: r1[2], m14: r1[3],
+            m21: r2[0], m22: r2[1], m23: r2[2], m24: r2[3],
+            m31: r3[0], m32: r3[1], m33: r3[2], m34: r3[3],
+            m41: r4[0], m42: r4[1], m43: r4[2], m44: r4[3],
+        }
+    }
+
+    pub fn to_rows(&self) -> [[f32; 4]; 4] {
+        [
+            [self.m11, self.m12, self.m13, self.m14],
+            [self.m21, self.m22, self.m23, self.m24],
+            [self.m31, self.m32, self.m33, self.m34],
+            [self.m41, self.m42, self.m43, self.m44],
+        ]
+    }
+
+    pub fn identity() -> Self {
+        Matrix4::from_rows([
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn translation(offset: Vector3) -> Self {
+        Matrix4::from_rows([
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [offset.x, offset.y, offset.z, 1.0],
+        ])
+    }
+
+    pub fn scaling(scale: Vector3) -> Self {
+        Matrix4::from_rows([
+            [scale.x, 0.0, 0.0, 0.0],
+            [0.0, scale.y, 0.0, 0.0],
+            [0.0, 0.0, scale.z, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    /// Rotation of `angle` radians about +X (clockwise looking down the axis
+    /// towards the origin, as usual for a left-handed system).
+    pub fn rotation_x(angle: f32) -> Self {
+        let (s, c) = angle.sin_cos();
+        Matrix4::from_rows([
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, c, s, 0.0],
+            [0.0, -s, c, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn rotation_y(angle: f32) -> Self {
+        let (s, c) = angle.sin_cos();
+        Matrix4::from_rows([
+            [c, 0.0, -s, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [s, 0.0, c, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn rotation_z(angle: f32) -> Self {
+        let (s, c) = angle.sin_cos();
+        Matrix4::from_rows([
+            [c, s, 0.0, 0.0],
+            [-s, c, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn transpose(&self) -> Self {
+        let r = self.to_rows();
+        Matrix4::from_rows([
+            [r[0][0], r[1][0], r[2][0], r[3][0]],
+            [r[0][1], r[1][1], r[2][1], r[3][1]],
+            [r[0][2], r[1][2], r[2][2], r[3][2]],
+            [r[0][3], r[1][3], r[2][3], r[3][3]],
+        ])
+    }
+
+    /// Transforms a point, assuming an affine matrix whose `w` column is
+    /// `(0, 0, 0, 1)`. Use `project_point` for projection matrices.
+    pub fn transform_point(&self, point: Vector3) -> Vector3 {
+        point.transform(self)
+    }
+
+    /// Transforms a direction: rotation and scale apply, translation doesn't.
+    pub fn transform_vector(&self, vector: Vector3) -> Vector3 {
+        vector.transform_vector(self)
+    }
+
+    /// Transforms `point` as `(x, y, z, 1)` and divides by the resulting `w`,
+    /// giving NDC for a view-projection matrix. Points on the camera plane,
+    /// where `w` is 0, come out non-finite.
+    pub fn project_point(&self, point: Vector3) -> Vector3 {
+        let w = point.x * self.m14 + point.y * self.m24 + point.z * self.m34 + self.m44;
+        let transformed = point.transform(self);
+        Vector3::new(transformed.x / w, transformed.y / w, transformed.z / w)
+    }
+
+    /// View matrix for a camera at `eye` looking at `target`. Under
+    /// `Handedness::Left` the camera looks down +Z in view space, under
+    /// `Handedness::Right` it looks down -Z. Looking at the eye itself keeps
+    /// the default orientation, and an `up` parallel to the view direction
+    /// falls back to the least aligned world axis.
+    pub fn look_at(eye: Vector3, target: Vector3, up: Vector3, handedness: Handedness) -> Self {
+        let z_axis = match handedness {
+            Handedness::Left => Vector3::new(target.x - eye.x, target.y - eye.y, target.z - eye.z),
+            Handedness::Right => Vector3::new(eye.x - target.x, eye.y - target.y, eye.z - target.z),
+        }
+        .try_normalize()
+        .unwrap_or(Vector3::new(0.0, 0.0, 1.0));
+        let x_axis = up
+            .cross(&z_axis)
+            .try_normalize()
+            .unwrap_or_else(|| z_axis.least_aligned_perpendicular());
+        let y_axis = z_axis.cross(&x_axis);
+
+        Matrix4::from_rows([
+            [x_axis.x, y_axis.x, z_axis.x, 0.0],
+            [x_axis.y, y_axis.y, z_axis.y, 0.0],
+            [x_axis.z, y_axis.z, z_axis.z, 0.0],
+            [-x_axis.dot(&eye), -y_axis.dot(&eye), -z_axis.dot(&eye), 1.0],
+        ])
+    }
+
+    /// Perspective projection mapping view depth `near..far` to wgpu's `0..1`
+    /// clip depth. `fovy` is the vertical field of view in radians.
+    pub fn perspective(fovy: f32, aspect: f32, near: f32, far: f32, handedness: Handedness) -> Self {
+        let y_scale = 1.0 / (fovy * 0.5).tan();
+        let x_scale = y_scale / aspect;
+        match handedness {
+            Handedness::Left => Matrix4::from_rows([
+                [x_scale, 0.0, 0.0, 0.0],
+                [0.0, y_scale, 0.0, 0.0],
+                [0.0, 0.0, far / (far - near), 1.0],
+                [0.0, 0.0, -near * far / (far - near), 0.0],
+            ]),
+            Handedness::Right => Matrix4::from_rows([
+                [x_scale, 0.0, 0.0, 0.0],
+                [0.0, y_scale, 0.0, 0.0],
+                [0.0, 0.0, far / (near - far), -1.0],
+                [0.0, 0.0, near * far / (near - far), 0.0],
+            ]),
+        }
+    }
+}
+
+impl Default for Matrix4 {
+    fn default() -> Self {
+        Matrix4::identity()
+    }
+}
+
+impl Mul for Matrix4 {
+    type Output = Matrix4;
+
+    /// `a * b` applies `a` first, then `b`.
+    fn mul(self, other: Matrix4) -> Matrix4 {
+        let a = self.to_rows();
+        let b = other.to_rows();
+        let mut out = [[0.0; 4]; 4];
+        for (row, out_row) in out.iter_mut().enumerate() {
+            for (col, value) in out_row.iter_mut().enumerate() {
+                *value = (0..4).map(|k| a[row][k] * b[k][col]).sum();
+            }
+        }
+        Matrix4::from_rows(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(actual: Vector3, expected: Vector3) {
+        assert!(actual.approx_eq(&expected, 1e-5), "{actual:?} != {expected:?}");
+    }
+
+    #[test]
+    fn left_handed_look_at_sees_down_positive_z() {
+        let view = Matrix4::look_at(
+            Vector3::new(0.0, 0.0, -5.0),
+            Vector3::zero(),
+            Vector3::new(0.0, 1.0, 0.0),
+            Handedness::Left,
+        );
+        assert_near(view.transform_point(Vector3::zero()), Vector3::new(0.0, 0.0, 5.0));
+        assert_near(view.transform_point(Vector3::new(1.0, 0.0, 0.0)), Vector3::new(1.0, 0.0, 5.0));
+    }
+
+    #[test]
+    fn right_handed_look_at_sees_down_negative_z() {
+        let view = Matrix4::look_at(
+            Vector3::new(0.0, 0.0, 5.0),
+            Vector3::zero(),
+            Vector3::new(0.0, 1.0, 0.0),
+            Handedness::Right,
+        );
+        assert_near(view.transform_point(Vector3::zero()), Vector3::new(0.0, 0.0, -5.0));
+        assert_near(view.transform_point(Vector3::new(1.0, 0.0, 0.0)), Vector3::new(1.0, 0.0, -5.0));
+    }
+
+    #[test]
+    fn look_at_the_eye_itself_only_translates() {
+        let eye = Vector3::new(1.0, 2.0, 3.0);
+        for handedness in [Handedness::Left, Handedness::Right] {
+            let view = Matrix4::look_at(eye, eye, Vector3::up(), handedness);
+            assert_near(view.transform_point(eye), Vector3::zero());
+            assert_near(view.transform_vector(Vector3::right()), Vector3::right());
+            assert_near(view.transform_vector(Vector3::up()), Vector3::up());
+        }
+    }
+
+    #[test]
+    fn look_at_along_up_is_not_degenerate() {
+        for handedness in [Handedness::Left, Handedness::Right] {
+            let view = Matrix4::look_at(Vector3::zero(), Vector3::new(0.0, 5.0, 0.0), Vector3::up(), handedness);
+            assert!(view.to_rows().iter().flatten().all(|c| c.is_finite()), "{view:?}");
+            let ahead = view.transform_point(Vector3::new(0.0, 5.0, 0.0));
+            assert_near(ahead, handedness.forward() * 5.0);
+        }
+    }
+
+    #[test]
+    fn perspective_maps_near_and_far_to_zero_and_one() {
+        let fovy = std::f32::consts::FRAC_PI_2;
+        let left = Matrix4::perspective(fovy, 1.0, 0.1, 100.0, Handedness::Left);
+        assert!(left.project_point(Vector3::new(0.0, 0.0, 0.1)).z.abs() < 1e-5);
+        assert!((left.project_point(Vector3::new(0.0, 0.0, 100.0)).z - 1.0).abs() < 1e-5);
+
+        let right = Matrix4::perspective(fovy, 1.0, 0.1, 100.0, Handedness::Right);
+        assert!(right.project_point(Vector3::new(0.0, 0.0, -0.1)).z.abs() < 1e-5);
+        assert!((right.project_point(Vector3::new(0.0, 0.0, -100.0)).z - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn perspective_edges_of_the_field_of_view_reach_ndc_bounds() {
+        let projection = Matrix4::perspective(std::f32::consts::FRAC_PI_2, 2.0, 0.1, 100.0, Handedness::Left);
+        let top = projection.project_point(Vector3::new(0.0, 10.0, 10.0));
+        let right = projection.project_point(Vector3::new(20.0, 0.0, 10.0));
+        assert!((top.y - 1.0).abs() < 1e-5);
+        assert!((right.x - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn product_applies_left_operand_first() {
+        let scale = Matrix4::scaling(Vector3::new(2.0, 2.0, 2.0));
+        let scale_then_move = scale * Matrix4::translation(Vector3::new(1.0, 0.0, 0.0));
+        assert_near(scale_then_move.transform_point(Vector3::new(1.0, 1.0, 1.0)), Vector3::new(3.0, 2.0, 2.0));
+        assert_eq!(Matrix4::identity() * scale_then_move, scale_then_move);
+    }
+
+    #[test]
+    fn project_point_divides_by_w() {
+        let (near, far) = (0.1, 100.0);
+        let projection = Matrix4::perspective(std::f32::consts::FRAC_PI_2, 1.0, near, far, Handedness::Left);
+        let point = Vector3::new(1.0, 2.0, 4.0);
+        let depth = (far / (far - near) * 4.0 - near * far / (far - near)) / 4.0;
+        assert_near(projection.project_point(point), Vector3::new(0.25, 0.5, depth));
+        // Without the divide, x and y keep their view-space size
+        let affine = projection.transform_point(point);
+        assert_near(Vector3::new(affine.x, affine.y, 0.0), Vector3::new(1.0, 2.0, 0.0));
+    }
+
+    #[test]
+    fn project_point_on_the_camera_plane_is_not_finite() {
+        let projection = Matrix4::perspective(std::f32::consts::FRAC_PI_2, 1.0, 0.1, 100.0, Handedness::Left);
+        assert!(!projection.project_point(Vector3::new(1.0, 1.0, 0.0)).is_finite());
+    }
+
+    #[test]
+    fn project_point_matches_transform_point_for_affine_matrices() {
+        let matrix = Matrix4::scaling(Vector3::new(2.0, 3.0, 4.0)) * Matrix4::translation(Vector3::new(1.0, -1.0, 0.5));
+        let point = Vector3::new(0.5, 1.0, -2.0);
+        assert_near(matrix.project_point(point), matrix.transform_point(point));
+    }
+
+    #[test]
+    fn transform_vector_ignores_translation() {
+        let matrix = Matrix4::scaling(Vector3::new(2.0, 2.0, 2.0)) * Matrix4::translation(Vector3::new(5.0, 5.0, 5.0));
+        assert_near(matrix.transform_vector(Vector3::new(1.0, 0.0, 0.0)), Vector3::new(2.0, 0.0, 0.0));
+        assert_near(matrix.transform_point(Vector3::new(1.0, 0.0, 0.0)), Vector3::new(7.0, 5.0, 5.0));
+    }
+}
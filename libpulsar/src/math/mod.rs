@@ -1,3 +1,53 @@
+//! Math types used by the engine.
+//!
+//! The crate is left-handed: +X is right, +Y is up and +Z points forward,
+//! into the screen. Matrices use the row-vector convention (`v * M`), matching
+//! `Vector3::transform`. Right-handed helpers are available by passing
+//! `Handedness::Right` explicitly.
+
+use serde::{Deserialize, Serialize};
+
+mod bounds;
+pub use bounds::{box_edges, Aabb, BoundingSphere};
+mod matrix;
+pub use matrix::Matrix4;
+mod noise;
+pub use noise::perlin_1d;
+mod ops;
+mod quaternion;
+pub use quaternion::{Quaternion, RotationOrder};
+mod ray;
+pub use ray::{Ray, RayHit};
+mod rng;
+pub use rng::Rng;
+mod transform;
+pub use transform::Transform;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Handedness {
+    /// +Z forward. The crate default.
+    #[default]
+    Left,
+    /// -Z forward, as used by glam's `*_rh` helpers.
+    Right,
+}
+
+impl Handedness {
+    /// Unit vector pointing forward (away from the viewer).
+    pub fn forward(self) -> Vector3 {
+        match self {
+            Handedness::Left => Vector3::new(0.0, 0.0, 1.0),
+            Handedness::Right => Vector3::new(0.0, 0.0, -1.0),
+        }
+    }
+}
+
+/// The convention used by `Vector3::forward`/`back` and the engine's own matrices.
+pub const HANDEDNESS: Handedness = Handedness::Left;
+
+/// Formats as `(x, y, z)`; precision and width apply to each component, so
+/// `{:.2}` prints `(1.00, 2.00, 3.00)`. `Debug` prints `Vector3(x, y, z)`.
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub struct Vector3 {
     pub x: f32,
     pub y: f32,
@@ -9,6 +59,20 @@ impl Vector3 {
         Vector3 { x, y, z }
     }
 
+    /// Builds a vector by calling `f` with each axis index (0 = x, 1 = y, 2 = z).
+    pub fn from_fn(mut f: impl FnMut(usize) -> f32) -> Self {
+        Vector3::new(f(0), f(1), f(2))
+    }
+
+    pub fn to_array(&self) -> [f32; 3] {
+        [self.x, self.y, self.z]
+    }
+
+    /// Iterates the components in x, y, z order.
+    pub fn iter(&self) -> impl Iterator<Item = f32> {
+        self.to_array().into_iter()
+    }
+
     pub fn zero() -> Self {
         Vector3::new(0.0, 0.0, 0.0)
     }
@@ -33,23 +97,66 @@ impl Vector3 {
         Vector3::new(1.0, 0.0, 0.0)
     }
 
+    /// Forward under the crate's `HANDEDNESS`.
     pub fn forward() -> Self {
-        Vector3::new(0.0, 0.0, 1.0)
+        HANDEDNESS.forward()
     }
 
     pub fn back() -> Self {
-        Vector3::new(0.0, 0.0, -1.0)
+        let forward = HANDEDNESS.forward();
+        Vector3::new(-forward.x, -forward.y, -forward.z)
+    }
+
+    /// True when every component differs from `other`'s by at most `epsilon`.
+    pub fn approx_eq(&self, other: &Vector3, epsilon: f32) -> bool {
+        (self.x - other.x).abs() <= epsilon
+            && (self.y - other.y).abs() <= epsilon
+            && (self.z - other.z).abs() <= epsilon
+    }
+
+    /// False if any component is NaN or infinite.
+    pub fn is_finite(&self) -> bool {
+        self.x.is_finite() && self.y.is_finite() && self.z.is_finite()
+    }
+
+    /// Replaces NaN components with zero, leaving the others untouched.
+    pub fn nan_to_zero(&self) -> Vector3 {
+        let fix = |v: f32| if v.is_nan() { 0.0 } else { v };
+        Vector3::new(fix(self.x), fix(self.y), fix(self.z))
     }
 
     pub fn magnitude(&self) -> f32 {
         (self.x * self.x + self.y * self.y + self.z * self.z).sqrt()
     }
 
+    /// Alias for `magnitude`.
+    pub fn length(&self) -> f32 {
+        self.magnitude()
+    }
+
+    /// Unit vector in the same direction. Produces NaNs for a zero-length
+    /// vector; use `normalize_or_zero` or `try_normalize` when that can happen.
     pub fn normalize(&self) -> Self {
         let mag = self.magnitude();
         Vector3::new(self.x / mag, self.y / mag, self.z / mag)
     }
 
+    /// Unit vector in the same direction, or `None` if the vector is too
+    /// short to have a meaningful direction.
+    pub fn try_normalize(&self) -> Option<Vector3> {
+        let mag = self.magnitude();
+        if mag.is_finite() && mag > f32::EPSILON {
+            Some(Vector3::new(self.x / mag, self.y / mag, self.z / mag))
+        } else {
+            None
+        }
+    }
+
+    /// Like `normalize`, but returns the zero vector instead of NaNs.
+    pub fn normalize_or_zero(&self) -> Vector3 {
+        self.try_normalize().unwrap_or_else(Vector3::zero)
+    }
+
     pub fn dot(&self, other: &Vector3) -> f32 {
         self.x * other.x + self.y * other.y + self.z * other.z
     }
@@ -70,20 +177,84 @@ impl Vector3 {
         )
     }
 
+    /// Spherical interpolation between two unit directions: the result stays
+    /// unit length and turns at a constant angular speed. Nearly parallel
+    /// inputs fall back to a normalized lerp; exactly opposite ones turn about
+    /// an arbitrary perpendicular axis.
+    pub fn slerp(&self, other: &Vector3, t: f32) -> Vector3 {
+        let cos_theta = self.dot(other).clamp(-1.0, 1.0);
+        if cos_theta > 0.9995 {
+            return self.lerp(other, t).normalize_or_zero();
+        }
+        let theta = cos_theta.acos();
+        // Direction perpendicular to `self` in the plane of rotation
+        let ortho = (other - self * cos_theta).try_normalize().unwrap_or_else(|| {
+            let axis = if self.x.abs() < 0.9 { Vector3::right() } else { Vector3::up() };
+            axis.cross(self).normalize()
+        });
+        let (sin, cos) = (theta * t).sin_cos();
+        self * cos + ortho * sin
+    }
+
     pub fn distance(&self, other: &Vector3) -> f32 {
         (self - other).magnitude()
     }
 
+    /// Unsigned angle between the vectors in radians, `0..=PI`. A zero-length
+    /// vector has no direction, so its angle to anything is 0.
     pub fn angle(&self, other: &Vector3) -> f32 {
         let dot = self.dot(other);
         let mag = self.magnitude() * other.magnitude();
-        dot.acos() / mag
+        if mag <= f32::EPSILON {
+            return 0.0;
+        }
+        // Rounding can push the cosine just past 1 for parallel vectors
+        (dot / mag).clamp(-1.0, 1.0).acos()
     }
 
     pub fn reflect(&self, normal: &Vector3) -> Vector3 {
         self - normal * 2.0 * self.dot(normal)
     }
 
+    /// Point at `radius` from the origin. `theta` is the azimuth about +Y in
+    /// radians, measured from +Z towards +X; `phi` is the angle down from +Y,
+    /// so `phi = 0` is straight up and `phi = PI / 2` lies on the XZ plane.
+    pub fn from_spherical(radius: f32, theta: f32, phi: f32) -> Vector3 {
+        let (sin_theta, cos_theta) = theta.sin_cos();
+        let (sin_phi, cos_phi) = phi.sin_cos();
+        Vector3::new(
+            radius * sin_phi * sin_theta,
+            radius * cos_phi,
+            radius * sin_phi * cos_theta,
+        )
+    }
+
+    /// Inverse of `from_spherical`, returning `(radius, theta, phi)` with
+    /// `theta` in `-PI..=PI` and `phi` in `0..=PI`. On the Y axis, where the
+    /// azimuth is undefined, `theta` is 0; the zero vector gives all zeros.
+    pub fn to_spherical(&self) -> (f32, f32, f32) {
+        let radius = self.magnitude();
+        if radius == 0.0 {
+            return (0.0, 0.0, 0.0);
+        }
+        let phi = (self.y / radius).clamp(-1.0, 1.0).acos();
+        let theta = if self.x == 0.0 && self.z == 0.0 { 0.0 } else { self.x.atan2(self.z) };
+        (radius, theta, phi)
+    }
+
+    /// Rotates this vector by `q`.
+    pub fn rotate_by(&self, q: &Quaternion) -> Vector3 {
+        q.rotate(*self)
+    }
+
+    /// Rotates this vector by `angle` radians about `axis` (see `Quaternion`
+    /// for the sign convention).
+    pub fn rotate_around_axis(&self, axis: &Vector3, angle: f32) -> Vector3 {
+        Quaternion::from_axis_angle(*axis, angle).rotate(*self)
+    }
+
+    /// Transforms this point by an affine matrix, ignoring the `w` column;
+    /// see `Matrix4::project_point` for projections.
     pub fn transform(&self, matrix: &Matrix4) -> Vector3 {
         Vector3::new(
             self.x * matrix.m11 + self.y * matrix.m21 + self.z * matrix.m31 + matrix.m41,
@@ -100,8 +271,10 @@ impl Vector3 {
         )
     }
 
+    /// Rotates the unit direction of this vector by `matrix`. A vector with
+    /// no direction stays zero.
     pub fn transform_direction(&self, matrix: &Matrix4) -> Vector3 {
-        self.normalize().transform_normal(matrix)
+        self.normalize_or_zero().transform_normal(matrix)
     }
 
     pub fn transform_position(&self, matrix: &Matrix4) -> Vector3 {
@@ -120,7 +293,179 @@ impl Vector3 {
         )
     }
 
-    pub fn transform_direction(&self, matrix: &Matrix4) -> Vector3 {
-        self.normalize().transform_vector(matrix)
+    /// Unit vector perpendicular to this unit vector, taken from the world
+    /// axis least aligned with it. Used when a cross product degenerates.
+    fn least_aligned_perpendicular(&self) -> Vector3 {
+        let axis = [Vector3::right(), Vector3::up(), Vector3::new(0.0, 0.0, 1.0)]
+            .into_iter()
+            .min_by(|a, b| a.dot(self).abs().total_cmp(&b.dot(self).abs()))
+            .unwrap();
+        // Never parallel to `self`, as it is the least aligned axis
+        (axis - *self * axis.dot(self)).normalize()
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn approx_eq_compares_each_component_within_epsilon() {
+        let a = Vector3::new(1.0, 2.0, 3.0);
+        assert!(a.approx_eq(&Vector3::new(1.0005, 1.9995, 3.0), 1e-3));
+        assert!(!a.approx_eq(&Vector3::new(1.0, 2.0, 3.01), 1e-3));
+        assert!(!a.approx_eq(&Vector3::new(f32::NAN, 2.0, 3.0), 1e-3));
+    }
+
+    #[test]
+    fn nan_and_infinite_components_are_not_finite() {
+        assert!(Vector3::new(1.0, -2.0, 0.0).is_finite());
+        assert!(!Vector3::new(f32::NAN, 0.0, 0.0).is_finite());
+        assert!(!Vector3::new(0.0, f32::INFINITY, 0.0).is_finite());
+        assert!(!Vector3::new(0.0, 0.0, f32::NEG_INFINITY).is_finite());
+    }
+
+    #[test]
+    fn nan_to_zero_only_replaces_nans() {
+        let fixed = Vector3::new(f32::NAN, 2.0, f32::INFINITY).nan_to_zero();
+        assert_eq!(fixed.x, 0.0);
+        assert_eq!(fixed.y, 2.0);
+        assert_eq!(fixed.z, f32::INFINITY);
+    }
+
+    #[test]
+    fn angle_between_orthogonal_and_parallel_vectors() {
+        let x = Vector3::new(2.0, 0.0, 0.0);
+        assert!((x.angle(&Vector3::new(0.0, 0.0, 3.0)) - std::f32::consts::FRAC_PI_2).abs() < 1e-6);
+        assert_eq!(x.angle(&Vector3::new(5.0, 0.0, 0.0)), 0.0);
+        assert!((x.angle(&Vector3::new(-0.5, 0.0, 0.0)) - std::f32::consts::PI).abs() < 1e-6);
+        let v = Vector3::new(0.3, -1.7, 2.9);
+        // acos is steep near 1, so a rounded cosine leaves a tiny angle
+        assert!(v.angle(&(v * 3.0)) < 1e-3);
+    }
+
+    #[test]
+    fn angle_to_a_zero_vector_is_zero() {
+        assert_eq!(Vector3::zero().angle(&Vector3::up()), 0.0);
+        assert_eq!(Vector3::up().angle(&Vector3::zero()), 0.0);
+    }
+
+    #[test]
+    fn zero_length_vectors_have_no_direction() {
+        assert_eq!(Vector3::zero().try_normalize(), None);
+        assert_eq!(Vector3::new(1e-30, 0.0, 0.0).try_normalize(), None);
+        assert_eq!(Vector3::zero().normalize_or_zero(), Vector3::zero());
+        assert!(Vector3::zero().normalize_or_zero().is_finite());
+    }
+
+    #[test]
+    fn transforming_a_vanishing_direction_stays_zero() {
+        let tiny = Vector3::new(1e-30, 0.0, 0.0);
+        assert!(!tiny.normalize().is_finite());
+        let rotation = Transform::new(Vector3::zero(), Vector3::new(0.3, 0.5, 0.0), Vector3::one()).rotation_matrix();
+        assert_eq!(tiny.transform_direction(&rotation), Vector3::zero());
+    }
+
+    #[test]
+    fn non_finite_vectors_have_no_direction() {
+        assert_eq!(Vector3::new(f32::NAN, 1.0, 0.0).try_normalize(), None);
+        assert_eq!(Vector3::new(f32::INFINITY, 0.0, 0.0).normalize_or_zero(), Vector3::zero());
+    }
+
+    #[test]
+    fn normalizing_keeps_the_direction_at_unit_length() {
+        let unit = Vector3::new(3.0, 0.0, 4.0).try_normalize().unwrap();
+        assert!(unit.approx_eq(&Vector3::new(0.6, 0.0, 0.8), 1e-6));
+        assert!((unit.length() - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn rotating_by_the_identity_changes_nothing() {
+        let v = Vector3::new(1.0, -2.0, 3.0);
+        assert_eq!(v.rotate_by(&Quaternion::identity()), v);
+        assert!(v.rotate_around_axis(&Vector3::up(), 0.0).approx_eq(&v, 1e-6));
+    }
+
+    #[test]
+    fn quarter_turns_about_forward_follow_the_handedness() {
+        let quarter = std::f32::consts::FRAC_PI_2;
+        let forward = Vector3::forward();
+        assert!(Vector3::up().rotate_around_axis(&forward, -quarter).approx_eq(&Vector3::right(), 1e-6));
+        assert!(Vector3::right().rotate_around_axis(&forward, quarter).approx_eq(&Vector3::up(), 1e-6));
+    }
+
+    #[test]
+    fn rotate_by_matches_the_rotation_matrix() {
+        let q = Quaternion::from_axis_angle(Vector3::new(1.0, 2.0, -0.5), 0.7);
+        let v = Vector3::new(0.3, -1.0, 2.0);
+        assert!(v.rotate_by(&q).approx_eq(&v.transform(&q.to_matrix()), 1e-5));
+    }
+
+    #[test]
+    fn rotating_about_a_zero_axis_is_a_no_op() {
+        let v = Vector3::new(1.0, 2.0, 3.0);
+        assert_eq!(v.rotate_around_axis(&Vector3::zero(), 1.0), v);
+    }
+
+    #[test]
+    fn spherical_round_trip_reproduces_the_vector() {
+        for v in [
+            Vector3::new(1.0, 2.0, 3.0),
+            Vector3::new(-4.0, -0.5, 2.0),
+            Vector3::new(0.3, 0.0, -7.0),
+        ] {
+            let (radius, theta, phi) = v.to_spherical();
+            assert!(Vector3::from_spherical(radius, theta, phi).approx_eq(&v, 1e-5), "{v:?}");
+        }
+    }
+
+    #[test]
+    fn spherical_angles_follow_the_documented_convention() {
+        use std::f32::consts::{FRAC_PI_2, PI};
+        assert!(Vector3::from_spherical(2.0, 0.0, FRAC_PI_2).approx_eq(&Vector3::new(0.0, 0.0, 2.0), 1e-6));
+        assert!(Vector3::from_spherical(1.0, FRAC_PI_2, FRAC_PI_2).approx_eq(&Vector3::right(), 1e-6));
+        assert!(Vector3::from_spherical(1.0, 0.0, 0.0).approx_eq(&Vector3::up(), 1e-6));
+        let (_, theta, phi) = Vector3::new(0.0, 0.0, -1.0).to_spherical();
+        assert!((theta.abs() - PI).abs() < 1e-6 && (phi - FRAC_PI_2).abs() < 1e-6);
+    }
+
+    #[test]
+    fn poles_and_the_origin_have_zero_azimuth() {
+        assert_eq!(Vector3::new(0.0, 3.0, 0.0).to_spherical(), (3.0, 0.0, 0.0));
+        assert_eq!(Vector3::new(0.0, -2.0, 0.0).to_spherical(), (2.0, 0.0, std::f32::consts::PI));
+        assert_eq!(Vector3::zero().to_spherical(), (0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn slerp_between_perpendicular_units_stays_unit_at_45_degrees() {
+        let (x, y) = (Vector3::right(), Vector3::up());
+        let half = x.slerp(&y, 0.5);
+        assert!((half.length() - 1.0).abs() < 1e-6);
+        assert!((half.dot(&x).acos() - std::f32::consts::FRAC_PI_4).abs() < 1e-5);
+        assert!((half.dot(&y).acos() - std::f32::consts::FRAC_PI_4).abs() < 1e-5);
+        // Constant angular speed: a quarter of the way is a quarter of the angle
+        let quarter = x.slerp(&y, 0.25);
+        assert!((quarter.dot(&x).acos() - std::f32::consts::FRAC_PI_8).abs() < 1e-5);
+    }
+
+    #[test]
+    fn slerp_hits_the_endpoints() {
+        let (from, to) = (Vector3::new(0.0, 0.0, 1.0), Vector3::new(0.6, 0.0, 0.8));
+        assert!(from.slerp(&to, 0.0).approx_eq(&from, 1e-6));
+        assert!(from.slerp(&to, 1.0).approx_eq(&to, 1e-6));
+    }
+
+    #[test]
+    fn slerp_handles_parallel_and_opposite_directions() {
+        let up = Vector3::up();
+        let nearly = Vector3::new(0.001, 1.0, 0.0).normalize();
+        let blended = up.slerp(&nearly, 0.5);
+        assert!((blended.length() - 1.0).abs() < 1e-6);
+
+        let halfway = up.slerp(&-up, 0.5);
+        assert!((halfway.length() - 1.0).abs() < 1e-5);
+        assert!(halfway.dot(&up).abs() < 1e-5, "{halfway:?}");
+    }
+}
+
+
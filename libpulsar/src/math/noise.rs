@@ -0,0 +1,25 @@
+/// One-dimensional gradient (Perlin) noise in roughly [-1, 1].
+///
+/// Smooth in `x` and zero at integer positions; different `seed`s give
+/// uncorrelated curves, so one seed per channel (x, y, roll, ...) keeps them
+/// from moving in lockstep.
+pub fn perlin_1d(x: f32, seed: u32) -> f32 {
+    let cell = x.floor();
+    let t = x - cell;
+    let cell = cell as i32;
+
+    let d0 = gradient(cell, seed) * t;
+    let d1 = gradient(cell.wrapping_add(1), seed) * (t - 1.0);
+    let fade = t * t * t * (t * (t * 6.0 - 15.0) + 10.0);
+    // Unit gradients only reach ±0.5 in 1D; rescale to ±1
+    (d0 + (d1 - d0) * fade) * 2.0
+}
+
+/// Pseudo-random slope in [-1, 1] for lattice point `i`.
+fn gradient(i: i32, seed: u32) -> f32 {
+    let mut h = (i as u32).wrapping_mul(0x9E37_79B1) ^ seed.wrapping_mul(0x85EB_CA77);
+    h ^= h >> 15;
+    h = h.wrapping_mul(0x2C1B_3C6D);
+    h ^= h >> 12;
+    h as f32 / u32::MAX as f32 * 2.0 - 1.0
+}
@@ -0,0 +1,225 @@
+use std::fmt;
+use std::ops::{Add, AddAssign, Div, Index, IndexMut, Mul, MulAssign, Neg, Sub, SubAssign};
+
+use super::Vector3;
+
+macro_rules! impl_component_op {
+    ($trait:ident, $method:ident, $op:tt) => {
+        impl $trait for Vector3 {
+            type Output = Vector3;
+
+            fn $method(self, rhs: Vector3) -> Vector3 {
+                Vector3::new(self.x $op rhs.x, self.y $op rhs.y, self.z $op rhs.z)
+            }
+        }
+
+        impl $trait<&Vector3> for Vector3 {
+            type Output = Vector3;
+
+            fn $method(self, rhs: &Vector3) -> Vector3 {
+                self $op *rhs
+            }
+        }
+
+        impl $trait<Vector3> for &Vector3 {
+            type Output = Vector3;
+
+            fn $method(self, rhs: Vector3) -> Vector3 {
+                *self $op rhs
+            }
+        }
+
+        impl $trait<&Vector3> for &Vector3 {
+            type Output = Vector3;
+
+            fn $method(self, rhs: &Vector3) -> Vector3 {
+                *self $op *rhs
+            }
+        }
+    };
+}
+
+impl_component_op!(Add, add, +);
+impl_component_op!(Sub, sub, -);
+
+impl Mul<f32> for Vector3 {
+    type Output = Vector3;
+
+    fn mul(self, rhs: f32) -> Vector3 {
+        Vector3::new(self.x * rhs, self.y * rhs, self.z * rhs)
+    }
+}
+
+impl Mul<f32> for &Vector3 {
+    type Output = Vector3;
+
+    fn mul(self, rhs: f32) -> Vector3 {
+        *self * rhs
+    }
+}
+
+impl Mul<Vector3> for f32 {
+    type Output = Vector3;
+
+    fn mul(self, rhs: Vector3) -> Vector3 {
+        rhs * self
+    }
+}
+
+impl Div<f32> for Vector3 {
+    type Output = Vector3;
+
+    fn div(self, rhs: f32) -> Vector3 {
+        Vector3::new(self.x / rhs, self.y / rhs, self.z / rhs)
+    }
+}
+
+impl Neg for Vector3 {
+    type Output = Vector3;
+
+    fn neg(self) -> Vector3 {
+        Vector3::new(-self.x, -self.y, -self.z)
+    }
+}
+
+impl AddAssign for Vector3 {
+    fn add_assign(&mut self, rhs: Vector3) {
+        *self = *self + rhs;
+    }
+}
+
+impl SubAssign for Vector3 {
+    fn sub_assign(&mut self, rhs: Vector3) {
+        *self = *self - rhs;
+    }
+}
+
+impl MulAssign<f32> for Vector3 {
+    fn mul_assign(&mut self, rhs: f32) {
+        *self = *self * rhs;
+    }
+}
+
+/// Indexes components by axis: 0 = x, 1 = y, 2 = z. Panics on any other index.
+impl Index<usize> for Vector3 {
+    type Output = f32;
+
+    fn index(&self, axis: usize) -> &f32 {
+        match axis {
+            0 => &self.x,
+            1 => &self.y,
+            2 => &self.z,
+            _ => panic!("Vector3 axis index out of range: {axis}"),
+        }
+    }
+}
+
+impl IndexMut<usize> for Vector3 {
+    fn index_mut(&mut self, axis: usize) -> &mut f32 {
+        match axis {
+            0 => &mut self.x,
+            1 => &mut self.y,
+            2 => &mut self.z,
+            _ => panic!("Vector3 axis index out of range: {axis}"),
+        }
+    }
+}
+
+impl From<glam::Vec3> for Vector3 {
+    fn from(v: glam::Vec3) -> Self {
+        Vector3::new(v.x, v.y, v.z)
+    }
+}
+
+impl From<Vector3> for glam::Vec3 {
+    fn from(v: Vector3) -> Self {
+        glam::Vec3::new(v.x, v.y, v.z)
+    }
+}
+
+impl Vector3 {
+    /// Writes `(x, y, z)`, formatting each component with `component` so the
+    /// caller's flags reach every one of them.
+    fn fmt_components(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        component: fn(&f32, &mut fmt::Formatter<'_>) -> fmt::Result,
+    ) -> fmt::Result {
+        f.write_str("(")?;
+        component(&self.x, f)?;
+        f.write_str(", ")?;
+        component(&self.y, f)?;
+        f.write_str(", ")?;
+        component(&self.z, f)?;
+        f.write_str(")")
+    }
+}
+
+impl fmt::Display for Vector3 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_components(f, fmt::Display::fmt)
+    }
+}
+
+impl fmt::Debug for Vector3 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Vector3")?;
+        self.fmt_components(f, fmt::Debug::fmt)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn index_reads_each_axis() {
+        let v = Vector3::new(1.0, 2.0, 3.0);
+        assert_eq!([v[0], v[1], v[2]], [1.0, 2.0, 3.0]);
+    }
+
+    #[test]
+    fn index_mut_writes_each_axis() {
+        let mut v = Vector3::zero();
+        for axis in 0..3 {
+            v[axis] = axis as f32 + 1.0;
+        }
+        assert_eq!(v, Vector3::new(1.0, 2.0, 3.0));
+    }
+
+    #[test]
+    #[should_panic(expected = "out of range")]
+    fn index_out_of_range_panics() {
+        let _ = Vector3::zero()[3];
+    }
+
+    #[test]
+    #[should_panic(expected = "out of range")]
+    fn index_mut_out_of_range_panics() {
+        Vector3::zero()[3] = 1.0;
+    }
+
+    #[test]
+    fn from_fn_and_iter_follow_axis_order() {
+        let v = Vector3::from_fn(|axis| axis as f32 * 10.0);
+        assert_eq!(v, Vector3::new(0.0, 10.0, 20.0));
+        assert_eq!(v.iter().collect::<Vec<_>>(), vec![0.0, 10.0, 20.0]);
+        assert_eq!(Vector3::from_fn(|axis| v[axis]), v);
+    }
+
+    #[test]
+    fn display_applies_precision_to_each_component() {
+        let v = Vector3::new(1.0, -2.5, 3.0);
+        assert_eq!(v.to_string(), "(1, -2.5, 3)");
+        assert_eq!(format!("{v:.2}"), "(1.00, -2.50, 3.00)");
+        assert_eq!(format!("{v:5.1}"), "(  1.0,  -2.5,   3.0)");
+    }
+
+    #[test]
+    fn debug_names_the_type_without_field_names() {
+        let v = Vector3::new(1.0, -2.5, 3.0);
+        assert_eq!(format!("{v:?}"), "Vector3(1.0, -2.5, 3.0)");
+        assert_eq!(format!("{v:.1?}"), "Vector3(1.0, -2.5, 3.0)");
+        assert_eq!(format!("{:?}", Some(Vector3::zero())), "Some(Vector3(0.0, 0.0, 0.0))");
+    }
+}
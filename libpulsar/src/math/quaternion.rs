@@ -0,0 +1,341 @@
+use std::ops::Mul;
+
+use super::{Matrix4, Vector3};
+
+/// Order in which Euler angles are applied; `Xyz` rotates about X first,
+/// then Y, then Z. Blender's rotation modes name orders the same way; Unity
+/// applies `Zxy`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RotationOrder {
+    #[default]
+    Xyz,
+    Xzy,
+    Yxz,
+    Yzx,
+    Zxy,
+    Zyx,
+}
+
+impl RotationOrder {
+    pub const ALL: [RotationOrder; 6] = [
+        RotationOrder::Xyz,
+        RotationOrder::Xzy,
+        RotationOrder::Yxz,
+        RotationOrder::Yzx,
+        RotationOrder::Zxy,
+        RotationOrder::Zyx,
+    ];
+
+    /// Axis indices (0 = X, 1 = Y, 2 = Z) in the order they are applied.
+    pub fn axes(self) -> [usize; 3] {
+        match self {
+            RotationOrder::Xyz => [0, 1, 2],
+            RotationOrder::Xzy => [0, 2, 1],
+            RotationOrder::Yxz => [1, 0, 2],
+            RotationOrder::Yzx => [1, 2, 0],
+            RotationOrder::Zxy => [2, 0, 1],
+            RotationOrder::Zyx => [2, 1, 0],
+        }
+    }
+
+    /// Whether the axes are an even permutation of X, Y, Z, which flips the
+    /// signs in the angle extraction.
+    fn is_cyclic(self) -> bool {
+        matches!(self, RotationOrder::Xyz | RotationOrder::Yzx | RotationOrder::Zxy)
+    }
+}
+
+/// Unit quaternion representing a rotation.
+///
+/// Rotations follow the crate's left-handed convention: a positive angle is
+/// clockwise when looking from the tip of the axis towards the origin, so
+/// rotating `Vector3::right()` by +90° about `Vector3::forward()` gives
+/// `Vector3::up()`, and `up()` by -90° gives `right()`. This matches
+/// `Matrix4::rotation_x/y/z`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quaternion {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+    pub w: f32,
+}
+
+impl Quaternion {
+    pub fn new(x: f32, y: f32, z: f32, w: f32) -> Self {
+        Quaternion { x, y, z, w }
+    }
+
+    pub fn identity() -> Self {
+        Quaternion::new(0.0, 0.0, 0.0, 1.0)
+    }
+
+    /// Rotation of `angle` radians about `axis`. A zero axis gives the identity.
+    pub fn from_axis_angle(axis: Vector3, angle: f32) -> Self {
+        let Some(axis) = axis.try_normalize() else {
+            return Quaternion::identity();
+        };
+        let (s, c) = (angle * 0.5).sin_cos();
+        Quaternion::new(axis.x * s, axis.y * s, axis.z * s, c)
+    }
+
+    /// `from_euler` in `RotationOrder::Xyz`, the order `Transform` stores its
+    /// Euler angles in.
+    pub fn from_euler_xyz(euler: Vector3) -> Self {
+        Quaternion::from_euler(RotationOrder::Xyz, euler.x, euler.y, euler.z)
+    }
+
+    /// `to_euler` in `RotationOrder::Xyz`, for `from_euler_xyz`.
+    pub fn to_euler_xyz(&self) -> Vector3 {
+        self.to_euler(RotationOrder::Xyz)
+    }
+
+    /// Rotation of `x`, `y` and `z` radians about the respective axes,
+    /// applied in `order`.
+    pub fn from_euler(order: RotationOrder, x: f32, y: f32, z: f32) -> Self {
+        let angles = [x, y, z];
+        let [i, j, k] = order.axes();
+        let about = |axis: usize| {
+            let mut v = [0.0; 3];
+            v[axis] = 1.0;
+            Quaternion::from_axis_angle(Vector3::new(v[0], v[1], v[2]), angles[axis])
+        };
+        about(k) * about(j) * about(i)
+    }
+
+    /// Angles about X, Y and Z that `from_euler` with the same `order` turns
+    /// back into this rotation. The middle axis stays within ±90°; at ±90° (gimbal lock)
+    /// the last axis is reported as zero and the first carries the remaining
+    /// rotation.
+    pub fn to_euler(&self, order: RotationOrder) -> Vector3 {
+        let [i, j, k] = order.axes();
+        // Column-vector matrix: r(a, b) is row a, column b of the transpose
+        let rows = self.to_matrix().to_rows();
+        let r = |a: usize, b: usize| rows[b][a];
+        let sign = if order.is_cyclic() { 1.0 } else { -1.0 };
+
+        let mut angles = [0.0; 3];
+        let cos_middle = r(i, i).hypot(sign * r(j, i));
+        angles[j] = (-sign * r(k, i)).atan2(cos_middle);
+        if cos_middle > 1e-4 {
+            angles[i] = (sign * r(k, j)).atan2(r(k, k));
+            angles[k] = (sign * r(j, i)).atan2(r(i, i));
+        } else {
+            angles[i] = (-sign * r(j, k)).atan2(r(j, j));
+        }
+        Vector3::new(angles[0], angles[1], angles[2])
+    }
+
+    /// Quaternion for the rotation part of a row-vector matrix (the inverse of
+    /// `to_matrix`). The matrix must be orthonormal.
+    pub fn from_rotation_matrix(m: &Matrix4) -> Self {
+        let trace = m.m11 + m.m22 + m.m33;
+        let q = if trace > 0.0 {
+            let s = (trace + 1.0).sqrt() * 2.0;
+            Quaternion::new((m.m23 - m.m32) / s, (m.m31 - m.m13) / s, (m.m12 - m.m21) / s, s * 0.25)
+        } else if m.m11 > m.m22 && m.m11 > m.m33 {
+            let s = (1.0 + m.m11 - m.m22 - m.m33).sqrt() * 2.0;
+            Quaternion::new(s * 0.25, (m.m12 + m.m21) / s, (m.m13 + m.m31) / s, (m.m23 - m.m32) / s)
+        } else if m.m22 > m.m33 {
+            let s = (1.0 + m.m22 - m.m11 - m.m33).sqrt() * 2.0;
+            Quaternion::new((m.m12 + m.m21) / s, s * 0.25, (m.m23 + m.m32) / s, (m.m31 - m.m13) / s)
+        } else {
+            let s = (1.0 + m.m33 - m.m11 - m.m22).sqrt() * 2.0;
+            Quaternion::new((m.m13 + m.m31) / s, (m.m23 + m.m32) / s, s * 0.25, (m.m12 - m.m21) / s)
+        };
+        q.normalize()
+    }
+
+    /// Orientation whose local +Z axis points along `forward` and whose local
+    /// +Y axis leans towards `up`. When `forward` is parallel to `up` the local
+    /// +X axis leans towards the world axis least aligned with `forward`
+    /// instead. A zero `forward` gives the identity.
+    pub fn look_rotation(forward: Vector3, up: Vector3) -> Self {
+        let Some(forward) = forward.try_normalize() else {
+            return Quaternion::identity();
+        };
+        let right = up
+            .cross(&forward)
+            .try_normalize()
+            .unwrap_or_else(|| forward.least_aligned_perpendicular());
+        let up = forward.cross(&right);
+        Quaternion::from_rotation_matrix(&Matrix4::from_rows([
+            [right.x, right.y, right.z, 0.0],
+            [up.x, up.y, up.z, 0.0],
+            [forward.x, forward.y, forward.z, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ]))
+    }
+
+    pub fn dot(&self, other: &Quaternion) -> f32 {
+        self.x * other.x + self.y * other.y + self.z * other.z + self.w * other.w
+    }
+
+    pub fn length(&self) -> f32 {
+        self.dot(self).sqrt()
+    }
+
+    /// Rescales to unit length, falling back to the identity for a zero quaternion.
+    pub fn normalize(&self) -> Quaternion {
+        let len = self.length();
+        if len <= f32::EPSILON || !len.is_finite() {
+            return Quaternion::identity();
+        }
+        Quaternion::new(self.x / len, self.y / len, self.z / len, self.w / len)
+    }
+
+    /// Angle in radians of the rotation taking `self` to `other`, in `0..=PI`.
+    pub fn angle_between(&self, other: &Quaternion) -> f32 {
+        2.0 * self.dot(other).abs().min(1.0).acos()
+    }
+
+    /// Spherical interpolation from `self` (t = 0) to `other` (t = 1) along
+    /// the shorter arc, at constant angular speed.
+    pub fn slerp(&self, other: &Quaternion, t: f32) -> Quaternion {
+        let mut cos_theta = self.dot(other);
+        let mut end = *other;
+        // q and -q are the same rotation; flip to take the shorter way round
+        if cos_theta < 0.0 {
+            cos_theta = -cos_theta;
+            end = Quaternion::new(-end.x, -end.y, -end.z, -end.w);
+        }
+        let (a, b) = if cos_theta > 0.9995 {
+            // Nearly parallel: sin(theta) is too small to divide by, lerp instead
+            (1.0 - t, t)
+        } else {
+            let theta = cos_theta.acos();
+            let sin_theta = theta.sin();
+            (((1.0 - t) * theta).sin() / sin_theta, (t * theta).sin() / sin_theta)
+        };
+        Quaternion::new(
+            self.x * a + end.x * b,
+            self.y * a + end.y * b,
+            self.z * a + end.z * b,
+            self.w * a + end.w * b,
+        )
+        .normalize()
+    }
+
+    /// The inverse rotation (for unit quaternions).
+    pub fn conjugate(&self) -> Quaternion {
+        Quaternion::new(-self.x, -self.y, -self.z, self.w)
+    }
+
+    pub fn rotate(&self, v: Vector3) -> Vector3 {
+        // v' = v + 2w(q x v) + 2q x (q x v), the expanded form of q v q*
+        let q = Vector3::new(self.x, self.y, self.z);
+        let t = q.cross(&v) * 2.0;
+        v + t * self.w + q.cross(&t)
+    }
+
+    /// Row-vector rotation matrix, usable with `Vector3::transform`.
+    pub fn to_matrix(&self) -> Matrix4 {
+        let Quaternion { x, y, z, w } = *self;
+        Matrix4::from_rows([
+            [1.0 - 2.0 * (y * y + z * z), 2.0 * (x * y + z * w), 2.0 * (x * z - y * w), 0.0],
+            [2.0 * (x * y - z * w), 1.0 - 2.0 * (x * x + z * z), 2.0 * (y * z + x * w), 0.0],
+            [2.0 * (x * z + y * w), 2.0 * (y * z - x * w), 1.0 - 2.0 * (x * x + y * y), 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+}
+
+impl Default for Quaternion {
+    fn default() -> Self {
+        Quaternion::identity()
+    }
+}
+
+impl Mul for Quaternion {
+    type Output = Quaternion;
+
+    /// Hamilton product: `a * b` applies `b` first, then `a`.
+    fn mul(self, rhs: Quaternion) -> Quaternion {
+        Quaternion::new(
+            self.w * rhs.x + self.x * rhs.w + self.y * rhs.z - self.z * rhs.y,
+            self.w * rhs.y - self.x * rhs.z + self.y * rhs.w + self.z * rhs.x,
+            self.w * rhs.z + self.x * rhs.y - self.y * rhs.x + self.z * rhs.w,
+            self.w * rhs.w - self.x * rhs.x - self.y * rhs.y - self.z * rhs.z,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `q` and `-q` are the same rotation.
+    fn same_rotation(a: &Quaternion, b: &Quaternion) -> bool {
+        a.dot(b).abs() > 1.0 - 1e-6
+    }
+
+    fn close(a: Vector3, b: Vector3) -> bool {
+        (a - b).length() < 1e-5
+    }
+
+    #[test]
+    fn every_order_round_trips_its_angles() {
+        let (x, y, z) = (0.3, -0.7, 1.2);
+        for order in RotationOrder::ALL {
+            let q = Quaternion::from_euler(order, x, y, z);
+            let angles = q.to_euler(order);
+            for (got, expected) in [(angles.x, x), (angles.y, y), (angles.z, z)] {
+                assert!((got - expected).abs() < 1e-4, "{order:?}: {angles:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn orders_apply_their_first_axis_first() {
+        let (x, z) = (std::f32::consts::FRAC_PI_2, std::f32::consts::FRAC_PI_2);
+        let xyz = Quaternion::from_euler(RotationOrder::Xyz, x, 0.0, z);
+        let zyx = Quaternion::from_euler(RotationOrder::Zyx, x, 0.0, z);
+        let about_x = Quaternion::from_axis_angle(Vector3::new(1.0, 0.0, 0.0), x);
+        let about_z = Quaternion::from_axis_angle(Vector3::new(0.0, 0.0, 1.0), z);
+        assert!(same_rotation(&xyz, &(about_z * about_x)));
+        assert!(same_rotation(&zyx, &(about_x * about_z)));
+        assert!(!same_rotation(&xyz, &zyx));
+    }
+
+    #[test]
+    fn xyz_helpers_match_the_default_order() {
+        let euler = Vector3::new(0.4, 0.5, -0.6);
+        assert_eq!(RotationOrder::default(), RotationOrder::Xyz);
+        let q = Quaternion::from_euler_xyz(euler);
+        assert!(same_rotation(&q, &Quaternion::from_euler(RotationOrder::Xyz, 0.4, 0.5, -0.6)));
+        assert_eq!(q.to_euler_xyz(), q.to_euler(RotationOrder::Xyz));
+    }
+
+    #[test]
+    fn gimbal_lock_keeps_the_rotation_in_every_order() {
+        let middle = std::f32::consts::FRAC_PI_2;
+        for order in RotationOrder::ALL {
+            let [first, second, last] = order.axes();
+            let mut angles = [0.0; 3];
+            angles[first] = 0.4;
+            angles[second] = middle;
+            angles[last] = 0.3;
+            let q = Quaternion::from_euler(order, angles[0], angles[1], angles[2]);
+            let euler = q.to_euler(order);
+            assert_eq!([euler.x, euler.y, euler.z][last], 0.0, "{order:?}");
+            let back = Quaternion::from_euler(order, euler.x, euler.y, euler.z);
+            assert!(same_rotation(&q, &back), "{order:?}: {euler:?}");
+        }
+    }
+
+    #[test]
+    fn look_rotation_along_up_on_x_is_not_degenerate() {
+        let x = Vector3::right();
+        let q = Quaternion::look_rotation(x, x);
+        assert!([q.x, q.y, q.z, q.w].iter().all(|c| c.is_finite()), "{q:?}");
+        assert!(close(q.rotate(Vector3::new(0.0, 0.0, 1.0)), x));
+        // The local X axis falls back to the least aligned world axis, Y
+        assert!(close(q.rotate(Vector3::right()), Vector3::up()));
+    }
+
+    #[test]
+    fn look_rotation_along_up_on_y_keeps_x_on_world_x() {
+        let q = Quaternion::look_rotation(Vector3::up(), Vector3::up());
+        assert!(close(q.rotate(Vector3::new(0.0, 0.0, 1.0)), Vector3::up()));
+        assert!(close(q.rotate(Vector3::right()), Vector3::right()));
+    }
+}
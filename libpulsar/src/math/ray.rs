@@ -0,0 +1,146 @@
+use super::Vector3;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ray {
+    pub origin: Vector3,
+    /// Always unit length.
+    pub direction: Vector3,
+}
+
+/// Where a ray struck a surface.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RayHit {
+    /// Distance along the ray.
+    pub distance: f32,
+    pub point: Vector3,
+    /// Unit surface normal, facing back towards the ray origin.
+    pub normal: Vector3,
+}
+
+impl Ray {
+    pub fn new(origin: Vector3, direction: Vector3) -> Self {
+        Ray {
+            origin,
+            direction: direction.normalize_or_zero(),
+        }
+    }
+
+    /// Point at distance `t` along the ray.
+    pub fn at(&self, t: f32) -> Vector3 {
+        self.origin + self.direction * t
+    }
+
+    /// Distance along the ray to the plane through `point` with `normal`, if
+    /// the ray hits it in front of its origin.
+    pub fn intersect_plane(&self, point: Vector3, normal: Vector3) -> Option<f32> {
+        let denom = normal.dot(&self.direction);
+        if denom.abs() <= f32::EPSILON {
+            return None;
+        }
+        let t = (point - self.origin).dot(&normal) / denom;
+        (t >= 0.0).then_some(t)
+    }
+
+    /// Distance along the ray to the first hit on the sphere, if any. Returns
+    /// 0 when the origin is inside the sphere.
+    pub fn intersect_sphere(&self, center: Vector3, radius: f32) -> Option<f32> {
+        let to_origin = self.origin - center;
+        let b = to_origin.dot(&self.direction);
+        let c = to_origin.dot(&to_origin) - radius * radius;
+        if c <= 0.0 {
+            return Some(0.0);
+        }
+        let discriminant = b * b - c;
+        if b > 0.0 || discriminant < 0.0 {
+            return None;
+        }
+        Some(-b - discriminant.sqrt())
+    }
+
+    /// Distance along the ray to the triangle `a, b, c`, hit from either
+    /// side (Möller–Trumbore).
+    pub fn intersect_triangle(&self, a: Vector3, b: Vector3, c: Vector3) -> Option<f32> {
+        let edge1 = b - a;
+        let edge2 = c - a;
+        let p = self.direction.cross(&edge2);
+        let det = edge1.dot(&p);
+        if det.abs() <= f32::EPSILON {
+            return None;
+        }
+        let inv_det = 1.0 / det;
+        let offset = self.origin - a;
+        let u = offset.dot(&p) * inv_det;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+        let q = offset.cross(&edge1);
+        let v = self.direction.dot(&q) * inv_det;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+        let t = edge2.dot(&q) * inv_det;
+        (t >= 0.0).then_some(t)
+    }
+
+    /// Closest approach between the ray and the segment `a..b`. Returns the
+    /// distance along the ray and the gap between the two at that point.
+    pub fn closest_to_segment(&self, a: Vector3, b: Vector3) -> (f32, f32) {
+        let segment = b - a;
+        let length_sq = segment.dot(&segment);
+        let offset = self.origin - a;
+
+        let d = self.direction.dot(&segment);
+        let e = self.direction.dot(&offset);
+        let f = segment.dot(&offset);
+        let denom = length_sq - d * d;
+
+        let s = if length_sq <= f32::EPSILON {
+            0.0
+        } else if denom.abs() <= f32::EPSILON * length_sq {
+            // Parallel: use whichever end of the segment the ray reaches first
+            if d > 0.0 { 0.0 } else { 1.0 }
+        } else {
+            ((f - e * d) / denom).clamp(0.0, 1.0)
+        };
+
+        let point = a + segment * s;
+        let t = (point - self.origin).dot(&self.direction).max(0.0);
+        (t, self.at(t).distance(&point))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn closest_to_segment_finds_the_crossing_point() {
+        let ray = Ray::new(Vector3::new(0.5, 10.0, 0.0), Vector3::new(0.0, -1.0, 0.0));
+        let (t, gap) = ray.closest_to_segment(Vector3::zero(), Vector3::new(1.0, 0.0, 0.0));
+        assert!((t - 10.0).abs() < 1e-5);
+        assert!(gap.abs() < 1e-5);
+    }
+
+    #[test]
+    fn closest_to_segment_clamps_to_the_segment_ends() {
+        let ray = Ray::new(Vector3::new(3.0, 10.0, 0.0), Vector3::new(0.0, -1.0, 0.0));
+        let (t, gap) = ray.closest_to_segment(Vector3::zero(), Vector3::new(1.0, 0.0, 0.0));
+        assert!((t - 10.0).abs() < 1e-5);
+        assert!((gap - 2.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn closest_to_parallel_segment_uses_its_nearer_end() {
+        let ray = Ray::new(Vector3::new(0.5, 10.0, 0.0), Vector3::new(0.0, -1.0, 0.0));
+        let (t, gap) = ray.closest_to_segment(Vector3::zero(), Vector3::new(0.0, 1.0, 0.0));
+        assert!((t - 9.0).abs() < 1e-5);
+        assert!((gap - 0.5).abs() < 1e-5);
+    }
+
+    #[test]
+    fn plane_behind_the_ray_is_missed() {
+        let ray = Ray::new(Vector3::zero(), Vector3::new(0.0, 1.0, 0.0));
+        assert_eq!(ray.intersect_plane(Vector3::new(0.0, -1.0, 0.0), Vector3::up()), None);
+        assert_eq!(ray.intersect_plane(Vector3::new(0.0, 2.0, 0.0), Vector3::up()), Some(2.0));
+    }
+}
@@ -0,0 +1,137 @@
+use serde::{Deserialize, Serialize};
+
+use super::{Matrix4, Quaternion, Vector3};
+
+/// Position, rotation and scale of an object relative to its parent.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Transform {
+    pub position: Vector3,
+    /// Euler angles in radians, applied X first, then Y, then Z.
+    pub rotation: Vector3,
+    pub scale: Vector3,
+}
+
+impl Transform {
+    pub fn new(position: Vector3, rotation: Vector3, scale: Vector3) -> Self {
+        Transform { position, rotation, scale }
+    }
+
+    pub fn identity() -> Self {
+        Transform::new(Vector3::zero(), Vector3::zero(), Vector3::one())
+    }
+
+    /// Transform at `eye`, with unit scale, whose forward (+Z) axis points at
+    /// `target` and whose up axis leans towards `up`. If `target` equals `eye`
+    /// the rotation is left at the identity.
+    pub fn looking_at(eye: Vector3, target: Vector3, up: Vector3) -> Self {
+        let rotation = Quaternion::look_rotation(target - eye, up);
+        Transform::new(eye, rotation.to_euler_xyz(), Vector3::one())
+    }
+
+    /// The Euler rotation as a quaternion.
+    pub fn rotation_quaternion(&self) -> Quaternion {
+        Quaternion::from_euler_xyz(self.rotation)
+    }
+
+    pub fn rotation_matrix(&self) -> Matrix4 {
+        Matrix4::rotation_x(self.rotation.x)
+            * Matrix4::rotation_y(self.rotation.y)
+            * Matrix4::rotation_z(self.rotation.z)
+    }
+
+    /// Direction the local +Z axis points after rotation.
+    pub fn forward(&self) -> Vector3 {
+        let m = self.rotation_matrix();
+        Vector3::new(m.m31, m.m32, m.m33)
+    }
+
+    /// Blend from `self` (t = 0) to `other` (t = 1): position and scale are
+    /// lerped, rotation is slerped along the shortest arc. `t` is clamped to
+    /// `0..=1`.
+    pub fn lerp(&self, other: &Transform, t: f32) -> Transform {
+        let t = t.clamp(0.0, 1.0);
+        let rotation = self.rotation_quaternion().slerp(&other.rotation_quaternion(), t);
+        Transform::new(
+            self.position.lerp(&other.position, t),
+            rotation.to_euler_xyz(),
+            self.scale.lerp(&other.scale, t),
+        )
+    }
+
+    /// Local-to-parent matrix: scale, then rotate, then translate.
+    pub fn matrix(&self) -> Matrix4 {
+        Matrix4::scaling(self.scale) * self.rotation_matrix() * Matrix4::translation(self.position)
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Transform::identity()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn looking_at_points_forward_from_eye_to_target() {
+        let eye = Vector3::new(1.0, 2.0, 3.0);
+        for target in [
+            Vector3::new(4.0, 6.0, 3.0),
+            Vector3::new(-2.0, 0.0, -5.0),
+            Vector3::new(1.0, 2.0, 10.0),
+            Vector3::new(1.0, 2.0, -10.0),
+        ] {
+            let transform = Transform::looking_at(eye, target, Vector3::up());
+            let expected = (target - eye).normalize();
+            assert!(transform.forward().approx_eq(&expected, 1e-5), "{target:?}");
+            assert_eq!(transform.position, eye);
+            assert_eq!(transform.scale, Vector3::one());
+        }
+    }
+
+    #[test]
+    fn looking_at_keeps_up_above_the_horizon() {
+        let transform = Transform::looking_at(Vector3::zero(), Vector3::new(3.0, 0.0, 4.0), Vector3::up());
+        let up = Vector3::up().transform_normal(&transform.rotation_matrix());
+        assert!(up.approx_eq(&Vector3::up(), 1e-5), "{up:?}");
+    }
+
+    #[test]
+    fn looking_at_the_eye_itself_keeps_the_identity_rotation() {
+        let eye = Vector3::new(1.0, 1.0, 1.0);
+        let transform = Transform::looking_at(eye, eye, Vector3::up());
+        assert_eq!(transform.rotation, Vector3::zero());
+    }
+
+    #[test]
+    fn lerp_halfway_gives_the_midpoint_and_half_the_rotation() {
+        let from = Transform::new(Vector3::new(0.0, 0.0, 0.0), Vector3::zero(), Vector3::one());
+        let to = Transform::new(
+            Vector3::new(2.0, 4.0, -6.0),
+            Vector3::new(0.0, std::f32::consts::FRAC_PI_2, 0.0),
+            Vector3::new(3.0, 3.0, 3.0),
+        );
+        let mid = from.lerp(&to, 0.5);
+        assert!(mid.position.approx_eq(&Vector3::new(1.0, 2.0, -3.0), 1e-6));
+        assert!(mid.scale.approx_eq(&Vector3::new(2.0, 2.0, 2.0), 1e-6));
+        let quarter_turn = Vector3::new(0.0, std::f32::consts::FRAC_PI_4, 0.0);
+        assert!(mid.rotation.approx_eq(&quarter_turn, 1e-5), "{:?}", mid.rotation);
+        let (start, end) = (from.rotation_quaternion(), to.rotation_quaternion());
+        let halfway = mid.rotation_quaternion();
+        assert!((start.angle_between(&halfway) - halfway.angle_between(&end)).abs() < 1e-5);
+    }
+
+    #[test]
+    fn lerp_clamps_t_to_the_endpoints() {
+        let from = Transform::new(Vector3::new(1.0, 0.0, 0.0), Vector3::new(0.3, 0.0, 0.0), Vector3::one());
+        let to = Transform::new(Vector3::new(5.0, 0.0, 0.0), Vector3::new(0.0, 0.0, 0.7), Vector3::new(2.0, 2.0, 2.0));
+        for (t, expected) in [(-1.0, from), (0.0, from), (1.0, to), (3.0, to)] {
+            let result = from.lerp(&to, t);
+            assert!(result.position.approx_eq(&expected.position, 1e-6), "t = {t}");
+            assert!(result.scale.approx_eq(&expected.scale, 1e-6), "t = {t}");
+            assert!(result.rotation.approx_eq(&expected.rotation, 1e-5), "t = {t}: {:?}", result.rotation);
+        }
+    }
+}
@@ -1,14 +1,52 @@
+use std::ops::Range;
+
 use bytemuck::{Pod, Zeroable};
+use glam::Vec3;
+use thiserror::Error;
 use wgpu::util::DeviceExt;
 
+use crate::engine::render::config::Labels;
+use crate::material::MaterialId;
+use crate::math::{BoundingSphere, Vector3};
+use crate::resources::{ResourceCategory, Tracked};
+use crate::simplify;
+
+/// Format of mesh depth textures, single- and multisampled.
+pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
 #[repr(C)]
 #[derive(Copy, Clone, Debug, Pod, Zeroable)]
 pub struct Vertex {
     pub position: [f32; 3],
+    /// sRGB-encoded; the shaders convert it to linear (see `color`).
     pub color: [f32; 3],
+    pub normal: [f32; 3],
+    /// Skeleton joints influencing this vertex, used by the skinned pipeline.
+    pub joint_indices: [u16; 4],
+    /// Influence of each joint. All zero means the vertex is not skinned.
+    pub weights: [f32; 4],
+    /// Texture coordinates; the base texture is multiplied with `color`.
+    pub uv: [f32; 2],
 }
 
 impl Vertex {
+    /// An unskinned vertex.
+    pub const fn new(position: [f32; 3], color: [f32; 3], normal: [f32; 3]) -> Self {
+        Vertex {
+            position,
+            color,
+            normal,
+            joint_indices: [0; 4],
+            weights: [0.0; 4],
+            uv: [0.0; 2],
+        }
+    }
+
+    pub fn with_uv(mut self, uv: [f32; 2]) -> Self {
+        self.uv = uv;
+        self
+    }
+
     pub fn desc() -> wgpu::VertexBufferLayout<'static> {
         wgpu::VertexBufferLayout {
             array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
@@ -24,85 +62,286 @@ impl Vertex {
                     shader_location: 1,
                     format: wgpu::VertexFormat::Float32x3,
                 },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 6]>() as wgpu::BufferAddress,
+                    shader_location: 2,
+                    format: wgpu::VertexFormat::Float32x3,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 9]>() as wgpu::BufferAddress,
+                    shader_location: 3,
+                    format: wgpu::VertexFormat::Uint16x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: (std::mem::size_of::<[f32; 9]>() + std::mem::size_of::<[u16; 4]>()) as wgpu::BufferAddress,
+                    shader_location: 4,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: (std::mem::size_of::<[f32; 13]>() + std::mem::size_of::<[u16; 4]>()) as wgpu::BufferAddress,
+                    shader_location: 5,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
             ],
         }
     }
 }
 
-pub struct Mesh {
-    pub vertex_buffer: wgpu::Buffer,
-    pub index_buffer: wgpu::Buffer,
-    pub num_indices: u32,
-    pub depth_texture: (wgpu::Texture, wgpu::TextureView),
+/// Whether a mesh's vertices can change after it is built.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MeshUsage {
+    /// Uploaded once; the cheapest option for geometry that never changes.
+    #[default]
+    Static,
+    /// Rewritable in place with `Mesh::update_vertices`, for animated or
+    /// procedural geometry.
+    Dynamic,
 }
 
-impl Mesh {
-    pub fn create_depth_texture(
-        device: &wgpu::Device,
-        config: &wgpu::SurfaceConfiguration,
-    ) -> (wgpu::Texture, wgpu::TextureView) {
-        let size = wgpu::Extent3d {
-            width: config.width,
-            height: config.height,
-            depth_or_array_layers: 1,
-        };
+impl MeshUsage {
+    pub fn vertex_buffer_usages(self) -> wgpu::BufferUsages {
+        match self {
+            MeshUsage::Static => wgpu::BufferUsages::VERTEX,
+            MeshUsage::Dynamic => wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        }
+    }
+}
 
-        let desc = wgpu::TextureDescriptor {
-            label: Some("Depth Texture"),
-            size,
-            mip_level_count: 1,
-            sample_count: 1,
-            dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Depth32Float,
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
-            view_formats: &[],
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum MeshUpdateError {
+    #[error("Static meshes can't be updated; build the mesh with MeshUsage::Dynamic")]
+    Static,
+    #[error("Expected {expected} vertices but got {actual}")]
+    VertexCountMismatch { expected: u32, actual: usize },
+}
+
+/// Tolerance on a normal's length before it counts as unnormalized.
+pub const NORMAL_LENGTH_TOLERANCE: f32 = 1e-3;
+
+/// A problem found by `MeshData::validate`. Triangles and vertices are
+/// numbered as in `MeshData::triangles` and `MeshData::vertices`.
+#[derive(Debug, Clone, Error, PartialEq)]
+pub enum MeshIssue {
+    #[error("Triangle {triangle} uses vertex {index}, but the mesh has {vertex_count} vertices")]
+    IndexOutOfRange { triangle: usize, index: usize, vertex_count: usize },
+    #[error("{count} trailing indices don't form a whole triangle")]
+    IncompleteTriangle { count: usize },
+    #[error("Triangle {triangle} has zero area")]
+    DegenerateTriangle { triangle: usize },
+    #[error("Vertex {vertex} has a non-finite position")]
+    NonFinitePosition { vertex: usize },
+    #[error("Vertex {vertex} has a non-finite normal")]
+    NonFiniteNormal { vertex: usize },
+    #[error("Vertex {vertex} has a normal of length {length}")]
+    UnnormalizedNormal { vertex: usize, length: f32 },
+}
+
+impl MeshIssue {
+    /// Whether the mesh can't be drawn safely: the GPU would read past the
+    /// vertex buffer or rasterize NaNs. The other issues only look wrong.
+    pub fn is_fatal(&self) -> bool {
+        matches!(
+            self,
+            MeshIssue::IndexOutOfRange { .. } | MeshIssue::NonFinitePosition { .. }
+        )
+    }
+}
+
+/// Order in which a triangle's vertices appear when seen from its front side.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WindingOrder {
+    #[default]
+    Ccw,
+    Cw,
+}
+
+impl WindingOrder {
+    pub fn front_face(self) -> wgpu::FrontFace {
+        match self {
+            WindingOrder::Ccw => wgpu::FrontFace::Ccw,
+            WindingOrder::Cw => wgpu::FrontFace::Cw,
+        }
+    }
+
+    /// Guesses the winding by comparing each triangle's geometric normal with
+    /// its vertex normals. Empty `indices` means every three vertices form a
+    /// triangle; triangles indexing past `vertices` are skipped. Returns
+    /// `None` when the mesh has no usable normals.
+    pub fn detect(vertices: &[Vertex], indices: &[u16]) -> Option<WindingOrder> {
+        let corner = |i: usize| if indices.is_empty() { i } else { indices[i] as usize };
+        let corner_count = if indices.is_empty() { vertices.len() } else { indices.len() };
+        let triangle_count = corner_count / 3;
+
+        let mut score = 0i64;
+        for triangle in 0..triangle_count {
+            let corners = [0, 1, 2].map(|k| vertices.get(corner(triangle * 3 + k)));
+            let [Some(a), Some(b), Some(c)] = corners else {
+                continue;
+            };
+            let [pa, pb, pc] = [a, b, c].map(|v| Vec3::from(v.position));
+            let geometric = (pb - pa).cross(pc - pa);
+            let shading = Vec3::from(a.normal) + Vec3::from(b.normal) + Vec3::from(c.normal);
+            let agreement = geometric.dot(shading);
+            if agreement > 0.0 {
+                score += 1;
+            } else if agreement < 0.0 {
+                score -= 1;
+            }
+        }
+
+        match score {
+            0 => None,
+            s if s > 0 => Some(WindingOrder::Ccw),
+            _ => Some(WindingOrder::Cw),
+        }
+    }
+}
+
+/// Reverses the winding of every triangle by swapping its last two corners.
+/// Flips `indices` when there are any and `vertices` otherwise.
+pub fn flip_winding(vertices: &mut [Vertex], indices: &mut [u16]) {
+    if indices.is_empty() {
+        for triangle in vertices.chunks_exact_mut(3) {
+            triangle.swap(1, 2);
+        }
+    } else {
+        for triangle in indices.chunks_exact_mut(3) {
+            triangle.swap(1, 2);
+        }
+    }
+}
+
+/// CPU-side geometry, for processing before it is uploaded as a `Mesh`.
+#[derive(Debug, Clone, Default)]
+pub struct MeshData {
+    pub vertices: Vec<Vertex>,
+    /// Empty when every three vertices form a triangle.
+    pub indices: Vec<u16>,
+}
+
+impl MeshData {
+    pub fn new(vertices: Vec<Vertex>, indices: Vec<u16>) -> Self {
+        Self { vertices, indices }
+    }
+
+    /// Vertex indices of each triangle's corners.
+    pub fn triangles(&self) -> impl Iterator<Item = [usize; 3]> + '_ {
+        let corners = if self.indices.is_empty() {
+            self.vertices.len()
+        } else {
+            self.indices.len()
         };
-        let texture = device.create_texture(&desc);
-        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        (0..corners / 3).map(move |triangle| {
+            [0, 1, 2].map(|k| {
+                let corner = triangle * 3 + k;
+                if self.indices.is_empty() {
+                    corner
+                } else {
+                    self.indices[corner] as usize
+                }
+            })
+        })
+    }
+
+    /// Positions of each triangle's corners.
+    pub fn triangle_positions(&self) -> impl Iterator<Item = [Vec3; 3]> + '_ {
+        self.triangles()
+            .map(|corners| corners.map(|i| Vec3::from(self.vertices[i].position)))
+    }
+
+    /// Checks for out-of-range indices, zero-area triangles and broken
+    /// positions and normals. All-zero normals are taken as missing rather
+    /// than unnormalized, as loaders leave them when a file has none.
+    pub fn validate(&self) -> Result<(), Vec<MeshIssue>> {
+        let mut issues = Vec::new();
+        let vertex_count = self.vertices.len();
+
+        for (vertex, v) in self.vertices.iter().enumerate() {
+            if !v.position.iter().all(|c| c.is_finite()) {
+                issues.push(MeshIssue::NonFinitePosition { vertex });
+            }
+            let normal = Vec3::from(v.normal);
+            if !normal.is_finite() {
+                issues.push(MeshIssue::NonFiniteNormal { vertex });
+            } else if normal != Vec3::ZERO && (normal.length() - 1.0).abs() > NORMAL_LENGTH_TOLERANCE {
+                issues.push(MeshIssue::UnnormalizedNormal { vertex, length: normal.length() });
+            }
+        }
+
+        let corners = if self.indices.is_empty() { vertex_count } else { self.indices.len() };
+        if corners % 3 != 0 {
+            issues.push(MeshIssue::IncompleteTriangle { count: corners % 3 });
+        }
 
-        (texture, view)
+        for (triangle, corners) in self.triangles().enumerate() {
+            if let Some(&index) = corners.iter().find(|&&index| index >= vertex_count) {
+                issues.push(MeshIssue::IndexOutOfRange { triangle, index, vertex_count });
+                continue;
+            }
+            let [a, b, c] = corners.map(|i| Vec3::from(self.vertices[i].position));
+            let area = (b - a).cross(c - a).length_squared();
+            // NaN positions are already reported above
+            if area.is_finite() && area <= f32::EPSILON * f32::EPSILON {
+                issues.push(MeshIssue::DegenerateTriangle { triangle });
+            }
+        }
+
+        if issues.is_empty() {
+            Ok(())
+        } else {
+            Err(issues)
+        }
     }
 
-    pub fn cube(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) -> Self {
+    /// Reduces the triangle count to about `target_ratio` of the original,
+    /// e.g. 0.5 for roughly half. See `simplify::simplify`.
+    pub fn simplify(&self, target_ratio: f32) -> MeshData {
+        simplify::simplify(self, target_ratio)
+    }
+
+    /// Unit cube with 24 vertices so each face has its own color and flat
+    /// outward normal. Every triangle is wound counter-clockwise when seen from
+    /// outside the cube, matching `FrontFace::Ccw` with back-face culling.
+    pub fn cube() -> Self {
         let vertices = [
             // Front face
-            Vertex { position: [-0.5, -0.5,  0.5], color: [1.0, 0.0, 0.0] },
-            Vertex { position: [ 0.5, -0.5,  0.5], color: [1.0, 0.0, 0.0] },
-            Vertex { position: [ 0.5,  0.5,  0.5], color: [1.0, 0.0, 0.0] },
-            Vertex { position: [-0.5,  0.5,  0.5], color: [1.0, 0.0, 0.0] },
+            Vertex::new([-0.5, -0.5,  0.5], [1.0, 0.0, 0.0], [ 0.0,  0.0,  1.0]),
+            Vertex::new([ 0.5, -0.5,  0.5], [1.0, 0.0, 0.0], [ 0.0,  0.0,  1.0]),
+            Vertex::new([ 0.5,  0.5,  0.5], [1.0, 0.0, 0.0], [ 0.0,  0.0,  1.0]),
+            Vertex::new([-0.5,  0.5,  0.5], [1.0, 0.0, 0.0], [ 0.0,  0.0,  1.0]),
             
             // Back face
-            Vertex { position: [-0.5, -0.5, -0.5], color: [0.0, 1.0, 0.0] },
-            Vertex { position: [-0.5,  0.5, -0.5], color: [0.0, 1.0, 0.0] },
-            Vertex { position: [ 0.5,  0.5, -0.5], color: [0.0, 1.0, 0.0] },
-            Vertex { position: [ 0.5, -0.5, -0.5], color: [0.0, 1.0, 0.0] },
+            Vertex::new([-0.5, -0.5, -0.5], [0.0, 1.0, 0.0], [ 0.0,  0.0, -1.0]),
+            Vertex::new([-0.5,  0.5, -0.5], [0.0, 1.0, 0.0], [ 0.0,  0.0, -1.0]),
+            Vertex::new([ 0.5,  0.5, -0.5], [0.0, 1.0, 0.0], [ 0.0,  0.0, -1.0]),
+            Vertex::new([ 0.5, -0.5, -0.5], [0.0, 1.0, 0.0], [ 0.0,  0.0, -1.0]),
             
             // Top face
-            Vertex { position: [-0.5,  0.5, -0.5], color: [0.0, 0.0, 1.0] },
-            Vertex { position: [-0.5,  0.5,  0.5], color: [0.0, 0.0, 1.0] },
-            Vertex { position: [ 0.5,  0.5,  0.5], color: [0.0, 0.0, 1.0] },
-            Vertex { position: [ 0.5,  0.5, -0.5], color: [0.0, 0.0, 1.0] },
+            Vertex::new([-0.5,  0.5, -0.5], [0.0, 0.0, 1.0], [ 0.0,  1.0,  0.0]),
+            Vertex::new([-0.5,  0.5,  0.5], [0.0, 0.0, 1.0], [ 0.0,  1.0,  0.0]),
+            Vertex::new([ 0.5,  0.5,  0.5], [0.0, 0.0, 1.0], [ 0.0,  1.0,  0.0]),
+            Vertex::new([ 0.5,  0.5, -0.5], [0.0, 0.0, 1.0], [ 0.0,  1.0,  0.0]),
             
             // Bottom face
-            Vertex { position: [-0.5, -0.5, -0.5], color: [1.0, 1.0, 0.0] },
-            Vertex { position: [ 0.5, -0.5, -0.5], color: [1.0, 1.0, 0.0] },
-            Vertex { position: [ 0.5, -0.5,  0.5], color: [1.0, 1.0, 0.0] },
-            Vertex { position: [-0.5, -0.5,  0.5], color: [1.0, 1.0, 0.0] },
+            Vertex::new([-0.5, -0.5, -0.5], [1.0, 1.0, 0.0], [ 0.0, -1.0,  0.0]),
+            Vertex::new([ 0.5, -0.5, -0.5], [1.0, 1.0, 0.0], [ 0.0, -1.0,  0.0]),
+            Vertex::new([ 0.5, -0.5,  0.5], [1.0, 1.0, 0.0], [ 0.0, -1.0,  0.0]),
+            Vertex::new([-0.5, -0.5,  0.5], [1.0, 1.0, 0.0], [ 0.0, -1.0,  0.0]),
             
             // Right face
-            Vertex { position: [ 0.5, -0.5, -0.5], color: [1.0, 0.0, 1.0] },
-            Vertex { position: [ 0.5,  0.5, -0.5], color: [1.0, 0.0, 1.0] },
-            Vertex { position: [ 0.5,  0.5,  0.5], color: [1.0, 0.0, 1.0] },
-            Vertex { position: [ 0.5, -0.5,  0.5], color: [1.0, 0.0, 1.0] },
+            Vertex::new([ 0.5, -0.5, -0.5], [1.0, 0.0, 1.0], [ 1.0,  0.0,  0.0]),
+            Vertex::new([ 0.5,  0.5, -0.5], [1.0, 0.0, 1.0], [ 1.0,  0.0,  0.0]),
+            Vertex::new([ 0.5,  0.5,  0.5], [1.0, 0.0, 1.0], [ 1.0,  0.0,  0.0]),
+            Vertex::new([ 0.5, -0.5,  0.5], [1.0, 0.0, 1.0], [ 1.0,  0.0,  0.0]),
             
             // Left face
-            Vertex { position: [-0.5, -0.5, -0.5], color: [0.0, 1.0, 1.0] },
-            Vertex { position: [-0.5, -0.5,  0.5], color: [0.0, 1.0, 1.0] },
-            Vertex { position: [-0.5,  0.5,  0.5], color: [0.0, 1.0, 1.0] },
-            Vertex { position: [-0.5,  0.5, -0.5], color: [0.0, 1.0, 1.0] },
+            Vertex::new([-0.5, -0.5, -0.5], [0.0, 1.0, 1.0], [-1.0,  0.0,  0.0]),
+            Vertex::new([-0.5, -0.5,  0.5], [0.0, 1.0, 1.0], [-1.0,  0.0,  0.0]),
+            Vertex::new([-0.5,  0.5,  0.5], [0.0, 1.0, 1.0], [-1.0,  0.0,  0.0]),
+            Vertex::new([-0.5,  0.5, -0.5], [0.0, 1.0, 1.0], [-1.0,  0.0,  0.0]),
         ];
 
-        let indices: &[u16] = &[
+        let indices = vec![
             0,  1,  2,  2,  3,  0,  // front
             4,  5,  6,  6,  7,  4,  // back
             8,  9,  10, 10, 11, 8,  // top
@@ -111,29 +350,536 @@ impl Mesh {
             20, 21, 22, 22, 23, 20, // left
         ];
 
+        Self::new(vertices.to_vec(), indices)
+    }
+
+    /// Unit cube sharing its 8 corner vertices between faces. Normals are the
+    /// average of the three adjoining face normals, so lighting is smooth
+    /// across edges. Winding is counter-clockwise from outside, as in `cube`.
+    pub fn cube_smooth() -> Self {
+        let corners: [[f32; 3]; 8] = [
+            [-0.5, -0.5, -0.5],
+            [ 0.5, -0.5, -0.5],
+            [ 0.5,  0.5, -0.5],
+            [-0.5,  0.5, -0.5],
+            [-0.5, -0.5,  0.5],
+            [ 0.5, -0.5,  0.5],
+            [ 0.5,  0.5,  0.5],
+            [-0.5,  0.5,  0.5],
+        ];
+        let inv_sqrt3 = 1.0 / 3.0f32.sqrt();
+        let vertices = corners.map(|[x, y, z]| {
+            Vertex::new(
+                [x, y, z],
+                [x + 0.5, y + 0.5, z + 0.5],
+                [x.signum() * inv_sqrt3, y.signum() * inv_sqrt3, z.signum() * inv_sqrt3],
+            )
+        });
+
+        let indices = vec![
+            4, 5, 6, 6, 7, 4, // front
+            0, 3, 2, 2, 1, 0, // back
+            3, 7, 6, 6, 2, 3, // top
+            0, 1, 5, 5, 4, 0, // bottom
+            1, 2, 6, 6, 5, 1, // right
+            0, 4, 7, 7, 3, 0, // left
+        ];
+
+        Self::new(vertices.to_vec(), indices)
+    }
+
+    pub fn upload(&self, device: &wgpu::Device, labels: &Labels, config: &wgpu::SurfaceConfiguration) -> Mesh {
+        self.upload_with_usage(device, labels, config, MeshUsage::Static)
+    }
+
+    pub fn upload_with_usage(
+        &self,
+        device: &wgpu::Device,
+        labels: &Labels,
+        config: &wgpu::SurfaceConfiguration,
+        usage: MeshUsage,
+    ) -> Mesh {
+        if self.indices.is_empty() {
+            Mesh::from_vertices_with_usage(device, labels, config, &self.vertices, usage)
+        } else {
+            Mesh::from_indexed_with_usage(device, labels, config, &self.vertices, &self.indices, usage)
+        }
+    }
+}
+
+/// What `Renderer::draw_mesh` issues for a mesh: an indexed draw over its
+/// index buffer, or a plain draw over its vertices.
+#[derive(Debug, PartialEq, Eq)]
+pub enum DrawCall<'a, B> {
+    Indexed(&'a B, Range<u32>),
+    Vertices(Range<u32>),
+}
+
+/// Meshes without indices, or with an empty index buffer, are drawn straight
+/// from their vertices.
+pub fn draw_call<B>(index_buffer: Option<&B>, num_indices: u32, num_vertices: u32) -> DrawCall<'_, B> {
+    match index_buffer {
+        Some(index_buffer) if num_indices > 0 => DrawCall::Indexed(index_buffer, 0..num_indices),
+        _ => DrawCall::Vertices(0..num_vertices),
+    }
+}
+
+pub struct Mesh {
+    pub vertex_buffer: Tracked<wgpu::Buffer>,
+    /// `None` for meshes drawn straight from the vertex buffer.
+    pub index_buffer: Option<Tracked<wgpu::Buffer>>,
+    pub num_vertices: u32,
+    pub num_indices: u32,
+    pub depth_texture: (Tracked<wgpu::Texture>, wgpu::TextureView),
+    /// Transparent meshes are left out of the depth prepass and drawn after
+    /// the opaque ones with the regular depth test.
+    pub transparent: bool,
+    /// Meshes that aren't counter-clockwise are drawn with a matching pipeline
+    /// and skip the depth prepass.
+    pub front_face: wgpu::FrontFace,
+    /// How the vertices (or indices) are assembled into primitives. Selects
+    /// the matching pipeline variant when the mesh is drawn.
+    pub topology: wgpu::PrimitiveTopology,
+    /// Object-space bounds of the vertices, computed when the mesh is built
+    /// and whenever the vertices are updated.
+    pub bounds: BoundingSphere,
+    /// Material registered with `Renderer::add_material` to draw with, or
+    /// `None` for the one set by `Renderer::set_material`.
+    pub material: Option<MaterialId>,
+    usage: MeshUsage,
+}
+
+impl Mesh {
+    /// Descriptor of a depth texture the size of `config`. `sample_count`
+    /// must match the color target it is drawn with, or pipeline validation
+    /// fails.
+    pub fn depth_texture_descriptor<'a>(
+        label: &'a str,
+        config: &wgpu::SurfaceConfiguration,
+        sample_count: u32,
+    ) -> wgpu::TextureDescriptor<'a> {
+        wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width: config.width,
+                height: config.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            format: DEPTH_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        }
+    }
+
+    pub fn create_depth_texture(
+        device: &wgpu::Device,
+        labels: &Labels,
+        config: &wgpu::SurfaceConfiguration,
+    ) -> (Tracked<wgpu::Texture>, wgpu::TextureView) {
+        Self::create_multisampled_depth_texture(device, labels, config, 1)
+    }
+
+    /// Depth texture for an MSAA color target with `sample_count` samples.
+    /// Recreate it with the color target on resize.
+    pub fn create_multisampled_depth_texture(
+        device: &wgpu::Device,
+        labels: &Labels,
+        config: &wgpu::SurfaceConfiguration,
+        sample_count: u32,
+    ) -> (Tracked<wgpu::Texture>, wgpu::TextureView) {
+        let label = labels.label(if sample_count > 1 { "MSAA Depth Texture" } else { "Depth Texture" });
+        let texture = device.create_texture(&Self::depth_texture_descriptor(&label, config, sample_count));
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        (Tracked::texture(texture, ResourceCategory::RenderTarget), view)
+    }
+
+    /// Uploads `MeshData::cube`.
+    pub fn cube(device: &wgpu::Device, labels: &Labels, config: &wgpu::SurfaceConfiguration) -> Self {
+        MeshData::cube().upload(device, labels, config)
+    }
+
+    /// Uploads `MeshData::cube_smooth`.
+    pub fn cube_smooth(device: &wgpu::Device, labels: &Labels, config: &wgpu::SurfaceConfiguration) -> Self {
+        MeshData::cube_smooth().upload(device, labels, config)
+    }
+
+    pub fn from_indexed(
+        device: &wgpu::Device,
+        labels: &Labels,
+        config: &wgpu::SurfaceConfiguration,
+        vertices: &[Vertex],
+        indices: &[u16],
+    ) -> Self {
+        Self::from_indexed_with_usage(device, labels, config, vertices, indices, MeshUsage::Static)
+    }
+
+    pub fn from_indexed_with_usage(
+        device: &wgpu::Device,
+        labels: &Labels,
+        config: &wgpu::SurfaceConfiguration,
+        vertices: &[Vertex],
+        indices: &[u16],
+        usage: MeshUsage,
+    ) -> Self {
         let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Vertex Buffer"),
-            contents: bytemuck::cast_slice(&vertices),
-            usage: wgpu::BufferUsages::VERTEX,
+            label: Some(&labels.label("Vertex Buffer")),
+            contents: bytemuck::cast_slice(vertices),
+            usage: usage.vertex_buffer_usages(),
         });
+        let vertex_buffer = Tracked::buffer(vertex_buffer, ResourceCategory::Mesh);
 
         let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Index Buffer"),
+            label: Some(&labels.label("Index Buffer")),
             contents: bytemuck::cast_slice(indices),
             usage: wgpu::BufferUsages::INDEX,
         });
 
-        let depth_texture = Self::create_depth_texture(device, config);
+        let depth_texture = Self::create_depth_texture(device, labels, config);
 
         Self {
             vertex_buffer,
-            index_buffer,
+            index_buffer: Some(Tracked::buffer(index_buffer, ResourceCategory::Mesh)),
+            num_vertices: vertices.len() as u32,
             num_indices: indices.len() as u32,
             depth_texture,
+            transparent: false,
+            front_face: wgpu::FrontFace::Ccw,
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            bounds: Self::compute_bounds(vertices),
+            material: None,
+            usage,
+        }
+    }
+
+    /// Builds a mesh without an index buffer; every three vertices form a triangle.
+    pub fn from_vertices(
+        device: &wgpu::Device,
+        labels: &Labels,
+        config: &wgpu::SurfaceConfiguration,
+        vertices: &[Vertex],
+    ) -> Self {
+        Self::from_vertices_with_usage(device, labels, config, vertices, MeshUsage::Static)
+    }
+
+    pub fn from_vertices_with_usage(
+        device: &wgpu::Device,
+        labels: &Labels,
+        config: &wgpu::SurfaceConfiguration,
+        vertices: &[Vertex],
+        usage: MeshUsage,
+    ) -> Self {
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&labels.label("Vertex Buffer")),
+            contents: bytemuck::cast_slice(vertices),
+            usage: usage.vertex_buffer_usages(),
+        });
+        let vertex_buffer = Tracked::buffer(vertex_buffer, ResourceCategory::Mesh);
+
+        let depth_texture = Self::create_depth_texture(device, labels, config);
+
+        Self {
+            vertex_buffer,
+            index_buffer: None,
+            num_vertices: vertices.len() as u32,
+            num_indices: 0,
+            depth_texture,
+            transparent: false,
+            front_face: wgpu::FrontFace::Ccw,
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            bounds: Self::compute_bounds(vertices),
+            material: None,
+            usage,
+        }
+    }
+
+    pub fn compute_bounds(vertices: &[Vertex]) -> BoundingSphere {
+        let points: Vec<Vector3> = vertices.iter().map(|v| Vector3::from(Vec3::from(v.position))).collect();
+        BoundingSphere::from_points(&points)
+    }
+
+    /// Reinterprets the vertices as strips, lines or points.
+    pub fn with_topology(mut self, topology: wgpu::PrimitiveTopology) -> Self {
+        self.topology = topology;
+        self
+    }
+
+    pub fn with_material(mut self, material: MaterialId) -> Self {
+        self.material = Some(material);
+        self
+    }
+
+    pub fn usage(&self) -> MeshUsage {
+        self.usage
+    }
+
+    /// Overwrites the vertex buffer of a `MeshUsage::Dynamic` mesh in place.
+    /// The vertex count can't change, since the indices still refer to it.
+    pub fn update_vertices(&mut self, queue: &wgpu::Queue, vertices: &[Vertex]) -> Result<(), MeshUpdateError> {
+        if self.usage == MeshUsage::Static {
+            return Err(MeshUpdateError::Static);
+        }
+        if vertices.len() != self.num_vertices as usize {
+            return Err(MeshUpdateError::VertexCountMismatch {
+                expected: self.num_vertices,
+                actual: vertices.len(),
+            });
+        }
+        queue.write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(vertices));
+        self.bounds = Self::compute_bounds(vertices);
+        Ok(())
+    }
+
+    pub fn draw_call(&self) -> DrawCall<'_, wgpu::Buffer> {
+        draw_call(self.index_buffer.as_deref(), self.num_indices, self.num_vertices)
+    }
+
+    pub fn is_indexed(&self) -> bool {
+        matches!(self.draw_call(), DrawCall::Indexed(..))
+    }
+
+    pub fn resize(&mut self, device: &wgpu::Device, labels: &Labels, config: &wgpu::SurfaceConfiguration) {
+        self.depth_texture = Self::create_depth_texture(device, labels, config);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::CameraUniform;
+    use crate::render_target::RenderTarget;
+    use crate::renderer::Renderer;
+    use crate::test_gpu;
+
+    #[test]
+    fn indexed_meshes_draw_their_indices() {
+        let buffer = ();
+        assert_eq!(draw_call(Some(&buffer), 36, 24), DrawCall::Indexed(&buffer, 0..36));
+    }
+
+    #[test]
+    fn meshes_without_indices_draw_their_vertices() {
+        assert_eq!(draw_call::<()>(None, 0, 3), DrawCall::Vertices(0..3));
+    }
+
+    #[test]
+    fn meshes_built_from_vertices_are_drawn_without_indices() {
+        let Some((device, _queue)) = test_gpu::device() else {
+            return;
+        };
+        let labels = Labels::default();
+        let config = test_gpu::surface_config(4, 4);
+        let vertices = clockwise_triangle();
+
+        let mesh = Mesh::from_vertices(&device, &labels, &config, &vertices);
+        assert!(!mesh.is_indexed());
+        assert!(matches!(mesh.draw_call(), DrawCall::Vertices(range) if range == (0..3)));
+
+        let indexed = Mesh::from_indexed(&device, &labels, &config, &vertices, &[0, 2, 1]);
+        assert!(indexed.is_indexed());
+        assert!(matches!(indexed.draw_call(), DrawCall::Indexed(_, range) if range == (0..3)));
+    }
+
+    #[test]
+    fn empty_index_buffer_falls_back_to_vertices() {
+        assert_eq!(draw_call(Some(&()), 0, 6), DrawCall::Vertices(0..6));
+    }
+
+    #[test]
+    fn cube_faces_share_one_outward_normal() {
+        let cube = MeshData::cube();
+        assert_eq!(cube.vertices.len(), 24);
+        for face in cube.vertices.chunks_exact(4) {
+            let normal = Vec3::from(face[0].normal);
+            assert!((normal.length() - 1.0).abs() < 1e-6);
+            for vertex in face {
+                assert_eq!(vertex.normal, face[0].normal);
+                // The face center lies along the normal on a unit cube
+                assert!(Vec3::from(vertex.position).dot(normal) > 0.0);
+            }
+        }
+    }
+
+    #[test]
+    fn cube_triangles_wind_counter_clockwise_from_outside() {
+        for cube in [MeshData::cube(), MeshData::cube_smooth()] {
+            for corners in cube.triangles() {
+                let [a, b, c] = corners.map(|i| Vec3::from(cube.vertices[i].position));
+                let centroid = (a + b + c) / 3.0;
+                assert!((b - a).cross(c - a).dot(centroid) > 0.0);
+            }
+            assert_eq!(WindingOrder::detect(&cube.vertices, &cube.indices), Some(WindingOrder::Ccw));
+        }
+    }
+
+    #[test]
+    fn smooth_cube_shares_corners_with_averaged_normals() {
+        let cube = MeshData::cube_smooth();
+        assert_eq!(cube.vertices.len(), 8);
+        assert_eq!(cube.indices.len(), 36);
+        for vertex in &cube.vertices {
+            let normal = Vec3::from(vertex.normal);
+            assert!((normal.length() - 1.0).abs() < 1e-6);
+            assert!(normal.abs_diff_eq(Vec3::from(vertex.position).normalize(), 1e-6));
         }
     }
 
-    pub fn resize(&mut self, device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) {
-        self.depth_texture = Self::create_depth_texture(device, config);
+    /// One triangle facing +Z by its normals but wound clockwise from there.
+    fn clockwise_triangle() -> Vec<Vertex> {
+        [[0.0, 0.0, 0.0], [0.0, 1.0, 0.0], [1.0, 0.0, 0.0]]
+            .map(|position| Vertex::new(position, [1.0; 3], [0.0, 0.0, 1.0]))
+            .to_vec()
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn winding_is_detected_from_the_normals() {
+        let vertices = clockwise_triangle();
+        assert_eq!(WindingOrder::detect(&vertices, &[0, 1, 2]), Some(WindingOrder::Cw));
+        assert_eq!(WindingOrder::detect(&vertices, &[0, 2, 1]), Some(WindingOrder::Ccw));
+        assert_eq!(WindingOrder::detect(&vertices, &[]), Some(WindingOrder::Cw));
+
+        let unlit = vertices.iter().map(|v| Vertex::new(v.position, v.color, [0.0; 3])).collect::<Vec<_>>();
+        assert_eq!(WindingOrder::detect(&unlit, &[0, 1, 2]), None);
+    }
+
+    #[test]
+    fn triangles_indexing_past_the_vertices_are_skipped_when_detecting_winding() {
+        let vertices = clockwise_triangle();
+        assert_eq!(WindingOrder::detect(&vertices, &[0, 1, 7]), None);
+        assert_eq!(WindingOrder::detect(&vertices, &[0, 1, 7, 0, 2, 1]), Some(WindingOrder::Ccw));
+    }
+
+    #[test]
+    fn flipping_a_clockwise_triangle_makes_it_counter_clockwise() {
+        let mut vertices = clockwise_triangle();
+        let mut indices = vec![0, 1, 2];
+        flip_winding(&mut vertices, &mut indices);
+        assert_eq!(indices, vec![0, 2, 1]);
+        assert_eq!(WindingOrder::detect(&vertices, &indices), Some(WindingOrder::Ccw));
+
+        // Without indices the vertices themselves are reordered
+        flip_winding(&mut vertices, &mut []);
+        assert_eq!(WindingOrder::detect(&vertices, &[]), Some(WindingOrder::Ccw));
+    }
+
+    #[test]
+    fn cube_bounds_reach_its_corners() {
+        let bounds = Mesh::compute_bounds(&MeshData::cube().vertices);
+        assert!((bounds.radius - 3f32.sqrt() / 2.0).abs() < 1e-5, "{bounds:?}");
+        assert!(bounds.center.approx_eq(&Vector3::zero(), 1e-5));
+    }
+
+    #[test]
+    fn dynamic_meshes_add_copy_dst_to_the_vertex_buffer() {
+        assert!(!MeshUsage::Static.vertex_buffer_usages().contains(wgpu::BufferUsages::COPY_DST));
+        assert!(MeshUsage::Dynamic.vertex_buffer_usages().contains(wgpu::BufferUsages::COPY_DST));
+    }
+
+    #[test]
+    fn dynamic_mesh_vertices_can_be_rewritten_and_static_ones_cannot() {
+        let Some((device, queue)) = test_gpu::device() else {
+            return;
+        };
+        let labels = Labels::default();
+        let config = test_gpu::surface_config(4, 4);
+        let renderer = pollster::block_on(Renderer::new(&device, &labels, &queue, &config)).unwrap();
+        let target = RenderTarget::new(&device, &labels, &config, 4, 4);
+        // Covers all of clip space
+        let triangle = |color| {
+            [[-1.0, -1.0], [3.0, -1.0], [-1.0, 3.0]].map(|[x, y]| Vertex::new([x, y, 0.5], color, [0.0, 0.0, 1.0]))
+        };
+        let center = |mesh: &Mesh| {
+            renderer.render_to(&device, &queue, &target, &CameraUniform::new(), &[mesh]);
+            let pixels = test_gpu::read_pixels(&device, &queue, &target.color_texture.0);
+            pixels[(2 * 4 + 2) * 4..][..4].to_vec()
+        };
+
+        let red = triangle([1.0, 0.0, 0.0]);
+        let mut dynamic = Mesh::from_vertices_with_usage(&device, &labels, &config, &red, MeshUsage::Dynamic);
+        assert!(dynamic.vertex_buffer.usage().contains(wgpu::BufferUsages::COPY_DST));
+        assert_eq!(center(&dynamic), [255, 0, 0, 255]);
+        dynamic.update_vertices(&queue, &triangle([0.0, 1.0, 0.0])).unwrap();
+        assert_eq!(center(&dynamic), [0, 255, 0, 255]);
+        assert_eq!(
+            dynamic.update_vertices(&queue, &red[..2]),
+            Err(MeshUpdateError::VertexCountMismatch { expected: 3, actual: 2 })
+        );
+
+        let mut fixed = Mesh::from_vertices(&device, &labels, &config, &red);
+        assert_eq!(fixed.update_vertices(&queue, &red), Err(MeshUpdateError::Static));
+        assert!(!fixed.vertex_buffer.usage().contains(wgpu::BufferUsages::COPY_DST));
+    }
+
+    #[test]
+    fn depth_texture_matches_the_color_sample_count() {
+        let config = test_gpu::surface_config(16, 8);
+        let descriptor = Mesh::depth_texture_descriptor("Depth", &config, 4);
+        assert_eq!(descriptor.sample_count, 4);
+        assert_eq!((descriptor.size.width, descriptor.size.height), (16, 8));
+        assert_eq!(Mesh::depth_texture_descriptor("Depth", &config, 1).sample_count, 1);
+
+        let Some((device, _queue)) = test_gpu::device() else {
+            return;
+        };
+        let (texture, _view) = Mesh::create_multisampled_depth_texture(&device, &Labels::default(), &config, 4);
+        assert_eq!(texture.sample_count(), 4);
+    }
+
+    fn triangle_data(indices: Vec<u16>) -> MeshData {
+        let vertices = [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]]
+            .map(|position| Vertex::new(position, [1.0; 3], [0.0, 0.0, 1.0]));
+        MeshData::new(vertices.to_vec(), indices)
+    }
+
+    #[test]
+    fn well_formed_meshes_validate() {
+        MeshData::cube().validate().unwrap();
+        triangle_data(vec![]).validate().unwrap();
+        triangle_data(vec![0, 1, 2]).validate().unwrap();
+    }
+
+    #[test]
+    fn validate_flags_a_bad_index_and_a_nan_position() {
+        let mut mesh = triangle_data(vec![0, 1, 2, 2, 1, 7]);
+        mesh.vertices[1].position[2] = f32::NAN;
+        let issues = mesh.validate().unwrap_err();
+        assert_eq!(
+            issues,
+            [
+                MeshIssue::NonFinitePosition { vertex: 1 },
+                MeshIssue::IndexOutOfRange {
+                    triangle: 1,
+                    index: 7,
+                    vertex_count: 3
+                },
+            ]
+        );
+        assert!(issues.iter().all(MeshIssue::is_fatal));
+    }
+
+    #[test]
+    fn validate_reports_cosmetic_issues_as_non_fatal() {
+        let mut mesh = triangle_data(vec![0, 1, 2, 0, 0, 1, 2]);
+        mesh.vertices[0].normal = [0.0, 0.0, 2.0];
+        mesh.vertices[2].normal = [f32::INFINITY, 0.0, 0.0];
+        let issues = mesh.validate().unwrap_err();
+        assert_eq!(
+            issues,
+            [
+                MeshIssue::UnnormalizedNormal { vertex: 0, length: 2.0 },
+                MeshIssue::NonFiniteNormal { vertex: 2 },
+                MeshIssue::IncompleteTriangle { count: 1 },
+                MeshIssue::DegenerateTriangle { triangle: 1 },
+            ]
+        );
+        assert!(!issues.iter().any(MeshIssue::is_fatal));
+
+        // Missing normals are left as zero by loaders and aren't an issue
+        let mut missing = triangle_data(vec![]);
+        missing.vertices.iter_mut().for_each(|vertex| vertex.normal = [0.0; 3]);
+        missing.validate().unwrap();
+    }
+}
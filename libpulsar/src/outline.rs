@@ -0,0 +1,402 @@
+use wgpu::util::DeviceExt;
+
+use crate::base::ActorId;
+use crate::engine::render::config::Labels;
+use crate::math::Matrix4;
+use crate::mesh::{Mesh, Vertex};
+use crate::picking::object_uniform;
+use crate::renderer::Renderer;
+use crate::resources::{ResourceCategory, Tracked};
+use crate::uniform_pool::UniformPool;
+
+/// The selection mask is drawn with the picking shader, so it holds pick ids.
+const MASK_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R32Uint;
+/// Thicker outlines are clamped; the composite samples a square this wide.
+pub const MAX_OUTLINE_THICKNESS: u32 = 8;
+
+/// Selection highlight drawn around one actor.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Outline {
+    pub actor: ActorId,
+    /// Linear RGBA, blended over the frame by alpha.
+    pub color: [f32; 4],
+    /// Width of the outline in pixels.
+    pub thickness: u32,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct OutlineUniform {
+    color: [f32; 4],
+    thickness: f32,
+    _padding: [f32; 3],
+}
+
+impl OutlineUniform {
+    fn new(outline: &Outline) -> Self {
+        Self {
+            color: outline.color,
+            thickness: outline.thickness.min(MAX_OUTLINE_THICKNESS) as f32,
+            _padding: [0.0; 3],
+        }
+    }
+}
+
+/// CPU mirror of `outline.wgsl`: whether pixel (`x`, `y`) is outside the
+/// mask but within `thickness` pixels of a pixel inside it.
+pub fn is_outline_pixel(covered: impl Fn(i32, i32) -> bool, x: i32, y: i32, thickness: u32) -> bool {
+    if covered(x, y) {
+        return false;
+    }
+    let radius = thickness.min(MAX_OUTLINE_THICKNESS) as i32;
+    (-radius..=radius).any(|dy| {
+        (-radius..=radius).any(|dx| dx * dx + dy * dy <= radius * radius && covered(x + dx, y + dy))
+    })
+}
+
+/// Draws the selected meshes into a mask, then colors the pixels around the
+/// mask's edge. The outline shows through occluders, as editors usually do.
+pub struct OutlinePass {
+    mask_pipeline: wgpu::RenderPipeline,
+    composite_pipeline: wgpu::RenderPipeline,
+    composite_layout: wgpu::BindGroupLayout,
+    objects: UniformPool,
+    uniform: wgpu::Buffer,
+    mask: (Tracked<wgpu::Texture>, wgpu::TextureView),
+    bind_group: wgpu::BindGroup,
+    format: wgpu::TextureFormat,
+    labels: Labels,
+}
+
+impl OutlinePass {
+    /// The outline is blended into targets of `format`, which may differ from
+    /// the surface's, e.g. the HDR scene target.
+    pub fn new(
+        device: &wgpu::Device,
+        labels: &Labels,
+        config: &wgpu::SurfaceConfiguration,
+        format: wgpu::TextureFormat,
+        outline: &Outline,
+    ) -> Self {
+        let objects = UniformPool::new(device, labels, 16);
+
+        let mask_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(&labels.label("Outline Mask Shader")),
+            source: wgpu::ShaderSource::Wgsl(include_str!("picking.wgsl").into()),
+        });
+        let mask_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some(&labels.label("Outline Mask Pipeline Layout")),
+            bind_group_layouts: &[&Renderer::camera_bind_group_layout(device, labels), objects.bind_group_layout()],
+            push_constant_ranges: &[],
+        });
+        let mask_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(&labels.label("Outline Mask Pipeline")),
+            layout: Some(&mask_layout),
+            vertex: wgpu::VertexState {
+                module: &mask_shader,
+                entry_point: Some("vs_main"),
+                buffers: &[Vertex::desc()],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &mask_shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: MASK_FORMAT,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(&labels.label("Outline Shader")),
+            source: wgpu::ShaderSource::Wgsl(include_str!("outline.wgsl").into()),
+        });
+        let composite_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Uint,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+            ],
+            label: Some(&labels.label("outline_bind_group_layout")),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some(&labels.label("Outline Pipeline Layout")),
+            bind_group_layouts: &[&composite_layout],
+            push_constant_ranges: &[],
+        });
+        let composite_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(&labels.label("Outline Pipeline")),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+
+        let uniform = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&labels.label("Outline Uniform Buffer")),
+            contents: bytemuck::bytes_of(&OutlineUniform::new(outline)),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let mask = Self::create_mask(device, labels, config.width, config.height);
+        let bind_group = Self::create_bind_group(device, labels, &composite_layout, &uniform, &mask.1);
+
+        Self {
+            mask_pipeline,
+            composite_pipeline,
+            composite_layout,
+            objects,
+            uniform,
+            mask,
+            bind_group,
+            format,
+            labels: labels.clone(),
+        }
+    }
+
+    /// Format of the targets `draw` can blend into.
+    pub fn format(&self) -> wgpu::TextureFormat {
+        self.format
+    }
+
+    fn create_mask(
+        device: &wgpu::Device,
+        labels: &Labels,
+        width: u32,
+        height: u32,
+    ) -> (Tracked<wgpu::Texture>, wgpu::TextureView) {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(&labels.label("Outline Mask Texture")),
+            size: wgpu::Extent3d {
+                width: width.max(1),
+                height: height.max(1),
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: MASK_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        (Tracked::texture(texture, ResourceCategory::RenderTarget), view)
+    }
+
+    fn create_bind_group(
+        device: &wgpu::Device,
+        labels: &Labels,
+        layout: &wgpu::BindGroupLayout,
+        uniform: &wgpu::Buffer,
+        mask: &wgpu::TextureView,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(mask),
+                },
+            ],
+            label: Some(&labels.label("outline_bind_group")),
+        })
+    }
+
+    pub fn resize(&mut self, device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) {
+        self.mask = Self::create_mask(device, &self.labels, config.width, config.height);
+        self.bind_group =
+            Self::create_bind_group(device, &self.labels, &self.composite_layout, &self.uniform, &self.mask.1);
+    }
+
+    pub fn set_outline(&self, queue: &wgpu::Queue, outline: &Outline) {
+        queue.write_buffer(&self.uniform, 0, bytemuck::bytes_of(&OutlineUniform::new(outline)));
+    }
+
+    /// Draws `meshes` into the mask as seen through `camera_bind_group`, then
+    /// blends the outline over `output`, which must match the surface size.
+    pub fn draw(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        camera_bind_group: &wgpu::BindGroup,
+        meshes: &[(Matrix4, &Mesh)],
+        output: &wgpu::TextureView,
+    ) {
+        if meshes.len() > self.objects.capacity() as usize {
+            self.objects = UniformPool::new(device, &self.labels, meshes.len().next_power_of_two() as u32);
+        }
+        self.objects.clear();
+        // Every selected mesh writes the same non-zero id
+        let offsets: Vec<_> = meshes
+            .iter()
+            .filter_map(|(world, _)| self.objects.push(queue, &object_uniform(0, world)))
+            .collect();
+
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some(&self.labels.label("Outline Mask Pass")),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &self.mask.1,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+            render_pass.set_pipeline(&self.mask_pipeline);
+            render_pass.set_bind_group(0, camera_bind_group, &[]);
+            for ((_, mesh), offset) in meshes.iter().zip(offsets) {
+                render_pass.set_bind_group(1, self.objects.bind_group(), &[offset]);
+                Renderer::draw_mesh(&mut render_pass, mesh);
+            }
+        }
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some(&self.labels.label("Outline Pass")),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: output,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        render_pass.set_pipeline(&self.composite_pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::base::{Actor, Scene};
+    use crate::camera::CameraUniform;
+    use crate::math::Vector3;
+    use crate::render_target::RenderTarget;
+    use crate::test_gpu;
+
+    const SIZE: i32 = 16;
+    const GREEN: [u8; 4] = [0, 255, 0, 255];
+
+    /// A 4x4 pixel square at (2, 6) of a 16x16 target.
+    fn square(x: i32, y: i32) -> bool {
+        (2..=5).contains(&x) && (6..=9).contains(&y)
+    }
+
+    #[test]
+    fn outline_rings_the_mask_without_covering_it() {
+        assert!(!is_outline_pixel(square, 3, 7, 1));
+        assert!(is_outline_pixel(square, 1, 7, 1));
+        assert!(is_outline_pixel(square, 3, 10, 1));
+        assert!(!is_outline_pixel(square, 0, 7, 1));
+        // The neighborhood is round: a diagonal neighbor is outside radius 1
+        assert!(!is_outline_pixel(square, 1, 5, 1));
+        assert!(is_outline_pixel(square, 1, 5, 2));
+    }
+
+    #[test]
+    fn thickness_is_clamped() {
+        let radius = MAX_OUTLINE_THICKNESS as i32;
+        assert!(is_outline_pixel(square, 5 + radius, 7, 100));
+        assert!(!is_outline_pixel(square, 6 + radius, 7, 100));
+        let outline = Outline {
+            actor: Scene::new().spawn(Actor::new()),
+            color: [1.0; 4],
+            thickness: 100,
+        };
+        assert_eq!(OutlineUniform::new(&outline).thickness, MAX_OUTLINE_THICKNESS as f32);
+    }
+
+    #[test]
+    fn outline_pixels_match_the_cpu_mirror() {
+        let Some((device, queue)) = test_gpu::device() else {
+            return;
+        };
+        let labels = Labels::default();
+        let config = test_gpu::surface_config(SIZE as u32, SIZE as u32);
+        let mut renderer = pollster::block_on(Renderer::new(&device, &labels, &queue, &config)).unwrap();
+        let target = RenderTarget::new(&device, &labels, &config, SIZE as u32, SIZE as u32);
+        renderer.render_to(&device, &queue, &target, &CameraUniform::new(), &[]);
+
+        // Two quarter-wide squares, the selected one on the left
+        let vertices = [[-0.25, -0.25], [0.25, -0.25], [0.25, 0.25], [-0.25, -0.25], [0.25, 0.25], [-0.25, 0.25]]
+            .map(|[x, y]| Vertex::new([x, y, 0.5], [1.0, 1.0, 1.0], [0.0, 0.0, 1.0]));
+        let quad = Mesh::from_vertices(&device, &labels, &config, &vertices);
+        let mut scene = Scene::new();
+        let (selected, other) = (scene.spawn(Actor::new()), scene.spawn(Actor::new()));
+        renderer.set_outline(&device, &queue, &config, selected, [0.0, 1.0, 0.0, 1.0], 1);
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        let objects = [
+            (selected, Matrix4::translation(Vector3::new(-0.5, 0.0, 0.0)), &quad),
+            (other, Matrix4::translation(Vector3::new(0.5, 0.0, 0.0)), &quad),
+        ];
+        renderer.draw_outline(&device, &queue, &mut encoder, target.color_view(), &objects);
+        queue.submit(std::iter::once(encoder.finish()));
+
+        let pixels = test_gpu::read_pixels(&device, &queue, &target.color_texture.0);
+        for y in 0..SIZE {
+            for x in 0..SIZE {
+                let start = ((y * SIZE + x) * 4) as usize;
+                let outlined = pixels[start..start + 4] == GREEN;
+                assert_eq!(outlined, is_outline_pixel(square, x, y, 1), "pixel ({x}, {y})");
+            }
+        }
+    }
+}
@@ -0,0 +1,56 @@
+// Selection outline: colors every pixel outside the selection mask that lies
+// within `thickness` pixels of a covered one.
+
+struct OutlineUniform {
+    color: vec4<f32>,
+    thickness: f32,
+    _padding0: f32,
+    _padding1: f32,
+    _padding2: f32,
+};
+
+@group(0) @binding(0)
+var<uniform> outline: OutlineUniform;
+// Non-zero where a selected mesh was drawn.
+@group(0) @binding(1)
+var mask: texture_2d<u32>;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) vid: u32) -> VertexOutput {
+    // Vertices (0,0), (2,0), (0,2) cover the whole screen with one triangle.
+    let uv = vec2<f32>(f32((vid << 1u) & 2u), f32(vid & 2u));
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+    return out;
+}
+
+fn covered(pixel: vec2<i32>, size: vec2<i32>) -> bool {
+    return textureLoad(mask, clamp(pixel, vec2<i32>(0), size - 1), 0).r != 0u;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let pixel = vec2<i32>(in.clip_position.xy);
+    let size = vec2<i32>(textureDimensions(mask));
+    // The object itself keeps its shading
+    if covered(pixel, size) {
+        discard;
+    }
+    let radius = i32(outline.thickness);
+    var near = false;
+    for (var y = -radius; y <= radius; y = y + 1) {
+        for (var x = -radius; x <= radius; x = x + 1) {
+            if x * x + y * y <= radius * radius && covered(pixel + vec2<i32>(x, y), size) {
+                near = true;
+            }
+        }
+    }
+    if !near {
+        discard;
+    }
+    return outline.color;
+}
@@ -0,0 +1,393 @@
+use crate::base::ActorId;
+use crate::engine::render::config::Labels;
+use crate::math::Matrix4;
+use crate::mesh::{Mesh, Vertex};
+use crate::renderer::Renderer;
+use crate::resources::{ResourceCategory, Tracked};
+use crate::screenshot::ScreenshotError;
+use crate::uniform_pool::{ObjectUniform, UniformPool};
+
+const ID_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R32Uint;
+const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+/// Written where nothing was drawn; object ids start at 1.
+const NO_OBJECT: u32 = 0;
+
+/// Pick id of the `index`th object drawn, packed the way `picking.wgsl`
+/// reads it back out of `ObjectUniform::color`.
+pub fn object_uniform(index: usize, world: &Matrix4) -> ObjectUniform {
+    let id = index as u32 + 1;
+    ObjectUniform {
+        model: world.to_rows(),
+        color: [f32::from_bits(id), 0.0, 0.0, 0.0],
+    }
+}
+
+/// Pixel-accurate picking: draws every object's id into an offscreen
+/// integer target and reads back the pixel under the cursor.
+pub struct PickingPass {
+    pipeline: wgpu::RenderPipeline,
+    objects: UniformPool,
+    ids: (Tracked<wgpu::Texture>, wgpu::TextureView),
+    depth: (Tracked<wgpu::Texture>, wgpu::TextureView),
+    /// Actors in the order they were last drawn; pick id `n` is `drawn[n - 1]`.
+    drawn: Vec<ActorId>,
+    reverse_z: bool,
+    labels: Labels,
+}
+
+impl PickingPass {
+    /// `width` and `height` should match the surface, so cursor positions
+    /// map one-to-one onto target pixels. `reverse_z` must match the camera.
+    pub fn new(
+        device: &wgpu::Device,
+        labels: &Labels,
+        width: u32,
+        height: u32,
+        reverse_z: bool,
+    ) -> Self {
+        let objects = UniformPool::new(device, labels, 64);
+        Self {
+            pipeline: Self::create_pipeline(device, labels, objects.bind_group_layout(), reverse_z),
+            objects,
+            ids: Self::create_target(
+                device,
+                width,
+                height,
+                ID_FORMAT,
+                &labels.label("Picking ID Texture"),
+            ),
+            depth: Self::create_target(
+                device,
+                width,
+                height,
+                DEPTH_FORMAT,
+                &labels.label("Picking Depth Texture"),
+            ),
+            drawn: Vec::new(),
+            reverse_z,
+            labels: labels.clone(),
+        }
+    }
+
+    fn create_pipeline(
+        device: &wgpu::Device,
+        labels: &Labels,
+        object_layout: &wgpu::BindGroupLayout,
+        reverse_z: bool,
+    ) -> wgpu::RenderPipeline {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(&labels.label("Picking Shader")),
+            source: wgpu::ShaderSource::Wgsl(include_str!("picking.wgsl").into()),
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some(&labels.label("Picking Pipeline Layout")),
+            bind_group_layouts: &[
+                &Renderer::camera_bind_group_layout(device, labels),
+                object_layout,
+            ],
+            push_constant_ranges: &[],
+        });
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(&labels.label("Picking Pipeline")),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[Vertex::desc()],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: ID_FORMAT,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            // Both sides, so back faces seen through open meshes still pick
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: if reverse_z {
+                    wgpu::CompareFunction::Greater
+                } else {
+                    wgpu::CompareFunction::Less
+                },
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        })
+    }
+
+    fn create_target(
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+        label: &str,
+    ) -> (Tracked<wgpu::Texture>, wgpu::TextureView) {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width: width.max(1),
+                height: height.max(1),
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        (
+            Tracked::texture(texture, ResourceCategory::RenderTarget),
+            view,
+        )
+    }
+
+    pub fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) {
+        let label = |name| self.labels.label(name);
+        self.ids = Self::create_target(
+            device,
+            width,
+            height,
+            ID_FORMAT,
+            &label("Picking ID Texture"),
+        );
+        self.depth = Self::create_target(
+            device,
+            width,
+            height,
+            DEPTH_FORMAT,
+            &label("Picking Depth Texture"),
+        );
+    }
+
+    pub fn set_reverse_z(&mut self, device: &wgpu::Device, reverse_z: bool) {
+        if self.reverse_z != reverse_z {
+            self.reverse_z = reverse_z;
+            self.pipeline = Self::create_pipeline(
+                device,
+                &self.labels,
+                self.objects.bind_group_layout(),
+                reverse_z,
+            );
+        }
+    }
+
+    /// Draws `objects`' ids as seen through `camera_bind_group`, replacing
+    /// the previous contents.
+    pub fn render(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        camera_bind_group: &wgpu::BindGroup,
+        objects: &[(ActorId, Matrix4, &Mesh)],
+    ) {
+        if objects.len() > self.objects.capacity() as usize {
+            self.objects = UniformPool::new(
+                device,
+                &self.labels,
+                objects.len().next_power_of_two() as u32,
+            );
+        }
+        self.objects.clear();
+        let offsets: Vec<_> = objects
+            .iter()
+            .enumerate()
+            .filter_map(|(index, (_, world, _))| {
+                self.objects.push(queue, &object_uniform(index, world))
+            })
+            .collect();
+        self.drawn = objects.iter().map(|(id, _, _)| *id).collect();
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some(&self.labels.label("Picking Encoder")),
+        });
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some(&self.labels.label("Picking Pass")),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &self.ids.1,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color {
+                            r: NO_OBJECT as f64,
+                            g: 0.0,
+                            b: 0.0,
+                            a: 0.0,
+                        }),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &self.depth.1,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(if self.reverse_z { 0.0 } else { 1.0 }),
+                        store: wgpu::StoreOp::Discard,
+                    }),
+                    stencil_ops: None,
+                }),
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+            render_pass.set_pipeline(&self.pipeline);
+            render_pass.set_bind_group(0, camera_bind_group, &[]);
+            for ((_, _, mesh), offset) in objects.iter().zip(offsets) {
+                render_pass.set_bind_group(1, self.objects.bind_group(), &[offset]);
+                Renderer::draw_mesh(&mut render_pass, mesh);
+            }
+        }
+        queue.submit(Some(encoder.finish()));
+    }
+
+    /// Actor drawn at pixel (`x`, `y`) by the last `render`, blocking until
+    /// the GPU has finished it. `None` for the background or a position
+    /// outside the target.
+    pub fn actor_at(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        x: u32,
+        y: u32,
+    ) -> Option<ActorId> {
+        let id = self.read_id(device, queue, x, y).ok().flatten()?;
+        id.checked_sub(1)
+            .and_then(|index| self.drawn.get(index as usize))
+            .copied()
+    }
+
+    /// Raw pick id at a pixel, 0 where nothing was drawn.
+    pub fn read_id(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        x: u32,
+        y: u32,
+    ) -> Result<Option<u32>, ScreenshotError> {
+        let texture = &self.ids.0;
+        if x >= texture.width() || y >= texture.height() {
+            return Ok(None);
+        }
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(&self.labels.label("Picking Readback Buffer")),
+            size: std::mem::size_of::<u32>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some(&self.labels.label("Picking Readback Encoder")),
+        });
+        encoder.copy_texture_to_buffer(
+            wgpu::TexelCopyTextureInfo {
+                texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d { x, y, z: 0 },
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::TexelCopyBufferInfo {
+                buffer: &buffer,
+                // A single row needs no stride
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: None,
+                    rows_per_image: None,
+                },
+            },
+            wgpu::Extent3d {
+                width: 1,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+        );
+        queue.submit(Some(encoder.finish()));
+
+        let slice = buffer.slice(..);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        let _ = device.poll(wgpu::Maintain::Wait);
+        receiver.recv().unwrap_or(Err(wgpu::BufferAsyncError))?;
+        let id = bytemuck::pod_read_unaligned::<u32>(&slice.get_mapped_range());
+        buffer.unmap();
+        Ok(Some(id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::base::{Actor, Scene};
+    use crate::camera::CameraUniform;
+    use crate::test_gpu;
+    use wgpu::util::DeviceExt;
+
+    const SIZE: u32 = 20;
+
+    #[test]
+    fn ids_start_at_one_so_zero_means_nothing() {
+        let uniform = object_uniform(0, &Matrix4::identity());
+        assert_eq!(uniform.color[0].to_bits(), 1);
+        assert_eq!(object_uniform(41, &Matrix4::identity()).color[0].to_bits(), 42);
+        assert_eq!(uniform.model, Matrix4::identity().to_rows());
+    }
+
+    #[test]
+    fn each_pixel_reads_back_the_nearest_quads_actor() {
+        let Some((device, queue)) = test_gpu::device() else {
+            return;
+        };
+        let labels = Labels::default();
+        let config = test_gpu::surface_config(SIZE, SIZE);
+        // A full-height quad one unit wide, counter-clockwise
+        let vertices = [[-0.5, -1.0], [0.5, -1.0], [0.5, 1.0], [-0.5, -1.0], [0.5, 1.0], [-0.5, 1.0]]
+            .map(|[x, y]| Vertex::new([x, y, 0.0], [1.0; 3], [0.0, 0.0, -1.0]));
+        let quad = Mesh::from_vertices(&device, &labels, &config, &vertices);
+        let camera_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: None,
+            contents: bytemuck::bytes_of(&CameraUniform::new()),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let camera_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &Renderer::camera_bind_group_layout(&device, &labels),
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: camera_buffer.as_entire_binding(),
+            }],
+            label: None,
+        });
+
+        // Overlapping in the middle columns, where the left quad is nearer
+        let mut scene = Scene::new();
+        let mut left = Actor::new();
+        left.set_position(-0.4, 0.0, 0.3);
+        let left = scene.spawn(left);
+        let mut right = Actor::new();
+        right.set_position(0.4, 0.0, 0.6);
+        let right = scene.spawn(right);
+
+        let mut picking = PickingPass::new(&device, &labels, SIZE, SIZE, false);
+        let mut pick = |x: u32| {
+            let position = (x as f64 + 0.5, SIZE as f64 / 2.0);
+            scene.pick_gpu(&mut picking, &device, &queue, &camera_bind_group, position, |_| Some(&quad))
+        };
+        assert_eq!(pick(2), Some(left));
+        assert_eq!(pick(10), Some(left));
+        assert_eq!(pick(17), Some(right));
+        assert_eq!(pick(0), None);
+        assert_eq!(pick(19), None);
+        assert_eq!(pick(SIZE + 5), None);
+
+        assert_eq!(picking.read_id(&device, &queue, 17, 10).unwrap(), Some(2));
+        assert_eq!(picking.read_id(&device, &queue, 0, 10).unwrap(), Some(NO_OBJECT));
+    }
+}
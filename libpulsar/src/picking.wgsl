@@ -0,0 +1,27 @@
+// Writes each object's pick id into an R32Uint target.
+
+struct CameraUniform {
+    view_proj: mat4x4<f32>,
+};
+
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+// `color.x` carries the pick id, bit-cast to a float.
+struct ObjectUniform {
+    model: mat4x4<f32>,
+    color: vec4<f32>,
+};
+
+@group(1) @binding(0)
+var<uniform> object: ObjectUniform;
+
+@vertex
+fn vs_main(@location(0) position: vec3<f32>) -> @builtin(position) vec4<f32> {
+    return camera.view_proj * object.model * vec4<f32>(position, 1.0);
+}
+
+@fragment
+fn fs_main() -> @location(0) u32 {
+    return bitcast<u32>(object.color.x);
+}
@@ -0,0 +1,230 @@
+use crate::engine::render::config::Labels;
+use crate::render_target::RenderTarget;
+
+/// Where the upscaled image lands in the window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PixelScaleLayout {
+    /// Whole-number magnification, at least 1.
+    pub scale: u32,
+    /// Letterbox offset of the image's top-left corner, in window pixels.
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Largest integer magnification of a `base_width`x`base_height` image that
+/// fits the window, centered with the remainder left as black bars. Windows
+/// smaller than the base report a scale of 1.
+pub fn integer_scale_layout(base_width: u32, base_height: u32, window_width: u32, window_height: u32) -> PixelScaleLayout {
+    let base_width = base_width.max(1);
+    let base_height = base_height.max(1);
+    let scale = (window_width / base_width).min(window_height / base_height).max(1);
+    let width = base_width * scale;
+    let height = base_height * scale;
+    PixelScaleLayout {
+        scale,
+        x: window_width.saturating_sub(width) / 2,
+        y: window_height.saturating_sub(height) / 2,
+        width,
+        height,
+    }
+}
+
+/// Renders the scene into a fixed low-resolution target and blits it to the
+/// window at an integer scale with nearest filtering, so pixel art stays crisp.
+pub struct PixelScalePass {
+    target: RenderTarget,
+    pipeline: wgpu::RenderPipeline,
+    bind_group: wgpu::BindGroup,
+    labels: Labels,
+}
+
+impl PixelScalePass {
+    pub fn new(
+        device: &wgpu::Device,
+        labels: &Labels,
+        config: &wgpu::SurfaceConfiguration,
+        base_width: u32,
+        base_height: u32,
+    ) -> Self {
+        let target = RenderTarget::new(device, labels, config, base_width, base_height);
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(&labels.label("Pixel Scale Shader")),
+            source: wgpu::ShaderSource::Wgsl(include_str!("pixel_scale.wgsl").into()),
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+            label: Some(&labels.label("pixel_scale_bind_group_layout")),
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some(&labels.label("Pixel Scale Pipeline Layout")),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(&labels.label("Pixel Scale Pipeline")),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: config.format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some(&labels.label("Pixel Scale Sampler")),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Nearest,
+            min_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(target.color_view()),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+            ],
+            label: Some(&labels.label("pixel_scale_bind_group")),
+        });
+
+        Self {
+            target,
+            pipeline,
+            bind_group,
+            labels: labels.clone(),
+        }
+    }
+
+    /// Low-resolution target the scene is rendered into.
+    pub fn target(&self) -> &RenderTarget {
+        &self.target
+    }
+
+    pub fn layout(&self, window_width: u32, window_height: u32) -> PixelScaleLayout {
+        integer_scale_layout(self.target.width, self.target.height, window_width, window_height)
+    }
+
+    /// Clears `output` to black and draws the scaled image into its letterbox.
+    pub fn blit(&self, encoder: &mut wgpu::CommandEncoder, output: &wgpu::TextureView, window_width: u32, window_height: u32) {
+        let layout = self.layout(window_width, window_height);
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some(&self.labels.label("Pixel Scale Pass")),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: output,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        // The viewport must stay inside the attachment, so a window smaller
+        // than the base resolution gets a shrunken (non-integer) image
+        render_pass.set_viewport(
+            layout.x as f32,
+            layout.y as f32,
+            layout.width.min(window_width) as f32,
+            layout.height.min(window_height) as f32,
+            0.0,
+            1.0,
+        );
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn largest_fitting_factor_is_chosen_and_letterboxed() {
+        // 320x180 fits three times vertically but only twice across 800 pixels
+        let layout = integer_scale_layout(320, 180, 800, 600);
+        assert_eq!(
+            layout,
+            PixelScaleLayout {
+                scale: 2,
+                x: 80,
+                y: 120,
+                width: 640,
+                height: 360,
+            }
+        );
+
+        let layout = integer_scale_layout(320, 180, 1024, 600);
+        assert_eq!(
+            layout,
+            PixelScaleLayout {
+                scale: 3,
+                x: 32,
+                y: 30,
+                width: 960,
+                height: 540,
+            }
+        );
+    }
+
+    #[test]
+    fn exact_multiples_have_no_bars() {
+        let layout = integer_scale_layout(320, 180, 1920, 1080);
+        assert_eq!((layout.scale, layout.x, layout.y), (6, 0, 0));
+    }
+
+    #[test]
+    fn windows_smaller_than_the_base_use_a_scale_of_one() {
+        let layout = integer_scale_layout(320, 180, 200, 100);
+        assert_eq!((layout.scale, layout.x, layout.y), (1, 0, 0));
+        assert_eq!(integer_scale_layout(0, 0, 10, 10).scale, 10);
+    }
+}
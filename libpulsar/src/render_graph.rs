@@ -0,0 +1,171 @@
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum RenderGraphError {
+    #[error("Pass \"{pass}\" depends on unknown pass \"{dependency}\"")]
+    UnknownDependency { pass: String, dependency: String },
+    #[error("Passes depend on each other in a cycle through \"{0}\"")]
+    Cycle(String),
+}
+
+/// Order to run passes in, as indices into `passes`, given each pass's name
+/// and the names of the passes it reads the output of. Independent passes
+/// keep the order they were added in.
+pub fn execution_order(passes: &[(&str, &[&str])]) -> Result<Vec<usize>, RenderGraphError> {
+    let index_of = |name: &str| passes.iter().position(|(pass, _)| *pass == name);
+    let mut dependencies = Vec::with_capacity(passes.len());
+    for (name, deps) in passes {
+        let resolved = deps
+            .iter()
+            .map(|dep| {
+                index_of(dep).ok_or_else(|| RenderGraphError::UnknownDependency {
+                    pass: name.to_string(),
+                    dependency: dep.to_string(),
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        dependencies.push(resolved);
+    }
+
+    let mut order = Vec::with_capacity(passes.len());
+    let mut scheduled = vec![false; passes.len()];
+    while order.len() < passes.len() {
+        // The first pass whose dependencies have all run
+        let Some(next) = (0..passes.len())
+            .find(|&i| !scheduled[i] && dependencies[i].iter().all(|&dep| scheduled[dep]))
+        else {
+            let stuck = (0..passes.len()).find(|&i| !scheduled[i]).unwrap_or_default();
+            return Err(RenderGraphError::Cycle(passes[stuck].0.to_string()));
+        };
+        scheduled[next] = true;
+        order.push(next);
+    }
+    Ok(order)
+}
+
+type RecordPass<'a> = Box<dyn FnOnce(&mut wgpu::CommandEncoder) + 'a>;
+
+struct GraphPass<'a> {
+    name: &'a str,
+    dependencies: &'a [&'a str],
+    record: RecordPass<'a>,
+}
+
+/// One frame's passes. Each pass records into its own command buffer, and
+/// `execute` hands all of them to a single `queue.submit` in dependency
+/// order, so adding passes doesn't add submissions.
+#[derive(Default)]
+pub struct RenderGraph<'a> {
+    passes: Vec<GraphPass<'a>>,
+}
+
+impl<'a> RenderGraph<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a pass that runs after every pass named in `dependencies`.
+    pub fn add_pass(
+        &mut self,
+        name: &'a str,
+        dependencies: &'a [&'a str],
+        record: impl FnOnce(&mut wgpu::CommandEncoder) + 'a,
+    ) -> &mut Self {
+        self.passes.push(GraphPass {
+            name,
+            dependencies,
+            record: Box::new(record),
+        });
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.passes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.passes.is_empty()
+    }
+
+    /// Records every pass into its own command buffer, in execution order.
+    pub fn record(self, device: &wgpu::Device) -> Result<Vec<wgpu::CommandBuffer>, RenderGraphError> {
+        let names: Vec<_> = self.passes.iter().map(|pass| (pass.name, pass.dependencies)).collect();
+        let order = execution_order(&names)?;
+        let mut passes: Vec<_> = self.passes.into_iter().map(Some).collect();
+        Ok(order
+            .into_iter()
+            .filter_map(|index| passes[index].take())
+            .map(|pass| {
+                let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some(pass.name) });
+                (pass.record)(&mut encoder);
+                encoder.finish()
+            })
+            .collect())
+    }
+
+    /// Records every pass and submits the command buffers together.
+    pub fn execute(self, device: &wgpu::Device, queue: &wgpu::Queue) -> Result<wgpu::SubmissionIndex, RenderGraphError> {
+        Ok(queue.submit(self.record(device)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_gpu;
+    use std::cell::RefCell;
+
+    #[test]
+    fn dependencies_run_first_and_independent_passes_keep_their_order() {
+        let passes: [(&str, &[&str]); 4] = [
+            ("overlay", &["main"]),
+            ("shadow", &[]),
+            ("main", &["shadow"]),
+            ("ui", &[]),
+        ];
+        assert_eq!(execution_order(&passes), Ok(vec![1, 2, 0, 3]));
+    }
+
+    #[test]
+    fn unknown_dependencies_and_cycles_are_errors() {
+        assert_eq!(
+            execution_order(&[("main", &["shadow"])]),
+            Err(RenderGraphError::UnknownDependency {
+                pass: "main".to_string(),
+                dependency: "shadow".to_string(),
+            })
+        );
+        assert_eq!(
+            execution_order(&[("a", &["b"]), ("b", &["a"])]),
+            Err(RenderGraphError::Cycle("a".to_string()))
+        );
+    }
+
+    #[test]
+    fn each_pass_records_one_command_buffer_in_dependency_order() {
+        let Some((device, queue)) = test_gpu::device() else {
+            return;
+        };
+        let recorded = RefCell::new(Vec::new());
+        let names = ["post", "main", "shadow", "ui", "overlay"];
+        let dependencies: [&[&str]; 5] = [&["main"], &["shadow"], &[], &["post"], &[]];
+        let graph = || {
+            let mut graph = RenderGraph::new();
+            for (name, deps) in names.iter().zip(&dependencies) {
+                let recorded = &recorded;
+                graph.add_pass(name, deps, move |_| recorded.borrow_mut().push(*name));
+            }
+            graph
+        };
+
+        let buffers = graph().record(&device).unwrap();
+        assert_eq!(buffers.len(), names.len());
+        assert_eq!(*recorded.borrow(), ["shadow", "main", "post", "ui", "overlay"]);
+
+        // All five go to the queue in a single submission
+        recorded.borrow_mut().clear();
+        let submission = graph().execute(&device, &queue).unwrap();
+        let _ = device.poll(wgpu::Maintain::WaitForSubmissionIndex(submission));
+        assert_eq!(recorded.borrow().len(), names.len());
+    }
+}
@@ -0,0 +1,155 @@
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+
+use thiserror::Error;
+
+use crate::assets::AssetHandle;
+use crate::camera::CameraUniform;
+
+/// Commands a game-logic thread can send to the render thread.
+#[derive(Debug, Clone)]
+pub enum RenderCommand {
+    /// Replaces the camera. Only the last one sent before a drain is used.
+    SetCamera(CameraUniform),
+    DrawMesh(AssetHandle),
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum RenderQueueError {
+    #[error("The render queue was dropped")]
+    Closed,
+    #[error("The render queue is full")]
+    Full,
+}
+
+/// Everything drained from the queue for one frame.
+#[derive(Debug, Clone, Default)]
+pub struct RenderFrame {
+    /// The most recent `SetCamera`, if any arrived since the last drain.
+    pub camera: Option<CameraUniform>,
+    /// Meshes in the order their `DrawMesh` commands were sent.
+    pub meshes: Vec<AssetHandle>,
+}
+
+impl RenderFrame {
+    /// Folds `command` into the frame, keeping submission order.
+    pub fn apply(&mut self, command: RenderCommand) {
+        match command {
+            RenderCommand::SetCamera(camera) => self.camera = Some(camera),
+            RenderCommand::DrawMesh(mesh) => self.meshes.push(mesh),
+        }
+    }
+}
+
+/// Sending half of a `RenderQueue`; clone it for each producer thread.
+#[derive(Debug, Clone)]
+pub struct RenderSender {
+    sender: SyncSender<RenderCommand>,
+}
+
+impl RenderSender {
+    /// Queues `command`, blocking while the queue is full so a simulation
+    /// running ahead of the renderer is slowed down to match.
+    pub fn send(&self, command: RenderCommand) -> Result<(), RenderQueueError> {
+        self.sender.send(command).map_err(|_| RenderQueueError::Closed)
+    }
+
+    /// Queues `command` without blocking, failing if the queue is full.
+    pub fn try_send(&self, command: RenderCommand) -> Result<(), RenderQueueError> {
+        self.sender.try_send(command).map_err(|err| match err {
+            TrySendError::Full(_) => RenderQueueError::Full,
+            TrySendError::Disconnected(_) => RenderQueueError::Closed,
+        })
+    }
+}
+
+/// Receiving half, owned by the render thread and drained once per frame.
+pub struct RenderQueue {
+    receiver: Receiver<RenderCommand>,
+}
+
+impl RenderQueue {
+    /// A queue holding at most `capacity` commands before senders block.
+    pub fn bounded(capacity: usize) -> (RenderSender, RenderQueue) {
+        let (sender, receiver) = mpsc::sync_channel(capacity.max(1));
+        (RenderSender { sender }, RenderQueue { receiver })
+    }
+
+    /// Takes every command queued so far without waiting for more.
+    pub fn drain(&self) -> RenderFrame {
+        let mut frame = RenderFrame::default();
+        for command in self.receiver.try_iter() {
+            frame.apply(command);
+        }
+        frame
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec3;
+
+    use super::*;
+    use crate::camera::Camera;
+
+    fn mesh(id: u64) -> RenderCommand {
+        RenderCommand::DrawMesh(AssetHandle(id))
+    }
+
+    #[test]
+    fn drain_keeps_submission_order_and_the_last_camera() {
+        let (sender, queue) = RenderQueue::bounded(8);
+        let mut moved = CameraUniform::new();
+        moved.update_view_proj(&Camera::new(Vec3::new(0.0, 0.0, 5.0), 1.0));
+
+        sender.send(RenderCommand::SetCamera(CameraUniform::new())).unwrap();
+        sender.send(mesh(2)).unwrap();
+        sender.send(RenderCommand::SetCamera(moved)).unwrap();
+        sender.send(mesh(1)).unwrap();
+
+        let frame = queue.drain();
+        assert_eq!(frame.camera.map(|camera| camera.position()), Some(moved.position()));
+        assert_eq!(frame.meshes, [AssetHandle(2), AssetHandle(1)]);
+
+        // Nothing new arrived, so the next frame is empty
+        let next = queue.drain();
+        assert!(next.camera.is_none() && next.meshes.is_empty());
+    }
+
+    #[test]
+    fn full_queue_rejects_try_send_until_drained() {
+        let (sender, queue) = RenderQueue::bounded(2);
+        sender.try_send(mesh(0)).unwrap();
+        sender.try_send(mesh(1)).unwrap();
+        assert_eq!(sender.try_send(mesh(2)), Err(RenderQueueError::Full));
+        assert_eq!(queue.drain().meshes.len(), 2);
+        sender.try_send(mesh(2)).unwrap();
+    }
+
+    #[test]
+    fn zero_capacity_still_buffers_one_command() {
+        let (sender, queue) = RenderQueue::bounded(0);
+        sender.try_send(mesh(0)).unwrap();
+        assert_eq!(sender.try_send(mesh(1)), Err(RenderQueueError::Full));
+        assert_eq!(queue.drain().meshes.len(), 1);
+    }
+
+    #[test]
+    fn sending_after_the_queue_is_dropped_fails() {
+        let (sender, queue) = RenderQueue::bounded(4);
+        drop(queue);
+        assert_eq!(sender.send(mesh(0)), Err(RenderQueueError::Closed));
+        assert_eq!(sender.try_send(mesh(0)), Err(RenderQueueError::Closed));
+    }
+
+    #[test]
+    fn commands_from_other_threads_arrive_in_order() {
+        let (sender, queue) = RenderQueue::bounded(4);
+        let producer = std::thread::spawn(move || {
+            for i in 0..4 {
+                sender.send(mesh(i)).unwrap();
+            }
+        });
+        producer.join().unwrap();
+        assert_eq!(queue.drain().meshes, (0..4).map(AssetHandle).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,295 @@
+use crate::engine::render::config::Labels;
+use crate::engine::stats::FrameStats;
+use crate::render_target::RenderTarget;
+
+pub const MIN_RENDER_SCALE: f32 = 0.5;
+pub const MAX_RENDER_SCALE: f32 = 2.0;
+
+/// Clamps `scale` into `MIN_RENDER_SCALE..=MAX_RENDER_SCALE`. NaN falls back to 1.
+pub fn clamp_render_scale(scale: f32) -> f32 {
+    if scale.is_nan() {
+        return 1.0;
+    }
+    scale.clamp(MIN_RENDER_SCALE, MAX_RENDER_SCALE)
+}
+
+/// Size of the offscreen target for a `width`x`height` window at `scale`,
+/// rounded to the nearest pixel and at least 1x1.
+pub fn scaled_size(width: u32, height: u32, scale: f32) -> (u32, u32) {
+    let scale = clamp_render_scale(scale);
+    let scaled = |size: u32| ((size as f32 * scale).round() as u32).max(1);
+    (scaled(width), scaled(height))
+}
+
+/// Adjusts the render scale from frame timings to hold a target frame rate.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DynamicScale {
+    pub target_fps: f32,
+    /// Scale change applied per adjustment.
+    pub step: f32,
+    /// Fraction of the target the frame rate may drift either way before the
+    /// scale changes, so it does not oscillate around the target.
+    pub tolerance: f32,
+    /// Frames recorded since the last adjustment before the next one; the
+    /// stats window needs time to reflect the previous change.
+    pub interval: u64,
+}
+
+impl Default for DynamicScale {
+    fn default() -> Self {
+        Self {
+            target_fps: 60.0,
+            step: 0.05,
+            tolerance: 0.1,
+            interval: 30,
+        }
+    }
+}
+
+impl DynamicScale {
+    /// New scale for an average frame rate of `fps` at `scale`: lowered when
+    /// below the target band, raised when comfortably above it.
+    pub fn adjust(&self, scale: f32, fps: f32) -> f32 {
+        if fps < self.target_fps * (1.0 - self.tolerance) {
+            clamp_render_scale(scale - self.step)
+        } else if fps > self.target_fps * (1.0 + self.tolerance) {
+            clamp_render_scale(scale + self.step)
+        } else {
+            scale
+        }
+    }
+
+    /// Like `adjust`, reading the frame rate from `stats`. Returns `None` when
+    /// there is nothing to measure yet.
+    pub fn adjust_from_stats(&self, scale: f32, stats: &FrameStats) -> Option<f32> {
+        stats.average_fps().map(|fps| self.adjust(scale, fps))
+    }
+}
+
+/// Renders the scene into a target sized by the render scale and stretches it
+/// over the whole window with linear filtering.
+pub struct RenderScalePass {
+    target: RenderTarget,
+    pipeline: wgpu::RenderPipeline,
+    bind_group: wgpu::BindGroup,
+    scale: f32,
+    labels: Labels,
+}
+
+impl RenderScalePass {
+    pub fn new(
+        device: &wgpu::Device,
+        labels: &Labels,
+        config: &wgpu::SurfaceConfiguration,
+        scale: f32,
+    ) -> Self {
+        let scale = clamp_render_scale(scale);
+        let (width, height) = scaled_size(config.width, config.height, scale);
+        let target = RenderTarget::new(device, labels, config, width, height);
+
+        // The pixel scale blit is a plain textured triangle; the filtering
+        // comes from the sampler bound below.
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(&labels.label("Render Scale Shader")),
+            source: wgpu::ShaderSource::Wgsl(include_str!("pixel_scale.wgsl").into()),
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+            label: Some(&labels.label("render_scale_bind_group_layout")),
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some(&labels.label("Render Scale Pipeline Layout")),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(&labels.label("Render Scale Pipeline")),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: config.format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some(&labels.label("Render Scale Sampler")),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(target.color_view()),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+            ],
+            label: Some(&labels.label("render_scale_bind_group")),
+        });
+
+        Self {
+            target,
+            pipeline,
+            bind_group,
+            scale,
+            labels: labels.clone(),
+        }
+    }
+
+    /// Offscreen target the scene is rendered into.
+    pub fn target(&self) -> &RenderTarget {
+        &self.target
+    }
+
+    pub fn scale(&self) -> f32 {
+        self.scale
+    }
+
+    /// Stretches the target over all of `output`.
+    pub fn blit(&self, encoder: &mut wgpu::CommandEncoder, output: &wgpu::TextureView) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some(&self.labels.label("Render Scale Pass")),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: output,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_gpu;
+
+    #[test]
+    fn render_scale_is_clamped_and_nan_means_native() {
+        assert_eq!(clamp_render_scale(0.1), MIN_RENDER_SCALE);
+        assert_eq!(clamp_render_scale(4.0), MAX_RENDER_SCALE);
+        assert_eq!(clamp_render_scale(0.75), 0.75);
+        assert_eq!(clamp_render_scale(f32::NAN), 1.0);
+    }
+
+    #[test]
+    fn scaled_size_rounds_and_never_reaches_zero() {
+        assert_eq!(scaled_size(1920, 1080, 0.5), (960, 540));
+        assert_eq!(scaled_size(1920, 1080, 0.75), (1440, 810));
+        assert_eq!(scaled_size(801, 601, 0.5), (401, 301));
+        assert_eq!(scaled_size(800, 600, 10.0), (1600, 1200));
+        assert_eq!(scaled_size(1, 0, 0.5), (1, 1));
+    }
+
+    #[test]
+    fn dynamic_scale_only_moves_outside_the_tolerance_band() {
+        let dynamic = DynamicScale::default();
+        assert_eq!(dynamic.adjust(1.0, 60.0), 1.0);
+        assert_eq!(dynamic.adjust(1.0, 55.0), 1.0);
+        assert_eq!(dynamic.adjust(1.0, 65.0), 1.0);
+        assert_eq!(dynamic.adjust(1.0, 40.0), 0.95);
+        assert_eq!(dynamic.adjust(1.0, 90.0), 1.05);
+        // The step never leaves the supported range
+        assert_eq!(dynamic.adjust(MIN_RENDER_SCALE, 10.0), MIN_RENDER_SCALE);
+        assert_eq!(dynamic.adjust(MAX_RENDER_SCALE, 500.0), MAX_RENDER_SCALE);
+    }
+
+    #[test]
+    fn dynamic_scale_waits_for_frame_timings() {
+        let dynamic = DynamicScale::default();
+        let mut stats = FrameStats::new(8);
+        assert_eq!(dynamic.adjust_from_stats(1.0, &stats), None);
+        for _ in 0..8 {
+            stats.record(1.0 / 30.0);
+        }
+        assert_eq!(dynamic.adjust_from_stats(1.0, &stats), Some(0.95));
+    }
+
+    #[test]
+    fn half_scale_target_is_stretched_over_the_whole_output() {
+        let Some((device, queue)) = test_gpu::device() else {
+            return;
+        };
+        let labels = Labels::default();
+        let config = test_gpu::surface_config(16, 8);
+        let pass = RenderScalePass::new(&device, &labels, &config, 0.5);
+        assert_eq!((pass.target().width, pass.target().height), (8, 4));
+        assert_eq!(pass.scale(), 0.5);
+
+        let output = RenderTarget::new(&device, &labels, &config, 16, 8);
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: None,
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: pass.target().color_view(),
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::GREEN),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        pass.blit(&mut encoder, output.color_view());
+        queue.submit(std::iter::once(encoder.finish()));
+
+        let pixels = test_gpu::read_pixels(&device, &queue, &output.color_texture.0);
+        assert_eq!(pixels.len(), 16 * 8 * 4);
+        assert!(pixels.chunks_exact(4).all(|pixel| pixel == [0, 255, 0, 255]), "{pixels:?}");
+    }
+}
@@ -0,0 +1,145 @@
+use crate::engine::render::config::Labels;
+use crate::mesh::Mesh;
+use crate::resources::{ResourceCategory, Tracked};
+
+/// Offscreen color and depth textures the scene can be rendered into, then
+/// sampled from a later pass (mirrors, portals, minimaps).
+pub struct RenderTarget {
+    pub color_texture: (Tracked<wgpu::Texture>, wgpu::TextureView),
+    pub depth_texture: (Tracked<wgpu::Texture>, wgpu::TextureView),
+    pub sampler: wgpu::Sampler,
+    pub width: u32,
+    pub height: u32,
+    pub format: wgpu::TextureFormat,
+    /// Set for reflection targets whose camera mirrors the scene; meshes are
+    /// then drawn with flipped front faces so culling stays correct.
+    pub mirrored: bool,
+}
+
+impl RenderTarget {
+    /// Creates a `width`x`height` target. The color format is taken from
+    /// `config` so the target is compatible with pipelines built for the surface.
+    pub fn new(
+        device: &wgpu::Device,
+        labels: &Labels,
+        config: &wgpu::SurfaceConfiguration,
+        width: u32,
+        height: u32,
+    ) -> Self {
+        Self::with_format(device, labels, config, width, height, config.format)
+    }
+
+    /// Like `new`, but with an explicit color format, e.g. `Rgba16Float` for
+    /// HDR scene rendering.
+    pub fn with_format(
+        device: &wgpu::Device,
+        labels: &Labels,
+        config: &wgpu::SurfaceConfiguration,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+    ) -> Self {
+        let width = width.max(1);
+        let height = height.max(1);
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        };
+
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(&labels.label("Render Target Color Texture")),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        // Reuse the mesh depth helper with a configuration sized to the target
+        let depth_config = wgpu::SurfaceConfiguration {
+            width,
+            height,
+            ..config.clone()
+        };
+        let depth_texture = Mesh::create_depth_texture(device, labels, &depth_config);
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some(&labels.label("Render Target Sampler")),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        Self {
+            color_texture: (Tracked::texture(texture, ResourceCategory::RenderTarget), view),
+            depth_texture,
+            sampler,
+            width,
+            height,
+            format,
+            mirrored: false,
+        }
+    }
+
+    pub fn color_view(&self) -> &wgpu::TextureView {
+        &self.color_texture.1
+    }
+
+    pub fn depth_view(&self) -> &wgpu::TextureView {
+        &self.depth_texture.1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::CameraUniform;
+    use crate::mesh::Vertex;
+    use crate::renderer::Renderer;
+    use crate::test_gpu;
+
+    #[test]
+    fn target_takes_the_surface_format_and_at_least_one_pixel() {
+        let Some((device, _queue)) = test_gpu::device() else {
+            return;
+        };
+        let config = test_gpu::surface_config(64, 64);
+        let target = RenderTarget::new(&device, &Labels::default(), &config, 0, 0);
+        assert_eq!((target.width, target.height), (1, 1));
+        assert_eq!(target.format, config.format);
+        assert!(!target.mirrored);
+    }
+
+    #[test]
+    fn render_to_draws_into_the_target() {
+        let Some((device, queue)) = test_gpu::device() else {
+            return;
+        };
+        let labels = Labels::default();
+        let config = test_gpu::surface_config(8, 8);
+        let renderer = pollster::block_on(Renderer::new(&device, &labels, &queue, &config)).unwrap();
+        let target = RenderTarget::new(&device, &labels, &config, 8, 8);
+
+        // One counter-clockwise triangle covering all of clip space
+        let vertices = [[-1.0, -1.0], [3.0, -1.0], [-1.0, 3.0]]
+            .map(|[x, y]| Vertex::new([x, y, 0.5], [1.0, 0.0, 0.0], [0.0, 0.0, 1.0]));
+        let mesh = Mesh::from_vertices(&device, &labels, &config, &vertices);
+        renderer.render_to(&device, &queue, &target, &CameraUniform::new(), &[&mesh]);
+
+        let pixels = test_gpu::read_pixels(&device, &queue, &target.color_texture.0);
+        assert!(
+            pixels.chunks_exact(4).all(|pixel| pixel == [255, 0, 0, 255]),
+            "{pixels:?}"
+        );
+    }
+}
@@ -1,38 +1,354 @@
+use std::cell::Cell;
+
+use futures::executor::block_on;
+use glam::Vec3;
+use log::{error, warn};
+use thiserror::Error;
 use wgpu::util::DeviceExt;
-use crate::{camera::CameraUniform, mesh::Vertex};
+
+use crate::axes_overlay::AxesOverlay;
+use crate::engine::render::config::Labels;
+use crate::engine::render::ctx::{capture_errors, ContextError};
+use crate::engine::stats::FrameStats;
+use crate::base::{ActorId, Scene};
+use crate::bloom::{BloomPass, BloomSettings, HDR_FORMAT};
+use crate::camera::Camera;
+use crate::cascaded_shadow::{shadow_bind_group_layout, CascadedShadowMaps};
+use crate::debug_lines::DebugLines;
+use crate::decal::DecalRenderer;
+use crate::deferred::{DeferredError, DeferredRenderer, PointLight};
+use crate::depth_resolve::{needs_depth_resolve, DepthResolveMode, DepthResolvePass};
+use crate::draw_list::{sort_draws, DrawCommand};
+use crate::material::{material_bind_group_layout, EnvironmentMap, Material, MaterialId, Texture};
+use crate::math::{Aabb, Matrix4, Transform};
+use crate::outline::{Outline, OutlinePass};
+use crate::pixel_scale::PixelScalePass;
+use crate::render_graph::RenderGraph;
+use crate::render_queue::{RenderFrame, RenderQueue};
+use crate::render_scale::{clamp_render_scale, DynamicScale, RenderScalePass};
+use crate::{camera::CameraUniform, fxaa::FxaaPass, mesh::{DrawCall, Vertex, DEPTH_FORMAT}, render_target::RenderTarget};
+use crate::resources::{ResourceCategory, Tracked};
+use crate::shader::{self, ShaderError};
+use crate::shadow::{ShadowQuality, ShadowSettings, MAX_CASCADES};
+use crate::ssao::SsaoSettings;
+use crate::tonemap::{supports_hdr, TonemapPass};
+
+/// Background the main pass clears to.
+const CLEAR_COLOR: wgpu::Color = wgpu::Color {
+    r: 0.1,
+    g: 0.2,
+    b: 0.3,
+    a: 1.0,
+};
+
+/// Color of actor bounds drawn by `Renderer::collect_debug_bounds`.
+pub const DEBUG_BOUNDS_COLOR: [f32; 3] = [1.0, 0.85, 0.0];
+/// Color of the camera frustum drawn by `Renderer::collect_debug_bounds`.
+pub const DEBUG_FRUSTUM_COLOR: [f32; 3] = [1.0, 0.0, 1.0];
+/// The shadow-casting light shines straight down until `set_light_direction`.
+const DEFAULT_LIGHT_DIRECTION: Vec3 = Vec3::NEG_Y;
+
+/// Anti-aliasing applied to the main pass.
+///
+/// `Msaa` gives the cleanest geometry edges but multiplies the color and depth
+/// memory and fill cost by the sample count. `Fxaa` is a single cheap
+/// full-screen pass that also smooths shading and texture edges, at the price
+/// of slightly blurring fine detail.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AaMode {
+    #[default]
+    None,
+    /// Multisampling with the given sample count (4 is supported everywhere).
+    /// A count of 1 is the same as `None`.
+    Msaa(u32),
+    Fxaa,
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum AaError {
+    #[error("{samples}x MSAA is not supported for {format:?}")]
+    UnsupportedSampleCount { samples: u32, format: wgpu::TextureFormat },
+}
+
+/// Checks that every target format of an MSAA pass, given with the adapter's
+/// feature flags for it, can be multisampled `samples` times.
+pub fn check_msaa_support(
+    samples: u32,
+    formats: &[(wgpu::TextureFormat, wgpu::TextureFormatFeatureFlags)],
+) -> Result<(), AaError> {
+    match formats.iter().find(|(_, flags)| !flags.sample_count_supported(samples)) {
+        Some(&(format, _)) => Err(AaError::UnsupportedSampleCount { samples, format }),
+        None => Ok(()),
+    }
+}
+
+/// Which depth pass a main-pass pipeline is built for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DepthStage {
+    /// Single pass: test `Less` and write depth.
+    Default,
+    /// Depth-only prepass with no color target.
+    Prepass,
+    /// Shading pass after a prepass: test `Equal`, depth already written.
+    AfterPrepass,
+}
+
+impl DepthStage {
+    /// With `reverse_z`, near is 1 and far is 0, so "closer" means greater.
+    fn depth_stencil(self, reverse_z: bool) -> wgpu::DepthStencilState {
+        let closer = if reverse_z {
+            wgpu::CompareFunction::Greater
+        } else {
+            wgpu::CompareFunction::Less
+        };
+        let (depth_write_enabled, depth_compare) = match self {
+            DepthStage::Default | DepthStage::Prepass => (true, closer),
+            DepthStage::AfterPrepass => (false, wgpu::CompareFunction::Equal),
+        };
+        wgpu::DepthStencilState {
+            format: wgpu::TextureFormat::Depth32Float,
+            depth_write_enabled,
+            depth_compare,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }
+    }
+
+    /// The prepass only fills the depth buffer, so it runs no fragment shader.
+    fn has_color_target(self) -> bool {
+        self != DepthStage::Prepass
+    }
+}
+
+/// Everything that varies between the main-pass pipelines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct PipelineKey {
+    sample_count: u32,
+    stage: DepthStage,
+    front_face: wgpu::FrontFace,
+    topology: wgpu::PrimitiveTopology,
+    reverse_z: bool,
+    /// Clamp depth to the far plane instead of clipping geometry past it.
+    unclipped_depth: bool,
+}
+
+impl PipelineKey {
+    fn new(sample_count: u32) -> Self {
+        Self {
+            sample_count,
+            stage: DepthStage::Default,
+            front_face: wgpu::FrontFace::Ccw,
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            reverse_z: false,
+            unclipped_depth: false,
+        }
+    }
+}
+
+/// Main-pass pipelines for one sample count.
+struct PipelineSet {
+    /// Used for everything without a prepass, and for transparent meshes with one.
+    color: wgpu::RenderPipeline,
+    /// `color` for meshes with clockwise front faces.
+    color_cw: wgpu::RenderPipeline,
+    /// Color pipelines for meshes that aren't triangle lists.
+    topologies: Vec<(wgpu::PrimitiveTopology, wgpu::FrontFace, wgpu::RenderPipeline)>,
+    /// `(prepass, after_prepass)` when the depth prepass is enabled.
+    prepass: Option<(wgpu::RenderPipeline, wgpu::RenderPipeline)>,
+}
+
+impl PipelineSet {
+    /// The color pipeline for `mesh`, using the opposite winding variant in
+    /// mirrored passes.
+    fn for_mesh(&self, mesh: &crate::mesh::Mesh, mirrored: bool) -> &wgpu::RenderPipeline {
+        self.get(self.id_for_mesh(mesh, mirrored))
+    }
+
+    /// Stable id of `for_mesh`'s pipeline: 0 for `color`, 1 for `color_cw`,
+    /// then 2 onwards in `topologies` order.
+    fn id_for_mesh(&self, mesh: &crate::mesh::Mesh, mirrored: bool) -> usize {
+        let front_face = pass_front_face(mesh.front_face, mirrored);
+        match (mesh.topology, front_face) {
+            (wgpu::PrimitiveTopology::TriangleList, wgpu::FrontFace::Ccw) => 0,
+            (wgpu::PrimitiveTopology::TriangleList, wgpu::FrontFace::Cw) => 1,
+            (topology, front_face) => {
+                let front_face = topology_front_face(topology, front_face);
+                self.topologies
+                    .iter()
+                    .position(|(t, f, _)| *t == topology && *f == front_face)
+                    .map(|index| index + 2)
+                    .expect("a pipeline is built for every topology")
+            }
+        }
+    }
+
+    fn get(&self, id: usize) -> &wgpu::RenderPipeline {
+        match id {
+            0 => &self.color,
+            1 => &self.color_cw,
+            _ => &self.topologies[id - 2].2,
+        }
+    }
+}
+
+/// Topologies besides `TriangleList` that get their own pipelines, with the
+/// front faces each is built for.
+const EXTRA_TOPOLOGIES: [(wgpu::PrimitiveTopology, wgpu::FrontFace); 5] = [
+    (wgpu::PrimitiveTopology::TriangleStrip, wgpu::FrontFace::Ccw),
+    (wgpu::PrimitiveTopology::TriangleStrip, wgpu::FrontFace::Cw),
+    (wgpu::PrimitiveTopology::LineList, wgpu::FrontFace::Ccw),
+    (wgpu::PrimitiveTopology::LineStrip, wgpu::FrontFace::Ccw),
+    (wgpu::PrimitiveTopology::PointList, wgpu::FrontFace::Ccw),
+];
+
+/// Lines and points have no faces, so their winding never selects a pipeline.
+fn topology_front_face(topology: wgpu::PrimitiveTopology, front_face: wgpu::FrontFace) -> wgpu::FrontFace {
+    match topology {
+        wgpu::PrimitiveTopology::TriangleList | wgpu::PrimitiveTopology::TriangleStrip => front_face,
+        _ => wgpu::FrontFace::Ccw,
+    }
+}
+
+/// Index format strips restart on. Meshes index with `u16`, and wgpu needs
+/// the format up front for indexed strip draws; lists must leave it unset.
+pub fn strip_index_format(topology: wgpu::PrimitiveTopology) -> Option<wgpu::IndexFormat> {
+    topology.is_strip().then_some(wgpu::IndexFormat::Uint16)
+}
+
+/// Whether pipelines on a device with `features` can clamp depth instead of
+/// clipping against the near and far planes.
+pub fn supports_depth_clamp(features: wgpu::Features) -> bool {
+    features.contains(wgpu::Features::DEPTH_CLIP_CONTROL)
+}
+
+/// Primitive state of a main-pass pipeline. Lines and points are never culled.
+pub fn primitive_state(topology: wgpu::PrimitiveTopology, front_face: wgpu::FrontFace) -> wgpu::PrimitiveState {
+    let has_faces = matches!(
+        topology,
+        wgpu::PrimitiveTopology::TriangleList | wgpu::PrimitiveTopology::TriangleStrip
+    );
+    wgpu::PrimitiveState {
+        topology,
+        strip_index_format: strip_index_format(topology),
+        front_face,
+        cull_mode: has_faces.then_some(wgpu::Face::Back),
+        polygon_mode: wgpu::PolygonMode::Fill,
+        unclipped_depth: false,
+        conservative: false,
+    }
+}
+
+/// Front face a mesh is drawn with in a pass. Mirroring the view (e.g. a
+/// reflection camera) reverses the on-screen winding of every triangle, so
+/// the front face flips and back-face culling keeps removing the far side.
+pub fn pass_front_face(front_face: wgpu::FrontFace, mirrored: bool) -> wgpu::FrontFace {
+    match (front_face, mirrored) {
+        (face, false) => face,
+        (wgpu::FrontFace::Ccw, true) => wgpu::FrontFace::Cw,
+        (wgpu::FrontFace::Cw, true) => wgpu::FrontFace::Ccw,
+    }
+}
+
+/// Draw-sorting key of a mesh's material: 0 for the renderer's default
+/// material, `id + 1` for one added with `Renderer::add_material`.
+fn material_key(mesh: &crate::mesh::Mesh) -> usize {
+    mesh.material.map_or(0, |MaterialId(index)| index + 1)
+}
+
+/// How the main pass shades the scene.
+///
+/// `Forward` shades each mesh as it is drawn. `Deferred` writes surface
+/// attributes to a G-buffer and lights them in one screen-space pass, which
+/// scales far better with many lights but skips MSAA and FXAA.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PipelineMode {
+    #[default]
+    Forward,
+    Deferred,
+}
 
 pub struct Renderer {
-    pipeline: wgpu::RenderPipeline,
+    pipeline: PipelineSet,
+    /// Pipelines matching the MSAA sample count, when multisampling is enabled.
+    msaa_pipeline: Option<PipelineSet>,
     camera_bind_group: wgpu::BindGroup,
     camera_buffer: wgpu::Buffer,
+    material_buffer: wgpu::Buffer,
+    /// Group 1 of every forward pipeline, for meshes without a `material`.
+    material_bind_group: wgpu::BindGroup,
+    /// Uniform buffers and bind groups of the materials added with
+    /// `add_material`, indexed by `MaterialId`.
+    materials: Vec<(wgpu::Buffer, wgpu::BindGroup)>,
+    /// Stands in for a missing base texture; created once.
+    white_texture: Texture,
+    /// Stands in for a material without an environment map; created once.
+    black_env_map: EnvironmentMap,
+    aa: AaMode,
+    msaa_color: Option<(Tracked<wgpu::Texture>, wgpu::TextureView)>,
+    msaa_depth: Option<(Tracked<wgpu::Texture>, wgpu::TextureView)>,
+    /// How to resolve `msaa_depth` for effects that sample depth, or `None`
+    /// to never resolve it.
+    depth_resolve: Option<DepthResolveMode>,
+    /// Present while multisampling, `depth_resolve` and a depth-sampling
+    /// effect are all enabled.
+    resolved_depth: Option<DepthResolvePass>,
+    /// Offscreen scene target the FXAA pass reads from.
+    scene_target: Option<RenderTarget>,
+    fxaa: Option<FxaaPass>,
+    bloom: Option<BloomSettings>,
+    hdr: bool,
+    exposure: f32,
+    /// Scene pipelines targeting `HDR_FORMAT`, built while HDR or bloom is on.
+    hdr_pipeline: Option<PipelineSet>,
+    hdr_target: Option<RenderTarget>,
+    bloom_pass: Option<BloomPass>,
+    /// Scene plus glow, for the tonemapper to read when HDR and bloom are on.
+    bloom_output: Option<RenderTarget>,
+    tonemap: Option<TonemapPass>,
+    shadow: ShadowSettings,
+    /// Group 2 of every forward pipeline; rendered before each frame.
+    shadow_maps: CascadedShadowMaps,
+    /// Direction the shadow-casting directional light shines in.
+    light_direction: Vec3,
+    depth_prepass: bool,
+    reverse_z: bool,
+    depth_clamp: bool,
+    ssao: Option<SsaoSettings>,
+    /// Present while `PipelineMode::Deferred` is selected.
+    deferred: Option<DeferredRenderer>,
+    pixel_scale: Option<PixelScalePass>,
+    render_scale: f32,
+    /// Present while the render scale is not 1.
+    render_scale_pass: Option<RenderScalePass>,
+    dynamic_scale: Option<DynamicScale>,
+    frames_since_scale_change: u64,
+    debug_bounds: bool,
+    axes_overlay: Option<AxesOverlay>,
+    decals: DecalRenderer,
+    outline: Option<(Outline, OutlinePass)>,
+    /// Last uniform passed to `update_camera`, for depth-sorting draws.
+    camera: Cell<CameraUniform>,
+    labels: Labels,
 }
 
 impl Renderer {
-    pub async fn new(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) -> Self {
-        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("Shader"),
-            source: wgpu::ShaderSource::Wgsl(include_str!("shader.wgsl").into()),
-        });
+    /// Fails if the shader or a pipeline is rejected by the device. Every
+    /// resource is labelled under `labels` (see `GpuConfig::labels`).
+    pub async fn new(
+        device: &wgpu::Device,
+        labels: &Labels,
+        queue: &wgpu::Queue,
+        config: &wgpu::SurfaceConfiguration,
+    ) -> Result<Self, ContextError> {
+        let shader = capture_errors(device, "Shader", || Self::create_shader(device, labels)).await?;
 
         let camera_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Camera Buffer"),
+            label: Some(&labels.label("Camera Buffer")),
             contents: bytemuck::cast_slice(&[CameraUniform::new()]),
             usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
         });
 
-        let camera_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            entries: &[wgpu::BindGroupLayoutEntry {
-                binding: 0,
-                visibility: wgpu::ShaderStages::VERTEX,
-                ty: wgpu::BindingType::Buffer {
-                    ty: wgpu::BufferBindingType::Uniform,
-                    has_dynamic_offset: false,
-                    min_binding_size: None,
-                },
-                count: None,
-            }],
-            label: Some("camera_bind_group_layout"),
-        });
+        let camera_bind_group_layout = Self::camera_bind_group_layout(device, labels);
 
         let camera_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
             layout: &camera_bind_group_layout,
@@ -40,104 +356,960 @@ impl Renderer {
                 binding: 0,
                 resource: camera_buffer.as_entire_binding(),
             }],
-            label: Some("camera_bind_group"),
+            label: Some(&labels.label("camera_bind_group")),
+        });
+
+        let material_layout = material_bind_group_layout(device, labels);
+        let material = Material::default();
+        let material_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&labels.label("Material Buffer")),
+            contents: bytemuck::bytes_of(&material.uniform()),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
         });
+        let white_texture = Texture::white(device, labels, queue);
+        let black_env_map = EnvironmentMap::black(device, labels, queue);
+        let material_bind_group = material.create_bind_group(
+            device,
+            labels,
+            &material_layout,
+            &material_buffer,
+            &white_texture,
+            &black_env_map,
+        );
+
+        let shadow = ShadowSettings::default();
+        let shadow_maps = CascadedShadowMaps::new(device, labels, &shadow);
+        let shadow_layout = shadow_bind_group_layout(device, labels);
 
         let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: Some("Render Pipeline Layout"),
-            bind_group_layouts: &[&camera_bind_group_layout],
+            label: Some(&labels.label("Render Pipeline Layout")),
+            bind_group_layouts: &[&camera_bind_group_layout, &material_layout, &shadow_layout],
             push_constant_ranges: &[],
         });
 
-        let pipeline = Self::create_pipeline(device, config, &shader, &render_pipeline_layout);
+        let pipeline = capture_errors(device, "Render Pipeline", || {
+            let key = PipelineKey::new(1);
+            Self::create_pipeline_set(device, labels, config, &shader, &render_pipeline_layout, key, false)
+        })
+        .await?;
+        let decals =
+            capture_errors(device, "Decal Renderer", || DecalRenderer::new(device, labels, config.format)).await?;
 
-        Self {
+        Ok(Self {
             pipeline,
+            msaa_pipeline: None,
             camera_bind_group,
             camera_buffer,
+            material_buffer,
+            material_bind_group,
+            materials: Vec::new(),
+            white_texture,
+            black_env_map,
+            aa: AaMode::None,
+            msaa_color: None,
+            msaa_depth: None,
+            depth_resolve: None,
+            resolved_depth: None,
+            scene_target: None,
+            fxaa: None,
+            bloom: None,
+            hdr: false,
+            exposure: 1.0,
+            hdr_pipeline: None,
+            hdr_target: None,
+            bloom_pass: None,
+            bloom_output: None,
+            tonemap: None,
+            shadow,
+            shadow_maps,
+            light_direction: DEFAULT_LIGHT_DIRECTION,
+            depth_prepass: false,
+            reverse_z: false,
+            depth_clamp: false,
+            ssao: None,
+            deferred: None,
+            pixel_scale: None,
+            render_scale: 1.0,
+            render_scale_pass: None,
+            dynamic_scale: None,
+            frames_since_scale_change: 0,
+            debug_bounds: false,
+            axes_overlay: None,
+            decals,
+            outline: None,
+            camera: Cell::new(CameraUniform::new()),
+            labels: labels.clone(),
+        })
+    }
+
+    /// Layout of the camera uniform at group 0, shared by every scene pipeline.
+    pub(crate) fn camera_bind_group_layout(device: &wgpu::Device, labels: &Labels) -> wgpu::BindGroupLayout {
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                // The fragment stage reads the eye position for reflections
+                visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+            label: Some(&labels.label("camera_bind_group_layout")),
+        })
+    }
+
+    fn create_shader(device: &wgpu::Device, labels: &Labels) -> wgpu::ShaderModule {
+        device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(&labels.label("Shader")),
+            source: wgpu::ShaderSource::Wgsl(shader::builtin("shader.wgsl").source.into()),
+        })
+    }
+
+    fn pipeline_key(&self, sample_count: u32) -> PipelineKey {
+        PipelineKey {
+            reverse_z: self.reverse_z,
+            unclipped_depth: self.depth_clamp,
+            ..PipelineKey::new(sample_count)
+        }
+    }
+
+    /// Depth the buffer is cleared to: the far plane's value.
+    fn depth_clear_value(&self) -> f32 {
+        if self.reverse_z {
+            0.0
+        } else {
+            1.0
+        }
+    }
+
+    pub fn reverse_z(&self) -> bool {
+        self.reverse_z
+    }
+
+    /// Maps the near plane to depth 1 and the far plane to 0, which spreads
+    /// the float depth buffer's precision far more evenly over distance.
+    /// Cameras rendered with this renderer must set `Camera::reverse_z` to match.
+    pub fn set_reverse_z(&mut self, device: &wgpu::Device, config: &wgpu::SurfaceConfiguration, enabled: bool) {
+        if self.reverse_z != enabled {
+            self.reverse_z = enabled;
+            self.resize(device, config);
+        }
+    }
+
+    pub fn depth_clamp(&self) -> bool {
+        self.depth_clamp
+    }
+
+    /// Clamps the depth of geometry beyond the far plane instead of clipping
+    /// it away. Needs `Features::DEPTH_CLIP_CONTROL`; without it, enabling
+    /// logs a warning and leaves the setting off. Returns whether the
+    /// requested setting is now active.
+    pub fn set_depth_clamp(
+        &mut self,
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        enabled: bool,
+    ) -> bool {
+        if enabled && !supports_depth_clamp(device.features()) {
+            warn!("Depth clamping needs DEPTH_CLIP_CONTROL, which the device lacks; keeping depth clipping");
+            return false;
+        }
+        if self.depth_clamp != enabled {
+            self.depth_clamp = enabled;
+            self.resize(device, config);
+        }
+        true
+    }
+
+    pub fn depth_prepass(&self) -> bool {
+        self.depth_prepass
+    }
+
+    /// Fills the depth buffer with opaque geometry first so the main pass only
+    /// shades visible pixels. Pays off for scenes with heavy overdraw.
+    pub fn set_depth_prepass(&mut self, device: &wgpu::Device, config: &wgpu::SurfaceConfiguration, enabled: bool) {
+        if self.depth_prepass != enabled {
+            self.depth_prepass = enabled;
+            self.resize(device, config);
+        }
+    }
+
+    /// Sets the material forward meshes are drawn with. Without a `texture`
+    /// the white fallback is bound, so only vertex colors and the base color
+    /// show.
+    pub fn set_material(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, material: &Material, texture: Option<&Texture>) {
+        queue.write_buffer(&self.material_buffer, 0, bytemuck::bytes_of(&material.uniform()));
+        self.material_bind_group = material.create_bind_group(
+            device,
+            &self.labels,
+            &material_bind_group_layout(device, &self.labels),
+            &self.material_buffer,
+            texture.unwrap_or(&self.white_texture),
+            &self.black_env_map,
+        );
+    }
+
+    /// Registers a material that meshes select with `Mesh::with_material`.
+    /// Without a `texture` the white fallback is bound.
+    pub fn add_material(
+        &mut self,
+        device: &wgpu::Device,
+        material: &Material,
+        texture: Option<&Texture>,
+    ) -> MaterialId {
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&self.labels.label("Material Buffer")),
+            contents: bytemuck::bytes_of(&material.uniform()),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let bind_group = material.create_bind_group(
+            device,
+            &self.labels,
+            &material_bind_group_layout(device, &self.labels),
+            &buffer,
+            texture.unwrap_or(&self.white_texture),
+            &self.black_env_map,
+        );
+        self.materials.push((buffer, bind_group));
+        MaterialId(self.materials.len() - 1)
+    }
+
+    /// Camera uniform at group 0, for passes drawn alongside the main pipeline.
+    pub fn camera_bind_group(&self) -> &wgpu::BindGroup {
+        &self.camera_bind_group
+    }
+
+    pub fn pipeline_mode(&self) -> PipelineMode {
+        if self.deferred.is_some() {
+            PipelineMode::Deferred
+        } else {
+            PipelineMode::Forward
+        }
+    }
+
+    /// Switches between forward and deferred shading. Fails, leaving the mode
+    /// unchanged, if the adapter cannot render to every G-buffer target at once.
+    pub fn set_pipeline_mode(
+        &mut self,
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        mode: PipelineMode,
+    ) -> Result<(), DeferredError> {
+        match mode {
+            PipelineMode::Forward => self.deferred = None,
+            PipelineMode::Deferred if self.deferred.is_none() => {
+                self.deferred = Some(DeferredRenderer::new(device, &self.labels, config, self.reverse_z)?);
+            }
+            PipelineMode::Deferred => {}
+        }
+        Ok(())
+    }
+
+    pub fn debug_bounds(&self) -> bool {
+        self.debug_bounds
+    }
+
+    /// Toggles the bounds wireframes added by `collect_debug_bounds`.
+    pub fn set_debug_bounds(&mut self, enabled: bool) {
+        self.debug_bounds = enabled;
+    }
+
+    /// When debug bounds are enabled, adds the world-space box of every
+    /// rendered actor in `scene` to `lines`, plus `frustum_camera`'s frustum.
+    /// `local_bounds` gives each actor's object-space box; actors it returns
+    /// `None` for are skipped.
+    pub fn collect_debug_bounds(
+        &self,
+        lines: &mut DebugLines,
+        scene: &Scene,
+        local_bounds: impl Fn(ActorId) -> Option<Aabb>,
+        frustum_camera: Option<&Camera>,
+    ) {
+        if !self.debug_bounds {
+            return;
+        }
+        for id in scene.rendered() {
+            if let (Some(bounds), Some(world)) = (local_bounds(id), scene.world_matrix(id)) {
+                lines.aabb(&bounds.transform(&world), DEBUG_BOUNDS_COLOR);
+            }
+        }
+        if let Some(camera) = frustum_camera {
+            lines.frustum(camera.build_view_projection_matrix(), DEBUG_FRUSTUM_COLOR);
+        }
+    }
+
+    pub fn axes_overlay(&self) -> bool {
+        self.axes_overlay.is_some()
+    }
+
+    /// Shows or hides the world-axes indicator in the bottom-left corner.
+    /// Call `update_axes_overlay` each frame to keep it aligned with the camera.
+    pub fn set_axes_overlay(&mut self, device: &wgpu::Device, config: &wgpu::SurfaceConfiguration, enabled: bool) {
+        match (enabled, &self.axes_overlay) {
+            (true, None) => self.axes_overlay = Some(AxesOverlay::new(device, &self.labels, config)),
+            (false, Some(_)) => self.axes_overlay = None,
+            _ => {}
+        }
+    }
+
+    /// Turns the axes overlay to match `camera`. Does nothing while it's hidden.
+    pub fn update_axes_overlay(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, camera: &Camera) {
+        if let Some(overlay) = &mut self.axes_overlay {
+            overlay.prepare(device, queue, camera);
+        }
+    }
+
+    /// Projects `texture` onto the scene through the box `transform` places,
+    /// along the box's forward axis (see `Decal`). Returns the decal's index
+    /// for `decals_mut().set_transform`. Decals are drawn in the forward
+    /// path without anti-aliasing or with FXAA; MSAA, HDR and deferred
+    /// rendering skip them.
+    pub fn add_decal(&mut self, device: &wgpu::Device, transform: &Transform, texture: Texture) -> usize {
+        self.decals.add(device, transform, texture)
+    }
+
+    pub fn decals(&self) -> &DecalRenderer {
+        &self.decals
+    }
+
+    pub fn decals_mut(&mut self) -> &mut DecalRenderer {
+        &mut self.decals
+    }
+
+    pub fn outline(&self) -> Option<Outline> {
+        self.outline.as_ref().map(|(outline, _)| *outline)
+    }
+
+    /// Highlights `actor` with a `thickness`-pixel outline of `color`
+    /// (linear RGBA) in `draw_outline`, replacing any previous selection.
+    pub fn set_outline(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        config: &wgpu::SurfaceConfiguration,
+        actor: ActorId,
+        color: [f32; 4],
+        thickness: u32,
+    ) {
+        let outline = Outline { actor, color, thickness };
+        match &mut self.outline {
+            Some((current, pass)) => {
+                pass.set_outline(queue, &outline);
+                *current = outline;
+            }
+            None => {
+                let pass = OutlinePass::new(device, &self.labels, config, self.color_format(config), &outline);
+                self.outline = Some((outline, pass));
+            }
+        }
+    }
+
+    pub fn clear_outline(&mut self) {
+        self.outline = None;
+    }
+
+    /// Outlines the selected actor's meshes among `objects` over `output`,
+    /// after the scene has been encoded into it. `output` must be the scene's
+    /// color target: `hdr_target()` while HDR or bloom is on, so the outline
+    /// goes through bloom and tonemapping with the scene, and the surface
+    /// otherwise. Does nothing without a selection or when the actor isn't in
+    /// `objects`.
+    pub fn draw_outline(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        output: &wgpu::TextureView,
+        objects: &[(ActorId, Matrix4, &crate::mesh::Mesh)],
+    ) {
+        let Some((outline, pass)) = &mut self.outline else {
+            return;
+        };
+        let selected: Vec<_> = objects
+            .iter()
+            .filter(|(actor, _, _)| *actor == outline.actor)
+            .map(|(_, world, mesh)| (*world, *mesh))
+            .collect();
+        if !selected.is_empty() {
+            pass.draw(device, queue, encoder, &self.camera_bind_group, &selected, output);
+        }
+    }
+
+    pub fn pixel_scale(&self) -> Option<&PixelScalePass> {
+        self.pixel_scale.as_ref()
+    }
+
+    /// Renders the scene at `base_width`x`base_height` and upscales it to the
+    /// window by the largest whole factor that fits, with nearest filtering
+    /// and black letterbox bars. Cameras should use the base aspect ratio.
+    /// Anti-aliasing and deferred shading are bypassed in this mode.
+    pub fn set_pixel_scale(
+        &mut self,
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        base_width: u32,
+        base_height: u32,
+    ) {
+        self.pixel_scale = Some(PixelScalePass::new(device, &self.labels, config, base_width, base_height));
+    }
+
+    /// Goes back to rendering at the window's resolution.
+    pub fn clear_pixel_scale(&mut self) {
+        self.pixel_scale = None;
+    }
+
+    pub fn render_scale(&self) -> f32 {
+        self.render_scale
+    }
+
+    /// Renders the scene at `scale` times the window resolution (clamped to
+    /// `0.5..=2.0`) and stretches it to the window. Returns the scale applied.
+    /// Ignored while pixel scaling is on; anti-aliasing, HDR and deferred
+    /// shading are bypassed below or above 1.
+    pub fn set_render_scale(&mut self, device: &wgpu::Device, config: &wgpu::SurfaceConfiguration, scale: f32) -> f32 {
+        self.render_scale = clamp_render_scale(scale);
+        self.create_render_scale_target(device, config);
+        self.render_scale
+    }
+
+    pub fn render_scale_target(&self) -> Option<&RenderTarget> {
+        self.render_scale_pass.as_ref().map(RenderScalePass::target)
+    }
+
+    pub fn dynamic_scale(&self) -> Option<DynamicScale> {
+        self.dynamic_scale
+    }
+
+    /// Lets `update_render_scale` steer the render scale towards
+    /// `dynamic.target_fps`; `None` keeps the current scale fixed.
+    pub fn set_dynamic_scale(&mut self, dynamic: Option<DynamicScale>) {
+        self.dynamic_scale = dynamic;
+        self.frames_since_scale_change = 0;
+    }
+
+    /// Call once per frame with the engine's frame statistics. Every
+    /// `interval` frames the scale is nudged towards the target frame rate.
+    /// Returns whether it changed.
+    pub fn update_render_scale(
+        &mut self,
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        stats: &FrameStats,
+    ) -> bool {
+        let Some(dynamic) = self.dynamic_scale else {
+            return false;
+        };
+        self.frames_since_scale_change += 1;
+        if self.frames_since_scale_change < dynamic.interval {
+            return false;
+        }
+        self.frames_since_scale_change = 0;
+        match dynamic.adjust_from_stats(self.render_scale, stats) {
+            Some(scale) if scale != self.render_scale => {
+                self.set_render_scale(device, config, scale);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn create_render_scale_target(&mut self, device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) {
+        self.render_scale_pass =
+            (self.render_scale != 1.0).then(|| RenderScalePass::new(device, &self.labels, config, self.render_scale));
+    }
+
+    /// Sets the lights accumulated by the deferred lighting pass. Has no
+    /// effect in forward mode.
+    pub fn set_lights(&self, queue: &wgpu::Queue, ambient: [f32; 3], lights: &[PointLight]) {
+        if let Some(deferred) = &self.deferred {
+            deferred.set_lights(queue, ambient, lights);
+        }
+    }
+
+    pub fn ssao(&self) -> Option<SsaoSettings> {
+        self.ssao
+    }
+
+    /// Sets SSAO for the deferred lighting pass, or turns it off with `None`.
+    /// In forward mode it only decides whether MSAA depth gets resolved.
+    pub fn set_ssao(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        config: &wgpu::SurfaceConfiguration,
+        settings: Option<SsaoSettings>,
+    ) {
+        if let Some(deferred) = &mut self.deferred {
+            deferred.set_ssao(queue, settings);
+        }
+        let resolve_changed = self.ssao.is_some() != settings.is_some();
+        self.ssao = settings;
+        if resolve_changed {
+            self.create_aa_targets(device, config);
+        }
+    }
+
+    pub fn bloom(&self) -> Option<BloomSettings> {
+        self.bloom
+    }
+
+    /// Makes pixels brighter than the threshold glow, or turns bloom off with
+    /// `None`. While on, the scene is rendered into an `HDR_FORMAT` target so
+    /// values above 1.0 survive until the bloom pass; anti-aliasing is
+    /// bypassed, as with pixel scaling. Without `set_hdr` the glow is added
+    /// straight onto the surface, which clamps it.
+    pub fn set_bloom(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        config: &wgpu::SurfaceConfiguration,
+        settings: Option<BloomSettings>,
+    ) {
+        match (settings, &mut self.bloom_pass) {
+            (Some(settings), Some(bloom)) => {
+                bloom.set_settings(queue, settings);
+                self.bloom = Some(settings);
+            }
+            _ => {
+                self.bloom = settings;
+                self.resize(device, config);
+            }
+        }
+    }
+
+    pub fn hdr(&self) -> bool {
+        self.hdr
+    }
+
+    /// Renders the scene into an `HDR_FORMAT` target and tonemaps it onto the
+    /// surface, so lighting above 1.0 rolls off instead of clipping.
+    /// `format_features` comes from `Adapter::get_texture_format_features`
+    /// for `HDR_FORMAT`; returns false, leaving HDR off, if it can't be
+    /// rendered to. Anti-aliasing is bypassed while HDR is on.
+    pub fn set_hdr(
+        &mut self,
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        format_features: &wgpu::TextureFormatFeatures,
+        enabled: bool,
+    ) -> bool {
+        if enabled && !supports_hdr(format_features) {
+            warn!("The adapter can't render to {:?}; keeping HDR off", HDR_FORMAT);
+            return false;
+        }
+        if self.hdr != enabled {
+            self.hdr = enabled;
+            self.resize(device, config);
+        }
+        true
+    }
+
+    pub fn exposure(&self) -> f32 {
+        self.exposure
+    }
+
+    /// Scales the HDR scene before tonemapping; 1.0 leaves it unchanged.
+    pub fn set_exposure(&mut self, queue: &wgpu::Queue, exposure: f32) {
+        self.exposure = exposure;
+        if let Some(tonemap) = &mut self.tonemap {
+            tonemap.set_exposure(queue, exposure);
+        }
+    }
+
+    /// The `HDR_FORMAT` target the scene was last rendered into, before bloom
+    /// and tonemapping, while HDR or bloom is on.
+    pub fn hdr_target(&self) -> Option<&RenderTarget> {
+        self.hdr_target.as_ref()
+    }
+
+    fn uses_hdr_target(&self) -> bool {
+        self.hdr || self.bloom.is_some()
+    }
+
+    /// Format of the target the scene is drawn into: `HDR_FORMAT` while HDR
+    /// or bloom is on, the surface format otherwise.
+    fn color_format(&self, config: &wgpu::SurfaceConfiguration) -> wgpu::TextureFormat {
+        if self.uses_hdr_target() {
+            HDR_FORMAT
+        } else {
+            config.format
+        }
+    }
+
+    fn create_hdr_targets(&mut self, device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) {
+        self.hdr_target = None;
+        self.bloom_pass = None;
+        self.bloom_output = None;
+        self.tonemap = None;
+        if !self.uses_hdr_target() {
+            return;
+        }
+        let (width, height) = (config.width, config.height);
+        let labels = &self.labels;
+        let scene = RenderTarget::with_format(device, labels, config, width, height, HDR_FORMAT);
+        // With HDR on, bloom writes to another HDR target for the tonemapper
+        let bloom_format = if self.hdr { HDR_FORMAT } else { config.format };
+        self.bloom_pass = self
+            .bloom
+            .map(|settings| BloomPass::new(device, labels, bloom_format, scene.color_view(), width, height, settings));
+        if self.hdr && self.bloom.is_some() {
+            self.bloom_output = Some(RenderTarget::with_format(device, labels, config, width, height, HDR_FORMAT));
+        }
+        if self.hdr {
+            let mut tonemap = TonemapPass::new(device, labels, config.format, self.exposure);
+            tonemap.set_source(device, self.bloom_output.as_ref().unwrap_or(&scene).color_view());
+            self.tonemap = Some(tonemap);
+        }
+        self.hdr_target = Some(scene);
+    }
+
+    /// Whether any enabled effect samples scene depth.
+    fn depth_effects(&self) -> bool {
+        self.ssao.is_some()
+    }
+
+    pub fn depth_resolve(&self) -> Option<DepthResolveMode> {
+        self.depth_resolve
+    }
+
+    /// Resolves the multisampled depth buffer into a single-sample texture
+    /// after the main pass, so effects that sample depth see correct edges.
+    /// Only happens while MSAA and a depth-sampling effect are both on.
+    pub fn set_depth_resolve(
+        &mut self,
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        mode: Option<DepthResolveMode>,
+    ) {
+        if self.depth_resolve != mode {
+            self.depth_resolve = mode;
+            self.create_aa_targets(device, config);
+        }
+    }
+
+    /// Single-sample depth resolved from the last MSAA frame, if resolving.
+    pub fn resolved_depth(&self) -> Option<&DepthResolvePass> {
+        self.resolved_depth.as_ref()
+    }
+
+    /// Checks WGSL source before it is handed to `create_shader_module`,
+    /// returning the line, column and message of the first error.
+    pub fn validate_shader(source: &str) -> Result<(), ShaderError> {
+        shader::validate_wgsl("shader", source)
+    }
+
+    pub fn shadow_settings(&self) -> &ShadowSettings {
+        &self.shadow
+    }
+
+    /// Selects the PCF kernel used by lit shaders (see `shadow_pcf.wgsl`),
+    /// from the next `update_shadows`.
+    pub fn set_shadow_quality(&mut self, quality: ShadowQuality) {
+        self.shadow.quality = quality;
+    }
+
+    /// Number of shadow cascades (clamped to `1..=MAX_CASCADES`) and the
+    /// split lambda between uniform (0) and logarithmic (1) spacing.
+    pub fn set_shadow_cascades(&mut self, count: u32, lambda: f32) {
+        self.shadow.cascades.count = count.clamp(1, MAX_CASCADES as u32);
+        self.shadow.cascades.lambda = lambda.clamp(0.0, 1.0);
+    }
+
+    /// Takes effect from the next `update_shadows`.
+    pub fn set_shadow_bias(&mut self, depth_bias: f32, normal_offset_bias: f32) {
+        self.shadow.depth_bias = depth_bias.max(0.0);
+        self.shadow.normal_offset_bias = normal_offset_bias.max(0.0);
+    }
+
+    pub fn light_direction(&self) -> Vec3 {
+        self.light_direction
+    }
+
+    /// Points the shadow-casting directional light along `direction`. Takes
+    /// effect at the next `update_shadows`.
+    pub fn set_light_direction(&mut self, direction: Vec3) {
+        self.light_direction = direction.try_normalize().unwrap_or(DEFAULT_LIGHT_DIRECTION);
+    }
+
+    /// Fits the shadow cascades to `camera` with the current shadow settings.
+    /// Call whenever the camera moves, alongside `update_camera`; until the
+    /// first call nothing is shadowed.
+    pub fn update_shadows(&mut self, queue: &wgpu::Queue, camera: &Camera) {
+        self.shadow_maps.update(queue, camera, self.light_direction, &self.shadow);
+    }
+
+    pub fn shadow_maps(&self) -> &CascadedShadowMaps {
+        &self.shadow_maps
+    }
+
+    pub fn aa(&self) -> AaMode {
+        self.aa
+    }
+
+    /// Switches anti-aliasing. An MSAA sample count the adapter can't use for
+    /// the surface or depth format is rejected and keeps the current mode.
+    pub fn set_aa(
+        &mut self,
+        adapter: &wgpu::Adapter,
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        aa: AaMode,
+    ) -> Result<(), AaError> {
+        let aa = match aa {
+            AaMode::Msaa(samples) if samples <= 1 => AaMode::None,
+            aa => aa,
+        };
+        if let AaMode::Msaa(samples) = aa {
+            let formats = [config.format, DEPTH_FORMAT]
+                .map(|format| (format, adapter.get_texture_format_features(format).flags));
+            check_msaa_support(samples, &formats)?;
+        }
+        self.aa = aa;
+        self.resize(device, config);
+        Ok(())
+    }
+
+    fn create_aa_targets(&mut self, device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) {
+        self.msaa_color = None;
+        self.msaa_depth = None;
+        self.resolved_depth = None;
+        self.scene_target = None;
+        match self.aa {
+            AaMode::None => {}
+            AaMode::Msaa(samples) => {
+                self.msaa_color = Some(Self::create_multisampled_texture(
+                    device,
+                    config,
+                    config.format,
+                    samples,
+                    &self.labels.label("MSAA Color Texture"),
+                ));
+                let depth = crate::mesh::Mesh::create_multisampled_depth_texture(device, &self.labels, config, samples);
+                if let Some(mode) = self.depth_resolve.filter(|_| needs_depth_resolve(samples, self.depth_effects())) {
+                    self.resolved_depth = Some(DepthResolvePass::new(device, &self.labels, config, &depth.1, mode));
+                }
+                self.msaa_depth = Some(depth);
+            }
+            AaMode::Fxaa => {
+                let target = RenderTarget::new(device, &self.labels, config, config.width, config.height);
+                let fxaa = self.fxaa.get_or_insert_with(|| FxaaPass::new(device, &self.labels, config.format));
+                fxaa.set_source(device, target.color_view());
+                self.scene_target = Some(target);
+            }
+        }
+    }
+
+    fn create_multisampled_texture(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+        label: &str,
+    ) -> (Tracked<wgpu::Texture>, wgpu::TextureView) {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width: config.width,
+                height: config.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        (Tracked::texture(texture, ResourceCategory::RenderTarget), view)
+    }
+
+    fn create_pipeline_set(
+        device: &wgpu::Device,
+        labels: &Labels,
+        config: &wgpu::SurfaceConfiguration,
+        shader: &wgpu::ShaderModule,
+        pipeline_layout: &wgpu::PipelineLayout,
+        base: PipelineKey,
+        depth_prepass: bool,
+    ) -> PipelineSet {
+        let create = |stage, front_face, topology| {
+            let key = PipelineKey {
+                stage,
+                front_face,
+                topology,
+                ..base
+            };
+            Self::create_pipeline(device, labels, config, shader, pipeline_layout, key)
+        };
+        let triangles = wgpu::PrimitiveTopology::TriangleList;
+        PipelineSet {
+            color: create(DepthStage::Default, wgpu::FrontFace::Ccw, triangles),
+            color_cw: create(DepthStage::Default, wgpu::FrontFace::Cw, triangles),
+            topologies: EXTRA_TOPOLOGIES
+                .into_iter()
+                .map(|(topology, front_face)| {
+                    (topology, front_face, create(DepthStage::Default, front_face, topology))
+                })
+                .collect(),
+            prepass: depth_prepass.then(|| {
+                (
+                    create(DepthStage::Prepass, wgpu::FrontFace::Ccw, triangles),
+                    create(DepthStage::AfterPrepass, wgpu::FrontFace::Ccw, triangles),
+                )
+            }),
         }
     }
 
     fn create_pipeline(
         device: &wgpu::Device,
+        labels: &Labels,
         config: &wgpu::SurfaceConfiguration,
         shader: &wgpu::ShaderModule,
         pipeline_layout: &wgpu::PipelineLayout,
+        key: PipelineKey,
     ) -> wgpu::RenderPipeline {
+        let PipelineKey {
+            sample_count,
+            stage,
+            front_face,
+            topology,
+            reverse_z,
+            unclipped_depth,
+        } = key;
+        let color_targets = [Some(wgpu::ColorTargetState {
+            format: config.format,
+            blend: Some(wgpu::BlendState::REPLACE),
+            write_mask: wgpu::ColorWrites::ALL,
+        })];
+        let fragment = stage.has_color_target().then(|| wgpu::FragmentState {
+            module: shader,
+            entry_point: Some("fs_main"),
+            targets: &color_targets,
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+        });
+
         device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Render Pipeline"),
+            label: Some(&labels.label(match stage {
+                DepthStage::Default => "Render Pipeline",
+                DepthStage::Prepass => "Depth Prepass Pipeline",
+                DepthStage::AfterPrepass => "Render Pipeline (after prepass)",
+            })),
             layout: Some(pipeline_layout),
             vertex: wgpu::VertexState {
                 module: shader,
                 entry_point: Some("vs_main"),
                 buffers: &[Vertex::desc()],
-                compilation_options: todo!(),
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
             },
-            fragment: Some(wgpu::FragmentState {
-                module: shader,
-                entry_point: Some("fs_main"),
-                targets: &[Some(wgpu::ColorTargetState {
-                    format: config.format,
-                    blend: Some(wgpu::BlendState::REPLACE),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-                compilation_options: todo!(),
-            }),
+            fragment,
             primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: Some(wgpu::Face::Back),
-                polygon_mode: wgpu::PolygonMode::Fill,
-                unclipped_depth: false,
-                conservative: false,
+                unclipped_depth,
+                ..primitive_state(topology, front_face)
             },
-            depth_stencil: Some(wgpu::DepthStencilState {
-                format: wgpu::TextureFormat::Depth32Float,
-                depth_write_enabled: true,
-                depth_compare: wgpu::CompareFunction::Less,
-                stencil: wgpu::StencilState::default(),
-                bias: wgpu::DepthBiasState::default(),
-            }),
+            depth_stencil: Some(stage.depth_stencil(reverse_z)),
             multisample: wgpu::MultisampleState {
-                count: 1,
+                count: sample_count,
                 mask: !0,
                 alpha_to_coverage_enabled: false,
             },
             multiview: None,
-            cache: todo!(),
+            cache: None,
         })
     }
 
+    /// Rebuilds the pipelines and attachments for a new surface size or
+    /// setting. If the device rejects the new pipelines, the error is logged
+    /// and the previous ones are kept.
     pub fn resize(&mut self, device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) {
+        if let Err(err) = block_on(self.rebuild_pipelines(device, config)) {
+            error!("Keeping previous pipelines: {}", err);
+        }
+        self.create_aa_targets(device, config);
+        self.create_hdr_targets(device, config);
+        self.create_render_scale_target(device, config);
+        if let Some(deferred) = &mut self.deferred {
+            deferred.resize(device, config, self.reverse_z);
+        }
+        if let Some(overlay) = &mut self.axes_overlay {
+            overlay.resize(device, config);
+        }
+        let color_format = self.color_format(config);
+        if let Some((outline, pass)) = &mut self.outline {
+            if pass.format() == color_format {
+                pass.resize(device, config);
+            } else {
+                *pass = OutlinePass::new(device, &self.labels, config, color_format, outline);
+            }
+        }
+    }
+
+    async fn rebuild_pipelines(
+        &mut self,
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+    ) -> Result<(), ContextError> {
         // Since our pipeline depends on the surface format, we need to recreate it
-        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("Shader"),
-            source: wgpu::ShaderSource::Wgsl(include_str!("shader.wgsl").into()),
-        });
+        let labels = &self.labels;
+        let shader = capture_errors(device, "Shader", || Self::create_shader(device, labels)).await?;
 
         // Recreate the pipeline layout
-        let camera_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            entries: &[wgpu::BindGroupLayoutEntry {
-                binding: 0,
-                visibility: wgpu::ShaderStages::VERTEX,
-                ty: wgpu::BindingType::Buffer {
-                    ty: wgpu::BufferBindingType::Uniform,
-                    has_dynamic_offset: false,
-                    min_binding_size: None,
-                },
-                count: None,
-            }],
-            label: Some("camera_bind_group_layout"),
-        });
+        let camera_bind_group_layout = Self::camera_bind_group_layout(device, labels);
+        let material_layout = material_bind_group_layout(device, labels);
+        let shadow_layout = shadow_bind_group_layout(device, labels);
 
         let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: Some("Render Pipeline Layout"),
-            bind_group_layouts: &[&camera_bind_group_layout],
+            label: Some(&labels.label("Render Pipeline Layout")),
+            bind_group_layouts: &[&camera_bind_group_layout, &material_layout, &shadow_layout],
             push_constant_ranges: &[],
         });
 
-        self.pipeline = Self::create_pipeline(device, config, &shader, &render_pipeline_layout);
+        let (pipeline, msaa_pipeline, hdr_pipeline) = capture_errors(device, "Render Pipeline", || {
+            let pipeline = Self::create_pipeline_set(
+                device,
+                labels,
+                config,
+                &shader,
+                &render_pipeline_layout,
+                self.pipeline_key(1),
+                self.depth_prepass,
+            );
+            let msaa_pipeline = match self.aa {
+                AaMode::Msaa(samples) if samples > 1 => Some(Self::create_pipeline_set(
+                    device,
+                    labels,
+                    config,
+                    &shader,
+                    &render_pipeline_layout,
+                    self.pipeline_key(samples),
+                    self.depth_prepass,
+                )),
+                _ => None,
+            };
+            let hdr_config = wgpu::SurfaceConfiguration {
+                format: HDR_FORMAT,
+                ..config.clone()
+            };
+            let hdr_pipeline = self.uses_hdr_target().then(|| {
+                Self::create_pipeline_set(
+                    device,
+                    labels,
+                    &hdr_config,
+                    &shader,
+                    &render_pipeline_layout,
+                    self.pipeline_key(1),
+                    self.depth_prepass,
+                )
+            });
+            (pipeline, msaa_pipeline, hdr_pipeline)
+        })
+        .await?;
+        self.pipeline = pipeline;
+        self.msaa_pipeline = msaa_pipeline;
+        self.hdr_pipeline = hdr_pipeline;
+        Ok(())
     }
 
     pub fn render(
@@ -153,31 +1325,296 @@ impl Renderer {
 
         // Update camera buffer
         self.update_camera(queue, camera_uniform);
+        self.decals.prepare(queue, camera_uniform);
+
+        let (width, height) = (output.texture.width(), output.texture.height());
+        let shadow_label = self.labels.label("Shadow Pass");
+        let main_label = self.labels.label("Main Pass");
+        let overlay_label = self.labels.label("Overlay Pass");
+        let main_dependencies = [shadow_label.as_str()];
+        let overlay_dependencies = [main_label.as_str()];
+
+        let mut graph = RenderGraph::new();
+        graph.add_pass(&shadow_label, &[], |encoder| self.shadow_maps.draw(encoder, &[mesh]));
+        graph.add_pass(&main_label, &main_dependencies, |encoder| {
+            self.encode_main_pass(device, encoder, &view, width, height, mesh)
+        });
+        // Drawn last so the indicator sits on top of the finished frame
+        if let Some(overlay) = &self.axes_overlay {
+            graph.add_pass(&overlay_label, &overlay_dependencies, |encoder| overlay.draw(encoder, &view));
+        }
+        // Every dependency above is added before the pass that names it
+        graph.execute(device, queue).expect("static pass graph is acyclic");
+
+        output.present();
+        Ok(())
+    }
+
+    /// Records the scene pass `render` draws into the `width`x`height`
+    /// surface `view`, including whichever post-processing is enabled.
+    fn encode_main_pass(
+        &self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        width: u32,
+        height: u32,
+        mesh: &crate::mesh::Mesh,
+    ) {
+        if let Some(pixel_scale) = &self.pixel_scale {
+            let target = pixel_scale.target();
+            self.encode_pass(encoder, &self.pipeline, target.color_view(), None, target.depth_view(), &[mesh], false);
+            pixel_scale.blit(encoder, view, width, height);
+        } else if let Some(scaled) = &self.render_scale_pass {
+            let target = scaled.target();
+            self.encode_pass(encoder, &self.pipeline, target.color_view(), None, target.depth_view(), &[mesh], false);
+            self.decals.draw(device, encoder, target.color_view(), target.depth_view());
+            scaled.blit(encoder, view);
+        } else if let Some(deferred) = &self.deferred {
+            deferred.render(encoder, &self.camera_bind_group, view, &[mesh]);
+        } else if let (Some(pipeline), Some(target)) = (&self.hdr_pipeline, &self.hdr_target) {
+            self.encode_pass(encoder, pipeline, target.color_view(), None, target.depth_view(), &[mesh], false);
+            match (&self.bloom_pass, &self.bloom_output, &self.tonemap) {
+                (Some(bloom), Some(output), Some(tonemap)) => {
+                    bloom.run(encoder, output.color_view());
+                    tonemap.run(encoder, view);
+                }
+                (Some(bloom), _, None) => bloom.run(encoder, view),
+                (None, _, Some(tonemap)) => tonemap.run(encoder, view),
+                _ => {}
+            }
+        } else {
+            match (self.aa, &self.msaa_pipeline, &self.msaa_color, &self.msaa_depth, &self.scene_target, &self.fxaa) {
+                (AaMode::Msaa(_), Some(pipeline), Some(color), Some(depth), _, _) => {
+                    // Draw multisampled, resolving straight into the surface texture
+                    self.encode_pass(encoder, pipeline, &color.1, Some(view), &depth.1, &[mesh], false);
+                    if let Some(resolve) = &self.resolved_depth {
+                        resolve.run(encoder);
+                    }
+                }
+                (AaMode::Fxaa, _, _, _, Some(target), Some(fxaa)) => {
+                    self.encode_pass(encoder, &self.pipeline, target.color_view(), None, target.depth_view(), &[mesh], false);
+                    self.decals.draw(device, encoder, target.color_view(), target.depth_view());
+                    fxaa.run(encoder, view);
+                }
+                _ => {
+                    self.encode_pass(encoder, &self.pipeline, view, None, &mesh.depth_texture.1, &[mesh], false);
+                    self.decals.draw(device, encoder, view, &mesh.depth_texture.1);
+                }
+            }
+        }
+    }
+
+    /// Renders `meshes` from `camera_uniform`'s point of view into `target`
+    /// instead of the surface, using the same pipeline as the main pass, or
+    /// its flipped-winding variant if `target.mirrored` is set.
+    pub fn render_to(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        target: &RenderTarget,
+        camera_uniform: &CameraUniform,
+        meshes: &[&crate::mesh::Mesh],
+    ) {
+        self.update_camera(queue, camera_uniform);
+
+        let shadow_label = self.labels.label("Shadow Pass");
+        let main_label = self.labels.label("Render Target Pass");
+        let main_dependencies = [shadow_label.as_str()];
+        let mut graph = RenderGraph::new();
+        graph.add_pass(&shadow_label, &[], |encoder| self.shadow_maps.draw(encoder, meshes));
+        graph.add_pass(&main_label, &main_dependencies, |encoder| {
+            let (color, depth) = (target.color_view(), target.depth_view());
+            self.encode_pass(encoder, &self.pipeline, color, None, depth, meshes, target.mirrored);
+        });
+        graph.execute(device, queue).expect("static pass graph is acyclic");
+    }
+
+    /// Draws `meshes` on top of what `color_view` and `depth_view` already
+    /// hold, without clearing either. Combine with `clear_depth` to layer draw
+    /// groups within one encoder.
+    pub fn draw_group(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        color_view: &wgpu::TextureView,
+        depth_view: &wgpu::TextureView,
+        meshes: &[&crate::mesh::Mesh],
+    ) {
+        self.encode_color_pass(encoder, color_view, None, depth_view, wgpu::LoadOp::Load, wgpu::LoadOp::Load, |render_pass| {
+            let mut material = None;
+            for mesh in meshes {
+                render_pass.set_pipeline(self.pipeline.for_mesh(mesh, false));
+                self.bind_material(render_pass, &mut material, material_key(mesh));
+                Self::draw_mesh(render_pass, mesh);
+            }
+        });
+    }
+
+    /// Resets `depth_view` to the far plane while leaving color untouched, so
+    /// groups drawn afterwards (e.g. a first-person weapon) appear in front of
+    /// everything drawn before.
+    pub fn clear_depth(&self, encoder: &mut wgpu::CommandEncoder, depth_view: &wgpu::TextureView) {
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some(&self.labels.label("Clear Depth Pass")),
+            color_attachments: &[],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: depth_view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(self.depth_clear_value()),
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+    }
+
+    fn encode_pass(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        pipelines: &PipelineSet,
+        color_view: &wgpu::TextureView,
+        resolve_target: Option<&wgpu::TextureView>,
+        depth_view: &wgpu::TextureView,
+        meshes: &[&crate::mesh::Mesh],
+        mirrored: bool,
+    ) {
+        let Some((prepass_pipeline, equal_pipeline)) = &pipelines.prepass else {
+            let depth_load = wgpu::LoadOp::Clear(self.depth_clear_value());
+            self.encode_color_pass(encoder, color_view, resolve_target, depth_view, wgpu::LoadOp::Clear(CLEAR_COLOR), depth_load, |render_pass| {
+                self.draw_sorted(render_pass, pipelines, meshes, mirrored);
+            });
+            return;
+        };
+        // Only opaque triangle lists drawn counter-clockwise in this pass go through the prepass
+        let prepassed = |mesh: &crate::mesh::Mesh| {
+            !mesh.transparent
+                && mesh.topology == wgpu::PrimitiveTopology::TriangleList
+                && pass_front_face(mesh.front_face, mirrored) == wgpu::FrontFace::Ccw
+        };
+
+        // Depth-only prepass over opaque geometry
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some(&self.labels.label("Depth Prepass")),
+                color_attachments: &[],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: depth_view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(self.depth_clear_value()),
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    stencil_ops: None,
+                }),
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+
+            render_pass.set_pipeline(prepass_pipeline);
+            render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
+            render_pass.set_bind_group(1, &self.material_bind_group, &[]);
+            render_pass.set_bind_group(2, self.shadow_maps.bind_group(), &[]);
+            for mesh in meshes.iter().filter(|&&mesh| prepassed(mesh)) {
+                Self::draw_mesh(&mut render_pass, mesh);
+            }
+        }
 
-        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
-            label: Some("Render Encoder"),
+        self.encode_color_pass(encoder, color_view, resolve_target, depth_view, wgpu::LoadOp::Clear(CLEAR_COLOR), wgpu::LoadOp::Load, |render_pass| {
+            render_pass.set_pipeline(equal_pipeline);
+            let mut material = None;
+            for mesh in meshes.iter().filter(|&&mesh| prepassed(mesh)) {
+                self.bind_material(render_pass, &mut material, material_key(mesh));
+                Self::draw_mesh(render_pass, mesh);
+            }
+            // The rest never reached the depth buffer, so test them normally
+            let rest: Vec<_> = meshes.iter().copied().filter(|mesh| !prepassed(mesh)).collect();
+            self.draw_sorted(render_pass, pipelines, &rest, mirrored);
         });
+    }
+
+    /// Draws opaque meshes grouped by pipeline and material, binding each
+    /// pipeline and material once, then transparent ones from farthest to
+    /// nearest by the view-space distance to their bounds' center.
+    fn draw_sorted(
+        &self,
+        render_pass: &mut wgpu::RenderPass,
+        pipelines: &PipelineSet,
+        meshes: &[&crate::mesh::Mesh],
+        mirrored: bool,
+    ) {
+        let mut pipeline = None;
+        let mut material = None;
+        for draw in self.sorted_draws(pipelines, meshes, mirrored) {
+            if pipeline != Some(draw.pipeline) {
+                render_pass.set_pipeline(pipelines.get(draw.pipeline));
+                pipeline = Some(draw.pipeline);
+            }
+            self.bind_material(render_pass, &mut material, draw.material);
+            Self::draw_mesh(render_pass, meshes[draw.index]);
+        }
+    }
+
+    /// `meshes` in the order `draw_sorted` draws them, keyed by pipeline and
+    /// `material_key`.
+    fn sorted_draws(&self, pipelines: &PipelineSet, meshes: &[&crate::mesh::Mesh], mirrored: bool) -> Vec<DrawCommand> {
+        let camera = self.camera.get();
+        let mut draws: Vec<DrawCommand> = meshes
+            .iter()
+            .enumerate()
+            .map(|(index, mesh)| DrawCommand {
+                pipeline: pipelines.id_for_mesh(mesh, mirrored),
+                material: material_key(mesh),
+                mesh: index,
+                transparent: mesh.transparent,
+                depth: camera.view_distance(mesh.bounds.center.into()),
+                index,
+            })
+            .collect();
+        sort_draws(&mut draws);
+        draws
+    }
+
+    /// Binds the material with sort key `key` at group 1, unless `bound`
+    /// says it already is.
+    fn bind_material(&self, render_pass: &mut wgpu::RenderPass, bound: &mut Option<usize>, key: usize) {
+        if *bound == Some(key) {
+            return;
+        }
+        // Ids from another renderer fall back to the default material
+        let bind_group = key
+            .checked_sub(1)
+            .and_then(|index| self.materials.get(index))
+            .map_or(&self.material_bind_group, |(_, bind_group)| bind_group);
+        render_pass.set_bind_group(1, bind_group, &[]);
+        *bound = Some(key);
+    }
 
+    fn encode_color_pass(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        color_view: &wgpu::TextureView,
+        resolve_target: Option<&wgpu::TextureView>,
+        depth_view: &wgpu::TextureView,
+        color_load: wgpu::LoadOp<wgpu::Color>,
+        depth_load: wgpu::LoadOp<f32>,
+        draw: impl FnOnce(&mut wgpu::RenderPass),
+    ) {
         {
             let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Render Pass"),
+                label: Some(&self.labels.label("Render Pass")),
                 color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
-                    resolve_target: None,
+                    view: color_view,
+                    resolve_target,
                     ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color {
-                            r: 0.1,
-                            g: 0.2,
-                            b: 0.3,
-                            a: 1.0,
-                        }),
+                        load: color_load,
                         store: wgpu::StoreOp::Store,
                     },
                 })],
                 depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                    view: &mesh.depth_texture.1, // Using the depth texture view from the mesh
+                    view: depth_view,
                     depth_ops: Some(wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(1.0),
+                        load: depth_load,
                         store: wgpu::StoreOp::Store,
                     }),
                     stencil_ops: None,
@@ -186,19 +1623,589 @@ impl Renderer {
                 timestamp_writes: None,
             });
 
-            render_pass.set_pipeline(&self.pipeline);
             render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
-            render_pass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
-            render_pass.set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
-            render_pass.draw_indexed(0..mesh.num_indices, 0, 0..1);
+            render_pass.set_bind_group(1, &self.material_bind_group, &[]);
+            render_pass.set_bind_group(2, self.shadow_maps.bind_group(), &[]);
+            draw(&mut render_pass);
         }
+    }
 
-        queue.submit(std::iter::once(encoder.finish()));
-        output.present();
-        Ok(())
+    /// Issues `draw_indexed` when the mesh carries indices and a plain `draw` otherwise.
+    pub fn draw_mesh(render_pass: &mut wgpu::RenderPass, mesh: &crate::mesh::Mesh) {
+        render_pass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+        match mesh.draw_call() {
+            DrawCall::Indexed(index_buffer, indices) => {
+                render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+                render_pass.draw_indexed(indices, 0, 0..1);
+            }
+            DrawCall::Vertices(vertices) => render_pass.draw(vertices, 0..1),
+        }
+    }
+
+    /// Takes this frame's commands from `commands`, uploading the latest
+    /// camera right away. The meshes and text are returned in submission
+    /// order for the caller to draw.
+    pub fn drain_commands(&self, queue: &wgpu::Queue, commands: &RenderQueue) -> RenderFrame {
+        let frame = commands.drain();
+        if let Some(camera_uniform) = &frame.camera {
+            self.update_camera(queue, camera_uniform);
+        }
+        frame
     }
 
     pub fn update_camera(&self, queue: &wgpu::Queue, camera_uniform: &CameraUniform) {
+        self.camera.set(*camera_uniform);
         queue.write_buffer(&self.camera_buffer, 0, bytemuck::cast_slice(&[*camera_uniform]));
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::draw_list::state_changes;
+    use crate::material::SamplerSettings;
+    use crate::mesh::Mesh;
+    use crate::{test_gpu, test_log};
+
+    /// A counter-clockwise triangle covering all of clip space at depth `z`.
+    fn full_screen(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration, z: f32, color: [f32; 3]) -> Mesh {
+        let vertices = [[-1.0, -1.0], [3.0, -1.0], [-1.0, 3.0]]
+            .map(|[x, y]| Vertex::new([x, y, z], color, [0.0, 0.0, 1.0]));
+        Mesh::from_vertices(device, &Labels::default(), config, &vertices)
+    }
+
+    #[test]
+    fn prepass_writes_depth_without_a_color_target() {
+        let prepass = DepthStage::Prepass.depth_stencil(false);
+        assert!(prepass.depth_write_enabled);
+        assert_eq!(prepass.depth_compare, wgpu::CompareFunction::Less);
+        assert!(!DepthStage::Prepass.has_color_target());
+    }
+
+    #[test]
+    fn pass_after_the_prepass_only_shades_the_visible_depth() {
+        let after = DepthStage::AfterPrepass.depth_stencil(false);
+        assert!(!after.depth_write_enabled);
+        assert_eq!(after.depth_compare, wgpu::CompareFunction::Equal);
+        assert!(DepthStage::AfterPrepass.has_color_target());
+        assert!(DepthStage::Default.has_color_target());
+    }
+
+    #[test]
+    fn prepass_keeps_the_nearest_surface() {
+        let Some((device, queue)) = test_gpu::device() else {
+            return;
+        };
+        let labels = Labels::default();
+        let config = test_gpu::surface_config(8, 8);
+        let mut renderer = pollster::block_on(Renderer::new(&device, &labels, &queue, &config)).unwrap();
+        renderer.set_depth_prepass(&device, &config, true);
+        assert!(renderer.depth_prepass());
+        let target = RenderTarget::new(&device, &labels, &config, 8, 8);
+
+        let far = full_screen(&device, &config, 0.7, [0.0, 1.0, 0.0]);
+        let near = full_screen(&device, &config, 0.3, [1.0, 0.0, 0.0]);
+        renderer.render_to(&device, &queue, &target, &CameraUniform::new(), &[&far, &near]);
+
+        let pixels = test_gpu::read_pixels(&device, &queue, &target.color_texture.0);
+        assert!(pixels.chunks_exact(4).all(|pixel| pixel == [255, 0, 0, 255]), "{pixels:?}");
+    }
+
+    #[test]
+    fn validate_shader_reports_a_missing_semicolon() {
+        let source = "fn f() -> f32 {\n    let x = 1.0 return x;\n}\n";
+        let error = Renderer::validate_shader(source).unwrap_err();
+        assert!(matches!(error, ShaderError::Compile { line: 2, .. }), "{error:?}");
+        Renderer::validate_shader("fn f() -> f32 {\n    let x = 1.0;\n    return x;\n}\n").unwrap();
+    }
+
+    #[test]
+    fn reverse_z_tests_for_greater_depth() {
+        assert_eq!(DepthStage::Default.depth_stencil(true).depth_compare, wgpu::CompareFunction::Greater);
+        assert_eq!(DepthStage::Prepass.depth_stencil(true).depth_compare, wgpu::CompareFunction::Greater);
+        assert_eq!(DepthStage::Default.depth_stencil(false).depth_compare, wgpu::CompareFunction::Less);
+        // Equal doesn't depend on the direction
+        assert_eq!(
+            DepthStage::AfterPrepass.depth_stencil(true).depth_compare,
+            wgpu::CompareFunction::Equal
+        );
+    }
+
+    #[test]
+    fn reverse_z_keeps_the_surface_with_the_greater_depth() {
+        let Some((device, queue)) = test_gpu::device() else {
+            return;
+        };
+        let labels = Labels::default();
+        let config = test_gpu::surface_config(8, 8);
+        let mut renderer = pollster::block_on(Renderer::new(&device, &labels, &queue, &config)).unwrap();
+        renderer.set_reverse_z(&device, &config, true);
+        assert_eq!(renderer.depth_clear_value(), 0.0);
+        let target = RenderTarget::new(&device, &labels, &config, 8, 8);
+
+        // The identity camera passes depth through, so 0.7 is the nearer one
+        let near = full_screen(&device, &config, 0.7, [1.0, 0.0, 0.0]);
+        let far = full_screen(&device, &config, 0.3, [0.0, 1.0, 0.0]);
+        renderer.render_to(&device, &queue, &target, &CameraUniform::new(), &[&near, &far]);
+
+        let pixels = test_gpu::read_pixels(&device, &queue, &target.color_texture.0);
+        assert!(pixels.chunks_exact(4).all(|pixel| pixel == [255, 0, 0, 255]), "{pixels:?}");
+    }
+
+    #[test]
+    fn mesh_drawn_after_a_depth_clear_appears_in_front() {
+        let Some((device, queue)) = test_gpu::device() else {
+            return;
+        };
+        let labels = Labels::default();
+        let config = test_gpu::surface_config(8, 8);
+        let renderer = pollster::block_on(Renderer::new(&device, &labels, &queue, &config)).unwrap();
+        let target = RenderTarget::new(&device, &labels, &config, 8, 8);
+        let scene = full_screen(&device, &config, 0.3, [1.0, 0.0, 0.0]);
+        let viewmodel = full_screen(&device, &config, 0.6, [0.0, 1.0, 0.0]);
+
+        let draw_viewmodel = |clear_depth: bool| {
+            renderer.render_to(&device, &queue, &target, &CameraUniform::new(), &[&scene]);
+            let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+            if clear_depth {
+                renderer.clear_depth(&mut encoder, target.depth_view());
+            }
+            renderer.draw_group(&mut encoder, target.color_view(), target.depth_view(), &[&viewmodel]);
+            queue.submit(std::iter::once(encoder.finish()));
+            test_gpu::read_pixels(&device, &queue, &target.color_texture.0)
+        };
+
+        // Behind the scene, the second group is hidden...
+        let pixels = draw_viewmodel(false);
+        assert!(pixels.chunks_exact(4).all(|pixel| pixel == [255, 0, 0, 255]), "{pixels:?}");
+        // ...until depth is cleared between the groups
+        let pixels = draw_viewmodel(true);
+        assert!(pixels.chunks_exact(4).all(|pixel| pixel == [0, 255, 0, 255]), "{pixels:?}");
+    }
+
+    #[test]
+    fn mirroring_flips_the_front_face() {
+        use wgpu::FrontFace::{Ccw, Cw};
+        assert_eq!(pass_front_face(Ccw, false), Ccw);
+        assert_eq!(pass_front_face(Cw, false), Cw);
+        assert_eq!(pass_front_face(Ccw, true), Cw);
+        assert_eq!(pass_front_face(Cw, true), Ccw);
+    }
+
+    #[test]
+    fn reflection_pass_selects_the_flipped_pipeline_variant() {
+        let Some((device, queue)) = test_gpu::device() else {
+            return;
+        };
+        let labels = Labels::default();
+        let config = test_gpu::surface_config(8, 8);
+        let renderer = pollster::block_on(Renderer::new(&device, &labels, &queue, &config)).unwrap();
+        let mut mesh = full_screen(&device, &config, 0.5, [1.0, 0.0, 0.0]);
+        assert_eq!(renderer.pipeline.id_for_mesh(&mesh, false), 0);
+        assert_eq!(renderer.pipeline.id_for_mesh(&mesh, true), 1);
+        mesh.front_face = wgpu::FrontFace::Cw;
+        assert_eq!(renderer.pipeline.id_for_mesh(&mesh, true), 0);
+    }
+
+    #[test]
+    fn mirrored_target_culls_the_other_side() {
+        let Some((device, queue)) = test_gpu::device() else {
+            return;
+        };
+        let labels = Labels::default();
+        let config = test_gpu::surface_config(8, 8);
+        let renderer = pollster::block_on(Renderer::new(&device, &labels, &queue, &config)).unwrap();
+        let mut target = RenderTarget::new(&device, &labels, &config, 8, 8);
+        let mut mesh = full_screen(&device, &config, 0.5, [1.0, 0.0, 0.0]);
+        let red_pixels = |target: &RenderTarget, mesh: &Mesh| {
+            renderer.render_to(&device, &queue, target, &CameraUniform::new(), &[mesh]);
+            let pixels = test_gpu::read_pixels(&device, &queue, &target.color_texture.0);
+            pixels.chunks_exact(4).filter(|&pixel| pixel == [255, 0, 0, 255]).count()
+        };
+
+        assert_eq!(red_pixels(&target, &mesh), 64);
+        // Seen in a mirror, the counter-clockwise triangle shows its back
+        target.mirrored = true;
+        assert_eq!(red_pixels(&target, &mesh), 0);
+        mesh.front_face = wgpu::FrontFace::Cw;
+        assert_eq!(red_pixels(&target, &mesh), 64);
+    }
+
+    #[test]
+    fn debug_bounds_add_twelve_edges_per_actor() {
+        let Some((device, queue)) = test_gpu::device() else {
+            return;
+        };
+        let labels = Labels::default();
+        let config = test_gpu::surface_config(8, 8);
+        let mut renderer = pollster::block_on(Renderer::new(&device, &labels, &queue, &config)).unwrap();
+        let mut lines = DebugLines::new(&device, &labels, &config, 1);
+        let mut scene = Scene::new();
+        let ids: Vec<ActorId> = (0..5)
+            .map(|x| {
+                let mut actor = crate::base::Actor::new();
+                actor.set_position(x as f32 * 2.0, 0.0, 0.0);
+                scene.spawn(actor)
+            })
+            .collect();
+        let unit = |_| {
+            Some(Aabb {
+                min: crate::math::Vector3::new(-0.5, -0.5, -0.5),
+                max: crate::math::Vector3::new(0.5, 0.5, 0.5),
+            })
+        };
+
+        renderer.collect_debug_bounds(&mut lines, &scene, unit, None);
+        assert!(lines.segments().is_empty());
+
+        renderer.set_debug_bounds(true);
+        renderer.collect_debug_bounds(&mut lines, &scene, unit, None);
+        assert_eq!(lines.segments().len(), 5 * 12);
+        assert!(lines.segments().iter().all(|segment| segment.color == DEBUG_BOUNDS_COLOR));
+
+        // Actors without bounds are skipped; the frustum adds twelve more
+        lines.clear();
+        let camera = Camera::new(Vec3::new(0.0, 0.0, 5.0), 1.0);
+        let skipped = ids[0];
+        renderer.collect_debug_bounds(&mut lines, &scene, |id| unit(id).filter(|_| id != skipped), Some(&camera));
+        assert_eq!(lines.segments().len(), 4 * 12 + 12);
+    }
+
+    #[test]
+    fn strips_set_a_strip_index_format_and_lists_do_not() {
+        use wgpu::PrimitiveTopology::*;
+        for topology in [TriangleStrip, LineStrip] {
+            let state = primitive_state(topology, wgpu::FrontFace::Ccw);
+            assert_eq!(state.strip_index_format, Some(wgpu::IndexFormat::Uint16));
+        }
+        for topology in [TriangleList, LineList, PointList] {
+            assert_eq!(primitive_state(topology, wgpu::FrontFace::Ccw).strip_index_format, None);
+        }
+        assert_eq!(primitive_state(PointList, wgpu::FrontFace::Ccw).cull_mode, None);
+    }
+
+    #[test]
+    fn point_list_mesh_selects_the_point_pipeline_and_draws_each_vertex() {
+        let Some((device, queue)) = test_gpu::device() else {
+            return;
+        };
+        let labels = Labels::default();
+        let config = test_gpu::surface_config(8, 8);
+        let renderer = pollster::block_on(Renderer::new(&device, &labels, &queue, &config)).unwrap();
+        let target = RenderTarget::new(&device, &labels, &config, 8, 8);
+        // One point at the center of each of five distinct pixels
+        let vertices = [(0, 0), (3, 1), (7, 2), (2, 5), (6, 7)]
+            .map(|(x, y)| [(x as f32 + 0.5) / 4.0 - 1.0, 1.0 - (y as f32 + 0.5) / 4.0])
+            .map(|[x, y]| Vertex::new([x, y, 0.5], [1.0, 0.0, 0.0], [0.0, 0.0, 1.0]));
+        let mesh = Mesh::from_vertices(&device, &labels, &config, &vertices)
+            .with_topology(wgpu::PrimitiveTopology::PointList);
+
+        let point = EXTRA_TOPOLOGIES
+            .iter()
+            .position(|(topology, _)| *topology == wgpu::PrimitiveTopology::PointList)
+            .unwrap();
+        assert_eq!(renderer.pipeline.id_for_mesh(&mesh, false), point + 2);
+        assert_eq!(renderer.pipeline.id_for_mesh(&mesh, true), point + 2);
+
+        renderer.render_to(&device, &queue, &target, &CameraUniform::new(), &[&mesh]);
+        let pixels = test_gpu::read_pixels(&device, &queue, &target.color_texture.0);
+        let red = pixels.chunks_exact(4).filter(|&pixel| pixel == [255, 0, 0, 255]).count();
+        assert_eq!(red, mesh.num_vertices as usize);
+    }
+
+    #[test]
+    fn depth_clamp_needs_depth_clip_control() {
+        assert!(!supports_depth_clamp(wgpu::Features::empty()));
+        assert!(supports_depth_clamp(wgpu::Features::DEPTH_CLIP_CONTROL));
+    }
+
+    #[test]
+    fn depth_clamp_without_the_feature_is_a_logged_no_op() {
+        // The test device requests no features, so it never has DEPTH_CLIP_CONTROL
+        let Some((device, queue)) = test_gpu::device() else {
+            return;
+        };
+        let labels = Labels::default();
+        let config = test_gpu::surface_config(8, 8);
+        let mut renderer = pollster::block_on(Renderer::new(&device, &labels, &queue, &config)).unwrap();
+
+        let (applied, warnings) = test_log::warnings(|| renderer.set_depth_clamp(&device, &config, true));
+        assert!(!applied);
+        assert_eq!(warnings.len(), 1, "{warnings:?}");
+        assert!(!renderer.depth_clamp());
+        assert!(!renderer.pipeline_key(1).unclipped_depth);
+
+        let (applied, warnings) = test_log::warnings(|| renderer.set_depth_clamp(&device, &config, false));
+        assert!(applied);
+        assert!(warnings.is_empty(), "{warnings:?}");
+    }
+
+    #[test]
+    fn white_texture_renders_like_vertex_colors_alone() {
+        let Some((device, queue)) = test_gpu::device() else {
+            return;
+        };
+        let labels = Labels::default();
+        let config = test_gpu::surface_config(8, 8);
+        let mut renderer = pollster::block_on(Renderer::new(&device, &labels, &queue, &config)).unwrap();
+        let target = RenderTarget::new(&device, &labels, &config, 8, 8);
+        let mesh = full_screen(&device, &config, 0.5, [0.8, 0.4, 0.2]);
+        let mut render = |texture: Option<&Texture>| {
+            renderer.set_material(&device, &queue, &Material::default(), texture);
+            renderer.render_to(&device, &queue, &target, &CameraUniform::new(), &[&mesh]);
+            test_gpu::read_pixels(&device, &queue, &target.color_texture.0)
+        };
+
+        let untextured = render(None);
+        let white = Texture::white(&device, &labels, &queue);
+        assert_eq!(render(Some(&white)), untextured);
+
+        // Anything else multiplies in: a red texture leaves only red
+        let red = Texture::from_rgba8(&device, &labels, &queue, 1, 1, &[255, 0, 0, 255], &Default::default()).unwrap();
+        let tinted = render(Some(&red));
+        assert!(tinted.chunks_exact(4).all(|pixel| pixel[1] == 0 && pixel[2] == 0));
+        assert_eq!(tinted[0], untextured[0]);
+    }
+
+    /// Features `set_hdr` accepts, whatever the test adapter reports.
+    fn hdr_features(flags: wgpu::TextureFormatFeatureFlags) -> wgpu::TextureFormatFeatures {
+        wgpu::TextureFormatFeatures {
+            allowed_usages: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_SRC,
+            flags,
+        }
+    }
+
+    /// Red channel of the top-left texel of an `HDR_FORMAT` texture.
+    fn read_hdr_red(device: &wgpu::Device, queue: &wgpu::Queue, texture: &wgpu::Texture) -> f32 {
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: 8,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            wgpu::TexelCopyBufferInfo {
+                buffer: &buffer,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: None,
+                    rows_per_image: None,
+                },
+            },
+            wgpu::Extent3d {
+                width: 1,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+        );
+        queue.submit(std::iter::once(encoder.finish()));
+        buffer.slice(..).map_async(wgpu::MapMode::Read, |result| result.unwrap());
+        let _ = device.poll(wgpu::Maintain::Wait);
+        let texel = buffer.slice(..).get_mapped_range();
+        let bits = u16::from_le_bytes([texel[0], texel[1]]);
+        // Normal half floats only: sign, 5 exponent bits, 10 mantissa bits
+        let sign = if bits & 0x8000 == 0 { 1.0 } else { -1.0 };
+        let exponent = i32::from((bits >> 10) & 0x1F) - 15;
+        sign * 2f32.powi(exponent) * (1.0 + f32::from(bits & 0x3FF) / 1024.0)
+    }
+
+    #[test]
+    fn hdr_without_a_filterable_format_is_a_logged_no_op() {
+        let Some((device, queue)) = test_gpu::device() else {
+            return;
+        };
+        let labels = Labels::default();
+        let config = test_gpu::surface_config(8, 8);
+        let mut renderer = pollster::block_on(Renderer::new(&device, &labels, &queue, &config)).unwrap();
+
+        let features = hdr_features(wgpu::TextureFormatFeatureFlags::empty());
+        let (applied, warnings) = test_log::warnings(|| renderer.set_hdr(&device, &config, &features, true));
+        assert!(!applied);
+        assert_eq!(warnings.len(), 1, "{warnings:?}");
+        assert!(renderer.hdr_target().is_none());
+    }
+
+    #[test]
+    fn hdr_target_keeps_values_above_one_before_tonemapping() {
+        let Some((device, queue)) = test_gpu::device() else {
+            return;
+        };
+        let labels = Labels::default();
+        let config = test_gpu::surface_config(8, 8);
+        let mut renderer = pollster::block_on(Renderer::new(&device, &labels, &queue, &config)).unwrap();
+        assert!(renderer.set_hdr(&device, &config, &hdr_features(wgpu::TextureFormatFeatureFlags::FILTERABLE), true));
+        // White vertices times a base color of 4.0 shade to 4.0
+        let material = Material {
+            base_color: [4.0, 4.0, 4.0, 1.0],
+            ..Default::default()
+        };
+        renderer.set_material(&device, &queue, &material, None);
+        renderer.update_camera(&queue, &CameraUniform::new());
+        let mesh = full_screen(&device, &config, 0.5, [1.0, 1.0, 1.0]);
+
+        let target = renderer.hdr_target().expect("HDR is on");
+        assert_eq!(target.color_texture.0.format(), HDR_FORMAT);
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        let pipeline = renderer.hdr_pipeline.as_ref().expect("HDR pipeline");
+        renderer.encode_pass(&mut encoder, pipeline, target.color_view(), None, target.depth_view(), &[&mesh], false);
+        queue.submit(std::iter::once(encoder.finish()));
+
+        let red = read_hdr_red(&device, &queue, &target.color_texture.0);
+        assert!((red - 4.0).abs() < 0.01, "{red}");
+
+        // The same draw into the surface format clips at one
+        let clipped = RenderTarget::new(&device, &labels, &config, 8, 8);
+        renderer.render_to(&device, &queue, &clipped, &CameraUniform::new(), &[&mesh]);
+        assert_eq!(test_gpu::read_pixels(&device, &queue, &clipped.color_texture.0)[0], 255);
+    }
+
+    #[test]
+    fn msaa_depth_has_the_msaa_sample_count() {
+        let Some((adapter, device, queue)) = test_gpu::adapter() else {
+            return;
+        };
+        let labels = Labels::default();
+        let config = test_gpu::surface_config(8, 8);
+        let mut renderer = pollster::block_on(Renderer::new(&device, &labels, &queue, &config)).unwrap();
+        renderer.set_aa(&adapter, &device, &config, AaMode::Msaa(4)).unwrap();
+
+        let (color, depth) = (renderer.msaa_color.as_ref().unwrap(), renderer.msaa_depth.as_ref().unwrap());
+        assert_eq!(color.0.sample_count(), 4);
+        assert_eq!(depth.0.sample_count(), 4);
+    }
+
+    #[test]
+    fn unsupported_sample_counts_are_rejected() {
+        let x4 = wgpu::TextureFormatFeatureFlags::MULTISAMPLE_X4;
+        let formats = [(wgpu::TextureFormat::Rgba8Unorm, x4), (DEPTH_FORMAT, x4)];
+        assert_eq!(check_msaa_support(4, &formats), Ok(()));
+        assert_eq!(
+            check_msaa_support(3, &formats),
+            Err(AaError::UnsupportedSampleCount { samples: 3, format: wgpu::TextureFormat::Rgba8Unorm })
+        );
+        let color_only = [(wgpu::TextureFormat::Rgba8Unorm, x4), (DEPTH_FORMAT, wgpu::TextureFormatFeatureFlags::empty())];
+        assert_eq!(
+            check_msaa_support(4, &color_only),
+            Err(AaError::UnsupportedSampleCount { samples: 4, format: DEPTH_FORMAT })
+        );
+    }
+
+    #[test]
+    fn set_aa_keeps_the_mode_for_unsupported_counts_and_treats_one_sample_as_none() {
+        let Some((adapter, device, queue)) = test_gpu::adapter() else {
+            return;
+        };
+        let labels = Labels::default();
+        let config = test_gpu::surface_config(8, 8);
+        let mut renderer = pollster::block_on(Renderer::new(&device, &labels, &queue, &config)).unwrap();
+        assert!(renderer.set_aa(&adapter, &device, &config, AaMode::Msaa(3)).is_err());
+        assert_eq!(renderer.aa(), AaMode::None);
+        assert!(renderer.msaa_color.is_none());
+
+        renderer.set_aa(&adapter, &device, &config, AaMode::Msaa(1)).unwrap();
+        assert_eq!(renderer.aa(), AaMode::None);
+        assert!(renderer.msaa_color.is_none() && renderer.msaa_depth.is_none());
+    }
+
+    /// A counter-clockwise white quad covering clip space from `left` to
+    /// `right`, full height.
+    fn strip(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration, left: f32, right: f32) -> Mesh {
+        let vertices = [[left, -1.0], [right, -1.0], [right, 1.0], [left, -1.0], [right, 1.0], [left, 1.0]]
+            .map(|[x, y]| Vertex::new([x, y, 0.5], [1.0, 1.0, 1.0], [0.0, 0.0, 1.0]));
+        Mesh::from_vertices(device, &Labels::default(), config, &vertices)
+    }
+
+    fn colored(base_color: [f32; 4]) -> Material {
+        Material {
+            base_color,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn each_mesh_is_drawn_with_its_own_material() {
+        let Some((device, queue)) = test_gpu::device() else {
+            return;
+        };
+        let labels = Labels::default();
+        let config = test_gpu::surface_config(8, 8);
+        let mut renderer = pollster::block_on(Renderer::new(&device, &labels, &queue, &config)).unwrap();
+        renderer.set_material(&device, &queue, &colored([0.0, 0.0, 1.0, 1.0]), None);
+        let red = renderer.add_material(&device, &colored([1.0, 0.0, 0.0, 1.0]), None);
+        let green = renderer.add_material(&device, &colored([0.0, 1.0, 0.0, 1.0]), None);
+
+        let left = strip(&device, &config, -1.0, -0.5).with_material(red);
+        let middle = strip(&device, &config, -0.5, 0.5);
+        let right = strip(&device, &config, 0.5, 1.0).with_material(green);
+        let target = RenderTarget::new(&device, &labels, &config, 8, 8);
+        renderer.render_to(&device, &queue, &target, &CameraUniform::new(), &[&left, &middle, &right]);
+
+        let pixels = test_gpu::read_pixels(&device, &queue, &target.color_texture.0);
+        let pixel = |x: usize| &pixels[x * 4..x * 4 + 4];
+        assert_eq!(pixel(0), [255, 0, 0, 255]);
+        assert_eq!(pixel(4), [0, 0, 255, 255]);
+        assert_eq!(pixel(7), [0, 255, 0, 255]);
+    }
+
+    #[test]
+    fn meshes_sharing_a_material_are_bound_once() {
+        let Some((device, queue)) = test_gpu::device() else {
+            return;
+        };
+        let labels = Labels::default();
+        let config = test_gpu::surface_config(8, 8);
+        let mut renderer = pollster::block_on(Renderer::new(&device, &labels, &queue, &config)).unwrap();
+        let red = renderer.add_material(&device, &colored([1.0, 0.0, 0.0, 1.0]), None);
+        let green = renderer.add_material(&device, &colored([0.0, 1.0, 0.0, 1.0]), None);
+
+        let meshes = [Some(red), None, Some(green), Some(red), None, Some(green)].map(|material| {
+            let mut mesh = strip(&device, &config, -1.0, 1.0);
+            mesh.material = material;
+            mesh
+        });
+        let meshes: Vec<&Mesh> = meshes.iter().collect();
+        let draws = renderer.sorted_draws(&renderer.pipeline, &meshes, false);
+        assert_eq!(draws.iter().map(|draw| draw.index).collect::<Vec<_>>(), [1, 4, 0, 3, 2, 5]);
+        // One bind per material instead of one per mesh
+        assert_eq!(state_changes(&draws), (1, 3));
+    }
+
+    #[test]
+    fn lod_bias_samples_coarser_mips() {
+        let Some((device, queue)) = test_gpu::device() else {
+            return;
+        };
+        let labels = Labels::default();
+        let config = test_gpu::surface_config(8, 8);
+        let mut renderer = pollster::block_on(Renderer::new(&device, &labels, &queue, &config)).unwrap();
+        let target = RenderTarget::new(&device, &labels, &config, 8, 8);
+        // One texel per pixel, so the unbiased sample reads mip 0 exactly
+        let vertices = [[-1.0, -1.0], [3.0, -1.0], [-1.0, 3.0]].map(|[x, y]| {
+            Vertex::new([x, y, 0.5], [1.0, 1.0, 1.0], [0.0, 0.0, 1.0]).with_uv([(x + 1.0) * 0.5, (1.0 - y) * 0.5])
+        });
+        let mesh = Mesh::from_vertices(&device, &labels, &config, &vertices);
+        let checker: Vec<u8> = (0..64).flat_map(|i: u32| [((i % 8 + i / 8) % 2 * 255) as u8; 4]).collect();
+        let texture = Texture::from_rgba8(&device, &labels, &queue, 8, 8, &checker, &Default::default()).unwrap();
+
+        let mut render = |lod_bias: f32| {
+            let material = Material::default().with_sampler(SamplerSettings::new(1, lod_bias).unwrap()).unwrap();
+            renderer.set_material(&device, &queue, &material, Some(&texture));
+            renderer.render_to(&device, &queue, &target, &CameraUniform::new(), &[&mesh]);
+            let pixels = test_gpu::read_pixels(&device, &queue, &target.color_texture.0);
+            pixels.chunks_exact(4).map(|pixel| pixel[0]).collect::<Vec<_>>()
+        };
+
+        let sharp = render(0.0);
+        assert_eq!(sharp.iter().min(), Some(&0));
+        assert_eq!(sharp.iter().max(), Some(&255));
+        // Mip 1 and beyond average the checker into an even gray
+        let blurred = render(1.0);
+        let (min, max) = (blurred.iter().min().unwrap(), blurred.iter().max().unwrap());
+        assert!(max - min <= 2, "{blurred:?}");
+        assert!((20..=235).contains(min), "{blurred:?}");
+    }
+}
@@ -0,0 +1,301 @@
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// What a tracked GPU resource is used for, for the per-category report.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ResourceCategory {
+    /// Vertex and index buffers.
+    Mesh,
+    /// Sampled textures such as atlases and environment maps.
+    Texture,
+    /// Color, depth and G-buffer attachments.
+    RenderTarget,
+    Other,
+}
+
+impl ResourceCategory {
+    pub const ALL: [ResourceCategory; 4] = [
+        ResourceCategory::Mesh,
+        ResourceCategory::Texture,
+        ResourceCategory::RenderTarget,
+        ResourceCategory::Other,
+    ];
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ResourceKind {
+    Buffer,
+    Texture,
+}
+
+/// Bytes in use by one category.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResourceUsage {
+    pub category: ResourceCategory,
+    pub buffer_bytes: u64,
+    pub texture_bytes: u64,
+}
+
+const CATEGORIES: usize = ResourceCategory::ALL.len();
+#[allow(clippy::declare_interior_mutable_const)]
+const ZERO: AtomicU64 = AtomicU64::new(0);
+#[allow(clippy::declare_interior_mutable_const)]
+const ZEROES: [AtomicU64; CATEGORIES] = [ZERO; CATEGORIES];
+
+static GLOBAL: ResourceTracker = ResourceTracker::new();
+
+/// Running totals of the GPU memory held by buffers and textures created
+/// through the crate's helpers. Sizes are what was requested from wgpu; the
+/// driver may round allocations up.
+#[derive(Debug)]
+pub struct ResourceTracker {
+    buffers: [AtomicU64; CATEGORIES],
+    textures: [AtomicU64; CATEGORIES],
+}
+
+impl ResourceTracker {
+    pub const fn new() -> Self {
+        Self {
+            buffers: ZEROES,
+            textures: ZEROES,
+        }
+    }
+
+    /// The tracker every crate helper reports to.
+    pub fn global() -> &'static ResourceTracker {
+        &GLOBAL
+    }
+
+    fn counter(&self, kind: ResourceKind, category: ResourceCategory) -> &AtomicU64 {
+        match kind {
+            ResourceKind::Buffer => &self.buffers[category.index()],
+            ResourceKind::Texture => &self.textures[category.index()],
+        }
+    }
+
+    /// Adds `bytes` until the returned allocation is dropped.
+    pub fn track(&'static self, kind: ResourceKind, category: ResourceCategory, bytes: u64) -> Allocation {
+        self.counter(kind, category).fetch_add(bytes, Ordering::Relaxed);
+        Allocation {
+            tracker: self,
+            kind,
+            category,
+            bytes,
+        }
+    }
+
+    pub fn bytes(&self, kind: ResourceKind, category: ResourceCategory) -> u64 {
+        self.counter(kind, category).load(Ordering::Relaxed)
+    }
+
+    pub fn total_buffer_bytes(&self) -> u64 {
+        self.buffers.iter().map(|bytes| bytes.load(Ordering::Relaxed)).sum()
+    }
+
+    pub fn total_texture_bytes(&self) -> u64 {
+        self.textures.iter().map(|bytes| bytes.load(Ordering::Relaxed)).sum()
+    }
+
+    pub fn report(&self) -> Vec<ResourceUsage> {
+        ResourceCategory::ALL
+            .iter()
+            .map(|&category| ResourceUsage {
+                category,
+                buffer_bytes: self.bytes(ResourceKind::Buffer, category),
+                texture_bytes: self.bytes(ResourceKind::Texture, category),
+            })
+            .collect()
+    }
+}
+
+impl Default for ResourceTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Bytes counted by a tracker, released when dropped.
+#[derive(Debug)]
+pub struct Allocation {
+    tracker: &'static ResourceTracker,
+    kind: ResourceKind,
+    category: ResourceCategory,
+    bytes: u64,
+}
+
+impl Allocation {
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+}
+
+impl Drop for Allocation {
+    fn drop(&mut self) {
+        self.tracker
+            .counter(self.kind, self.category)
+            .fetch_sub(self.bytes, Ordering::Relaxed);
+    }
+}
+
+/// A GPU resource whose size stays counted in `ResourceTracker::global()`
+/// for as long as it lives. Derefs to the wrapped resource.
+#[derive(Debug)]
+pub struct Tracked<T> {
+    resource: T,
+    allocation: Allocation,
+}
+
+impl<T> Tracked<T> {
+    pub fn allocation(&self) -> &Allocation {
+        &self.allocation
+    }
+
+    pub fn into_inner(self) -> T {
+        self.resource
+    }
+}
+
+impl Tracked<wgpu::Buffer> {
+    pub fn buffer(buffer: wgpu::Buffer, category: ResourceCategory) -> Self {
+        let allocation = ResourceTracker::global().track(ResourceKind::Buffer, category, buffer.size());
+        Self {
+            resource: buffer,
+            allocation,
+        }
+    }
+}
+
+impl Tracked<wgpu::Texture> {
+    pub fn texture(texture: wgpu::Texture, category: ResourceCategory) -> Self {
+        let bytes = texture_bytes(
+            texture.format(),
+            texture.size(),
+            texture.dimension(),
+            texture.mip_level_count(),
+            texture.sample_count(),
+        );
+        let allocation = ResourceTracker::global().track(ResourceKind::Texture, category, bytes);
+        Self {
+            resource: texture,
+            allocation,
+        }
+    }
+}
+
+impl<T> Deref for Tracked<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.resource
+    }
+}
+
+impl<T> DerefMut for Tracked<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.resource
+    }
+}
+
+/// Size of a texture with every mip level and sample.
+pub fn texture_bytes(
+    format: wgpu::TextureFormat,
+    size: wgpu::Extent3d,
+    dimension: wgpu::TextureDimension,
+    mip_level_count: u32,
+    sample_count: u32,
+) -> u64 {
+    // Depth formats without a fixed copy size (e.g. Depth24Plus) are counted
+    // as 4 bytes, which is what drivers typically allocate
+    let block_bytes = format
+        .block_copy_size(None)
+        .or_else(|| format.target_pixel_byte_cost())
+        .unwrap_or(4) as u64;
+    let (block_width, block_height) = format.block_dimensions();
+    (0..mip_level_count)
+        .map(|mip| {
+            let width = (size.width >> mip).max(1);
+            let height = (size.height >> mip).max(1);
+            let layers = match dimension {
+                wgpu::TextureDimension::D3 => (size.depth_or_array_layers >> mip).max(1),
+                _ => size.depth_or_array_layers,
+            };
+            let blocks = width.div_ceil(block_width) as u64 * height.div_ceil(block_height) as u64;
+            blocks * layers as u64 * block_bytes
+        })
+        .sum::<u64>()
+        * sample_count as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::render::config::Labels;
+    use crate::mesh::{MeshData, Vertex};
+    use crate::test_gpu;
+
+    /// A tracker of its own, so tests running in parallel don't share totals.
+    fn tracker() -> &'static ResourceTracker {
+        Box::leak(Box::new(ResourceTracker::new()))
+    }
+
+    #[test]
+    fn allocations_are_counted_until_dropped() {
+        let tracker = tracker();
+        let vertices = tracker.track(ResourceKind::Buffer, ResourceCategory::Mesh, 1000);
+        let atlas = tracker.track(ResourceKind::Texture, ResourceCategory::Texture, 4096);
+        let depth = tracker.track(ResourceKind::Texture, ResourceCategory::RenderTarget, 256);
+        assert_eq!(tracker.total_buffer_bytes(), 1000);
+        assert_eq!(tracker.total_texture_bytes(), 4352);
+        assert_eq!(
+            tracker.report()[ResourceCategory::Texture.index()],
+            ResourceUsage {
+                category: ResourceCategory::Texture,
+                buffer_bytes: 0,
+                texture_bytes: 4096,
+            }
+        );
+
+        drop(atlas);
+        assert_eq!(tracker.total_texture_bytes(), 256);
+        drop((vertices, depth));
+        assert_eq!((tracker.total_buffer_bytes(), tracker.total_texture_bytes()), (0, 0));
+    }
+
+    #[test]
+    fn texture_bytes_include_mips_samples_and_blocks() {
+        let size = |width, height| wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        };
+        let d2 = wgpu::TextureDimension::D2;
+        let rgba = wgpu::TextureFormat::Rgba8Unorm;
+        assert_eq!(texture_bytes(rgba, size(4, 4), d2, 1, 1), 64);
+        // 4x4 + 2x2 + 1x1 pixels
+        assert_eq!(texture_bytes(rgba, size(4, 4), d2, 3, 1), 84);
+        assert_eq!(texture_bytes(rgba, size(4, 4), d2, 1, 4), 256);
+        // Four 8-byte blocks
+        assert_eq!(texture_bytes(wgpu::TextureFormat::Bc1RgbaUnorm, size(8, 8), d2, 1, 1), 32);
+    }
+
+    #[test]
+    fn uploading_a_mesh_tracks_its_buffers() {
+        let Some((device, _queue)) = test_gpu::device() else {
+            return;
+        };
+        let data = MeshData::cube();
+        let mesh = data.upload(&device, &Labels::default(), &test_gpu::surface_config(4, 4));
+
+        let vertex_bytes = (data.vertices.len() * std::mem::size_of::<Vertex>()) as u64;
+        let index_bytes = (data.indices.len() * std::mem::size_of::<u16>()) as u64;
+        assert_eq!(mesh.vertex_buffer.allocation().bytes(), vertex_bytes);
+        assert_eq!(mesh.index_buffer.as_ref().unwrap().allocation().bytes(), index_bytes);
+        // Other tests may hold buffers too, but never fewer than this mesh's
+        let global = ResourceTracker::global();
+        assert!(global.bytes(ResourceKind::Buffer, ResourceCategory::Mesh) >= vertex_bytes + index_bytes);
+    }
+}
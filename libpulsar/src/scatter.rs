@@ -0,0 +1,151 @@
+use glam::{Mat4, Quat, Vec3};
+
+use crate::math::Rng;
+use crate::mesh::MeshData;
+
+/// One scattered object (grass blade, rock, tree).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScatterInstance {
+    pub position: Vec3,
+    /// Direction the instance's local +Y points: the surface normal when
+    /// aligning to the surface, world up otherwise.
+    pub up: Vec3,
+    /// Rotation about `up`, in radians.
+    pub yaw: f32,
+    pub scale: f32,
+}
+
+impl ScatterInstance {
+    pub fn model_matrix(&self) -> Mat4 {
+        let rotation = Quat::from_rotation_arc(Vec3::Y, self.up) * Quat::from_rotation_y(self.yaw);
+        Mat4::from_scale_rotation_translation(Vec3::splat(self.scale), rotation, self.position)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScatterOptions {
+    /// Uniform scale range, `min..max`.
+    pub scale: (f32, f32),
+    /// Tilt instances to the surface normal instead of keeping them upright.
+    pub align_to_normal: bool,
+}
+
+impl Default for ScatterOptions {
+    fn default() -> Self {
+        Self {
+            scale: (0.8, 1.2),
+            align_to_normal: false,
+        }
+    }
+}
+
+/// Scatters about `density` instances per square unit over the surface of
+/// `mesh`, with random yaw and scale. The same `seed` always gives the same
+/// instances.
+pub fn scatter_on_mesh(mesh: &MeshData, density: f32, seed: u64) -> Vec<ScatterInstance> {
+    scatter_on_mesh_with(mesh, density, seed, &ScatterOptions::default())
+}
+
+pub fn scatter_on_mesh_with(
+    mesh: &MeshData,
+    density: f32,
+    seed: u64,
+    options: &ScatterOptions,
+) -> Vec<ScatterInstance> {
+    let triangles: Vec<[Vec3; 3]> = mesh.triangle_positions().collect();
+
+    // Running total of triangle areas, so picking a uniform value in
+    // 0..total lands on each triangle in proportion to its area
+    let mut total_area = 0.0;
+    let cumulative_areas: Vec<f32> = triangles
+        .iter()
+        .map(|[a, b, c]| {
+            total_area += 0.5 * (*b - *a).cross(*c - *a).length();
+            total_area
+        })
+        .collect();
+    if total_area <= 0.0 || density <= 0.0 {
+        return Vec::new();
+    }
+
+    let mut rng = Rng::new(seed);
+    let count = (total_area * density).round() as usize;
+    (0..count)
+        .map(|_| {
+            let pick = rng.next_f32() * total_area;
+            let index = cumulative_areas
+                .partition_point(|&area| area <= pick)
+                .min(triangles.len() - 1);
+            let [a, b, c] = triangles[index];
+
+            // Uniform point in the triangle: fold the far half of the
+            // parallelogram back onto it
+            let (mut u, mut v) = (rng.next_f32(), rng.next_f32());
+            if u + v > 1.0 {
+                u = 1.0 - u;
+                v = 1.0 - v;
+            }
+            let position = a + (b - a) * u + (c - a) * v;
+
+            let up = if options.align_to_normal {
+                (b - a).cross(c - a).try_normalize().unwrap_or(Vec3::Y)
+            } else {
+                Vec3::Y
+            };
+            ScatterInstance {
+                position,
+                up,
+                yaw: rng.range(0.0, std::f32::consts::TAU),
+                scale: rng.range(options.scale.0, options.scale.1),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mesh::Vertex;
+
+    /// Flat `size` by `size` square on the XZ plane, facing +Y.
+    fn plane(size: f32) -> MeshData {
+        let h = size * 0.5;
+        let vertices = [[-h, 0.0, -h], [-h, 0.0, h], [h, 0.0, h], [h, 0.0, -h]]
+            .map(|position| Vertex {
+                position,
+                ..bytemuck::Zeroable::zeroed()
+            })
+            .to_vec();
+        MeshData::new(vertices, vec![0, 1, 2, 0, 2, 3])
+    }
+
+    #[test]
+    fn scattering_on_a_plane_stays_within_its_bounds() {
+        let options = ScatterOptions {
+            align_to_normal: true,
+            ..Default::default()
+        };
+        let instances = scatter_on_mesh_with(&plane(10.0), 2.0, 7, &options);
+        assert_eq!(instances.len(), 200);
+        for instance in &instances {
+            let p = instance.position;
+            assert!(p.x.abs() <= 5.0 + 1e-4 && p.z.abs() <= 5.0 + 1e-4, "{p:?}");
+            assert!(p.y.abs() < 1e-6, "{p:?}");
+            assert!(instance.up.abs_diff_eq(Vec3::Y, 1e-6), "{:?}", instance.up);
+            assert!((0.8..=1.2).contains(&instance.scale));
+        }
+    }
+
+    #[test]
+    fn same_seed_gives_the_same_instances() {
+        let mesh = plane(4.0);
+        assert_eq!(scatter_on_mesh(&mesh, 3.0, 11), scatter_on_mesh(&mesh, 3.0, 11));
+        assert_ne!(scatter_on_mesh(&mesh, 3.0, 11), scatter_on_mesh(&mesh, 3.0, 12));
+    }
+
+    #[test]
+    fn empty_mesh_or_zero_density_scatters_nothing() {
+        assert!(scatter_on_mesh(&MeshData::new(Vec::new(), Vec::new()), 1.0, 0).is_empty());
+        assert!(scatter_on_mesh(&plane(4.0), 0.0, 0).is_empty());
+    }
+}
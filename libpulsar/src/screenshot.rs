@@ -0,0 +1,194 @@
+use std::path::Path;
+
+use thiserror::Error;
+
+use crate::engine::render::config::Labels;
+
+#[derive(Debug, Error)]
+pub enum ScreenshotError {
+    #[error("Screenshots of {0:?} textures are not supported")]
+    UnsupportedFormat(wgpu::TextureFormat),
+    #[error("Failed to read the frame back from the GPU: {0}")]
+    Readback(#[from] wgpu::BufferAsyncError),
+    #[error("Failed to write the screenshot: {0}")]
+    Encode(#[from] image::ImageError),
+}
+
+/// Bytes per row of a `width` pixel RGBA8 texture copied into a buffer;
+/// wgpu requires rows padded to `COPY_BYTES_PER_ROW_ALIGNMENT`.
+pub fn padded_bytes_per_row(width: u32) -> u32 {
+    let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+    (width * 4).div_ceil(align) * align
+}
+
+/// Strips the row padding from copied texture data and converts it to tightly
+/// packed RGBA8. wgpu textures and PNGs both store the top row first, so no
+/// flip is needed.
+pub fn unpack_rows(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    format: wgpu::TextureFormat,
+) -> Result<Vec<u8>, ScreenshotError> {
+    let bgra = match format {
+        wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => false,
+        wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => true,
+        other => return Err(ScreenshotError::UnsupportedFormat(other)),
+    };
+    let row_bytes = width as usize * 4;
+    let mut rgba = Vec::with_capacity(row_bytes * height as usize);
+    for row in data.chunks(padded_bytes_per_row(width) as usize).take(height as usize) {
+        let row = &row[..row_bytes];
+        if bgra {
+            for pixel in row.chunks_exact(4) {
+                rgba.extend_from_slice(&[pixel[2], pixel[1], pixel[0], pixel[3]]);
+            }
+        } else {
+            rgba.extend_from_slice(row);
+        }
+    }
+    Ok(rgba)
+}
+
+/// Copies `texture` back to the CPU as RGBA8 rows, blocking until the GPU is
+/// done. The texture needs `TextureUsages::COPY_SRC`.
+pub fn read_texture(
+    device: &wgpu::Device,
+    labels: &Labels,
+    queue: &wgpu::Queue,
+    texture: &wgpu::Texture,
+) -> Result<Vec<u8>, ScreenshotError> {
+    let (width, height) = (texture.width(), texture.height());
+    // Fail before doing any GPU work
+    unpack_rows(&[], width, 0, texture.format())?;
+
+    let bytes_per_row = padded_bytes_per_row(width);
+    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some(&labels.label("Screenshot Buffer")),
+        size: bytes_per_row as wgpu::BufferAddress * height as wgpu::BufferAddress,
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some(&labels.label("Screenshot Encoder")),
+    });
+    encoder.copy_texture_to_buffer(
+        texture.as_image_copy(),
+        wgpu::TexelCopyBufferInfo {
+            buffer: &buffer,
+            layout: wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(bytes_per_row),
+                rows_per_image: Some(height),
+            },
+        },
+        texture.size(),
+    );
+    queue.submit(Some(encoder.finish()));
+
+    let slice = buffer.slice(..);
+    let (sender, receiver) = std::sync::mpsc::channel();
+    slice.map_async(wgpu::MapMode::Read, move |result| {
+        let _ = sender.send(result);
+    });
+    let _ = device.poll(wgpu::Maintain::Wait);
+    receiver.recv().unwrap_or(Err(wgpu::BufferAsyncError))?;
+
+    let rgba = unpack_rows(&slice.get_mapped_range(), width, height, texture.format());
+    buffer.unmap();
+    rgba
+}
+
+/// Encodes tightly packed RGBA8 pixels as a PNG at `path`.
+pub fn save_png(path: impl AsRef<Path>, width: u32, height: u32, rgba: &[u8]) -> Result<(), ScreenshotError> {
+    image::save_buffer_with_format(
+        path,
+        rgba,
+        width,
+        height,
+        image::ExtendedColorType::Rgba8,
+        image::ImageFormat::Png,
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_gpu;
+
+    #[test]
+    fn rows_are_padded_to_the_copy_alignment() {
+        assert_eq!(padded_bytes_per_row(1), 256);
+        assert_eq!(padded_bytes_per_row(64), 256);
+        assert_eq!(padded_bytes_per_row(65), 512);
+    }
+
+    #[test]
+    fn unpacking_strips_padding_and_swizzles_bgra() {
+        let (width, height) = (2, 2);
+        let stride = padded_bytes_per_row(width) as usize;
+        let mut data = vec![0xAA; stride * height as usize];
+        data[..8].copy_from_slice(&[1, 2, 3, 4, 5, 6, 7, 8]);
+        data[stride..stride + 8].copy_from_slice(&[9, 10, 11, 12, 13, 14, 15, 16]);
+
+        let rgba = unpack_rows(&data, width, height, wgpu::TextureFormat::Rgba8Unorm).unwrap();
+        assert_eq!(rgba, (1..=16).collect::<Vec<u8>>());
+        let bgra = unpack_rows(&data, width, height, wgpu::TextureFormat::Bgra8UnormSrgb).unwrap();
+        assert_eq!(&bgra[..8], &[3, 2, 1, 4, 7, 6, 5, 8]);
+        assert!(matches!(
+            unpack_rows(&data, width, height, wgpu::TextureFormat::R8Unorm),
+            Err(ScreenshotError::UnsupportedFormat(wgpu::TextureFormat::R8Unorm))
+        ));
+    }
+
+    #[test]
+    fn saved_clear_color_frame_has_the_clear_color_top_left() {
+        let Some((device, queue)) = test_gpu::device() else {
+            return;
+        };
+        let (width, height) = (70, 3);
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: None,
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: test_gpu::FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color {
+                        r: 1.0,
+                        g: 0.0,
+                        b: 0.2,
+                        a: 1.0,
+                    }),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            ..Default::default()
+        });
+        queue.submit(Some(encoder.finish()));
+
+        let rgba = read_texture(&device, &Labels::default(), &queue, &texture).unwrap();
+        let path = std::env::temp_dir().join(format!("pulsar_screenshot_{}.png", std::process::id()));
+        save_png(&path, width, height, &rgba).unwrap();
+        let image = image::open(&path).unwrap().to_rgba8();
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(image.dimensions(), (width, height));
+        assert_eq!(image.get_pixel(0, 0).0, [255, 0, 51, 255]);
+    }
+}
@@ -0,0 +1,301 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use thiserror::Error;
+
+const INCLUDE_DIRECTIVE: &str = "//!include";
+
+#[derive(Debug, Error)]
+pub enum ShaderError {
+    #[error("Failed to read shader {path}: {source}")]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("Malformed include directive in {file}:{line}, expected //!include \"path.wgsl\"")]
+    MalformedInclude { file: PathBuf, line: usize },
+    #[error("Cyclic include of {0}")]
+    IncludeCycle(PathBuf),
+    #[error("{file}:{line}:{column}: {message}")]
+    Compile {
+        file: PathBuf,
+        line: usize,
+        column: usize,
+        message: String,
+    },
+}
+
+/// WGSL source with all includes inlined, remembering where each line came
+/// from so errors can point at the original file.
+#[derive(Debug, Default, Clone)]
+pub struct ShaderSource {
+    pub source: String,
+    origins: Vec<(PathBuf, usize)>,
+}
+
+impl ShaderSource {
+    /// File and line (both 1-based) that produced `line` of the combined source.
+    pub fn origin(&self, line: usize) -> Option<(&Path, usize)> {
+        let (file, original) = self.origins.get(line.checked_sub(1)?)?;
+        Some((file.as_path(), *original))
+    }
+
+    fn push_line(&mut self, text: &str, file: &Path, line: usize) {
+        self.source.push_str(text);
+        self.source.push('\n');
+        self.origins.push((file.to_path_buf(), line));
+    }
+}
+
+/// Loads `entry` (relative to `root`) and recursively inlines every
+/// `//!include "path.wgsl"` directive, resolving paths against `root`.
+/// A file included more than once is only inlined the first time.
+pub fn preprocess(root: impl AsRef<Path>, entry: impl AsRef<Path>) -> Result<ShaderSource, ShaderError> {
+    let root = root.as_ref();
+    preprocess_with(entry.as_ref(), |path| std::fs::read_to_string(root.join(path)))
+}
+
+/// Like `preprocess`, but reads files through `read` so shaders can come from
+/// memory or an asset archive.
+pub fn preprocess_with(
+    entry: &Path,
+    mut read: impl FnMut(&Path) -> std::io::Result<String>,
+) -> Result<ShaderSource, ShaderError> {
+    let mut out = ShaderSource::default();
+    let mut stack = Vec::new();
+    let mut included = HashSet::new();
+    expand(entry, &mut read, &mut stack, &mut included, &mut out)?;
+    Ok(out)
+}
+
+fn expand(
+    path: &Path,
+    read: &mut impl FnMut(&Path) -> std::io::Result<String>,
+    stack: &mut Vec<PathBuf>,
+    included: &mut HashSet<PathBuf>,
+    out: &mut ShaderSource,
+) -> Result<(), ShaderError> {
+    let path = normalize(path);
+    if stack.contains(&path) {
+        return Err(ShaderError::IncludeCycle(path));
+    }
+    if !included.insert(path.clone()) {
+        return Ok(());
+    }
+
+    let text = read(&path).map_err(|source| ShaderError::Io {
+        path: path.clone(),
+        source,
+    })?;
+
+    stack.push(path.clone());
+    for (index, line) in text.lines().enumerate() {
+        let line_number = index + 1;
+        match line.trim_start().strip_prefix(INCLUDE_DIRECTIVE) {
+            Some(rest) => {
+                let target = parse_include(rest).ok_or_else(|| ShaderError::MalformedInclude {
+                    file: path.clone(),
+                    line: line_number,
+                })?;
+                expand(Path::new(target), read, stack, included, out)?;
+            }
+            None => out.push_line(line, &path, line_number),
+        }
+    }
+    stack.pop();
+    Ok(())
+}
+
+fn parse_include(rest: &str) -> Option<&str> {
+    let rest = rest.trim();
+    rest.strip_prefix('"')?.strip_suffix('"').filter(|path| !path.is_empty())
+}
+
+/// The crate's own WGSL files that `builtin` shaders may include.
+fn read_builtin(path: &Path) -> std::io::Result<String> {
+    let source = match path.to_str() {
+        Some("shader.wgsl") => include_str!("shader.wgsl"),
+        Some("shadow_cascades.wgsl") => include_str!("shadow_cascades.wgsl"),
+        Some("shadow_pcf.wgsl") => include_str!("shadow_pcf.wgsl"),
+        Some("environment.wgsl") => include_str!("environment.wgsl"),
+        _ => return Err(std::io::ErrorKind::NotFound.into()),
+    };
+    Ok(source.to_string())
+}
+
+/// `entry`, one of the crate's own WGSL files, with its includes inlined.
+pub(crate) fn builtin(entry: &str) -> ShaderSource {
+    preprocess_with(Path::new(entry), read_builtin).expect("built-in shaders only include built-in files")
+}
+
+/// Drops `.` components so `a/./b.wgsl` and `a/b.wgsl` count as the same file.
+fn normalize(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|component| !matches!(component, std::path::Component::CurDir))
+        .collect()
+}
+
+/// Parses and validates WGSL with naga without touching the GPU, so tools can
+/// show shader errors instead of crashing on them. `label` names the source in
+/// the returned error.
+pub fn validate_wgsl(label: &str, source: &str) -> Result<(), ShaderError> {
+    let compile_error = |location: Option<naga::SourceLocation>, message: String| {
+        let (line, column) = location
+            .map(|location| (location.line_number as usize, location.line_position as usize))
+            .unwrap_or((0, 0));
+        ShaderError::Compile {
+            file: PathBuf::from(label),
+            line,
+            column,
+            message,
+        }
+    };
+
+    let module = naga::front::wgsl::parse_str(source)
+        .map_err(|error| compile_error(error.location(source), error.message().to_string()))?;
+    naga::valid::Validator::new(naga::valid::ValidationFlags::all(), naga::valid::Capabilities::all())
+        .validate(&module)
+        .map_err(|error| compile_error(error.location(source), error.as_inner().to_string()))?;
+    Ok(())
+}
+
+/// Creates a shader module from preprocessed source, reporting the first
+/// compile error against the file and line it originally came from.
+pub async fn create_shader_module(
+    device: &wgpu::Device,
+    label: &str,
+    source: &ShaderSource,
+) -> Result<wgpu::ShaderModule, ShaderError> {
+    // Capture the validation error so a bad shader doesn't reach the
+    // device's uncaptured error handler, which panics by default.
+    device.push_error_scope(wgpu::ErrorFilter::Validation);
+    let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some(label),
+        source: wgpu::ShaderSource::Wgsl(source.source.as_str().into()),
+    });
+    let info = module.get_compilation_info().await;
+    let scope_error = device.pop_error_scope().await;
+
+    let error = info
+        .messages
+        .into_iter()
+        .find(|message| message.message_type == wgpu::CompilationMessageType::Error);
+    match (error, scope_error) {
+        (None, None) => Ok(module),
+        (None, Some(error)) => Err(ShaderError::Compile {
+            file: PathBuf::from(label),
+            line: 0,
+            column: 0,
+            message: error.to_string(),
+        }),
+        (Some(message), _) => {
+            let (line, column) = message
+                .location
+                .map(|location| (location.line_number as usize, location.line_position as usize))
+                .unwrap_or((0, 0));
+            let (file, line) = source
+                .origin(line)
+                .map(|(file, line)| (file.to_path_buf(), line))
+                .unwrap_or_else(|| (PathBuf::from(label), line));
+            Err(ShaderError::Compile {
+                file,
+                line,
+                column,
+                message: message.message,
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn files(entries: &[(&str, &str)]) -> impl FnMut(&Path) -> std::io::Result<String> {
+        let files: HashMap<PathBuf, String> = entries
+            .iter()
+            .map(|(path, source)| (PathBuf::from(path), source.to_string()))
+            .collect();
+        move |path| files.get(path).cloned().ok_or_else(|| std::io::ErrorKind::NotFound.into())
+    }
+
+    #[test]
+    fn include_is_inlined_in_place() {
+        let read = files(&[
+            ("main.wgsl", "// main\n//!include \"common.wgsl\"\nfn main() {}"),
+            ("common.wgsl", "const ONE: f32 = 1.0;"),
+        ]);
+        let source = preprocess_with(Path::new("main.wgsl"), read).unwrap();
+        assert_eq!(source.source, "// main\nconst ONE: f32 = 1.0;\nfn main() {}\n");
+        assert_eq!(source.origin(2), Some((Path::new("common.wgsl"), 1)));
+        assert_eq!(source.origin(3), Some((Path::new("main.wgsl"), 3)));
+        assert_eq!(source.origin(4), None);
+    }
+
+    #[test]
+    fn file_included_twice_is_inlined_once() {
+        let read = files(&[
+            ("main.wgsl", "//!include \"a.wgsl\"\n//!include \"./a.wgsl\""),
+            ("a.wgsl", "const A: u32 = 1u;"),
+        ]);
+        let source = preprocess_with(Path::new("main.wgsl"), read).unwrap();
+        assert_eq!(source.source, "const A: u32 = 1u;\n");
+    }
+
+    #[test]
+    fn cyclic_include_is_rejected() {
+        let read = files(&[
+            ("a.wgsl", "//!include \"b.wgsl\""),
+            ("b.wgsl", "//!include \"a.wgsl\""),
+        ]);
+        let error = preprocess_with(Path::new("a.wgsl"), read).unwrap_err();
+        assert!(matches!(error, ShaderError::IncludeCycle(path) if path == Path::new("a.wgsl")));
+    }
+
+    #[test]
+    fn malformed_and_missing_includes_are_reported() {
+        let read = files(&[("main.wgsl", "\n//!include common.wgsl")]);
+        let error = preprocess_with(Path::new("main.wgsl"), read).unwrap_err();
+        assert!(matches!(error, ShaderError::MalformedInclude { line: 2, .. }));
+
+        let read = files(&[("main.wgsl", "//!include \"missing.wgsl\"")]);
+        let error = preprocess_with(Path::new("main.wgsl"), read).unwrap_err();
+        assert!(matches!(error, ShaderError::Io { path, .. } if path == Path::new("missing.wgsl")));
+    }
+
+    #[test]
+    fn builtin_shaders_pass_validation() {
+        let source = builtin("shader.wgsl");
+        validate_wgsl("shader.wgsl", &source.source).unwrap();
+    }
+
+    #[test]
+    fn invalid_wgsl_reports_the_line_of_the_error() {
+        let error = validate_wgsl("broken.wgsl", "const A: f32 = 1.0;\nconst B: f32 = ;\n").unwrap_err();
+        let ShaderError::Compile { file, line, message, .. } = error else {
+            panic!("expected a compile error, got {error:?}");
+        };
+        assert_eq!(file, Path::new("broken.wgsl"));
+        assert_eq!(line, 2);
+        assert!(!message.is_empty());
+    }
+
+    #[test]
+    fn compile_errors_point_at_the_included_file() {
+        let Some((device, _queue)) = crate::test_gpu::device() else {
+            return;
+        };
+        let read = files(&[
+            ("main.wgsl", "// main\n//!include \"bad.wgsl\""),
+            ("bad.wgsl", "fn f() -> f32 {\n    return 1.0 +;\n}"),
+        ]);
+        let source = preprocess_with(Path::new("main.wgsl"), read).unwrap();
+        let error = pollster::block_on(create_shader_module(&device, "main.wgsl", &source)).unwrap_err();
+        assert!(
+            matches!(&error, ShaderError::Compile { file, line: 2, .. } if file == Path::new("bad.wgsl")),
+            "{error:?}"
+        );
+    }
+}
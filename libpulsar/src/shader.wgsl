@@ -1,31 +1,109 @@
 struct CameraUniform {
     view_proj: mat4x4<f32>,
+    position: vec4<f32>,
 };
 
 @group(0) @binding(0)
 var<uniform> camera: CameraUniform;
 
+struct MaterialUniform {
+    base_color: vec4<f32>,
+    metallic: f32,
+    roughness: f32,
+    env_mip_count: f32,
+    lod_bias: f32,
+};
+
+@group(1) @binding(0)
+var<uniform> material: MaterialUniform;
+@group(1) @binding(1)
+var base_texture: texture_2d<f32>;
+@group(1) @binding(2)
+var base_sampler: sampler;
+@group(1) @binding(3)
+var env_map: texture_cube<f32>;
+@group(1) @binding(4)
+var env_sampler: sampler;
+
+@group(2) @binding(0)
+var shadow_cascades: texture_depth_2d_array;
+@group(2) @binding(1)
+var shadow_sampler: sampler_comparison;
+@group(2) @binding(2)
+var<uniform> cascades: CascadeParams;
+@group(2) @binding(3)
+var<uniform> shadow: ShadowParams;
+
+//!include "shadow_cascades.wgsl"
+//!include "environment.wgsl"
+
+// Light left in fully shadowed areas, standing in for ambient light.
+const SHADOWED_LIGHT: f32 = 0.35;
+
 struct VertexInput {
     @location(0) position: vec3<f32>,
     @location(1) color: vec3<f32>,
+    @location(2) normal: vec3<f32>,
+    @location(5) uv: vec2<f32>,
 };
 
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) color: vec3<f32>,
+    @location(1) uv: vec2<f32>,
+    @location(2) world_position: vec3<f32>,
+    @location(3) normal: vec3<f32>,
 };
 
+// Vertex colors are authored in sRGB (see `color.rs`); lighting and the sRGB
+// surface both expect linear values.
+fn srgb_to_linear(c: vec3<f32>) -> vec3<f32> {
+    let low = c / 12.92;
+    let high = pow((c + 0.055) / 1.055, vec3<f32>(2.4));
+    return select(high, low, c <= vec3<f32>(0.04045));
+}
+
 @vertex
 fn vs_main(
     model: VertexInput,
 ) -> VertexOutput {
     var out: VertexOutput;
-    out.color = model.color;
+    out.color = srgb_to_linear(model.color);
+    out.uv = model.uv;
+    out.world_position = model.position;
+    out.normal = model.normal;
     out.clip_position = camera.view_proj * vec4<f32>(model.position, 1.0);
     return out;
 }
 
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // The white fallback texture and base color leave vertex colors untouched
+    let texel = textureSampleBias(base_texture, base_sampler, in.uv, material.lod_bias);
+    let lit = cascaded_shadow_factor(in.world_position, normalize(in.normal));
+    let light = mix(SHADOWED_LIGHT, 1.0, lit);
+    let albedo = texel * material.base_color;
+    var rgb = in.color * light * albedo.rgb;
+    // Materials without an environment map have no mips to sample
+    if material.env_mip_count > 0.0 {
+        let view_dir = normalize(camera.position.xyz - in.world_position);
+        rgb += environment_specular(
+            albedo.rgb,
+            material.metallic,
+            material.roughness,
+            view_dir,
+            normalize(in.normal),
+            env_map,
+            env_sampler,
+            material.env_mip_count,
+            material.lod_bias,
+        );
+    }
+    return vec4<f32>(rgb, albedo.a);
+}
+
+// Vertex colors only, for pipelines without a material bind group.
+@fragment
+fn fs_vertex_color(in: VertexOutput) -> @location(0) vec4<f32> {
     return vec4<f32>(in.color, 1.0);
 }
@@ -0,0 +1,182 @@
+use bytemuck::{Pod, Zeroable};
+
+/// Softness of shadow edges, selecting the percentage-closer filtering kernel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ShadowQuality {
+    /// A single hardware-filtered comparison sample.
+    Hard,
+    #[default]
+    Pcf3x3,
+    Pcf5x5,
+}
+
+impl ShadowQuality {
+    /// Width of the square sample kernel.
+    pub fn kernel_size(self) -> u32 {
+        match self {
+            ShadowQuality::Hard => 1,
+            ShadowQuality::Pcf3x3 => 3,
+            ShadowQuality::Pcf5x5 => 5,
+        }
+    }
+}
+
+/// Shadow filtering and bias settings.
+///
+/// `depth_bias` pushes the compared depth away from the light to fight shadow
+/// acne, but too much detaches shadows from their casters ("peter-panning").
+/// `normal_offset_bias` instead moves the lookup along the surface normal,
+/// which fixes acne on steep slopes with less peter-panning. Tune them
+/// independently.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ShadowSettings {
+    pub quality: ShadowQuality,
+    pub depth_bias: f32,
+    pub normal_offset_bias: f32,
+    pub cascades: CascadeSettings,
+}
+
+impl Default for ShadowSettings {
+    fn default() -> Self {
+        Self {
+            quality: ShadowQuality::default(),
+            depth_bias: 0.005,
+            normal_offset_bias: 0.02,
+            cascades: CascadeSettings::default(),
+        }
+    }
+}
+
+/// Most cascades `shadow_cascades.wgsl` can select between.
+pub const MAX_CASCADES: usize = 4;
+
+/// How the view frustum is divided between shadow cascades.
+///
+/// `lambda` blends the split scheme: 0 spaces the splits evenly between near
+/// and far, 1 spaces them logarithmically, which matches how perspective
+/// shrinks distant texels but leaves the far cascades huge.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CascadeSettings {
+    /// Between 1 and `MAX_CASCADES`.
+    pub count: u32,
+    pub lambda: f32,
+    /// Width and height of each cascade's shadow map.
+    pub resolution: u32,
+}
+
+impl Default for CascadeSettings {
+    fn default() -> Self {
+        Self {
+            count: 4,
+            lambda: 0.75,
+            resolution: 2048,
+        }
+    }
+}
+
+/// Far distance of each of `count` cascades covering `near..far`, blending
+/// logarithmic and uniform splits by `lambda`. The last split is `far`.
+pub fn cascade_splits(near: f32, far: f32, count: u32, lambda: f32) -> Vec<f32> {
+    let count = count.clamp(1, MAX_CASCADES as u32);
+    let lambda = lambda.clamp(0.0, 1.0);
+    (1..=count)
+        .map(|i| {
+            let p = i as f32 / count as f32;
+            let log = near * (far / near).powf(p);
+            let uniform = near + (far - near) * p;
+            lambda * log + (1.0 - lambda) * uniform
+        })
+        .collect()
+}
+
+/// CPU mirror of `cascade_index` in `shadow_cascades.wgsl`: the cascade
+/// covering `view_depth`, or `None` beyond the last split.
+pub fn cascade_for_depth(splits: &[f32], view_depth: f32) -> Option<usize> {
+    splits.iter().position(|split| view_depth <= *split)
+}
+
+impl ShadowSettings {
+    pub fn uniform(&self) -> ShadowUniform {
+        ShadowUniform {
+            kernel_size: self.quality.kernel_size(),
+            depth_bias: self.depth_bias,
+            normal_offset_bias: self.normal_offset_bias,
+            _padding: 0.0,
+        }
+    }
+}
+
+/// Layout of the `ShadowParams` struct in `shadow_pcf.wgsl`.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, Pod, Zeroable)]
+pub struct ShadowUniform {
+    pub kernel_size: u32,
+    pub depth_bias: f32,
+    pub normal_offset_bias: f32,
+    _padding: f32,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_splits(actual: Vec<f32>, expected: &[f32]) {
+        assert_eq!(actual.len(), expected.len(), "{actual:?}");
+        for (a, e) in actual.iter().zip(expected) {
+            assert!((a - e).abs() < 1e-4, "{actual:?} != {expected:?}");
+        }
+    }
+
+    #[test]
+    fn lambda_zero_spaces_splits_evenly() {
+        assert_splits(cascade_splits(1.0, 101.0, 4, 0.0), &[26.0, 51.0, 76.0, 101.0]);
+    }
+
+    #[test]
+    fn lambda_one_spaces_splits_logarithmically() {
+        assert_splits(cascade_splits(1.0, 16.0, 4, 1.0), &[2.0, 4.0, 8.0, 16.0]);
+    }
+
+    #[test]
+    fn lambda_blends_both_schemes() {
+        // Halfway between [4.75, 8.5, 12.25, 16] and [2, 4, 8, 16]
+        assert_splits(cascade_splits(1.0, 16.0, 4, 0.5), &[3.375, 6.25, 10.125, 16.0]);
+    }
+
+    #[test]
+    fn splits_increase_and_end_at_far() {
+        for lambda in [0.0, 0.25, 0.75, 1.0] {
+            let splits = cascade_splits(0.1, 500.0, 3, lambda);
+            assert!(splits.windows(2).all(|pair| pair[0] < pair[1]), "{splits:?}");
+            assert!((splits[2] - 500.0).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn quality_selects_kernel_size() {
+        assert_eq!(ShadowQuality::Hard.kernel_size(), 1);
+        assert_eq!(ShadowQuality::Pcf3x3.kernel_size(), 3);
+        assert_eq!(ShadowQuality::Pcf5x5.kernel_size(), 5);
+    }
+
+    #[test]
+    fn uniform_carries_kernel_and_biases() {
+        let settings = ShadowSettings {
+            quality: ShadowQuality::Pcf5x5,
+            depth_bias: 0.01,
+            normal_offset_bias: 0.05,
+            ..Default::default()
+        };
+        let uniform = settings.uniform();
+        assert_eq!(uniform.kernel_size, 5);
+        assert_eq!(uniform.depth_bias, 0.01);
+        assert_eq!(uniform.normal_offset_bias, 0.05);
+    }
+
+    #[test]
+    fn count_and_lambda_are_clamped() {
+        assert_eq!(cascade_splits(1.0, 10.0, 0, 0.0), vec![10.0]);
+        assert_eq!(cascade_splits(1.0, 10.0, 9, 0.0).len(), MAX_CASCADES);
+        assert_eq!(cascade_splits(1.0, 16.0, 4, 7.0), cascade_splits(1.0, 16.0, 4, 1.0));
+    }
+}
@@ -0,0 +1,40 @@
+// Cascaded shadow lookup, shared by lit shaders through
+// `//!include "shadow_cascades.wgsl"`. Filtering comes from `shadow_pcf.wgsl`,
+// so the including shader declares that file's bindings too, plus (see
+// `CascadedShadowMaps`):
+//
+//   var<uniform> cascades: CascadeParams;
+
+//!include "shadow_pcf.wgsl"
+
+struct CascadeParams {
+    view_proj: array<mat4x4<f32>, 4>,
+    // Far distance of each cascade along the camera's view direction.
+    splits: vec4<f32>,
+    camera_position: vec4<f32>,
+    camera_forward: vec4<f32>,
+    count: u32,
+};
+
+// First cascade whose range reaches `view_depth`; `count` when it is beyond
+// the last one.
+fn cascade_index(view_depth: f32) -> u32 {
+    var index = 0u;
+    for (var i = 0u; i < cascades.count; i = i + 1u) {
+        if view_depth > cascades.splits[i] {
+            index = i + 1u;
+        }
+    }
+    return index;
+}
+
+// `world_pos` and `normal` are in world space. Returns 1.0 for fully lit,
+// 0.0 for shadowed; fragments past the last cascade are lit.
+fn cascaded_shadow_factor(world_pos: vec3<f32>, normal: vec3<f32>) -> f32 {
+    let view_depth = dot(world_pos - cascades.camera_position.xyz, cascades.camera_forward.xyz);
+    let index = cascade_index(view_depth);
+    if index >= cascades.count {
+        return 1.0;
+    }
+    return shadow_factor(world_pos, normal, cascades.view_proj[index], i32(index));
+}
@@ -0,0 +1,42 @@
+// Percentage-closer filtered shadow lookup, shared by lit shaders through
+// `//!include "shadow_pcf.wgsl"`. The including shader declares the bindings
+// (see `shadow_bind_group_layout`):
+//
+//   var shadow_cascades: texture_depth_2d_array;
+//   var shadow_sampler: sampler_comparison;
+//   var<uniform> shadow: ShadowParams;
+
+struct ShadowParams {
+    kernel_size: u32,
+    depth_bias: f32,
+    normal_offset_bias: f32,
+    padding: f32,
+};
+
+// `world_pos` and `normal` are in world space, `light_view_proj` maps world
+// space to the light's clip space for the shadow map in array layer `layer`.
+// Returns 1.0 for fully lit, 0.0 for shadowed.
+fn shadow_factor(
+    world_pos: vec3<f32>,
+    normal: vec3<f32>,
+    light_view_proj: mat4x4<f32>,
+    layer: i32,
+) -> f32 {
+    let offset_pos = world_pos + normal * shadow.normal_offset_bias;
+    let light_clip = light_view_proj * vec4<f32>(offset_pos, 1.0);
+    let ndc = light_clip.xyz / light_clip.w;
+    let uv = vec2<f32>(ndc.x * 0.5 + 0.5, -ndc.y * 0.5 + 0.5);
+    let depth = ndc.z - shadow.depth_bias;
+
+    let texel = 1.0 / vec2<f32>(textureDimensions(shadow_cascades));
+    let radius = i32(shadow.kernel_size / 2u);
+    var lit = 0.0;
+    for (var y = -radius; y <= radius; y++) {
+        for (var x = -radius; x <= radius; x++) {
+            let offset = vec2<f32>(f32(x), f32(y)) * texel;
+            lit += textureSampleCompareLevel(shadow_cascades, shadow_sampler, uv + offset, layer, depth);
+        }
+    }
+    let taps = f32(shadow.kernel_size * shadow.kernel_size);
+    return lit / taps;
+}
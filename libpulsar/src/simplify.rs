@@ -0,0 +1,395 @@
+//! Quadric error metric mesh simplification (Garland & Heckbert), used to
+//! build LOD levels from a full-detail `MeshData`.
+
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+
+use glam::{DMat3, DVec3, Vec2, Vec3};
+
+use crate::mesh::{MeshData, Vertex};
+
+/// Sum of squared distances to a set of planes, stored as the upper triangle
+/// of the symmetric 4x4 matrix `[n, d] * [n, d]^T`.
+#[derive(Debug, Clone, Copy, Default)]
+struct Quadric([f64; 10]);
+
+impl Quadric {
+    fn from_plane(normal: DVec3, d: f64) -> Self {
+        let [a, b, c] = normal.to_array();
+        Quadric([a * a, a * b, a * c, a * d, b * b, b * c, b * d, c * c, c * d, d * d])
+    }
+
+    fn combined(&self, other: &Quadric) -> Quadric {
+        let mut sum = self.0;
+        for (value, other) in sum.iter_mut().zip(other.0) {
+            *value += other;
+        }
+        Quadric(sum)
+    }
+
+    fn error(&self, p: DVec3) -> f64 {
+        let q = &self.0;
+        let (x, y, z) = (p.x, p.y, p.z);
+        q[0] * x * x + 2.0 * q[1] * x * y + 2.0 * q[2] * x * z + 2.0 * q[3] * x
+            + q[4] * y * y + 2.0 * q[5] * y * z + 2.0 * q[6] * y
+            + q[7] * z * z + 2.0 * q[8] * z
+            + q[9]
+    }
+
+    /// Position with the least error, if the quadric isn't degenerate (e.g.
+    /// all of its planes are parallel).
+    fn optimum(&self) -> Option<DVec3> {
+        let q = &self.0;
+        let a = DMat3::from_cols(
+            DVec3::new(q[0], q[1], q[2]),
+            DVec3::new(q[1], q[4], q[5]),
+            DVec3::new(q[2], q[5], q[7]),
+        );
+        if a.determinant().abs() < 1e-12 {
+            return None;
+        }
+        Some(-(a.inverse() * DVec3::new(q[3], q[6], q[8])))
+    }
+}
+
+/// A possible collapse of `remove` into `keep`, valid while neither vertex
+/// has changed since it was queued.
+struct Collapse {
+    cost: f64,
+    keep: usize,
+    remove: usize,
+    versions: (u32, u32),
+}
+
+impl PartialEq for Collapse {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Collapse {}
+
+impl PartialOrd for Collapse {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Collapse {
+    /// Reversed, so the `BinaryHeap` pops the cheapest collapse first.
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.cost.total_cmp(&self.cost)
+    }
+}
+
+struct Simplifier {
+    vertices: Vec<Vertex>,
+    positions: Vec<DVec3>,
+    quadrics: Vec<Quadric>,
+    /// Boundary and seam vertices, which never move.
+    locked: Vec<bool>,
+    versions: Vec<u32>,
+    triangles: Vec<[usize; 3]>,
+    live: Vec<bool>,
+    live_count: usize,
+    /// Triangles around each vertex; may include dead ones.
+    adjacency: Vec<Vec<usize>>,
+    queue: BinaryHeap<Collapse>,
+}
+
+impl Simplifier {
+    fn new(vertices: Vec<Vertex>, triangles: Vec<[usize; 3]>) -> Self {
+        let positions: Vec<DVec3> = vertices.iter().map(|v| Vec3::from(v.position).as_dvec3()).collect();
+        let mut quadrics = vec![Quadric::default(); vertices.len()];
+        let mut adjacency = vec![Vec::new(); vertices.len()];
+        let mut edge_uses: HashMap<(usize, usize), u32> = HashMap::new();
+        for (index, triangle) in triangles.iter().enumerate() {
+            let [a, b, c] = triangle.map(|i| positions[i]);
+            if let Some(normal) = (b - a).cross(c - a).try_normalize() {
+                let plane = Quadric::from_plane(normal, -normal.dot(a));
+                for &corner in triangle {
+                    quadrics[corner] = quadrics[corner].combined(&plane);
+                }
+            }
+            for k in 0..3 {
+                adjacency[triangle[k]].push(index);
+                let (from, to) = (triangle[k], triangle[(k + 1) % 3]);
+                *edge_uses.entry((from.min(to), from.max(to))).or_default() += 1;
+            }
+        }
+        // Open edges, and edges along UV or normal seams where the vertices
+        // are split, are only used by one triangle
+        let mut locked = vec![false; vertices.len()];
+        for (&(a, b), &uses) in &edge_uses {
+            if uses != 2 {
+                locked[a] = true;
+                locked[b] = true;
+            }
+        }
+
+        let live_count = triangles.len();
+        let mut simplifier = Self {
+            versions: vec![0; vertices.len()],
+            vertices,
+            positions,
+            quadrics,
+            locked,
+            live: vec![true; triangles.len()],
+            triangles,
+            live_count,
+            adjacency,
+            queue: BinaryHeap::new(),
+        };
+        for (a, b) in edge_uses.into_keys() {
+            simplifier.queue_edge(a, b);
+        }
+        simplifier
+    }
+
+    /// Where the merged vertex goes, how far along `keep`..`remove` that is,
+    /// and the resulting error.
+    fn target(&self, keep: usize, remove: usize) -> (DVec3, f64, f64) {
+        let quadric = self.quadrics[keep].combined(&self.quadrics[remove]);
+        let (from, to) = (self.positions[keep], self.positions[remove]);
+        let position = if self.locked[keep] {
+            from
+        } else {
+            let midpoint = (from + to) * 0.5;
+            let candidates = [from, to, midpoint];
+            let fallback = candidates
+                .into_iter()
+                .min_by(|a, b| quadric.error(*a).total_cmp(&quadric.error(*b)))
+                .unwrap_or(midpoint);
+            // Nearly flat neighborhoods can put the optimum far off the edge
+            quadric
+                .optimum()
+                .filter(|optimum| optimum.distance(midpoint) <= from.distance(to))
+                .unwrap_or(fallback)
+        };
+        let edge = to - from;
+        let t = if edge.length_squared() > 0.0 {
+            ((position - from).dot(edge) / edge.length_squared()).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        (position, t, quadric.error(position))
+    }
+
+    fn queue_edge(&mut self, a: usize, b: usize) {
+        let (keep, remove) = match (self.locked[a], self.locked[b]) {
+            (true, true) => return,
+            (false, true) => (b, a),
+            _ => (a, b),
+        };
+        let (_, _, cost) = self.target(keep, remove);
+        self.queue.push(Collapse {
+            cost,
+            keep,
+            remove,
+            versions: (self.versions[keep], self.versions[remove]),
+        });
+    }
+
+    /// Whether moving `keep` and `remove` to `position` would turn any
+    /// surviving triangle over or squash it flat.
+    fn flips(&self, keep: usize, remove: usize, position: DVec3) -> bool {
+        let moved = |i: usize| if i == keep || i == remove { position } else { self.positions[i] };
+        self.adjacency[keep].iter().chain(&self.adjacency[remove]).any(|&index| {
+            let triangle = self.triangles[index];
+            if !self.live[index] || (triangle.contains(&keep) && triangle.contains(&remove)) {
+                return false;
+            }
+            let [a, b, c] = triangle.map(|i| self.positions[i]);
+            let [na, nb, nc] = triangle.map(moved);
+            let before = (b - a).cross(c - a);
+            let after = (nb - na).cross(nc - na);
+            after.dot(before) <= 0.0
+        })
+    }
+
+    fn collapse(&mut self, keep: usize, remove: usize) -> bool {
+        let (position, t, _) = self.target(keep, remove);
+        if self.flips(keep, remove, position) {
+            return false;
+        }
+
+        let (from, to) = (self.vertices[keep], self.vertices[remove]);
+        let t = t as f32;
+        let lerp = |a: [f32; 3], b: [f32; 3]| Vec3::from(a).lerp(Vec3::from(b), t);
+        let merged = &mut self.vertices[keep];
+        merged.position = position.as_vec3().to_array();
+        merged.color = lerp(from.color, to.color).to_array();
+        merged.normal = lerp(from.normal, to.normal).normalize_or_zero().to_array();
+        merged.uv = Vec2::from(from.uv).lerp(Vec2::from(to.uv), t).to_array();
+        // Blending skin weights of different joints isn't meaningful
+        if t > 0.5 {
+            merged.joint_indices = to.joint_indices;
+            merged.weights = to.weights;
+        }
+        self.positions[keep] = position;
+        self.quadrics[keep] = self.quadrics[keep].combined(&self.quadrics[remove]);
+        self.versions[keep] += 1;
+        self.versions[remove] += 1;
+
+        for index in std::mem::take(&mut self.adjacency[remove]) {
+            if !self.live[index] {
+                continue;
+            }
+            let triangle = &mut self.triangles[index];
+            if triangle.contains(&keep) {
+                self.live[index] = false;
+                self.live_count -= 1;
+            } else {
+                for corner in triangle.iter_mut().filter(|corner| **corner == remove) {
+                    *corner = keep;
+                }
+                self.adjacency[keep].push(index);
+            }
+        }
+        let live = &self.live;
+        self.adjacency[keep].retain(|index| live[*index]);
+
+        let mut neighbors: Vec<usize> = self.adjacency[keep]
+            .iter()
+            .flat_map(|index| self.triangles[*index])
+            .filter(|vertex| *vertex != keep)
+            .collect();
+        neighbors.sort_unstable();
+        neighbors.dedup();
+        for neighbor in neighbors {
+            self.queue_edge(keep, neighbor);
+        }
+        true
+    }
+
+    fn run(&mut self, target_triangles: usize) {
+        while self.live_count > target_triangles {
+            let Some(candidate) = self.queue.pop() else {
+                break;
+            };
+            let current = (self.versions[candidate.keep], self.versions[candidate.remove]);
+            if candidate.versions == current {
+                self.collapse(candidate.keep, candidate.remove);
+            }
+        }
+    }
+
+    /// The surviving triangles, with unused vertices dropped.
+    fn finish(self) -> MeshData {
+        let mut remap = vec![None; self.vertices.len()];
+        let mut vertices = Vec::new();
+        let mut indices = Vec::with_capacity(self.live_count * 3);
+        for (triangle, _) in self.triangles.iter().zip(&self.live).filter(|(_, live)| **live) {
+            for &corner in triangle {
+                let index = *remap[corner].get_or_insert_with(|| {
+                    vertices.push(self.vertices[corner]);
+                    (vertices.len() - 1) as u16
+                });
+                indices.push(index);
+            }
+        }
+        MeshData::new(vertices, indices)
+    }
+}
+
+/// Merges bitwise identical vertices, so a non-indexed mesh gets shared
+/// edges to collapse.
+fn weld(data: &MeshData) -> (Vec<Vertex>, Vec<[usize; 3]>) {
+    if !data.indices.is_empty() {
+        return (data.vertices.clone(), data.triangles().collect());
+    }
+    let mut vertices = Vec::new();
+    let mut lookup: HashMap<Vec<u8>, usize> = HashMap::new();
+    let mut welded: Vec<usize> = Vec::with_capacity(data.vertices.len());
+    for vertex in &data.vertices {
+        let index = *lookup.entry(bytemuck::bytes_of(vertex).to_vec()).or_insert_with(|| {
+            vertices.push(*vertex);
+            vertices.len() - 1
+        });
+        welded.push(index);
+    }
+    let triangles = data.triangles().map(|corners| corners.map(|i| welded[i])).collect();
+    (vertices, triangles)
+}
+
+/// Collapses edges in order of least added error until about
+/// `target_ratio` of the triangles remain. Boundary and seam vertices stay
+/// put, so the result can end up above the target. The ratio is clamped to
+/// `0..=1`; the result is always indexed.
+pub fn simplify(data: &MeshData, target_ratio: f32) -> MeshData {
+    let (vertices, triangles) = weld(data);
+    let target_ratio = if target_ratio.is_nan() { 1.0 } else { target_ratio.clamp(0.0, 1.0) };
+    let target_triangles = (triangles.len() as f32 * target_ratio).round() as usize;
+    let mut simplifier = Simplifier::new(vertices, triangles);
+    simplifier.run(target_triangles);
+    simplifier.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Closed unit UV sphere with shared vertices, so nothing is a boundary
+    /// or a seam.
+    fn sphere(stacks: usize, slices: usize) -> MeshData {
+        let point = |stack: usize, slice: usize| {
+            let polar = std::f32::consts::PI * stack as f32 / stacks as f32;
+            let azimuth = std::f32::consts::TAU * slice as f32 / slices as f32;
+            let p = [polar.sin() * azimuth.cos(), polar.cos(), polar.sin() * azimuth.sin()];
+            Vertex::new(p, [1.0; 3], p)
+        };
+        let mut vertices = vec![point(0, 0)];
+        for stack in 1..stacks {
+            vertices.extend((0..slices).map(|slice| point(stack, slice)));
+        }
+        vertices.push(point(stacks, 0));
+        let bottom = vertices.len() - 1;
+        let ring = |stack: usize, slice: usize| 1 + (stack - 1) * slices + slice % slices;
+
+        let mut indices = Vec::new();
+        for slice in 0..slices {
+            indices.extend([0, ring(1, slice + 1), ring(1, slice)]);
+            indices.extend([bottom, ring(stacks - 1, slice), ring(stacks - 1, slice + 1)]);
+            for stack in 1..stacks - 1 {
+                let (a, b) = (ring(stack, slice), ring(stack, slice + 1));
+                let (c, d) = (ring(stack + 1, slice), ring(stack + 1, slice + 1));
+                indices.extend([a, b, d, a, d, c]);
+            }
+        }
+        MeshData::new(vertices, indices.into_iter().map(|i| i as u16).collect())
+    }
+
+    fn radius(data: &MeshData) -> f32 {
+        data.vertices.iter().map(|v| Vec3::from(v.position).length()).fold(0.0, f32::max)
+    }
+
+    #[test]
+    fn halving_a_dense_sphere_roughly_halves_its_triangles_and_keeps_its_size() {
+        let dense = sphere(24, 48);
+        let before = dense.triangles().count();
+        let simplified = simplify(&dense, 0.5);
+        let after = simplified.triangles().count();
+        assert!(after <= before * 11 / 20, "{before} -> {after}");
+        assert!(after >= before * 9 / 20, "{before} -> {after}");
+        assert!((radius(&simplified) - radius(&dense)).abs() < 0.05, "{}", radius(&simplified));
+    }
+
+    #[test]
+    fn ratio_is_clamped() {
+        let dense = sphere(8, 16);
+        let count = dense.triangles().count();
+        assert_eq!(simplify(&dense, 1.0).triangles().count(), count);
+        assert_eq!(simplify(&dense, 3.0).triangles().count(), count);
+        assert_eq!(simplify(&dense, f32::NAN).triangles().count(), count);
+        assert!(simplify(&dense, -1.0).triangles().count() < count / 4);
+    }
+
+    #[test]
+    fn non_indexed_meshes_are_welded_before_collapsing() {
+        let indexed = sphere(12, 24);
+        let flat = MeshData::new(indexed.triangles().flatten().map(|i| indexed.vertices[i]).collect(), Vec::new());
+        let simplified = simplify(&flat, 0.5);
+        assert!(!simplified.indices.is_empty());
+        assert!(simplified.triangles().count() <= flat.triangles().count() * 11 / 20);
+    }
+}
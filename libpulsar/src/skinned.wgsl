@@ -0,0 +1,70 @@
+// Same shading as shader.wgsl, with positions and normals deformed by a
+// weighted sum of joint matrices.
+
+struct CameraUniform {
+    view_proj: mat4x4<f32>,
+};
+
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+// Uploaded row-major from `Matrix4`, so `joints[i] * v` computes `v * M`.
+@group(1) @binding(0)
+var<storage, read> joints: array<mat4x4<f32>>;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) color: vec3<f32>,
+    @location(2) normal: vec3<f32>,
+    @location(3) joint_indices: vec4<u32>,
+    @location(4) weights: vec4<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+};
+
+// Vertex colors are authored in sRGB (see `color.rs`); lighting and the sRGB
+// surface both expect linear values.
+fn srgb_to_linear(c: vec3<f32>) -> vec3<f32> {
+    let low = c / 12.92;
+    let high = pow((c + 0.055) / 1.055, vec3<f32>(2.4));
+    return select(high, low, c <= vec3<f32>(0.04045));
+}
+
+@vertex
+fn vs_main(model: VertexInput) -> VertexOutput {
+    var position = vec4<f32>(model.position, 1.0);
+    var normal = model.normal;
+    let total = model.weights.x + model.weights.y + model.weights.z + model.weights.w;
+    if (total > 0.0) {
+        let count = arrayLength(&joints);
+        var skinned = vec4<f32>(0.0);
+        var skinned_normal = vec3<f32>(0.0);
+        for (var i = 0u; i < 4u; i = i + 1u) {
+            let weight = model.weights[i];
+            let joint = model.joint_indices[i];
+            if (weight == 0.0 || joint >= count) {
+                continue;
+            }
+            let matrix = joints[joint];
+            skinned += weight * (matrix * position);
+            skinned_normal += weight * (mat3x3<f32>(matrix[0].xyz, matrix[1].xyz, matrix[2].xyz) * normal);
+        }
+        position = vec4<f32>(skinned.xyz, 1.0);
+        normal = normalize(skinned_normal);
+    }
+
+    var out: VertexOutput;
+    out.color = srgb_to_linear(model.color);
+    out.normal = normal;
+    out.clip_position = camera.view_proj * position;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(in.color, 1.0);
+}
@@ -0,0 +1,314 @@
+use crate::engine::render::config::Labels;
+use crate::math::{Matrix4, Transform, Vector3};
+use crate::mesh::{Mesh, Vertex};
+use crate::renderer::Renderer;
+
+/// One bone of a skeleton.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Joint {
+    pub name: String,
+    /// Index of the parent joint; parents must come before their children.
+    pub parent: Option<usize>,
+    /// Maps mesh space into this joint's space in the bind pose.
+    pub inverse_bind: Matrix4,
+}
+
+/// Joint hierarchy a skinned mesh is bound to.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Skeleton {
+    pub joints: Vec<Joint>,
+}
+
+impl Skeleton {
+    pub fn new(joints: Vec<Joint>) -> Self {
+        Skeleton { joints }
+    }
+
+    pub fn len(&self) -> usize {
+        self.joints.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.joints.is_empty()
+    }
+
+    /// Composes each joint's local `pose` with its parents into mesh space.
+    /// Joints without a pose entry use the identity.
+    pub fn global_matrices(&self, pose: &[Transform]) -> Vec<Matrix4> {
+        let mut globals: Vec<Matrix4> = Vec::with_capacity(self.joints.len());
+        for (index, joint) in self.joints.iter().enumerate() {
+            let local = pose.get(index).map_or_else(Matrix4::identity, Transform::matrix);
+            let global = match joint.parent {
+                Some(parent) if parent < index => local * globals[parent],
+                _ => local,
+            };
+            globals.push(global);
+        }
+        globals
+    }
+
+    /// Skinning matrices for `pose`, ready for `SkinnedRenderer::set_palette`.
+    pub fn palette(&self, pose: &[Transform]) -> Vec<Matrix4> {
+        self.global_matrices(pose)
+            .into_iter()
+            .zip(&self.joints)
+            .map(|(global, joint)| joint.inverse_bind * global)
+            .collect()
+    }
+}
+
+/// Deforms a vertex on the CPU exactly like `skinned.wgsl`, returning its
+/// position and normal. Vertices without weights are returned unchanged.
+pub fn skin_vertex(vertex: &Vertex, palette: &[Matrix4]) -> (Vector3, Vector3) {
+    let [px, py, pz] = vertex.position;
+    let [nx, ny, nz] = vertex.normal;
+    let position = Vector3::new(px, py, pz);
+    let normal = Vector3::new(nx, ny, nz);
+
+    let mut skinned = Vector3::zero();
+    let mut skinned_normal = Vector3::zero();
+    let mut total = 0.0;
+    for (&joint, &weight) in vertex.joint_indices.iter().zip(&vertex.weights) {
+        let Some(matrix) = palette.get(joint as usize) else {
+            continue;
+        };
+        if weight == 0.0 {
+            continue;
+        }
+        skinned += position.transform(matrix) * weight;
+        skinned_normal += normal.transform_normal(matrix) * weight;
+        total += weight;
+    }
+    if total == 0.0 {
+        return (position, normal);
+    }
+    (skinned, skinned_normal.normalize_or_zero())
+}
+
+/// Draws meshes deformed by a joint palette held in a storage buffer.
+pub struct SkinnedRenderer {
+    pipeline: wgpu::RenderPipeline,
+    palette_layout: wgpu::BindGroupLayout,
+    palette_buffer: wgpu::Buffer,
+    palette_bind_group: wgpu::BindGroup,
+    max_joints: usize,
+    labels: Labels,
+}
+
+impl SkinnedRenderer {
+    pub fn new(
+        device: &wgpu::Device,
+        labels: &Labels,
+        config: &wgpu::SurfaceConfiguration,
+        sample_count: u32,
+        max_joints: usize,
+    ) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(&labels.label("Skinned Shader")),
+            source: wgpu::ShaderSource::Wgsl(include_str!("skinned.wgsl").into()),
+        });
+
+        let palette_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only: true },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+            label: Some(&labels.label("joint_palette_bind_group_layout")),
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some(&labels.label("Skinned Pipeline Layout")),
+            bind_group_layouts: &[&Renderer::camera_bind_group_layout(device, labels), &palette_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(&labels.label("Skinned Pipeline")),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[Vertex::desc()],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: config.format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: Some(wgpu::Face::Back),
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: wgpu::TextureFormat::Depth32Float,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: sample_count,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+            cache: None,
+        });
+
+        let max_joints = max_joints.max(1);
+        let palette_buffer = Self::create_palette_buffer(device, labels, max_joints);
+        let palette_bind_group = Self::create_palette_bind_group(device, labels, &palette_layout, &palette_buffer);
+
+        Self {
+            pipeline,
+            palette_layout,
+            palette_buffer,
+            palette_bind_group,
+            max_joints,
+            labels: labels.clone(),
+        }
+    }
+
+    fn create_palette_buffer(device: &wgpu::Device, labels: &Labels, joints: usize) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(&labels.label("Joint Palette Buffer")),
+            size: (joints * std::mem::size_of::<[[f32; 4]; 4]>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    fn create_palette_bind_group(
+        device: &wgpu::Device,
+        labels: &Labels,
+        layout: &wgpu::BindGroupLayout,
+        buffer: &wgpu::Buffer,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+            label: Some(&labels.label("joint_palette_bind_group")),
+        })
+    }
+
+    pub fn max_joints(&self) -> usize {
+        self.max_joints
+    }
+
+    /// Uploads this frame's skinning matrices, growing the buffer if the
+    /// skeleton has more joints than it holds.
+    pub fn set_palette(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, palette: &[Matrix4]) {
+        if palette.len() > self.max_joints {
+            self.max_joints = palette.len().next_power_of_two();
+            self.palette_buffer = Self::create_palette_buffer(device, &self.labels, self.max_joints);
+            self.palette_bind_group =
+                Self::create_palette_bind_group(device, &self.labels, &self.palette_layout, &self.palette_buffer);
+        }
+        let rows: Vec<[[f32; 4]; 4]> = palette.iter().map(Matrix4::to_rows).collect();
+        queue.write_buffer(&self.palette_buffer, 0, bytemuck::cast_slice(&rows));
+    }
+
+    pub fn draw(&self, render_pass: &mut wgpu::RenderPass, camera_bind_group: &wgpu::BindGroup, mesh: &Mesh) {
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        render_pass.set_bind_group(1, &self.palette_bind_group, &[]);
+        Renderer::draw_mesh(render_pass, mesh);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn skinned(position: [f32; 3], joint_indices: [u16; 4], weights: [f32; 4]) -> Vertex {
+        Vertex {
+            joint_indices,
+            weights,
+            ..Vertex::new(position, [1.0; 3], [0.0, 1.0, 0.0])
+        }
+    }
+
+    fn translated(x: f32, y: f32, z: f32) -> Transform {
+        Transform::new(Vector3::new(x, y, z), Vector3::zero(), Vector3::one())
+    }
+
+    fn assert_near(actual: Vector3, expected: Vector3) {
+        assert!((actual - expected).length() < 1e-5, "{actual:?} != {expected:?}");
+    }
+
+    #[test]
+    fn vertex_weighted_to_one_bone_follows_its_matrix_exactly() {
+        let bone =
+            Matrix4::rotation_y(0.7) * Matrix4::rotation_x(-0.3) * Matrix4::translation(Vector3::new(1.0, 2.0, 3.0));
+        let palette = [Matrix4::identity(), bone];
+        let vertex = skinned([0.5, -1.0, 2.0], [1, 0, 0, 0], [1.0, 0.0, 0.0, 0.0]);
+        let (position, normal) = skin_vertex(&vertex, &palette);
+        assert_eq!(position, bone.transform_point(Vector3::new(0.5, -1.0, 2.0)));
+        assert_near(normal, Vector3::up().transform_normal(&bone));
+    }
+
+    #[test]
+    fn weights_blend_between_bones() {
+        let palette = [
+            Matrix4::translation(Vector3::new(2.0, 0.0, 0.0)),
+            Matrix4::translation(Vector3::new(0.0, 4.0, 0.0)),
+        ];
+        let vertex = skinned([0.0, 0.0, 0.0], [0, 1, 0, 0], [0.5, 0.5, 0.0, 0.0]);
+        assert_near(skin_vertex(&vertex, &palette).0, Vector3::new(1.0, 2.0, 0.0));
+    }
+
+    #[test]
+    fn unweighted_vertices_are_unchanged() {
+        let palette = [Matrix4::translation(Vector3::new(5.0, 0.0, 0.0))];
+        let vertex = Vertex::new([1.0, 2.0, 3.0], [1.0; 3], [0.0, 0.0, 1.0]);
+        assert_eq!(
+            skin_vertex(&vertex, &palette),
+            (Vector3::new(1.0, 2.0, 3.0), Vector3::new(0.0, 0.0, 1.0))
+        );
+    }
+
+    #[test]
+    fn palette_composes_parents_and_removes_the_bind_pose() {
+        // Root at the origin, child one unit above it in the bind pose
+        let skeleton = Skeleton::new(vec![
+            Joint {
+                name: String::from("root"),
+                parent: None,
+                inverse_bind: Matrix4::identity(),
+            },
+            Joint {
+                name: String::from("child"),
+                parent: Some(0),
+                inverse_bind: Matrix4::translation(Vector3::new(0.0, -1.0, 0.0)),
+            },
+        ]);
+        let vertex = skinned([0.0, 1.5, 0.0], [1, 0, 0, 0], [1.0, 0.0, 0.0, 0.0]);
+
+        let rest = skeleton.palette(&[Transform::identity(), translated(0.0, 1.0, 0.0)]);
+        assert_near(skin_vertex(&vertex, &rest).0, Vector3::new(0.0, 1.5, 0.0));
+
+        // Moving the root carries the child with it
+        let moved = skeleton.palette(&[translated(2.0, 0.0, 0.0), translated(0.0, 1.0, 0.0)]);
+        assert_near(skin_vertex(&vertex, &moved).0, Vector3::new(2.0, 1.5, 0.0));
+    }
+}
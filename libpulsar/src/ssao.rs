@@ -0,0 +1,472 @@
+use std::collections::HashMap;
+
+use bytemuck::{Pod, Zeroable};
+
+use crate::deferred::GBuffer;
+use crate::engine::render::config::Labels;
+use crate::renderer::Renderer;
+use crate::resources::{ResourceCategory, Tracked};
+
+/// Hemisphere probes per pixel; must match `ssao.wgsl`.
+pub const KERNEL_SIZE: usize = 32;
+
+const OCCLUSION_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R8Unorm;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SsaoSettings {
+    /// World-space radius of the sampled hemisphere.
+    pub radius: f32,
+    /// Offset along the normal before sampling, to stop flat surfaces
+    /// occluding themselves.
+    pub bias: f32,
+    /// Exponent applied to the occlusion term; above 1 darkens creases further.
+    pub intensity: f32,
+}
+
+impl Default for SsaoSettings {
+    fn default() -> Self {
+        Self {
+            radius: 0.5,
+            bias: 0.025,
+            intensity: 1.0,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+struct SsaoUniform {
+    radius: f32,
+    bias: f32,
+    intensity: f32,
+    _padding: f32,
+    kernel: [[f32; 4]; KERNEL_SIZE],
+}
+
+/// Deterministic probe offsets in the unit hemisphere around +Z, spread by a
+/// golden-angle spiral and packed towards the center so nearby geometry
+/// counts for more.
+pub fn hemisphere_kernel(count: usize) -> Vec<[f32; 4]> {
+    let golden_angle = std::f32::consts::PI * (3.0 - 5.0_f32.sqrt());
+    (0..count)
+        .map(|i| {
+            let t = (i as f32 + 0.5) / count as f32;
+            // Keep probes off the tangent plane, where they'd hit the surface itself
+            let z = 0.1 + 0.9 * t;
+            let r = (1.0 - z * z).sqrt();
+            let phi = i as f32 * golden_angle;
+            // Decorrelate length from elevation
+            let s = ((i * 7) % count) as f32 / count as f32;
+            let scale = 0.1 + 0.9 * s * s;
+            [r * phi.cos() * scale, r * phi.sin() * scale, z * scale, 0.0]
+        })
+        .collect()
+}
+
+/// SSAO for the deferred path: an occlusion pass over the G-buffer followed
+/// by a blur, producing a single-channel term that scales ambient light.
+pub struct SsaoPass {
+    occlusion_pipeline: wgpu::RenderPipeline,
+    blur_pipeline: wgpu::RenderPipeline,
+    gbuffer_layout: wgpu::BindGroupLayout,
+    blur_layout: wgpu::BindGroupLayout,
+    gbuffer_bind_group: wgpu::BindGroup,
+    blur_bind_group: wgpu::BindGroup,
+    settings_buffer: wgpu::Buffer,
+    raw: (Tracked<wgpu::Texture>, wgpu::TextureView),
+    blurred: (Tracked<wgpu::Texture>, wgpu::TextureView),
+    reverse_z: bool,
+    labels: Labels,
+}
+
+impl SsaoPass {
+    pub fn new(
+        device: &wgpu::Device,
+        labels: &Labels,
+        config: &wgpu::SurfaceConfiguration,
+        gbuffer: &GBuffer,
+        reverse_z: bool,
+    ) -> Self {
+        let texture_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+        let gbuffer_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                texture_entry(0),
+                texture_entry(1),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+            label: Some(&labels.label("ssao_bind_group_layout")),
+        });
+        let blur_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[texture_entry(0)],
+            label: Some(&labels.label("ssao_blur_bind_group_layout")),
+        });
+
+        let settings_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(&labels.label("SSAO Settings Buffer")),
+            size: std::mem::size_of::<SsaoUniform>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let occlusion_pipeline = Self::create_occlusion_pipeline(device, labels, &gbuffer_layout, reverse_z);
+        let blur_pipeline = Self::create_blur_pipeline(device, labels, &blur_layout);
+
+        let raw = Self::create_target(device, config, &labels.label("SSAO Raw"));
+        let blurred = Self::create_target(device, config, &labels.label("SSAO"));
+        let (gbuffer_bind_group, blur_bind_group) =
+            Self::create_bind_groups(device, labels, &gbuffer_layout, &blur_layout, gbuffer, &settings_buffer, &raw.1);
+
+        Self {
+            occlusion_pipeline,
+            blur_pipeline,
+            gbuffer_layout,
+            blur_layout,
+            gbuffer_bind_group,
+            blur_bind_group,
+            settings_buffer,
+            raw,
+            blurred,
+            reverse_z,
+            labels: labels.clone(),
+        }
+    }
+
+    fn create_occlusion_pipeline(
+        device: &wgpu::Device,
+        labels: &Labels,
+        gbuffer_layout: &wgpu::BindGroupLayout,
+        reverse_z: bool,
+    ) -> wgpu::RenderPipeline {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(&labels.label("SSAO Shader")),
+            source: wgpu::ShaderSource::Wgsl(include_str!("ssao.wgsl").into()),
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some(&labels.label("SSAO Pipeline Layout")),
+            bind_group_layouts: &[&Renderer::camera_bind_group_layout(device, labels), gbuffer_layout],
+            push_constant_ranges: &[],
+        });
+        let constants = HashMap::from([("REVERSE_Z".to_string(), if reverse_z { 1.0 } else { 0.0 })]);
+        Self::create_fullscreen_pipeline(device, &labels.label("SSAO Pipeline"), &layout, &shader, &constants)
+    }
+
+    fn create_blur_pipeline(
+        device: &wgpu::Device,
+        labels: &Labels,
+        blur_layout: &wgpu::BindGroupLayout,
+    ) -> wgpu::RenderPipeline {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(&labels.label("SSAO Blur Shader")),
+            source: wgpu::ShaderSource::Wgsl(include_str!("ssao_blur.wgsl").into()),
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some(&labels.label("SSAO Blur Pipeline Layout")),
+            bind_group_layouts: &[blur_layout],
+            push_constant_ranges: &[],
+        });
+        let label = labels.label("SSAO Blur Pipeline");
+        Self::create_fullscreen_pipeline(device, &label, &layout, &shader, &HashMap::new())
+    }
+
+    fn create_fullscreen_pipeline(
+        device: &wgpu::Device,
+        label: &str,
+        layout: &wgpu::PipelineLayout,
+        shader: &wgpu::ShaderModule,
+        constants: &HashMap<String, f64>,
+    ) -> wgpu::RenderPipeline {
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(label),
+            layout: Some(layout),
+            vertex: wgpu::VertexState {
+                module: shader,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: OCCLUSION_FORMAT,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions {
+                    constants,
+                    ..Default::default()
+                },
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        })
+    }
+
+    fn create_target(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        label: &str,
+    ) -> (Tracked<wgpu::Texture>, wgpu::TextureView) {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width: config.width.max(1),
+                height: config.height.max(1),
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: OCCLUSION_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        (Tracked::texture(texture, ResourceCategory::RenderTarget), view)
+    }
+
+    fn create_bind_groups(
+        device: &wgpu::Device,
+        labels: &Labels,
+        gbuffer_layout: &wgpu::BindGroupLayout,
+        blur_layout: &wgpu::BindGroupLayout,
+        gbuffer: &GBuffer,
+        settings_buffer: &wgpu::Buffer,
+        raw: &wgpu::TextureView,
+    ) -> (wgpu::BindGroup, wgpu::BindGroup) {
+        let gbuffer_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: gbuffer_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&gbuffer.position.1),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&gbuffer.normal.1),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: settings_buffer.as_entire_binding(),
+                },
+            ],
+            label: Some(&labels.label("ssao_bind_group")),
+        });
+        let blur_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: blur_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(raw),
+            }],
+            label: Some(&labels.label("ssao_blur_bind_group")),
+        });
+        (gbuffer_bind_group, blur_bind_group)
+    }
+
+    /// The blurred occlusion term: 1 where unoccluded, lower in creases.
+    pub fn output(&self) -> &wgpu::TextureView {
+        &self.blurred.1
+    }
+
+    /// Recreates the targets for a resized (and so recreated) G-buffer, and
+    /// the occlusion pipeline if the depth convention changed.
+    pub fn resize(
+        &mut self,
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        gbuffer: &GBuffer,
+        reverse_z: bool,
+    ) {
+        if self.reverse_z != reverse_z {
+            self.reverse_z = reverse_z;
+            self.occlusion_pipeline =
+                Self::create_occlusion_pipeline(device, &self.labels, &self.gbuffer_layout, reverse_z);
+        }
+        self.raw = Self::create_target(device, config, &self.labels.label("SSAO Raw"));
+        self.blurred = Self::create_target(device, config, &self.labels.label("SSAO"));
+        (self.gbuffer_bind_group, self.blur_bind_group) = Self::create_bind_groups(
+            device,
+            &self.labels,
+            &self.gbuffer_layout,
+            &self.blur_layout,
+            gbuffer,
+            &self.settings_buffer,
+            &self.raw.1,
+        );
+    }
+
+    pub fn set_settings(&self, queue: &wgpu::Queue, settings: &SsaoSettings) {
+        let mut uniform = SsaoUniform::zeroed();
+        uniform.radius = settings.radius;
+        uniform.bias = settings.bias;
+        uniform.intensity = settings.intensity;
+        for (slot, probe) in uniform.kernel.iter_mut().zip(hemisphere_kernel(KERNEL_SIZE)) {
+            *slot = probe;
+        }
+        queue.write_buffer(&self.settings_buffer, 0, bytemuck::bytes_of(&uniform));
+    }
+
+    /// Records the occlusion and blur passes. The G-buffer must already hold
+    /// this frame's geometry.
+    pub fn run(&self, encoder: &mut wgpu::CommandEncoder, camera_bind_group: &wgpu::BindGroup) {
+        {
+            let label = self.labels.label("SSAO Pass");
+            let mut render_pass = Self::begin_pass(encoder, &label, &self.raw.1, wgpu::LoadOp::Load);
+            render_pass.set_pipeline(&self.occlusion_pipeline);
+            render_pass.set_bind_group(0, camera_bind_group, &[]);
+            render_pass.set_bind_group(1, &self.gbuffer_bind_group, &[]);
+            render_pass.draw(0..3, 0..1);
+        }
+        let label = self.labels.label("SSAO Blur Pass");
+        let mut render_pass = Self::begin_pass(encoder, &label, &self.blurred.1, wgpu::LoadOp::Load);
+        render_pass.set_pipeline(&self.blur_pipeline);
+        render_pass.set_bind_group(0, &self.blur_bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+
+    /// Fills the output with "no occlusion", for frames where SSAO is off.
+    pub fn clear(&self, encoder: &mut wgpu::CommandEncoder) {
+        let label = self.labels.label("SSAO Clear Pass");
+        Self::begin_pass(encoder, &label, &self.blurred.1, wgpu::LoadOp::Clear(wgpu::Color::WHITE));
+    }
+
+    fn begin_pass<'a>(
+        encoder: &'a mut wgpu::CommandEncoder,
+        label: &str,
+        view: &wgpu::TextureView,
+        load: wgpu::LoadOp<wgpu::Color>,
+    ) -> wgpu::RenderPass<'a> {
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some(label),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::{Camera, CameraUniform};
+    use crate::deferred::DeferredRenderer;
+    use crate::mesh::{Mesh, Vertex};
+    use crate::render_target::RenderTarget;
+    use crate::test_gpu;
+    use glam::Vec3;
+    use wgpu::util::DeviceExt;
+
+    const SIZE: u32 = 64;
+
+    #[test]
+    fn kernel_stays_in_the_unit_hemisphere() {
+        let kernel = hemisphere_kernel(KERNEL_SIZE);
+        assert_eq!(kernel.len(), KERNEL_SIZE);
+        for [x, y, z, w] in &kernel {
+            assert!(*z > 0.0, "probe below the surface: {:?}", [x, y, z]);
+            assert!((x * x + y * y + z * z).sqrt() <= 1.0 + 1e-6);
+            assert_eq!(*w, 0.0);
+        }
+        assert_eq!(kernel, hemisphere_kernel(KERNEL_SIZE));
+    }
+
+    /// A white floor meeting a white back wall along the X axis at z = -1.
+    fn corner(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) -> Mesh {
+        let quad = |corners: [[f32; 3]; 4], normal| {
+            [0, 1, 2, 0, 2, 3].map(|i| Vertex::new(corners[i], [1.0; 3], normal))
+        };
+        let floor = quad([[-3.0, 0.0, 3.0], [3.0, 0.0, 3.0], [3.0, 0.0, -1.0], [-3.0, 0.0, -1.0]], [0.0, 1.0, 0.0]);
+        let wall = quad([[-3.0, 0.0, -1.0], [3.0, 0.0, -1.0], [3.0, 3.0, -1.0], [-3.0, 3.0, -1.0]], [0.0, 0.0, 1.0]);
+        Mesh::from_vertices(device, &Labels::default(), config, &[floor, wall].concat())
+    }
+
+    /// Red channel of the lit image at the pixels `points` land on, under
+    /// white ambient light only.
+    fn lit_at(ssao: Option<SsaoSettings>, points: &[Vec3]) -> Option<Vec<u8>> {
+        let (device, queue) = test_gpu::device()?;
+        let labels = Labels::default();
+        let config = test_gpu::surface_config(SIZE, SIZE);
+        let mut camera = Camera::new(Vec3::new(0.0, 1.0, 3.0), 1.0);
+        camera.look_at(Vec3::new(0.0, 0.5, -1.0), Vec3::Y);
+        let mut uniform = CameraUniform::new();
+        uniform.update_view_proj(&camera);
+        let camera_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: None,
+            contents: bytemuck::bytes_of(&uniform),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let camera_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &Renderer::camera_bind_group_layout(&device, &labels),
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: camera_buffer.as_entire_binding(),
+            }],
+            label: None,
+        });
+
+        let mut deferred = DeferredRenderer::new(&device, &labels, &config, false).ok()?;
+        deferred.set_lights(&queue, [1.0, 1.0, 1.0], &[]);
+        deferred.set_ssao(&queue, ssao);
+        let target = RenderTarget::new(&device, &labels, &config, SIZE, SIZE);
+        let mesh = corner(&device, &config);
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        deferred.render(&mut encoder, &camera_bind_group, target.color_view(), &[&mesh]);
+        queue.submit(std::iter::once(encoder.finish()));
+
+        let pixels = test_gpu::read_pixels(&device, &queue, &target.color_texture.0);
+        let view_proj = camera.build_view_projection_matrix();
+        let red = points
+            .iter()
+            .map(|point| {
+                let ndc = view_proj.project_point3(*point);
+                let x = ((ndc.x * 0.5 + 0.5) * SIZE as f32) as u32;
+                let y = ((0.5 - ndc.y * 0.5) * SIZE as f32) as u32;
+                pixels[((y * SIZE + x) * 4) as usize]
+            })
+            .collect();
+        Some(red)
+    }
+
+    #[test]
+    fn ssao_darkens_the_corner_but_not_the_open_floor() {
+        let points = [Vec3::new(0.0, 0.05, -0.95), Vec3::new(0.0, 0.0, 0.5)];
+        let Some(without) = lit_at(None, &points) else {
+            return;
+        };
+        assert!(without.iter().all(|&red| red >= 250), "{without:?}");
+
+        let with = lit_at(Some(SsaoSettings::default()), &points).unwrap();
+        let (corner, flat) = (with[0], with[1]);
+        assert!(flat >= 240, "open floor was darkened to {flat}");
+        assert!(corner + 20 < flat, "corner {corner} is not darker than the floor {flat}");
+    }
+}
@@ -0,0 +1,91 @@
+// Screen-space ambient occlusion: probes a normal-oriented hemisphere around
+// each G-buffer position and counts the probes hidden behind other geometry.
+
+override REVERSE_Z: bool = false;
+
+const KERNEL_SIZE: u32 = 32u;
+
+struct CameraUniform {
+    view_proj: mat4x4<f32>,
+};
+
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+struct Settings {
+    radius: f32,
+    bias: f32,
+    intensity: f32,
+    _padding: f32,
+    kernel: array<vec4<f32>, KERNEL_SIZE>,
+};
+
+@group(1) @binding(0)
+var gbuffer_position: texture_2d<f32>;
+@group(1) @binding(1)
+var gbuffer_normal: texture_2d<f32>;
+@group(1) @binding(2)
+var<uniform> settings: Settings;
+
+@vertex
+fn vs_main(@builtin(vertex_index) vid: u32) -> @builtin(position) vec4<f32> {
+    // Vertices (0,0), (2,0), (0,2) cover the whole screen with one triangle.
+    let uv = vec2<f32>(f32((vid << 1u) & 2u), f32(vid & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+// Depth that grows away from the camera under either depth convention.
+fn view_depth(clip: vec4<f32>) -> f32 {
+    let depth = clip.z / clip.w;
+    return select(depth, -depth, REVERSE_Z);
+}
+
+@fragment
+fn fs_main(@builtin(position) frag_coord: vec4<f32>) -> @location(0) f32 {
+    let coord = vec2<i32>(frag_coord.xy);
+    let position = textureLoad(gbuffer_position, coord, 0);
+    if (position.w == 0.0) {
+        return 1.0;
+    }
+    let normal = textureLoad(gbuffer_normal, coord, 0).xyz;
+    let size = vec2<f32>(textureDimensions(gbuffer_position));
+
+    // Rotate the kernel around the normal in a 4x4 pattern, which the blur
+    // pass averages away, so fewer probes still cover the hemisphere.
+    let cell = vec2<u32>(coord) % 4u;
+    let angle = f32(cell.x + cell.y * 4u) * (6.2831853 / 16.0);
+    var up = vec3<f32>(0.0, 1.0, 0.0);
+    if (abs(normal.y) > 0.99) {
+        up = vec3<f32>(1.0, 0.0, 0.0);
+    }
+    let t0 = normalize(cross(up, normal));
+    let b0 = cross(normal, t0);
+    let tangent = t0 * cos(angle) + b0 * sin(angle);
+    let bitangent = cross(normal, tangent);
+
+    let origin = position.xyz + normal * settings.bias;
+    var occlusion = 0.0;
+    for (var i = 0u; i < KERNEL_SIZE; i = i + 1u) {
+        let k = settings.kernel[i].xyz;
+        let probe = origin + (tangent * k.x + bitangent * k.y + normal * k.z) * settings.radius;
+        let clip = camera.view_proj * vec4<f32>(probe, 1.0);
+        if (clip.w <= 0.0) {
+            continue;
+        }
+        let ndc = clip.xy / clip.w;
+        let uv = vec2<f32>(ndc.x * 0.5 + 0.5, 0.5 - ndc.y * 0.5);
+        if (any(uv < vec2<f32>(0.0)) || any(uv >= vec2<f32>(1.0))) {
+            continue;
+        }
+        let scene = textureLoad(gbuffer_position, vec2<i32>(uv * size), 0);
+        if (scene.w == 0.0) {
+            continue;
+        }
+        if (view_depth(camera.view_proj * vec4<f32>(scene.xyz, 1.0)) < view_depth(clip)) {
+            // Fade out occluders well outside the radius, such as a distant
+            // wall seen past a silhouette edge.
+            occlusion += smoothstep(0.0, 1.0, settings.radius / max(distance(scene.xyz, position.xyz), 1e-4));
+        }
+    }
+    return pow(1.0 - occlusion / f32(KERNEL_SIZE), settings.intensity);
+}
@@ -0,0 +1,25 @@
+// 4x4 box blur over the raw SSAO term, matching the kernel rotation pattern.
+
+@group(0) @binding(0)
+var occlusion: texture_2d<f32>;
+
+@vertex
+fn vs_main(@builtin(vertex_index) vid: u32) -> @builtin(position) vec4<f32> {
+    // Vertices (0,0), (2,0), (0,2) cover the whole screen with one triangle.
+    let uv = vec2<f32>(f32((vid << 1u) & 2u), f32(vid & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+@fragment
+fn fs_main(@builtin(position) frag_coord: vec4<f32>) -> @location(0) f32 {
+    let size = vec2<i32>(textureDimensions(occlusion));
+    let coord = vec2<i32>(frag_coord.xy);
+    var sum = 0.0;
+    for (var y = -2; y < 2; y = y + 1) {
+        for (var x = -2; x < 2; x = x + 1) {
+            let texel = clamp(coord + vec2<i32>(x, y), vec2<i32>(0), size - 1);
+            sum += textureLoad(occlusion, texel, 0).r;
+        }
+    }
+    return sum / 16.0;
+}
@@ -0,0 +1,363 @@
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+
+use thiserror::Error;
+
+use crate::assets::{Asset, AssetHandle, AssetLoader};
+use crate::math::Vector3;
+use crate::mesh::Mesh;
+
+/// A cell of the streaming grid, which divides the XZ plane into squares.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ChunkCoord {
+    pub x: i32,
+    pub z: i32,
+}
+
+impl ChunkCoord {
+    pub fn new(x: i32, z: i32) -> Self {
+        Self { x, z }
+    }
+
+    /// Chunk containing `position`; height is ignored.
+    pub fn containing(position: Vector3, chunk_size: f32) -> Self {
+        Self::new((position.x / chunk_size).floor() as i32, (position.z / chunk_size).floor() as i32)
+    }
+
+    /// Center of the chunk, at height 0.
+    pub fn center(self, chunk_size: f32) -> Vector3 {
+        Vector3::new((self.x as f32 + 0.5) * chunk_size, 0.0, (self.z as f32 + 0.5) * chunk_size)
+    }
+
+    /// Horizontal distance from `position` to the chunk's center.
+    pub fn distance_to(self, position: Vector3, chunk_size: f32) -> f32 {
+        let center = self.center(chunk_size);
+        (center.x - position.x).hypot(center.z - position.z)
+    }
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum StreamingError {
+    #[error("Chunk size {0} is not a positive number")]
+    InvalidChunkSize(f32),
+    #[error("Load radius {load} and unload radius {unload} must be finite, with unload >= load >= 0")]
+    InvalidRadii { load: f32, unload: f32 },
+    #[error("At least one chunk must be loaded per frame")]
+    NoLoadsPerFrame,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StreamingConfig {
+    /// Side length of a chunk in world units.
+    pub chunk_size: f32,
+    /// Chunks whose centers come within this distance of the camera are loaded.
+    pub load_radius: f32,
+    /// Loaded chunks are only dropped beyond this distance. Keeping it larger
+    /// than `load_radius` stops chunks on the edge from reloading every time
+    /// the camera wobbles across it.
+    pub unload_radius: f32,
+    /// Most chunks to start loading in one update, nearest first. Teleporting
+    /// into an empty area then queues the rest over the following frames
+    /// instead of all at once.
+    pub max_loads_per_frame: usize,
+}
+
+impl Default for StreamingConfig {
+    fn default() -> Self {
+        Self {
+            chunk_size: 64.0,
+            load_radius: 128.0,
+            unload_radius: 160.0,
+            max_loads_per_frame: DEFAULT_MAX_LOADS_PER_FRAME,
+        }
+    }
+}
+
+/// `StreamingConfig::max_loads_per_frame` unless set otherwise.
+pub const DEFAULT_MAX_LOADS_PER_FRAME: usize = 4;
+
+impl StreamingConfig {
+    pub fn new(chunk_size: f32, load_radius: f32, unload_radius: f32) -> Result<Self, StreamingError> {
+        let config = Self {
+            chunk_size,
+            load_radius,
+            unload_radius,
+            max_loads_per_frame: DEFAULT_MAX_LOADS_PER_FRAME,
+        };
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn validate(&self) -> Result<(), StreamingError> {
+        if !(self.chunk_size.is_finite() && self.chunk_size > 0.0) {
+            return Err(StreamingError::InvalidChunkSize(self.chunk_size));
+        }
+        if !(self.unload_radius.is_finite() && self.load_radius >= 0.0 && self.unload_radius >= self.load_radius) {
+            return Err(StreamingError::InvalidRadii {
+                load: self.load_radius,
+                unload: self.unload_radius,
+            });
+        }
+        if self.max_loads_per_frame == 0 {
+            return Err(StreamingError::NoLoadsPerFrame);
+        }
+        Ok(())
+    }
+
+    /// Every chunk within `load_radius` of `position`, nearest first. An
+    /// invalid config has no chunks in range.
+    pub fn chunks_in_range(&self, position: Vector3) -> Vec<ChunkCoord> {
+        if self.validate().is_err() {
+            return Vec::new();
+        }
+        let center = ChunkCoord::containing(position, self.chunk_size);
+        let reach = (self.load_radius / self.chunk_size).ceil() as i32 + 1;
+        let mut chunks: Vec<_> = (-reach..=reach)
+            .flat_map(|dz| (-reach..=reach).map(move |dx| ChunkCoord::new(center.x + dx, center.z + dz)))
+            .filter(|chunk| chunk.distance_to(position, self.chunk_size) <= self.load_radius)
+            .collect();
+        chunks.sort_by(|a, b| {
+            a.distance_to(position, self.chunk_size)
+                .total_cmp(&b.distance_to(position, self.chunk_size))
+                .then(a.cmp(b))
+        });
+        chunks
+    }
+}
+
+/// Chunks to start loading and to drop after the camera moved.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StreamingUpdate {
+    /// Nearest first, at most `StreamingConfig::max_loads_per_frame`.
+    pub load: Vec<ChunkCoord>,
+    pub unload: Vec<ChunkCoord>,
+}
+
+impl StreamingUpdate {
+    pub fn is_empty(&self) -> bool {
+        self.load.is_empty() && self.unload.is_empty()
+    }
+}
+
+/// What changes for a camera at `position` when `resident` chunks are
+/// loaded (or loading). Chunks past the per-frame load budget are left for
+/// later updates.
+pub fn plan_streaming(config: &StreamingConfig, resident: &HashSet<ChunkCoord>, position: Vector3) -> StreamingUpdate {
+    let load = config
+        .chunks_in_range(position)
+        .into_iter()
+        .filter(|chunk| !resident.contains(chunk))
+        .take(config.max_loads_per_frame)
+        .collect();
+    let mut unload: Vec<_> = resident
+        .iter()
+        .copied()
+        .filter(|chunk| chunk.distance_to(position, config.chunk_size) > config.unload_radius)
+        .collect();
+    unload.sort();
+    StreamingUpdate { load, unload }
+}
+
+/// Loads chunk meshes around the camera on the asset loader's worker threads
+/// and unloads them again, freeing their GPU buffers, once the camera is far
+/// enough away.
+pub struct StreamingWorld {
+    config: StreamingConfig,
+    /// File holding a chunk's mesh, or `None` where the world has no chunk.
+    source: Box<dyn Fn(ChunkCoord) -> Option<PathBuf>>,
+    /// Chunks requested from the loader. Empty cells are remembered as
+    /// `None` so they are not looked up again every frame.
+    chunks: HashMap<ChunkCoord, Option<AssetHandle>>,
+}
+
+impl StreamingWorld {
+    pub fn new(
+        config: StreamingConfig,
+        source: impl Fn(ChunkCoord) -> Option<PathBuf> + 'static,
+    ) -> Result<Self, StreamingError> {
+        config.validate()?;
+        Ok(Self {
+            config,
+            source: Box::new(source),
+            chunks: HashMap::new(),
+        })
+    }
+
+    pub fn config(&self) -> &StreamingConfig {
+        &self.config
+    }
+
+    /// Chunks currently loaded or loading, including empty cells.
+    pub fn resident(&self) -> HashSet<ChunkCoord> {
+        self.chunks.keys().copied().collect()
+    }
+
+    /// Starts loading the chunks that came into range of `camera` and
+    /// unloads those that left it. Call once per frame, before
+    /// `AssetLoader::poll`.
+    pub fn update(&mut self, loader: &mut AssetLoader, camera: Vector3) -> StreamingUpdate {
+        let update = plan_streaming(&self.config, &self.resident(), camera);
+        for chunk in &update.unload {
+            if let Some(Some(handle)) = self.chunks.remove(chunk) {
+                loader.unload(handle);
+            }
+        }
+        for chunk in &update.load {
+            let handle = (self.source)(*chunk).map(|path| loader.load_async(path));
+            self.chunks.insert(*chunk, handle);
+        }
+        update
+    }
+
+    pub fn handle(&self, chunk: ChunkCoord) -> Option<AssetHandle> {
+        self.chunks.get(&chunk).copied().flatten()
+    }
+
+    /// Meshes of the chunks that have finished loading.
+    pub fn loaded_meshes<'a>(&'a self, loader: &'a AssetLoader) -> impl Iterator<Item = (ChunkCoord, &'a Mesh)> + 'a {
+        self.chunks.iter().filter_map(move |(chunk, handle)| match loader.get((*handle)?) {
+            Some(Asset::Mesh(mesh)) => Some((*chunk, mesh)),
+            _ => None,
+        })
+    }
+
+    /// Unloads every chunk.
+    pub fn clear(&mut self, loader: &mut AssetLoader) {
+        for handle in self.chunks.drain().filter_map(|(_, handle)| handle) {
+            loader.unload(handle);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assets::AssetState;
+
+    const CONFIG: StreamingConfig = StreamingConfig {
+        chunk_size: 10.0,
+        load_radius: 10.0,
+        unload_radius: 20.0,
+        max_loads_per_frame: DEFAULT_MAX_LOADS_PER_FRAME,
+    };
+
+    #[test]
+    fn chunk_coords_floor_towards_negative_infinity() {
+        assert_eq!(ChunkCoord::containing(Vector3::new(5.0, 100.0, 15.0), 10.0), ChunkCoord::new(0, 1));
+        assert_eq!(ChunkCoord::containing(Vector3::new(-0.5, 0.0, -10.0), 10.0), ChunkCoord::new(-1, -1));
+        assert_eq!(ChunkCoord::new(-1, 2).center(10.0), Vector3::new(-5.0, 0.0, 25.0));
+    }
+
+    #[test]
+    fn chunks_in_range_are_sorted_nearest_first() {
+        // From the middle of chunk (0, 0) the four edge neighbors are 10 away
+        // and the diagonal ones about 14
+        let chunks = CONFIG.chunks_in_range(Vector3::new(5.0, 0.0, 5.0));
+        assert_eq!(
+            chunks,
+            [
+                ChunkCoord::new(0, 0),
+                ChunkCoord::new(-1, 0),
+                ChunkCoord::new(0, -1),
+                ChunkCoord::new(0, 1),
+                ChunkCoord::new(1, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn chunks_between_the_radii_stay_resident() {
+        let resident: HashSet<_> = CONFIG.chunks_in_range(Vector3::new(5.0, 0.0, 5.0)).into_iter().collect();
+        // A chunk east: (-1, 0) is 20 away, out of load range but not beyond
+        // the unload radius, while the neighbors of (1, 0) come into range
+        let update = plan_streaming(&CONFIG, &resident, Vector3::new(15.0, 0.0, 5.0));
+        assert!(update.unload.is_empty(), "{update:?}");
+        assert_eq!(update.load, [ChunkCoord::new(1, -1), ChunkCoord::new(1, 1), ChunkCoord::new(2, 0)]);
+
+        let far = plan_streaming(&CONFIG, &resident, Vector3::new(35.0, 0.0, 5.0));
+        let unloaded = [ChunkCoord::new(-1, 0), ChunkCoord::new(0, -1), ChunkCoord::new(0, 0), ChunkCoord::new(0, 1)];
+        assert_eq!(far.unload, unloaded);
+        assert!(plan_streaming(&CONFIG, &resident, Vector3::new(5.0, 0.0, 5.0)).is_empty());
+    }
+
+    #[test]
+    fn loads_beyond_the_per_frame_budget_wait_for_later_updates() {
+        let config = StreamingConfig {
+            max_loads_per_frame: 2,
+            ..CONFIG
+        };
+        let mut resident = HashSet::new();
+        let position = Vector3::new(5.0, 0.0, 5.0);
+        let mut frames = Vec::new();
+        loop {
+            let update = plan_streaming(&config, &resident, position);
+            if update.is_empty() {
+                break;
+            }
+            resident.extend(update.load.iter().copied());
+            frames.push(update.load);
+        }
+        // The same order as all at once, just spread over three frames
+        assert_eq!(frames.concat(), CONFIG.chunks_in_range(position));
+        assert_eq!(frames.iter().map(Vec::len).collect::<Vec<_>>(), [2, 2, 1]);
+        assert_eq!(frames[0][0], ChunkCoord::new(0, 0));
+    }
+
+    #[test]
+    fn a_zero_load_budget_is_rejected() {
+        let config = StreamingConfig {
+            max_loads_per_frame: 0,
+            ..CONFIG
+        };
+        assert_eq!(config.validate(), Err(StreamingError::NoLoadsPerFrame));
+        assert!(StreamingWorld::new(config, |_| None).is_err());
+    }
+
+    #[test]
+    fn world_loads_in_range_chunks_and_unloads_far_ones() {
+        let mut loader = AssetLoader::new();
+        // Only chunks with an even x have a file
+        let mut world = StreamingWorld::new(CONFIG, |chunk| {
+            (chunk.x % 2 == 0).then(|| PathBuf::from(format!("chunk_{}_{}.obj", chunk.x, chunk.z)))
+        })
+        .unwrap();
+        let update = world.update(&mut loader, Vector3::new(5.0, 0.0, 5.0));
+        assert_eq!(update.load.len(), DEFAULT_MAX_LOADS_PER_FRAME);
+        // The farthest chunk in range is started on the next update
+        assert_eq!(world.update(&mut loader, Vector3::new(5.0, 0.0, 5.0)).load, [ChunkCoord::new(1, 0)]);
+        assert_eq!(world.resident().len(), 5);
+        assert_eq!(world.handle(ChunkCoord::new(-1, 0)), None);
+        let handle = world.handle(ChunkCoord::new(0, 0)).expect("chunk (0, 0) has a file");
+        assert!(matches!(loader.state(handle), Some(AssetState::Loading)));
+
+        // Standing still changes nothing; empty cells aren't looked up again
+        assert!(world.update(&mut loader, Vector3::new(5.0, 0.0, 5.0)).is_empty());
+
+        world.update(&mut loader, Vector3::new(500.0, 0.0, 5.0));
+        assert!(!world.resident().contains(&ChunkCoord::new(0, 0)));
+        assert!(loader.state(handle).is_none());
+
+        world.clear(&mut loader);
+        assert!(world.resident().is_empty());
+    }
+
+    #[test]
+    fn non_positive_chunk_sizes_are_rejected() {
+        for chunk_size in [0.0, -10.0, f32::NAN, f32::INFINITY] {
+            assert!(matches!(StreamingConfig::new(chunk_size, 10.0, 20.0), Err(StreamingError::InvalidChunkSize(_))));
+            let config = StreamingConfig { chunk_size, ..CONFIG };
+            assert!(config.chunks_in_range(Vector3::zero()).is_empty());
+            assert!(StreamingWorld::new(config, |_| None).is_err());
+        }
+        assert_eq!(StreamingConfig::new(10.0, 10.0, 20.0), Ok(CONFIG));
+    }
+
+    #[test]
+    fn unload_radius_below_the_load_radius_is_rejected() {
+        assert_eq!(
+            StreamingConfig::new(10.0, 20.0, 10.0),
+            Err(StreamingError::InvalidRadii { load: 20.0, unload: 10.0 })
+        );
+        assert!(StreamingConfig::new(10.0, f32::NAN, 10.0).is_err());
+    }
+}
@@ -0,0 +1,58 @@
+//! Headless GPU access for tests. Machines without a usable adapter get
+//! `None`, and the tests needing one return early instead of failing. CI
+//! installs lavapipe and sets `PULSAR_REQUIRE_GPU`, which turns a missing
+//! adapter into a failure so those tests can't pass without running.
+
+use crate::engine::render::config::Labels;
+use crate::screenshot;
+
+/// Color format of `surface_config`; linear so pixels read back as written.
+pub(crate) const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
+
+/// Set to make tests fail, rather than skip, when no adapter is available.
+const REQUIRE_GPU_VAR: &str = "PULSAR_REQUIRE_GPU";
+
+pub(crate) fn device() -> Option<(wgpu::Device, wgpu::Queue)> {
+    adapter().map(|(_, device, queue)| (device, queue))
+}
+
+/// Like `device`, for tests that also query the adapter.
+pub(crate) fn adapter() -> Option<(wgpu::Adapter, wgpu::Device, wgpu::Queue)> {
+    let gpu = request_device();
+    if gpu.is_none() && std::env::var_os(REQUIRE_GPU_VAR).is_some() {
+        panic!("{REQUIRE_GPU_VAR} is set but no GPU adapter is available");
+    }
+    gpu
+}
+
+fn request_device() -> Option<(wgpu::Adapter, wgpu::Device, wgpu::Queue)> {
+    let instance = wgpu::Instance::default();
+    let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions::default()))?;
+    let descriptor = wgpu::DeviceDescriptor {
+        label: Some("Test Device"),
+        required_features: wgpu::Features::empty(),
+        required_limits: adapter.limits(),
+        memory_hints: wgpu::MemoryHints::default(),
+    };
+    let (device, queue) = pollster::block_on(adapter.request_device(&descriptor, None)).ok()?;
+    Some((adapter, device, queue))
+}
+
+/// Stands in for a window surface of `width`x`height` pixels.
+pub(crate) fn surface_config(width: u32, height: u32) -> wgpu::SurfaceConfiguration {
+    wgpu::SurfaceConfiguration {
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+        format: FORMAT,
+        width,
+        height,
+        present_mode: wgpu::PresentMode::Fifo,
+        desired_maximum_frame_latency: 2,
+        alpha_mode: wgpu::CompositeAlphaMode::Auto,
+        view_formats: Vec::new(),
+    }
+}
+
+/// Tightly packed RGBA8 contents of `texture`.
+pub(crate) fn read_pixels(device: &wgpu::Device, queue: &wgpu::Queue, texture: &wgpu::Texture) -> Vec<u8> {
+    screenshot::read_texture(device, &Labels::default(), queue, texture).expect("texture readback failed")
+}
@@ -0,0 +1,48 @@
+//! Captures log records in tests, so a test can check that a warning was
+//! logged. Records are kept per thread, so tests running in parallel only see
+//! their own.
+
+use std::cell::RefCell;
+use std::sync::Once;
+
+use log::{Level, Log, Metadata, Record};
+
+thread_local! {
+    static RECORDS: RefCell<Vec<(Level, String)>> = const { RefCell::new(Vec::new()) };
+}
+
+struct CaptureLogger;
+
+impl Log for CaptureLogger {
+    fn enabled(&self, _metadata: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        RECORDS.with(|records| records.borrow_mut().push((record.level(), record.args().to_string())));
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: CaptureLogger = CaptureLogger;
+static INSTALL: Once = Once::new();
+
+/// Runs `f`, returning its result and the warnings it logged on this thread.
+pub(crate) fn warnings<T>(f: impl FnOnce() -> T) -> (T, Vec<String>) {
+    INSTALL.call_once(|| {
+        log::set_logger(&LOGGER).expect("no other logger is installed in tests");
+        log::set_max_level(log::LevelFilter::Trace);
+    });
+    RECORDS.with(|records| records.borrow_mut().clear());
+    let value = f();
+    let warnings = RECORDS.with(|records| {
+        records
+            .borrow_mut()
+            .drain(..)
+            .filter(|(level, _)| *level == Level::Warn)
+            .map(|(_, message)| message)
+            .collect()
+    });
+    (value, warnings)
+}
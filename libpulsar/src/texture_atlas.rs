@@ -0,0 +1,289 @@
+use thiserror::Error;
+
+use crate::engine::render::config::Labels;
+use crate::resources::{ResourceCategory, Tracked};
+
+const BYTES_PER_PIXEL: usize = 4;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum AtlasError {
+    #[error("No room left in the atlas for a {width}x{height} image")]
+    Full { width: u32, height: u32 },
+    #[error("Expected {expected} bytes of RGBA8 data for the image, got {actual}")]
+    SizeMismatch { expected: usize, actual: usize },
+    #[error("Image has zero width or height")]
+    Empty,
+}
+
+/// Pixel rectangle of one packed image inside the atlas, excluding padding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AtlasRect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl AtlasRect {
+    /// Normalized `[u_min, v_min, u_max, v_max]` for sampling from an atlas of
+    /// the given size.
+    pub fn uv(&self, atlas_width: u32, atlas_height: u32) -> [f32; 4] {
+        [
+            self.x as f32 / atlas_width as f32,
+            self.y as f32 / atlas_height as f32,
+            (self.x + self.width) as f32 / atlas_width as f32,
+            (self.y + self.height) as f32 / atlas_height as f32,
+        ]
+    }
+
+    pub fn overlaps(&self, other: &AtlasRect) -> bool {
+        self.x < other.x + other.width
+            && other.x < self.x + self.width
+            && self.y < other.y + other.height
+            && other.y < self.y + self.height
+    }
+}
+
+/// A row of slots sharing one height, filled left to right.
+struct Shelf {
+    y: u32,
+    height: u32,
+    cursor: u32,
+}
+
+/// Packs many small RGBA8 images into one texture so a sprite batch can draw
+/// them all with a single binding.
+///
+/// Images are placed on shelves (rows); each goes on the shelf that wastes the
+/// least height, or opens a new one. Every image is surrounded by `padding`
+/// pixels copied from its own edges, so linear filtering at the border of a
+/// rect doesn't bleed in its neighbours.
+pub struct TextureAtlas {
+    width: u32,
+    height: u32,
+    padding: u32,
+    pixels: Vec<u8>,
+    shelves: Vec<Shelf>,
+    rects: Vec<AtlasRect>,
+}
+
+impl TextureAtlas {
+    pub fn new(width: u32, height: u32, padding: u32) -> Self {
+        Self {
+            width,
+            height,
+            padding,
+            pixels: vec![0; width as usize * height as usize * BYTES_PER_PIXEL],
+            shelves: Vec::new(),
+            rects: Vec::new(),
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Rects of all packed images, in the order they were added.
+    pub fn rects(&self) -> &[AtlasRect] {
+        &self.rects
+    }
+
+    /// RGBA8 contents of the whole atlas, row by row.
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    /// Copies a `width`x`height` RGBA8 image into the atlas and returns where
+    /// it ended up. The atlas is left untouched if it doesn't fit.
+    pub fn add(&mut self, width: u32, height: u32, rgba: &[u8]) -> Result<AtlasRect, AtlasError> {
+        if width == 0 || height == 0 {
+            return Err(AtlasError::Empty);
+        }
+        let expected = width as usize * height as usize * BYTES_PER_PIXEL;
+        if rgba.len() != expected {
+            return Err(AtlasError::SizeMismatch {
+                expected,
+                actual: rgba.len(),
+            });
+        }
+
+        let (slot_x, slot_y) = self
+            .allocate(width + 2 * self.padding, height + 2 * self.padding)
+            .ok_or(AtlasError::Full { width, height })?;
+        let rect = AtlasRect {
+            x: slot_x + self.padding,
+            y: slot_y + self.padding,
+            width,
+            height,
+        };
+        self.blit(&rect, rgba);
+        self.rects.push(rect);
+        Ok(rect)
+    }
+
+    /// Finds room for a slot of the given size, returning its top-left corner.
+    fn allocate(&mut self, width: u32, height: u32) -> Option<(u32, u32)> {
+        if width > self.width {
+            return None;
+        }
+
+        let best = self
+            .shelves
+            .iter_mut()
+            .filter(|shelf| shelf.height >= height && self.width - shelf.cursor >= width)
+            .min_by_key(|shelf| shelf.height - height);
+        if let Some(shelf) = best {
+            let x = shelf.cursor;
+            shelf.cursor += width;
+            return Some((x, shelf.y));
+        }
+
+        let y = self.shelves.last().map_or(0, |shelf| shelf.y + shelf.height);
+        if self.height - y < height {
+            return None;
+        }
+        self.shelves.push(Shelf { y, height, cursor: width });
+        Some((0, y))
+    }
+
+    /// Writes the image into `rect` and extrudes its edges into the padding.
+    fn blit(&mut self, rect: &AtlasRect, rgba: &[u8]) {
+        let pad = self.padding as i64;
+        for dy in -pad..rect.height as i64 + pad {
+            let src_y = dy.clamp(0, rect.height as i64 - 1) as usize;
+            for dx in -pad..rect.width as i64 + pad {
+                let src_x = dx.clamp(0, rect.width as i64 - 1) as usize;
+                let src = (src_y * rect.width as usize + src_x) * BYTES_PER_PIXEL;
+                let dst_x = (rect.x as i64 + dx) as usize;
+                let dst_y = (rect.y as i64 + dy) as usize;
+                let dst = (dst_y * self.width as usize + dst_x) * BYTES_PER_PIXEL;
+                self.pixels[dst..dst + BYTES_PER_PIXEL].copy_from_slice(&rgba[src..src + BYTES_PER_PIXEL]);
+            }
+        }
+    }
+
+    /// Uploads the atlas as an sRGB texture for sampling.
+    pub fn create_texture(
+        &self,
+        device: &wgpu::Device,
+        labels: &Labels,
+        queue: &wgpu::Queue,
+    ) -> (Tracked<wgpu::Texture>, wgpu::TextureView) {
+        let size = wgpu::Extent3d {
+            width: self.width,
+            height: self.height,
+            depth_or_array_layers: 1,
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(&labels.label("Texture Atlas")),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        queue.write_texture(
+            wgpu::TexelCopyTextureInfo {
+                texture: &texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            &self.pixels,
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(self.width * BYTES_PER_PIXEL as u32),
+                rows_per_image: Some(self.height),
+            },
+            size,
+        );
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        (Tracked::texture(texture, ResourceCategory::Texture), view)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solid(width: u32, height: u32, value: u8) -> Vec<u8> {
+        vec![value; width as usize * height as usize * BYTES_PER_PIXEL]
+    }
+
+    #[test]
+    fn four_images_fill_the_atlas_without_overlapping() {
+        let mut atlas = TextureAtlas::new(64, 64, 0);
+        for value in 1..=4 {
+            atlas.add(32, 32, &solid(32, 32, value)).unwrap();
+        }
+        let rects = atlas.rects();
+        assert_eq!(rects.len(), 4);
+        for (i, a) in rects.iter().enumerate() {
+            assert!(a.x + a.width <= 64 && a.y + a.height <= 64, "{a:?}");
+            for b in &rects[i + 1..] {
+                assert!(!a.overlaps(b), "{a:?} overlaps {b:?}");
+            }
+        }
+        // Every pixel belongs to exactly one image
+        assert!(atlas.pixels().iter().all(|&value| value != 0));
+    }
+
+    #[test]
+    fn image_that_does_not_fit_is_reported_and_leaves_the_atlas_untouched() {
+        let mut atlas = TextureAtlas::new(64, 64, 0);
+        for _ in 0..4 {
+            atlas.add(32, 32, &solid(32, 32, 1)).unwrap();
+        }
+        let before = atlas.pixels().to_vec();
+        assert_eq!(
+            atlas.add(1, 1, &solid(1, 1, 2)),
+            Err(AtlasError::Full { width: 1, height: 1 })
+        );
+        assert_eq!(atlas.rects().len(), 4);
+        assert_eq!(atlas.pixels(), before.as_slice());
+    }
+
+    #[test]
+    fn padding_repeats_the_image_edges() {
+        let mut atlas = TextureAtlas::new(8, 8, 1);
+        let rgba: Vec<u8> = (0..4).flat_map(|pixel| [pixel * 10; BYTES_PER_PIXEL]).collect();
+        let rect = atlas.add(2, 2, &rgba).unwrap();
+        assert_eq!((rect.x, rect.y), (1, 1));
+
+        let pixel = |x: usize, y: usize| atlas.pixels()[(y * 8 + x) * BYTES_PER_PIXEL];
+        assert_eq!(pixel(0, 0), 0);
+        assert_eq!(pixel(3, 0), 10);
+        assert_eq!(pixel(0, 3), 20);
+        assert_eq!(pixel(3, 3), 30);
+        // The next image starts after this one's padding
+        let next = atlas.add(1, 1, &solid(1, 1, 5)).unwrap();
+        assert!(next.x >= 5 || next.y >= 5, "{next:?}");
+    }
+
+    #[test]
+    fn malformed_images_are_rejected() {
+        let mut atlas = TextureAtlas::new(16, 16, 0);
+        assert_eq!(atlas.add(0, 4, &[]), Err(AtlasError::Empty));
+        assert_eq!(
+            atlas.add(2, 2, &[0; 4]),
+            Err(AtlasError::SizeMismatch { expected: 16, actual: 4 })
+        );
+    }
+
+    #[test]
+    fn uv_rect_is_normalized_to_the_atlas_size() {
+        let rect = AtlasRect {
+            x: 16,
+            y: 32,
+            width: 16,
+            height: 32,
+        };
+        assert_eq!(rect.uv(64, 64), [0.25, 0.5, 0.5, 1.0]);
+    }
+}
@@ -0,0 +1,41 @@
+// Maps an HDR scene into the 0..1 range of the swapchain with exposure and
+// the ACES filmic curve.
+
+struct TonemapUniform {
+    exposure: f32,
+    _padding: vec3<f32>,
+};
+
+@group(0) @binding(0)
+var<uniform> tonemap: TonemapUniform;
+@group(0) @binding(1)
+var scene: texture_2d<f32>;
+@group(0) @binding(2)
+var scene_sampler: sampler;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) vid: u32) -> VertexOutput {
+    // Vertices (0,0), (2,0), (0,2) cover the whole screen with one triangle.
+    let uv = vec2<f32>(f32((vid << 1u) & 2u), f32(vid & 2u));
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+    out.uv = vec2<f32>(uv.x, 1.0 - uv.y);
+    return out;
+}
+
+// Narkowicz's fit of the ACES curve; mirrors `tonemap::aces_filmic`.
+fn aces_filmic(x: vec3<f32>) -> vec3<f32> {
+    let mapped = (x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14);
+    return clamp(mapped, vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let hdr = textureSample(scene, scene_sampler, in.uv);
+    return vec4<f32>(aces_filmic(max(hdr.rgb, vec3<f32>(0.0)) * tonemap.exposure), hdr.a);
+}
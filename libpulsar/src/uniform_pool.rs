@@ -0,0 +1,204 @@
+use bytemuck::{Pod, Zeroable};
+use glam::Mat4;
+
+use crate::engine::render::config::Labels;
+
+/// Per-object data packed into the pool.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+pub struct ObjectUniform {
+    pub model: [[f32; 4]; 4],
+    pub color: [f32; 4],
+}
+
+impl ObjectUniform {
+    pub fn new(model: Mat4, color: [f32; 4]) -> Self {
+        Self {
+            model: model.to_cols_array_2d(),
+            color,
+        }
+    }
+}
+
+/// Packs many `ObjectUniform`s into a single buffer so the whole scene can be
+/// drawn with one bind group, selecting each object with a dynamic offset.
+pub struct UniformPool {
+    buffer: wgpu::Buffer,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    stride: wgpu::BufferAddress,
+    capacity: u32,
+    len: u32,
+}
+
+impl UniformPool {
+    pub fn new(device: &wgpu::Device, labels: &Labels, capacity: u32) -> Self {
+        let alignment = device.limits().min_uniform_buffer_offset_alignment as wgpu::BufferAddress;
+        let stride = Self::aligned_stride(std::mem::size_of::<ObjectUniform>() as wgpu::BufferAddress, alignment);
+        let capacity = capacity.max(1);
+
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(&labels.label("Uniform Pool Buffer")),
+            size: stride * capacity as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: true,
+                    min_binding_size: wgpu::BufferSize::new(std::mem::size_of::<ObjectUniform>() as u64),
+                },
+                count: None,
+            }],
+            label: Some(&labels.label("uniform_pool_bind_group_layout")),
+        });
+
+        // The binding only covers one element; the dynamic offset picks which.
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                    buffer: &buffer,
+                    offset: 0,
+                    size: wgpu::BufferSize::new(std::mem::size_of::<ObjectUniform>() as u64),
+                }),
+            }],
+            label: Some(&labels.label("uniform_pool_bind_group")),
+        });
+
+        Self {
+            buffer,
+            bind_group_layout,
+            bind_group,
+            stride,
+            capacity,
+            len: 0,
+        }
+    }
+
+    /// Rounds `size` up to the next multiple of `alignment`.
+    pub fn aligned_stride(size: wgpu::BufferAddress, alignment: wgpu::BufferAddress) -> wgpu::BufferAddress {
+        let alignment = alignment.max(1);
+        size.div_ceil(alignment) * alignment
+    }
+
+    /// Byte offset of slot `index`, suitable for `set_bind_group`'s dynamic offsets.
+    pub fn offset(&self, index: u32) -> wgpu::DynamicOffset {
+        Self::slot_offset(index, self.stride)
+    }
+
+    fn slot_offset(index: u32, stride: wgpu::BufferAddress) -> wgpu::DynamicOffset {
+        (index as wgpu::BufferAddress * stride) as wgpu::DynamicOffset
+    }
+
+    /// Writes `uniform` into the next free slot and returns its dynamic offset,
+    /// or `None` once the pool is full.
+    pub fn push(&mut self, queue: &wgpu::Queue, uniform: &ObjectUniform) -> Option<wgpu::DynamicOffset> {
+        if self.len >= self.capacity {
+            return None;
+        }
+        let offset = self.offset(self.len);
+        queue.write_buffer(&self.buffer, offset as wgpu::BufferAddress, bytemuck::cast_slice(&[*uniform]));
+        self.len += 1;
+        Some(offset)
+    }
+
+    /// Forgets all slots so the pool can be refilled next frame.
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    pub fn len(&self) -> u32 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn capacity(&self) -> u32 {
+        self.capacity
+    }
+
+    pub fn stride(&self) -> wgpu::BufferAddress {
+        self.stride
+    }
+
+    pub fn bind_group_layout(&self) -> &wgpu::BindGroupLayout {
+        &self.bind_group_layout
+    }
+
+    pub fn bind_group(&self) -> &wgpu::BindGroup {
+        &self.bind_group
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_gpu;
+
+    #[test]
+    fn stride_rounds_up_to_alignment() {
+        assert_eq!(UniformPool::aligned_stride(80, 256), 256);
+        assert_eq!(UniformPool::aligned_stride(256, 256), 256);
+        assert_eq!(UniformPool::aligned_stride(257, 256), 512);
+        assert_eq!(UniformPool::aligned_stride(80, 64), 128);
+    }
+
+    #[test]
+    fn zero_alignment_is_treated_as_one() {
+        assert_eq!(UniformPool::aligned_stride(80, 0), 80);
+    }
+
+    #[test]
+    fn object_uniform_stride_is_aligned() {
+        let size = std::mem::size_of::<ObjectUniform>() as wgpu::BufferAddress;
+        let stride = UniformPool::aligned_stride(size, 256);
+        assert!(stride >= size);
+        assert_eq!(stride % 256, 0);
+    }
+
+    #[test]
+    fn slot_offsets_are_multiples_of_the_stride() {
+        let stride = UniformPool::aligned_stride(80, 256);
+        let offsets: Vec<_> = (0..4).map(|index| UniformPool::slot_offset(index, stride)).collect();
+        assert_eq!(offsets, vec![0, 256, 512, 768]);
+    }
+
+    #[test]
+    fn every_slot_offset_is_aligned_and_accepted_by_the_bind_group() {
+        let Some((device, queue)) = test_gpu::device() else {
+            return;
+        };
+        let alignment = device.limits().min_uniform_buffer_offset_alignment;
+        let mut pool = UniformPool::new(&device, &Labels::default(), 5);
+        let offsets: Vec<_> = (0..pool.capacity())
+            .map(|_| pool.push(&queue, &ObjectUniform::new(Mat4::IDENTITY, [1.0; 4])).unwrap())
+            .collect();
+        assert!(pool.push(&queue, &ObjectUniform::new(Mat4::IDENTITY, [1.0; 4])).is_none());
+        for (index, offset) in offsets.iter().enumerate() {
+            assert_eq!(*offset, pool.offset(index as u32));
+            assert_eq!(offset % alignment, 0, "slot {index} at {offset} is not {alignment}-aligned");
+        }
+
+        // Out-of-bounds or misaligned dynamic offsets fail validation here
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor::default());
+            for offset in &offsets {
+                pass.set_bind_group(0, pool.bind_group(), &[*offset]);
+            }
+        }
+        queue.submit([encoder.finish()]);
+        let error = pollster::block_on(device.pop_error_scope());
+        assert!(error.is_none(), "{error:?}");
+    }
+}
@@ -1,7 +1,8 @@
-use winit::event_loop::EventLoop;
-
 fn main() {
-    env_logger::init();
+    // Not fatal: the engine still runs, just without log output
+    if let Err(err) = libpulsar::init_logging(libpulsar::LogFormat::from_env()) {
+        eprintln!("Failed to initialize logging: {err}");
+    }
     
     // let event_loop = EventLoop::new().unwrap();
     // let mut engine = pollster::block_on(Engine::new(&event_loop));